@@ -0,0 +1,123 @@
+use crate::config::AppConfig;
+use crate::focused_window::FocusedWindow;
+use serde::{Deserialize, Serialize};
+
+/// Behaviour for one target window, matched case-insensitively against the focused
+/// app's process or window class name and, when `title` is set, its window title.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppRule {
+    pub app: String,
+    /// Part of the window title, e.g. a project name in an IDE; empty matches any.
+    #[serde(default)]
+    pub title: String,
+    /// Press Enter after pasting, so dictating into a chat also sends the message.
+    #[serde(default)]
+    pub send_enter: bool,
+    /// A `paste::PASTE_KEYS_*` shortcut used for this app instead of the global one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paste_keys: Option<String>,
+    /// A `paste::OUTPUT_*` mode used for this app instead of the global one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_mode: Option<String>,
+    /// A `paste::APPEND_*` suffix used for this app instead of the global one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append_after: Option<String>,
+    /// Transcription language for this app instead of the configured one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+impl AppRule {
+    /// Whether `other` would match the same windows, so setting it replaces this rule.
+    pub fn same_target(&self, other: &AppRule) -> bool {
+        self.app.trim().eq_ignore_ascii_case(other.app.trim())
+            && self.title.trim().eq_ignore_ascii_case(other.title.trim())
+    }
+
+    /// Overrides the settings this rule changes in `config`.
+    pub fn apply(&self, config: &mut AppConfig) {
+        if let Some(keys) = &self.paste_keys {
+            config.paste_keys = keys.clone();
+        }
+        if let Some(mode) = &self.output_mode {
+            config.output_mode = mode.clone();
+        }
+        if let Some(language) = &self.language {
+            config.language = language.clone();
+        }
+        if let Some(append) = &self.append_after {
+            config.append_after = append.clone();
+        }
+    }
+
+    fn matches(&self, window: &FocusedWindow) -> bool {
+        let app = self.app.trim().to_lowercase();
+        let title = self.title.trim().to_lowercase();
+        (!app.is_empty() || !title.is_empty())
+            && window.app.to_lowercase().contains(&app)
+            && window.title.to_lowercase().contains(&title)
+    }
+}
+
+/// The rule whose `app` and `title` are the longest match for `window`, so "slack" can
+/// be refined by a more specific "slack huddle" rule.
+pub fn matching<'a>(rules: &'a [AppRule], window: &FocusedWindow) -> Option<&'a AppRule> {
+    rules
+        .iter()
+        .filter(|rule| rule.matches(window))
+        .max_by_key(|rule| rule.app.trim().len() + rule.title.trim().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matching, AppRule};
+    use crate::config::AppConfig;
+    use crate::focused_window::FocusedWindow;
+    use crate::paste::{APPEND_NEWLINE, APPEND_SPACE};
+
+    #[test]
+    fn most_specific_rule_wins() {
+        let rule = |app: &str, title: &str, send_enter| AppRule {
+            app: app.to_string(),
+            title: title.to_string(),
+            send_enter,
+            paste_keys: None,
+            output_mode: None,
+            append_after: None,
+            language: None,
+        };
+        let window = |app: &str, title: &str| FocusedWindow {
+            app: app.to_string(),
+            title: title.to_string(),
+        };
+        let rules = vec![
+            rule("slack", "", true),
+            rule("Slack Huddle", "", false),
+            rule("", "", true),
+            rule("code", "notes", false),
+        ];
+        let send_enter = |app, title| matching(&rules, &window(app, title)).map(|r| r.send_enter);
+        assert_eq!(send_enter("Slack", ""), Some(true));
+        assert_eq!(send_enter("slack huddle", ""), Some(false));
+        assert!(send_enter("Terminal", "").is_none());
+        assert_eq!(
+            send_enter("Code", "notes.md - Visual Studio Code"),
+            Some(false)
+        );
+        assert!(send_enter("Code", "main.rs - Visual Studio Code").is_none());
+
+        let mut config = AppConfig {
+            append_after: APPEND_SPACE.to_string(),
+            ..AppConfig::default()
+        };
+        rule("slack", "", true).apply(&mut config);
+        assert_eq!(config.append_after, APPEND_SPACE);
+        AppRule {
+            append_after: Some(APPEND_NEWLINE.to_string()),
+            ..rule("slack", "", true)
+        }
+        .apply(&mut config);
+        assert_eq!(config.append_after, APPEND_NEWLINE);
+    }
+}
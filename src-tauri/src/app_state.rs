@@ -1,32 +1,182 @@
-use crate::audio::resample_to_16k;
-use crate::command_errors::CommandError;
-use crate::config::{load_config, save_config, AppConfig};
+use crate::app_rules::{self, AppRule};
+use crate::audio::{
+    self, apply_gain, resample_for_whisper, AudioBuffer, AudioHost, CaptureOptions, InputDevice,
+    LevelMeter, Recorder, GAIN_AGC, GAIN_OFF, GAIN_PEAK, HOST_AUTO, RESAMPLER_LINEAR,
+    RESAMPLER_SINC, SOURCE_MICROPHONE, SOURCE_MIXED, SOURCE_SYSTEM,
+};
+use crate::child_protocol::{read_frame, write_frame, write_pcm, Request, RequestBody, Response};
+use crate::child_socket::{self, ChildLink};
+use crate::command_errors::{
+    CommandError, ModelTooLarge, TranscriberUnavailable, TranscriptionCancelled,
+    TranscriptionTimedOut,
+};
+use crate::compute::{self, ComputeBackend, ComputeReport};
+use crate::config::{load_config, AppConfig, ConfigStore};
+use crate::corrections::{self, CorrectionStore, CorrectionSuggestion};
+use crate::cues::{self, Cue};
+use crate::diagnostics::{Diagnostics, RunTelemetry, SessionStats, SlowRun};
+use crate::dictionary::{self, MergeSummary};
+use crate::echo;
+use crate::events::EventBus;
+use crate::focused_window::{self, FocusedWindow};
+use crate::global_config;
+use crate::history::{self, HistoryEntry, HistoryPage, Revision};
 use crate::hotkeys::Hotkey;
+use crate::latency::{self, Benchmarks};
 use crate::licensing;
-use crate::models;
-use crate::paste::paste_text;
-use crate::recording::RecorderWorker;
-use crate::tray::{TrayController, TrayMode};
+use crate::local_api;
+use crate::managed_config;
+use crate::memory;
+use crate::models::{self, ModelCompute};
+use crate::normalize;
+use crate::paste::{
+    self, copy_text, paste_guarded, PasteOutcome, ProgressiveTyper, APPEND_NEWLINE, APPEND_NONE,
+    APPEND_SPACE, CLIPBOARD_GUARD_OFF, CLIPBOARD_GUARD_RESTORE, CLIPBOARD_GUARD_REVIEW,
+    OUTPUT_CLIPBOARD, OUTPUT_PASTE, OUTPUT_PROGRESSIVE, OUTPUT_TYPE, PASTE_KEYS_AUTO,
+};
+use crate::post_processing::{
+    self, apply_replacements, filter_profanity, ProfanityFilter, ReplacementRule, BIDI_EMBEDDING,
+    BIDI_MARKS, BIDI_OFF, PROFANITY_MASK, PROFANITY_OFF, PROFANITY_REMOVE,
+};
+use crate::proxy;
+use crate::quota::{self, QuotaState};
+use crate::recording::{self, ClippingDetector, EnergyVad, RecorderWorker};
+use crate::retention;
+use crate::retranscribe::{self, RetranscribeConfig};
+use crate::sandbox;
+use crate::schedule::{self, ScheduleConfig, ScheduleStatus};
+use crate::self_test::{self, SelfTest, SelfTestReport};
+use crate::speech::{self, READ_ALOUD_AFTER, READ_ALOUD_BEFORE, READ_ALOUD_OFF};
+use crate::streaming::{self, ChunkCursor};
+use crate::subtitles;
+use crate::supervisor::{ComponentHealth, Supervisor};
+use crate::thermal;
+use crate::transcription::{
+    self, AdvancedDecoding, ContextOptions, DecodingParams, Segment, TranscribeOptions, Transcript,
+    AUTO_LANGUAGE,
+};
+use crate::tray::{
+    TrayController, TrayMode, ACTION_NEXT_LANGUAGE, ACTION_NONE, ACTION_TOGGLE_RECORDING,
+};
+use crate::usage::{self, UsageEntry};
 use crate::wayland_hotkeys::WaylandHotkeys;
+use crate::wayland_input;
+use crate::windows::{self, TaskbarProgress};
 use anyhow::{Context, Result};
+use arboard::Clipboard;
 use serde::Serialize;
-use std::io::{BufRead, BufReader, Write};
-use std::process::{ChildStdin, ChildStdout, Command, Stdio};
-use std::sync::{Arc, Mutex};
-use std::{env, fs, path::PathBuf, time::SystemTime};
-use tauri::{AppHandle, Emitter};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tokio::sync::Notify;
 use tokio::task;
+use whisper_rs::WhisperContext;
+
+const MONITOR_INTERVAL_MS: u64 = 50;
+const MONITOR_START_TICKS: u32 = 40;
+const MIN_PREROLL_MS: u64 = 250;
+const MAX_BEAM_SIZE: u32 = 8;
+/// Whisper keeps only the last ~224 prompt tokens; longer text is wasted work.
+const MAX_INITIAL_PROMPT_CHARS: usize = 1_000;
+const MIC_TEST_DURATION: Duration = Duration::from_secs(3);
+const CLIPPING_WINDOW: Duration = Duration::from_secs(1);
+/// Processing slower than this multiple of the audio length counts as slow; the floor
+/// leaves room for a cold model load on short clips.
+const SLOW_PROCESSING_FACTOR: u32 = 3;
+const MIN_SLOW_PROCESSING: Duration = Duration::from_secs(10);
+/// A request may also take this many times the length of its audio before the watchdog
+/// gives up on the transcriber.
+const TIMEOUT_AUDIO_FACTOR: u64 = 4;
+const PARTIAL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_PREROLL_MS: u64 = 3_000;
+/// Tries of one transcription, each on a freshly spawned transcriber after the first,
+/// waiting twice as long before every retry.
+const TRANSCRIBE_ATTEMPTS: u32 = 3;
+const TRANSCRIBE_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const SCHEDULE_TICK: Duration = Duration::from_secs(60);
+/// How long quitting waits for the dictation in progress before keeping its audio for
+/// `retranscribe_last` instead.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the idle local API listener checks for connections and setting changes.
+const LOCAL_API_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// External audio is transcribed in pieces this long, so dictation never waits on the
+/// transcriber for more than one of them.
+const EXTERNAL_CHUNK: Duration = Duration::from_secs(30);
+/// How long the window manager gets to hand focus back to the app behind the history
+/// window before a segment is pasted into it.
+const FOCUS_RETURN_DELAY: Duration = Duration::from_millis(250);
+/// Also the throttle: at most one entry is re-run per tick.
+const RETRANSCRIBE_TICK: Duration = Duration::from_secs(60);
+/// Ticks between re-reading the UTC offset, so DST changes are picked up within an hour.
+const SCHEDULE_OFFSET_REFRESH_TICKS: u64 = 60;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub config: Arc<Mutex<AppConfig>>,
+    pub config: ConfigStore,
     pub tray: TrayController,
+    pub events: EventBus,
     pub hotkey: Arc<Mutex<Hotkey>>,
+    pub incognito_hotkey: Arc<Mutex<Option<Hotkey>>>,
+    pub tag_hotkeys: Arc<Mutex<Vec<(Hotkey, String)>>>,
     pub recorder: RecorderWorker,
+    pub supervisor: Supervisor,
     pub wayland_hotkeys: Option<WaylandHotkeys>,
     license_public_keys: Vec<String>,
     license_issuer: String,
     transcribe: Arc<Mutex<Option<TranscribeServer>>>,
+    partial: Arc<Mutex<Option<Arc<Mutex<PartialSession>>>>>,
+    running_child: Arc<Running>,
+    /// The background retranscription in flight, cancelled when a dictation starts.
+    retranscribing: Arc<Running>,
+    /// Initial prompt for the current recording only, replacing `initial_prompt`.
+    prompt_override: Arc<Mutex<Option<String>>>,
+    /// Tag for the current recording, stored on its history entry.
+    dictation_tag: Arc<Mutex<Option<String>>>,
+    /// Model for the current recording instead of the active one.
+    dictation_model: Arc<Mutex<Option<String>>>,
+    /// Transcriber of `quick_model`, kept warm next to the active model's.
+    quick: Arc<Mutex<Option<TranscribeServer>>>,
+    /// Transcriber of a model requested for one job that is neither active nor quick, so
+    /// it never evicts the warm ones; unloaded once the job is done.
+    other: Arc<Mutex<Option<TranscribeServer>>>,
+    pub quick_hotkey: Arc<Mutex<Option<Hotkey>>>,
+    corrections: Arc<Mutex<CorrectionStore>>,
+    history: Arc<Mutex<Vec<HistoryEntry>>>,
+    history_ids: Arc<history::IdCounter>,
+    recording_session: Arc<AtomicU64>,
+    diagnostics: Arc<Mutex<Diagnostics>>,
+    /// Decode speeds behind `max_latency_ms`, persisted across launches.
+    benchmarks: Arc<Mutex<Benchmarks>>,
+    preload: Arc<Mutex<Option<Arc<Notify>>>>,
+    schedule_status: Arc<Mutex<Option<ScheduleStatus>>>,
+    last_transcription: Arc<Mutex<Option<LastTranscription>>>,
+    /// Session-only, never persisted: dictations are delivered but leave no trace in
+    /// history, stats, diagnostics or kept recordings.
+    incognito: Arc<AtomicBool>,
+    last_audio: Arc<Mutex<Option<LastAudio>>>,
+    /// Audio of the dictation being transcribed, kept so quitting can persist it.
+    in_flight: Arc<Mutex<Option<Arc<Vec<f32>>>>>,
+    shutting_down: Arc<AtomicBool>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreloadEvent {
+    pub model_id: String,
+    pub stage: &'static str,
+    pub message: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -35,9 +185,44 @@ pub struct ModelListResponse {
     pub active_model: String,
 }
 
+/// The last dictation's 16 kHz audio, for `retranscribe_last`.
+#[derive(Clone)]
+struct LastAudio {
+    created_at: u64,
+    history_id: u64,
+    samples: Arc<Vec<f32>>,
+}
+
+/// Partial transcription state of the current recording when streaming is on.
+#[derive(Default)]
+struct PartialSession {
+    stopped: bool,
+    text: String,
+    /// Shared with the blocking task typing into it, so delivery at stop waits for a
+    /// batch in flight instead of typing over it.
+    typer: Option<Arc<Mutex<ProgressiveTyper>>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MicrophoneTest {
+    pub device: String,
+    pub peak: f32,
+    pub rms: f32,
+    pub duration_ms: u64,
+}
+
 #[derive(Serialize)]
 pub struct StatusResponse {
     pub recording: bool,
+    pub paused: bool,
+    pub model_loaded: bool,
+    pub schedule: Option<ScheduleStatus>,
+    pub incognito: bool,
+    /// A failed or interrupted dictation is waiting for `retranscribe_last`.
+    pub recoverable: bool,
+    /// Workers that panicked and are restarting or gave up.
+    pub degraded: Vec<ComponentHealth>,
 }
 
 #[derive(Serialize, Clone)]
@@ -47,6 +232,10 @@ pub struct ModelProgress {
     pub total: Option<u64>,
     pub done: bool,
     pub error: Option<String>,
+    /// Smoothed download speed, once it has been measured.
+    pub bytes_per_sec: Option<u64>,
+    /// Seconds left at that speed, when the size is known.
+    pub eta_secs: Option<u64>,
 }
 
 #[derive(Serialize, Clone)]
@@ -55,6 +244,31 @@ pub struct TranscriptionEvent {
     pub text: String,
     pub model_id: String,
     pub duration_ms: u64,
+    /// Present when `word_timestamps` is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<Segment>>,
+    /// Average token probability of each segment, in order, sent even without `segments`.
+    pub segment_confidence: Vec<f32>,
+    /// Mean of `segment_confidence`; absent when there are no segments.
+    pub confidence: Option<f32>,
+}
+
+/// The most recent dictation that produced text, for "what did I just dictate" views.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LastTranscription {
+    pub text: String,
+    /// Foreground app when the text was delivered, where the platform reports it and an
+    /// app rule or the paste shortcut needed it looked up.
+    pub target_app: Option<String>,
+    /// `clipboard_paste`, `progressive_typing` or `clipboard_copy`.
+    pub backend: &'static str,
+    /// `pasted`, `held`, `copied` or `failed`.
+    pub outcome: &'static str,
+    pub created_at: u64,
+    pub history_id: u64,
+    pub recording_ms: u64,
+    pub processing_ms: u64,
 }
 
 impl AppState {
@@ -71,7 +285,7 @@ impl AppState {
             if installed_ids.contains(&config.preferred_model) {
                 config.active_model = config.preferred_model.clone();
             } else {
-                config.active_model = "base".to_string();
+                config.active_model = managed_config::policy().fallback_model();
             }
         }
         let hotkey = Hotkey::parse(&config.shortcut).unwrap_or(Hotkey {
@@ -80,33 +294,118 @@ impl AppState {
             shift: false,
             key: rdev::Key::Space,
         });
-        let wayland_hotkeys = WaylandHotkeys::start(app.clone(), config.shortcut.clone());
+        let incognito_hotkey = Hotkey::parse(&config.incognito_shortcut);
+        let quick_hotkey = Hotkey::parse(&config.quick_shortcut);
+        let tag_hotkeys = parse_tag_hotkeys(&config.tag_shortcuts);
+        let wayland_hotkeys = WaylandHotkeys::start(app.clone(), &config);
+        wayland_input::start(app.clone(), config.remote_desktop_token.clone());
+        let benchmarks = latency::load_benchmarks()
+            .unwrap_or_default()
+            .for_hardware(&latency::hardware_fingerprint(&config.compute_backend));
+        let config = ConfigStore::new(config);
+        let supervisor = Supervisor::default();
+        let history = history::load_history().unwrap_or_default();
         let state = Self {
-            config: Arc::new(Mutex::new(config)),
+            events: EventBus::new(config.clone()),
+            config,
             tray: TrayController::new(),
             hotkey: Arc::new(Mutex::new(hotkey)),
-            recorder: RecorderWorker::new(),
+            incognito_hotkey: Arc::new(Mutex::new(incognito_hotkey)),
+            tag_hotkeys: Arc::new(Mutex::new(tag_hotkeys)),
+            recorder: RecorderWorker::new(&supervisor),
+            supervisor,
             wayland_hotkeys,
             license_public_keys: licensing::trusted_public_keys(),
             license_issuer: licensing::license_issuer(),
             transcribe: Arc::new(Mutex::new(None)),
+            corrections: Arc::new(Mutex::new(corrections::load_store().unwrap_or_default())),
+            history_ids: Arc::new(history::IdCounter::after(&history)),
+            history: Arc::new(Mutex::new(history)),
+            recording_session: Arc::new(AtomicU64::new(0)),
+            preload: Arc::new(Mutex::new(None)),
+            schedule_status: Arc::new(Mutex::new(None)),
+            last_transcription: Arc::new(Mutex::new(None)),
+            incognito: Arc::new(AtomicBool::new(false)),
+            running_child: Arc::new(Running::default()),
+            retranscribing: Arc::new(Running::default()),
+            prompt_override: Arc::new(Mutex::new(None)),
+            dictation_tag: Arc::new(Mutex::new(None)),
+            dictation_model: Arc::new(Mutex::new(None)),
+            quick: Arc::new(Mutex::new(None)),
+            other: Arc::new(Mutex::new(None)),
+            quick_hotkey: Arc::new(Mutex::new(quick_hotkey)),
+            partial: Arc::new(Mutex::new(None)),
+            diagnostics: Arc::new(Mutex::new(Diagnostics::default())),
+            benchmarks: Arc::new(Mutex::new(benchmarks)),
+            last_audio: Arc::new(Mutex::new(None)),
+            in_flight: Arc::new(Mutex::new(None)),
+            shutting_down: Arc::new(AtomicBool::new(false)),
         };
-        state.tray.start_animation();
+        {
+            let config = state.config.snapshot();
+            proxy::set(&config.proxy_url);
+            if config.audio_host != HOST_AUTO {
+                let _ = state.recorder.set_host(&config.audio_host);
+            }
+            if config.avoid_bluetooth_input {
+                let _ = state.recorder.set_avoid_bluetooth(true);
+            }
+            if config.echo_cancellation {
+                let _ = state.recorder.set_echo_cancellation(true);
+            }
+            if config.compress_recording {
+                let _ = state.recorder.set_compress_recording(true);
+            }
+            if !config.input_channels.is_empty() {
+                let _ = state
+                    .recorder
+                    .set_input_channels(config.input_channels.clone());
+            }
+            if config.capture_source != SOURCE_MICROPHONE {
+                let _ = state.recorder.set_source(&config.capture_source);
+            }
+            state.recorder.set_monitor_volume(config.monitor_volume);
+            if config.monitor_input {
+                let _ = state.recorder.set_monitor(true);
+            }
+            if config.low_latency {
+                let _ = state.recorder.set_low_latency(true);
+            }
+            if config.preroll_enabled {
+                let _ = state
+                    .recorder
+                    .set_preroll(Some(Duration::from_millis(config.preroll_ms)));
+            }
+        }
+        if let Err(err) = state.replay_usage_journal() {
+            eprintln!("usage journal replay failed: {err:#}");
+        }
+        if let Err(err) = state.apply_latency_budget() {
+            eprintln!("latency budget failed: {err:#}");
+        }
+        state.tray.start_animation(&state.supervisor);
         state.tray.set_mode(TrayMode::Idle);
         Ok(state)
     }
 
-    pub async fn list_models(&self) -> Result<ModelListResponse> {
-        let models = models::list_models()?;
-        let config = self.config.lock().unwrap().clone();
+    pub async fn list_models(&self, filter: &models::ModelFilter) -> Result<ModelListResponse> {
+        let models = models::list_models()?
+            .into_iter()
+            .filter(|model| filter.matches(&model.tags))
+            .collect();
+        let config = self.config.snapshot();
         Ok(ModelListResponse {
             models,
-            active_model: config.active_model,
+            active_model: config.active_model.clone(),
         })
     }
 
     pub async fn download_model(&self, app: &AppHandle, model_id: &str) -> Result<()> {
+        if !managed_config::policy().model_allowed(model_id) {
+            return Err(CommandError::model_not_allowed().into());
+        }
         let app_handle = app.clone();
+        let events = self.events.clone();
         let model_id_owned = model_id.to_string();
         let start_event = ModelProgress {
             model_id: model_id_owned.clone(),
@@ -114,19 +413,37 @@ impl AppState {
             total: None,
             done: false,
             error: None,
+            bytes_per_sec: None,
+            eta_secs: None,
         };
-        let _ = app.emit("models:progress", start_event);
-        let result = models::download_model_with_progress(model_id, move |downloaded, total| {
+        self.events.emit(app, "models:progress", start_event);
+        let taskbar = TaskbarProgress::new(app);
+        let connections = self.config.snapshot().download_connections;
+        let speed = Mutex::new(models::DownloadSpeed::default());
+        let progress = move |downloaded, total| {
+            taskbar.update(downloaded, total);
+            let (bytes_per_sec, eta_secs) =
+                speed
+                    .lock()
+                    .unwrap()
+                    .sample(Instant::now(), downloaded, total);
             let event = ModelProgress {
                 model_id: model_id_owned.clone(),
                 downloaded,
                 total,
                 done: false,
                 error: None,
+                bytes_per_sec,
+                eta_secs,
             };
-            let _ = app_handle.emit("models:progress", event);
-        })
-        .await;
+            events.emit(&app_handle, "models:progress", event);
+        };
+        let mut result = models::download_model_with_progress(model_id, connections, &progress)
+            .await
+            .map(|_| ());
+        if result.is_ok() && compute::COREML {
+            result = models::download_coreml_encoder(model_id, &progress).await;
+        }
 
         match result {
             Ok(_) => {
@@ -136,9 +453,11 @@ impl AppState {
                     total: None,
                     done: true,
                     error: None,
+                    bytes_per_sec: None,
+                    eta_secs: None,
                 };
-                let _ = app.emit("models:progress", event);
-                Ok(())
+                self.events.emit(app, "models:progress", event);
+                self.apply_latency_budget()
             }
             Err(err) => {
                 let event = ModelProgress {
@@ -147,13 +466,63 @@ impl AppState {
                     total: None,
                     done: true,
                     error: Some(err.to_string()),
+                    bytes_per_sec: None,
+                    eta_secs: None,
                 };
-                let _ = app.emit("models:progress", event);
+                self.events.emit(app, "models:progress", event);
                 Err(err)
             }
         }
     }
 
+    pub async fn repair_model(
+        &self,
+        app: &AppHandle,
+        model_id: &str,
+    ) -> Result<models::RepairOutcome> {
+        if !managed_config::policy().model_allowed(model_id) {
+            return Err(CommandError::model_not_allowed().into());
+        }
+        let app_handle = app.clone();
+        let events = self.events.clone();
+        let model_id_owned = model_id.to_string();
+        let taskbar = TaskbarProgress::new(app);
+        let connections = self.config.snapshot().download_connections;
+        let speed = Mutex::new(models::DownloadSpeed::default());
+        let result =
+            models::repair_model_with_progress(model_id, connections, move |downloaded, total| {
+                taskbar.update(downloaded, total);
+                let (bytes_per_sec, eta_secs) =
+                    speed
+                        .lock()
+                        .unwrap()
+                        .sample(Instant::now(), downloaded, total);
+                let event = ModelProgress {
+                    model_id: model_id_owned.clone(),
+                    downloaded,
+                    total,
+                    done: false,
+                    error: None,
+                    bytes_per_sec,
+                    eta_secs,
+                };
+                events.emit(&app_handle, "models:progress", event);
+            })
+            .await;
+
+        let event = ModelProgress {
+            model_id: model_id.to_string(),
+            downloaded: 0,
+            total: None,
+            done: true,
+            error: result.as_ref().err().map(|err| err.to_string()),
+            bytes_per_sec: None,
+            eta_secs: None,
+        };
+        self.events.emit(app, "models:progress", event);
+        result
+    }
+
     pub async fn delete_model(&self, model_id: &str) -> Result<()> {
         models::delete_model(model_id)?;
         let installed = models::list_models()?;
@@ -162,329 +531,3651 @@ impl AppState {
             .filter(|m| m.installed)
             .map(|m| m.id)
             .collect();
-        let mut config = self.config.lock().unwrap();
-        if config.active_model == model_id {
-            if installed_ids.contains(&config.preferred_model) {
-                config.active_model = config.preferred_model.clone();
-            } else if installed_ids.contains(&"base".to_string()) {
-                config.active_model = "base".to_string();
-            } else {
-                config.active_model = "none".to_string();
+        if self.config.snapshot().active_model == model_id {
+            self.config.update(|config| {
+                if installed_ids.contains(&config.preferred_model) {
+                    config.active_model = config.preferred_model.clone();
+                } else if installed_ids.contains(&"base".to_string()) {
+                    config.active_model = "base".to_string();
+                } else {
+                    config.active_model = "none".to_string();
+                }
+            })?;
+        }
+        self.apply_latency_budget()
+    }
+
+    /// Brings a model file into the models folder and keeps it only if the transcriber
+    /// can load it and run it on a second of silence.
+    pub async fn import_model(&self, path: PathBuf, name: String) -> Result<String> {
+        if managed_config::policy().allowed_models.is_some() {
+            return Err(CommandError::model_not_allowed().into());
+        }
+        let config = self.config.snapshot();
+        let model_id = task::spawn_blocking(move || -> Result<String> {
+            let model_id = models::import_model(&path, &name)?;
+            let loaded = (|| {
+                let silence = vec![0.0; 16_000];
+                let request = RequestBody::Transcribe {
+                    samples: silence.len(),
+                    partial: false,
+                    options: transcribe_options(&config),
+                };
+                let mut server =
+                    spawn_server(&model_id, &ServerOptions::from_config(&config, &model_id))?;
+                server.apply_decoding(&decoding_params(&config))?;
+                server.transcribe(&Running::default(), &request, &silence)
+            })();
+            if let Err(err) = loaded {
+                let _ = models::delete_model(&model_id);
+                return Err(err.context(format!("{} does not load", path.display())));
             }
-            save_config(&config)?;
+            Ok(model_id)
+        })
+        .await
+        .context("import model task")??;
+        self.apply_latency_budget()?;
+        Ok(model_id)
+    }
+
+    /// Picking a model by hand turns the latency budget off. Refused with
+    /// `ModelTooLarge` when the device lacks the memory to load it.
+    pub async fn set_active_model(&self, model_id: &str) -> Result<()> {
+        if !managed_config::policy().model_allowed(model_id) {
+            return Err(CommandError::model_not_allowed().into());
         }
-        Ok(())
+        if let Some(info) = models::get_model_info(model_id) {
+            let config = self.config.snapshot();
+            let context = ServerOptions::from_config(&config, model_id).context;
+            let server = self.transcribe.clone();
+            let model_id = model_id.to_string();
+            task::spawn_blocking(move || {
+                // The warm server makes way for the new model, so its memory counts as
+                // free; a busy one is still serving the active model.
+                let loaded = match server.try_lock() {
+                    Ok(guard) => guard.as_ref().map(|server| server.model_id.clone()),
+                    Err(_) => Some(config.active_model.clone()),
+                };
+                let freed_mb = loaded
+                    .filter(|loaded| *loaded != model_id)
+                    .and_then(|loaded| models::get_model_info(&loaded))
+                    .map_or(0, |loaded| memory::required_mb(loaded.size_mb));
+                memory::check_model_fits(
+                    &model_id,
+                    info.size_mb,
+                    &context.backend,
+                    context.gpu_device,
+                    freed_mb,
+                )
+            })
+            .await
+            .context("memory check task")??;
+        }
+        self.config.update(|config| {
+            config.active_model = model_id.to_string();
+            config.preferred_model = model_id.to_string();
+            config.max_latency_ms = 0;
+            config.latency_pick = None;
+        })
     }
 
-    pub fn set_active_model(&self, model_id: &str) -> Result<()> {
-        let mut config = self.config.lock().unwrap();
-        config.active_model = model_id.to_string();
-        config.preferred_model = model_id.to_string();
-        save_config(&config)?;
-        Ok(())
+    /// Lets the app choose the model and decoding mode that finish a typical dictation
+    /// within `max_latency_ms` on this machine; 0 turns it off and keeps the active model.
+    pub fn set_max_latency(&self, max_latency_ms: u64) -> Result<()> {
+        self.config.update(|config| {
+            config.max_latency_ms = max_latency_ms;
+        })?;
+        self.apply_latency_budget()
+    }
+
+    fn apply_latency_budget(&self) -> Result<()> {
+        apply_latency_budget(&self.config, &self.benchmarks)
     }
 
     pub fn get_settings(&self) -> Result<AppConfig> {
-        Ok(self.config.lock().unwrap().clone())
+        Ok((*self.config.snapshot()).clone())
     }
 
     pub fn set_language(&self, language: &str) -> Result<()> {
-        let mut config = self.config.lock().unwrap();
-        config.language = language.to_string();
-        save_config(&config)?;
-        Ok(())
+        self.config.update(|config| {
+            config.language = language.to_string();
+        })?;
+        // English-only models stop or start being candidates.
+        self.apply_latency_budget()
     }
 
-    pub fn import_license_file(&self, path: &str) -> Result<licensing::LicenseImportResponse> {
-        let mut config = self.config.lock().unwrap();
-        let import_result = licensing::import_license_file(
-            path,
-            &mut config,
-            &self.license_public_keys,
-            &self.license_issuer,
-        );
-        save_config(&config)?;
-        match import_result {
-            Ok(()) => Ok(licensing::build_import_response(&config)),
-            Err(err) => Err(err),
-        }
+    pub fn set_pinned_languages(&self, languages: Vec<String>) -> Result<()> {
+        self.config.update(|config| {
+            config.pinned_languages = languages
+                .into_iter()
+                .map(|language| language.trim().to_string())
+                .filter(|language| !language.is_empty())
+                .collect();
+        })
     }
 
-    pub fn remove_license(&self) -> Result<()> {
-        let mut config = self.config.lock().unwrap();
-        licensing::clear_license(&mut config);
-        save_config(&config)?;
-        Ok(())
+    /// Steps through the pinned languages, wrapping around; returns the new language.
+    pub fn cycle_language(&self, step: i32) -> Result<String> {
+        self.config.update(|config| {
+            let pinned = &config.pinned_languages;
+            if pinned.is_empty() {
+                return config.language.clone();
+            }
+            let next = match pinned.iter().position(|lang| *lang == config.language) {
+                Some(index) => {
+                    (index as i64 + step as i64).rem_euclid(pinned.len() as i64) as usize
+                }
+                None => 0,
+            };
+            config.language = pinned[next].clone();
+            config.language.clone()
+        })
     }
 
-    pub fn get_license_state(&self) -> Result<licensing::LicenseState> {
-        let mut config = self.config.lock().unwrap();
-        let validation = licensing::validate_current_license(
-            &mut config,
-            &self.license_public_keys,
-            &self.license_issuer,
-        )?;
-        save_config(&config)?;
-        Ok(licensing::build_license_state(&config, validation.message))
+    pub fn set_start_hidden(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.start_hidden = enabled;
+        })
     }
 
-    fn decrement_transcriptions(&self) -> Result<()> {
-        let mut config = self.config.lock().unwrap();
-        if config.entitlement == licensing::ENTITLEMENT_PRO
-            && config.license_status == licensing::LICENSE_STATUS_VALID
-        {
-            return Ok(());
-        }
-        if config.free_transcriptions_left > 0 {
-            config.free_transcriptions_left -= 1;
-            save_config(&config)?;
-        }
-        Ok(())
+    pub fn set_tray_middle_click_action(&self, action: &str) -> Result<()> {
+        let action = match action {
+            ACTION_TOGGLE_RECORDING => ACTION_TOGGLE_RECORDING,
+            ACTION_NEXT_LANGUAGE => ACTION_NEXT_LANGUAGE,
+            _ => ACTION_NONE,
+        };
+        self.config.update(|config| {
+            config.tray_middle_click_action = action.to_string();
+        })
     }
 
-    fn increment_total_transcriptions(&self) -> Result<()> {
-        let mut config = self.config.lock().unwrap();
-        config.total_transcriptions_count = config.total_transcriptions_count.saturating_add(1);
-        save_config(&config)?;
-        Ok(())
+    pub fn set_vad_auto_stop(
+        &self,
+        enabled: bool,
+        silence_ms: Option<u64>,
+        threshold: Option<f32>,
+    ) -> Result<()> {
+        self.config.update(|config| {
+            config.vad_auto_stop = enabled;
+            if let Some(silence_ms) = silence_ms {
+                config.vad_silence_ms = silence_ms.max(200);
+            }
+            if let Some(threshold) = threshold {
+                config.vad_threshold = threshold.clamp(0.0, 1.0);
+            }
+        })
     }
 
-    pub async fn preload_transcribe_server(&self, app: &AppHandle) -> Result<()> {
-        let config = self.config.lock().unwrap().clone();
-        let model_id = config.active_model.clone();
-        if model_id == "none" {
-            return Ok(());
-        }
-        let model_path = models::model_path(&model_id)?;
-        if !models::model_is_valid(&model_id)? {
-            self.download_model(app, &model_id).await?;
-        }
-        let model_path_str = model_path.to_string_lossy().to_string();
-        let mut guard = self.transcribe.lock().unwrap();
-        let needs_restart = guard
-            .as_ref()
-            .map(|s| s.model_id != model_id)
-            .unwrap_or(true);
-        if needs_restart {
-            *guard = Some(spawn_server(&model_id, &model_path_str)?);
+    pub fn set_sandbox_transcriber(&self, enabled: bool) -> Result<()> {
+        if enabled {
+            if self.config.snapshot().in_process_transcription {
+                anyhow::bail!("an in-process model cannot be sandboxed; turn that off first");
+            }
+            sandbox::check_available()?;
         }
-        Ok(())
+        self.config.update(|config| {
+            config.sandbox_transcriber = enabled;
+        })
     }
 
-    pub fn set_shortcut(&self, shortcut: &str) -> Result<()> {
-        let mut config = self.config.lock().unwrap();
-        config.shortcut = shortcut.to_string();
-        save_config(&config)?;
-        if let Some(parsed) = Hotkey::parse(shortcut) {
-            let mut hk = self.hotkey.lock().unwrap();
-            *hk = parsed;
-        }
-        if let Some(wayland) = &self.wayland_hotkeys {
-            wayland.update(shortcut.to_string());
-        }
-        Ok(())
+    pub fn list_compute_backends(&self) -> Vec<ComputeBackend> {
+        compute::list_backends()
     }
 
-    pub fn status(&self) -> StatusResponse {
-        let recording = self.recorder.is_recording();
-        StatusResponse { recording }
+    /// The GPUs found and what the active model will load on, with the reason when that
+    /// is the CPU.
+    pub async fn compute_report(&self) -> Result<ComputeReport> {
+        let config = self.config.snapshot();
+        let backend = ServerOptions::from_config(&config, &config.active_model)
+            .context
+            .backend;
+        task::spawn_blocking(move || compute::report(&backend))
+            .await
+            .context("compute report task")
     }
 
-    fn validate_recording_entitlement(&self, app: &AppHandle) -> Result<()> {
-        let mut config = self.config.lock().unwrap();
-        let validation = licensing::validate_current_license(
-            &mut config,
-            &self.license_public_keys,
-            &self.license_issuer,
-        )?;
-        let free_left = config.free_transcriptions_left;
-        save_config(&config)?;
-
-        if validation.is_pro() || free_left > 0 {
-            return Ok(());
+    /// Overrides how `model_id` is loaded; `None` goes back to the global backend. The next
+    /// transcription with that model reloads it.
+    pub fn set_model_compute(&self, model_id: &str, compute: Option<ModelCompute>) -> Result<()> {
+        if models::get_model_info(model_id).is_none() {
+            anyhow::bail!("unknown model {model_id}");
         }
+        self.config.update(|config| {
+            match compute {
+                Some(compute) => config.model_compute.insert(model_id.to_string(), compute),
+                None => config.model_compute.remove(model_id),
+            };
+        })
+    }
 
-        self.tray.set_mode(TrayMode::Error);
-        let error = CommandError::free_limit_reached();
-        let _ = app.emit(
-            "status:changed",
-            serde_json::json!({
-                "status": "error",
-                "code": error.code,
-                "message": error.message,
-            }),
-        );
-        Err(error.into())
+    /// Rejects backends this build lacks; the next transcription reloads the model.
+    pub fn set_compute_backend(&self, backend: &str) -> Result<()> {
+        compute::gpu_mode(backend)?;
+        self.config.update(|config| {
+            config.compute_backend = backend.to_string();
+        })?;
+        self.apply_latency_budget()
     }
 
-    pub fn start_recording(&self, app: &AppHandle) -> Result<()> {
-        if self.recorder.is_recording() {
-            return Ok(());
+    /// 0 lets a transcription run as long as it takes. The next transcription restarts
+    /// the transcriber with the new limit. An in-process model cannot be interrupted, so
+    /// it only takes 0.
+    pub fn set_transcription_timeout(&self, secs: u64) -> Result<()> {
+        if secs != 0 && self.config.snapshot().in_process_transcription {
+            anyhow::bail!("an in-process model cannot be timed out; turn that off first");
         }
-        self.validate_recording_entitlement(app)?;
-        self.recorder.start().context("start recorder")?;
-        self.tray.set_mode(TrayMode::Recording);
-        let _ = app.emit(
-            "status:changed",
-            serde_json::json!({ "status": "recording", "message": null }),
-        );
+        self.config.update(|config| {
+            config.transcription_timeout_secs = secs;
+        })
+    }
+
+    /// Takes effect from the next download; 1 fetches over a single connection.
+    pub fn set_download_connections(&self, connections: u32) -> Result<()> {
+        self.config.update(|config| {
+            config.download_connections = connections.clamp(1, models::MAX_DOWNLOAD_CONNECTIONS);
+        })
+    }
+
+    /// Routes model downloads and checkout through `url`, an http(s) or SOCKS5 proxy;
+    /// empty falls back to the proxy environment variables.
+    pub fn set_proxy(&self, url: &str) -> Result<()> {
+        proxy::validate(url)?;
+        self.config.update(|config| {
+            config.proxy_url = url.trim().to_string();
+        })?;
+        proxy::set(url);
         Ok(())
     }
 
-    pub async fn stop_recording(&self, app: &AppHandle) -> Result<String> {
-        if !self.recorder.is_recording() {
-            return Ok(String::new());
-        }
-        self.tray.set_mode(TrayMode::Processing);
-        let _ = app.emit(
-            "status:changed",
-            serde_json::json!({ "status": "processing", "message": null }),
-        );
-        let audio = resample_to_16k(self.recorder.stop()?);
-        if audio.samples.is_empty() {
-            self.tray.set_mode(TrayMode::Idle);
-            return Ok(String::new());
+    /// Takes effect on the next transcription, which loads the model in the new mode.
+    /// Refused while the sandbox or a timeout is on, since both need a child to act on.
+    pub fn set_in_process_transcription(&self, enabled: bool) -> Result<()> {
+        let config = self.config.snapshot();
+        if enabled && (config.sandbox_transcriber || config.transcription_timeout_secs != 0) {
+            anyhow::bail!(
+                "in-process transcription cannot be sandboxed or timed out; turn off the transcriber sandbox and set the timeout to 0 first"
+            );
         }
-        let config = self.config.lock().unwrap().clone();
-        let model_id = config.active_model.clone();
-        let model_path = models::model_path(&model_id)?;
-        if !models::model_is_valid(&model_id)? {
-            self.download_model(app, &model_id).await?;
-        }
-        let wav_path = write_temp_wav(&audio.samples)?;
-        let model_path_str = model_path.to_string_lossy().to_string();
-        let wav_path_str = wav_path.to_string_lossy().to_string();
-        let server = self.transcribe.clone();
-        let model_id_clone = model_id.clone();
-        let start = std::time::Instant::now();
-        let language = config.language.clone();
-        let text_result = task::spawn_blocking(move || {
-            transcribe_with_server(
-                server,
-                &model_id_clone,
-                &model_path_str,
-                &wav_path_str,
-                &language,
-            )
+        self.config.update(|config| {
+            config.in_process_transcription = enabled;
         })
-        .await
-        .context("transcribe task")?;
-        let text = match text_result {
-            Ok(text) => text,
-            Err(err) => {
-                self.tray.set_mode(TrayMode::Error);
-                let _ = app.emit(
-                    "status:changed",
-                    serde_json::json!({ "status": "error", "message": err.to_string() }),
-                );
-                return Err(err);
+    }
+
+    pub fn set_output_mode(&self, mode: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.output_mode = match mode {
+                OUTPUT_PROGRESSIVE => OUTPUT_PROGRESSIVE,
+                OUTPUT_TYPE => OUTPUT_TYPE,
+                OUTPUT_CLIPBOARD => OUTPUT_CLIPBOARD,
+                _ => OUTPUT_PASTE,
             }
-        };
-        let _ = fs::remove_file(&wav_path);
-        if !text.is_empty() {
-            let _ = paste_text(&text);
-            let _ = self.increment_total_transcriptions();
-            let _ = self.decrement_transcriptions();
+            .to_string();
+        })
+    }
+
+    pub fn set_paste_keys(&self, keys: &str) -> Result<()> {
+        if !paste::is_paste_keys(keys) {
+            anyhow::bail!("unknown paste keys {keys}");
         }
-        let _ = app.emit(
-            "transcription:result",
-            TranscriptionEvent {
-                text: text.clone(),
-                model_id: model_id.clone(),
-                duration_ms: start.elapsed().as_millis() as u64,
-            },
-        );
-        self.tray.set_mode(TrayMode::Idle);
-        let _ = app.emit(
-            "status:changed",
-            serde_json::json!({ "status": "idle", "message": null }),
-        );
-        Ok(text)
+        self.config.update(|config| {
+            config.paste_keys = keys.to_string();
+        })
     }
-}
 
-fn write_temp_wav(samples: &[f32]) -> Result<PathBuf> {
-    let mut path = env::temp_dir();
-    let stamp = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis();
-    path.push(format!("whisperdict-{}.wav", stamp));
+    /// Remembers keyboard access granted through the RemoteDesktop portal.
+    pub fn set_remote_desktop_token(&self, token: String) -> Result<()> {
+        self.config.update(|config| {
+            config.remote_desktop_token = token;
+        })
+    }
 
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 16_000,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let mut writer = hound::WavWriter::create(&path, spec).context("create wav")?;
-    for &sample in samples {
-        let clamped = sample.clamp(-1.0, 1.0);
-        let value = (clamped * i16::MAX as f32) as i16;
-        writer.write_sample(value).context("write wav sample")?;
+    pub fn set_append_after(&self, append: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.append_after = match append {
+                APPEND_SPACE => APPEND_SPACE,
+                APPEND_NEWLINE => APPEND_NEWLINE,
+                _ => APPEND_NONE,
+            }
+            .to_string();
+        })
     }
-    writer.finalize().context("finalize wav")?;
-    Ok(path)
-}
 
-struct TranscribeServer {
-    model_id: String,
-    stdin: ChildStdin,
-    stdout: BufReader<ChildStdout>,
-}
+    pub fn set_restore_clipboard(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.restore_clipboard = enabled;
+        })
+    }
 
-fn transcribe_with_server(
-    server: Arc<Mutex<Option<TranscribeServer>>>,
-    model_id: &str,
-    model_path: &str,
-    wav_path: &str,
-    language: &str,
-) -> Result<String> {
-    let mut guard = server.lock().unwrap();
-    let needs_restart = guard
-        .as_ref()
-        .map(|s| s.model_id != model_id)
-        .unwrap_or(true);
+    pub fn set_clipboard_guard(&self, mode: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.clipboard_guard = match mode {
+                CLIPBOARD_GUARD_OFF => CLIPBOARD_GUARD_OFF,
+                CLIPBOARD_GUARD_REVIEW => CLIPBOARD_GUARD_REVIEW,
+                _ => CLIPBOARD_GUARD_RESTORE,
+            }
+            .to_string();
+        })
+    }
 
-    if needs_restart {
-        *guard = Some(spawn_server(model_id, model_path)?);
+    pub fn set_bidi_marks(&self, mode: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.bidi_marks = match mode {
+                BIDI_MARKS => BIDI_MARKS,
+                BIDI_EMBEDDING => BIDI_EMBEDDING,
+                _ => BIDI_OFF,
+            }
+            .to_string();
+        })
     }
 
-    let srv = guard.as_mut().context("missing server")?;
-    writeln!(srv.stdin, "{}\t{}", language, wav_path).context("write wav path")?;
-    srv.stdin.flush().context("flush stdin")?;
-    let mut line = String::new();
-    let read = srv.stdout.read_line(&mut line).context("read child")?;
-    if read == 0 || line.trim().is_empty() {
-        *guard = Some(spawn_server(model_id, model_path)?);
-        let srv = guard.as_mut().context("missing server")?;
-        writeln!(srv.stdin, "{}\t{}", language, wav_path).context("write wav path retry")?;
-        srv.stdin.flush().context("flush stdin retry")?;
-        line.clear();
-        srv.stdout
-            .read_line(&mut line)
-            .context("read child retry")?;
+    pub fn set_event_limits(
+        &self,
+        throttle_ms: BTreeMap<String, u64>,
+        max_string_len: usize,
+    ) -> Result<()> {
+        self.config.update(|config| {
+            config.event_throttle_ms = throttle_ms;
+            config.max_event_string_len = max_string_len;
+        })
     }
-    Ok(line.trim().to_string())
-}
 
-fn spawn_server(model_id: &str, model_path: &str) -> Result<TranscribeServer> {
-    let exe = env::current_exe().context("current exe")?;
-    let mut child = Command::new(exe)
-        .arg("--transcribe-server")
-        .arg("--model")
-        .arg(model_path)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .context("spawn server")?;
+    pub fn set_streaming_partials(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.streaming_partials = enabled;
+        })
+    }
 
-    let stdin = child.stdin.take().context("child stdin")?;
-    let stdout = child.stdout.take().context("child stdout")?;
-    Ok(TranscribeServer {
-        model_id: model_id.to_string(),
-        stdin,
-        stdout: BufReader::new(stdout),
-    })
+    pub fn set_read_aloud(&self, mode: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.read_aloud = match mode {
+                READ_ALOUD_BEFORE => READ_ALOUD_BEFORE,
+                READ_ALOUD_AFTER => READ_ALOUD_AFTER,
+                _ => READ_ALOUD_OFF,
+            }
+            .to_string();
+        })
+    }
+
+    pub fn set_gain_mode(&self, mode: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.gain_mode = match mode {
+                GAIN_PEAK => GAIN_PEAK,
+                GAIN_AGC => GAIN_AGC,
+                _ => GAIN_OFF,
+            }
+            .to_string();
+        })
+    }
+
+    pub fn get_diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            components: self.supervisor.components(),
+            ..self.diagnostics.lock().unwrap().clone()
+        }
+    }
+
+    pub fn set_preroll(&self, enabled: bool, preroll_ms: Option<u64>) -> Result<()> {
+        let preroll_ms = self.config.update(|config| {
+            config.preroll_enabled = enabled;
+            if let Some(preroll_ms) = preroll_ms {
+                config.preroll_ms = preroll_ms.clamp(MIN_PREROLL_MS, MAX_PREROLL_MS);
+            }
+            config.preroll_ms
+        })?;
+        self.recorder
+            .set_preroll(enabled.then(|| Duration::from_millis(preroll_ms)))?;
+        Ok(())
+    }
+
+    pub fn set_capture_source(&self, source: &str) -> Result<()> {
+        let source = match source {
+            SOURCE_SYSTEM => SOURCE_SYSTEM,
+            SOURCE_MIXED => SOURCE_MIXED,
+            _ => SOURCE_MICROPHONE,
+        };
+        self.config.update(|config| {
+            config.capture_source = source.to_string();
+        })?;
+        self.recorder.set_source(source)?;
+        Ok(())
+    }
+
+    pub fn set_avoid_bluetooth_input(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.avoid_bluetooth_input = enabled;
+        })?;
+        self.recorder.set_avoid_bluetooth(enabled)?;
+        Ok(())
+    }
+
+    pub fn set_compress_recording(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.compress_recording = enabled;
+        })?;
+        self.recorder.set_compress_recording(enabled)?;
+        Ok(())
+    }
+
+    pub fn set_echo_cancellation(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.echo_cancellation = enabled;
+        })?;
+        self.recorder.set_echo_cancellation(enabled)?;
+        Ok(())
+    }
+
+    pub fn set_presentation_mode(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.presentation_mode = enabled;
+        })?;
+        self.tray.set_presentation(enabled);
+        Ok(())
+    }
+
+    /// Records a few seconds from the configured microphone outside of a dictation,
+    /// optionally playing it back, so a broken setup shows up in settings.
+    pub async fn test_microphone(&self, playback: bool) -> Result<MicrophoneTest> {
+        if self.recorder.is_recording() {
+            anyhow::bail!("cannot test the microphone while recording");
+        }
+        let config = self.config.snapshot();
+        let options = CaptureOptions {
+            source: SOURCE_MICROPHONE.to_string(),
+            input_channels: config.input_channels.clone(),
+            avoid_bluetooth: config.avoid_bluetooth_input,
+            host: config.audio_host.clone(),
+            echo_cancellation: false,
+            compress: false,
+        };
+        task::spawn_blocking(move || -> Result<MicrophoneTest> {
+            let mut recorder = Recorder::open(Arc::new(LevelMeter::new()), &options)?;
+            let device = recorder.device_name().to_string();
+            let started = Instant::now();
+            recorder.begin(started, None)?;
+            while started.elapsed() < MIC_TEST_DURATION {
+                std::thread::sleep(Duration::from_millis(100));
+                recorder.drain();
+            }
+            let buffer = recorder.stop()?.buffer;
+            if buffer.samples.is_empty() || buffer.sample_rate == 0 {
+                anyhow::bail!("no audio was captured from {device}");
+            }
+            let peak = buffer
+                .samples
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            let rms = (buffer.samples.iter().map(|s| s * s).sum::<f32>()
+                / buffer.samples.len() as f32)
+                .sqrt();
+            let duration_ms = buffer.samples.len() as u64 * 1000 / buffer.sample_rate as u64;
+            if playback {
+                audio::play_buffer(buffer, &options.host)?;
+            }
+            Ok(MicrophoneTest {
+                device,
+                peak,
+                rms,
+                duration_ms,
+            })
+        })
+        .await
+        .context("microphone test task")?
+    }
+
+    /// Runs the pipeline on a phrase spoken by the system voice, skipping the microphone
+    /// and the paste keystroke, and reports which stage breaks; the transcript must
+    /// contain the phrase. Without a system voice a tone is decoded instead, which only
+    /// has to come through the model without an error.
+    pub async fn self_test(&self) -> Result<SelfTestReport> {
+        let config = self.config.snapshot();
+        let server = self.transcribe.clone();
+        task::spawn_blocking(move || {
+            let mut test = SelfTest::default();
+            let sample = test.run(self_test::STAGE_AUDIO, || Ok(self_test::sample()));
+            if sample
+                .as_ref()
+                .is_some_and(|sample| sample.phrase.is_none())
+            {
+                test.note("no system voice installed, decoding a tone".to_string());
+            }
+            let phrase = sample.as_ref().and_then(|sample| sample.phrase);
+            let audio = match sample {
+                Some(sample) => test.run(self_test::STAGE_RESAMPLE, || {
+                    let audio = resample_for_whisper(sample.audio.clone(), &config.resampler);
+                    self_test::check_resampled(&sample.audio, &audio)?;
+                    Ok(audio)
+                }),
+                None => {
+                    test.skip(self_test::STAGE_RESAMPLE);
+                    None
+                }
+            };
+            let model = test.run(self_test::STAGE_MODEL, || {
+                if !models::model_is_valid(&config.active_model)? {
+                    anyhow::bail!("model {} is not downloaded", config.active_model);
+                }
+                Ok(())
+            });
+            let transcript = match (audio, model) {
+                (Some(audio), Some(())) => test.run(self_test::STAGE_TRANSCRIBE, || {
+                    let transcript = transcribe_with_server(
+                        server,
+                        &Running::default(),
+                        &config.active_model,
+                        &audio.samples,
+                        &transcribe_options(&config),
+                        &ServerOptions::from_config(&config, &config.active_model),
+                        &decoding_params(&config),
+                    )?;
+                    if phrase.is_some() {
+                        self_test::check_heard(&transcript.text)?;
+                    }
+                    Ok(transcript)
+                }),
+                _ => {
+                    test.skip(self_test::STAGE_TRANSCRIBE);
+                    None
+                }
+            };
+            if let Some(transcript) = transcript.as_ref().filter(|t| !t.text.is_empty()) {
+                test.note(format!("heard \"{}\"", transcript.text));
+            }
+            let text = match transcript {
+                Some(transcript) => test.run(self_test::STAGE_POST_PROCESSING, || {
+                    let language = if transcript.language.is_empty() {
+                        config.language.clone()
+                    } else {
+                        transcript.language
+                    };
+                    let (text, _) =
+                        post_process(&transcript.text, transcript.segments, &language, &config);
+                    Ok(text)
+                }),
+                None => {
+                    test.skip(self_test::STAGE_POST_PROCESSING);
+                    None
+                }
+            };
+            match text {
+                Some(text) => {
+                    // The tone may decode to nothing, which some clipboards do not hold.
+                    let text = if text.is_empty() {
+                        self_test::PHRASE
+                    } else {
+                        &text
+                    };
+                    test.run(self_test::STAGE_CLIPBOARD, || {
+                        self_test::check_clipboard(text)
+                    });
+                }
+                None => test.skip(self_test::STAGE_CLIPBOARD),
+            }
+            test.finish()
+        })
+        .await
+        .context("self-test task")
+    }
+
+    pub fn list_audio_hosts(&self) -> Vec<AudioHost> {
+        audio::list_hosts()
+    }
+
+    pub fn set_audio_host(&self, host: &str) -> Result<()> {
+        let known = audio::list_hosts()
+            .iter()
+            .any(|candidate| candidate.name.eq_ignore_ascii_case(host));
+        let host = if known { host } else { HOST_AUTO };
+        self.config.update(|config| {
+            config.audio_host = host.to_string();
+        })?;
+        self.recorder.set_host(host)?;
+        Ok(())
+    }
+
+    pub fn get_input_device(&self) -> Result<InputDevice> {
+        audio::default_input_device(&self.config.snapshot().audio_host)
+    }
+
+    /// Pins `device` to one input channel, or restores the downmix with `None`.
+    pub fn set_input_channel(&self, device: &str, channel: Option<u16>) -> Result<()> {
+        let input_channels = self.config.update(|config| {
+            match channel {
+                Some(channel) => config.input_channels.insert(device.to_string(), channel),
+                None => config.input_channels.remove(device),
+            };
+            config.input_channels.clone()
+        })?;
+        self.recorder.set_input_channels(input_channels)?;
+        Ok(())
+    }
+
+    pub fn set_recording_retention(
+        &self,
+        enabled: bool,
+        dir: Option<String>,
+        keep_count: Option<u32>,
+        keep_days: Option<u32>,
+    ) -> Result<()> {
+        let config = self.config.update(|config| {
+            config.keep_recordings = enabled;
+            config.recordings_dir = dir.filter(|dir| !dir.trim().is_empty());
+            if let Some(keep_count) = keep_count {
+                config.recordings_keep_count = keep_count;
+            }
+            if let Some(keep_days) = keep_days {
+                config.recordings_keep_days = keep_days;
+            }
+            config.clone()
+        })?;
+        if config.keep_recordings {
+            let dir = recordings_dir(&config)?;
+            if dir.exists() {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                retention::prune(
+                    &dir,
+                    now,
+                    config.recordings_keep_count,
+                    config.recordings_keep_days,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_input_monitoring(&self, enabled: bool, volume: Option<f32>) -> Result<()> {
+        let config = self.config.update(|config| {
+            config.monitor_input = enabled;
+            if let Some(volume) = volume {
+                config.monitor_volume = volume.clamp(0.0, 1.0);
+            }
+            config.clone()
+        })?;
+        self.recorder.set_monitor_volume(config.monitor_volume);
+        self.recorder.set_monitor(enabled)?;
+        Ok(())
+    }
+
+    pub fn set_sound_cues(
+        &self,
+        on_start: bool,
+        on_stop: bool,
+        on_paste: bool,
+        volume: Option<f32>,
+    ) -> Result<()> {
+        self.config.update(|config| {
+            config.cue_on_start = on_start;
+            config.cue_on_stop = on_stop;
+            config.cue_on_paste = on_paste;
+            if let Some(volume) = volume {
+                config.cue_volume = volume.clamp(0.0, 1.0);
+            }
+        })
+    }
+
+    pub fn set_max_recording_secs(&self, seconds: u64) -> Result<()> {
+        self.config.update(|config| {
+            config.max_recording_secs = seconds;
+        })
+    }
+
+    pub fn set_low_latency(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.low_latency = enabled;
+        })?;
+        self.recorder.set_low_latency(enabled)?;
+        Ok(())
+    }
+
+    pub fn set_resampler(&self, resampler: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.resampler = if resampler == RESAMPLER_LINEAR {
+                RESAMPLER_LINEAR.to_string()
+            } else {
+                RESAMPLER_SINC.to_string()
+            };
+        })
+    }
+
+    /// A rejected license still replaces the old one, so the quota is re-sent either way.
+    pub fn import_license_file(
+        &self,
+        app: &AppHandle,
+        path: &str,
+    ) -> Result<licensing::LicenseImportResponse> {
+        let imported = self.config.update(|config| {
+            licensing::import_license_file(
+                path,
+                config,
+                &self.license_public_keys,
+                &self.license_issuer,
+            )
+            .map(|()| licensing::build_import_response(config))
+        })?;
+        self.emit_quota(app);
+        imported
+    }
+
+    pub fn remove_license(&self, app: &AppHandle) -> Result<()> {
+        self.config.update(|config| {
+            licensing::clear_license(config);
+        })?;
+        self.emit_quota(app);
+        Ok(())
+    }
+
+    pub fn get_license_state(&self) -> Result<licensing::LicenseState> {
+        self.config.update(|config| {
+            let validation = licensing::validate_current_license(
+                config,
+                &self.license_public_keys,
+                &self.license_issuer,
+            )?;
+            Ok(licensing::build_license_state(config, validation.message))
+        })?
+    }
+
+    /// Charges a finished dictation to the quota and, when `counted`, the totals. The
+    /// entry reaches the journal before the config, so a crash in between is replayed
+    /// at the next start instead of lost.
+    fn record_usage(&self, audio_ms: u64, counted: bool) -> Result<()> {
+        self.config.update(|config| {
+            let entry = UsageEntry {
+                seq: config.usage_seq + 1,
+                audio_ms,
+                at: unix_timestamp(),
+                counted,
+            };
+            let appended = usage::journal_path().and_then(|path| usage::append(&path, &entry));
+            if let Err(err) = appended {
+                eprintln!("usage journal write failed: {err:#}");
+            }
+            usage::apply(config, &entry, global_config::free_quota());
+        })
+    }
+
+    /// Applies journal entries the config missed because the app died between the two
+    /// writes, then drops the journal. It is dropped even when nothing in it could be
+    /// read, so the next entry is not appended onto a line torn by the crash.
+    fn replay_usage_journal(&self) -> Result<()> {
+        let path = usage::journal_path()?;
+        let entries = usage::read(&path)?;
+        if !entries.is_empty() {
+            let policy = global_config::free_quota();
+            self.config.update(|config| {
+                for entry in &entries {
+                    usage::apply(config, entry, policy);
+                }
+            })?;
+        }
+        usage::compact(&path)
+    }
+
+    /// Saves the config once more and drops the journal only after that save succeeded;
+    /// otherwise the journal stays for `replay_usage_journal` at the next start.
+    fn flush_usage_journal(&self) -> Result<()> {
+        self.config.update(|_| ())?;
+        usage::compact(&usage::journal_path()?)
+    }
+
+    pub fn get_quota(&self) -> QuotaState {
+        quota::state(
+            &self.config.snapshot(),
+            global_config::free_quota(),
+            unix_timestamp(),
+        )
+    }
+
+    fn emit_quota(&self, app: &AppHandle) {
+        let quota = self.get_quota();
+        self.tray.set_tooltip(Some(&quota_tooltip(&quota)));
+        self.events.emit(app, "quota:changed", quota);
+    }
+
+    fn set_clipping_tooltip(&self, clipping: bool) {
+        if clipping {
+            self.tray.set_tooltip(Some(
+                "Whisperdict: input is clipping, lower the microphone gain",
+            ));
+        } else {
+            self.reset_tooltip();
+        }
+    }
+
+    fn reset_tooltip(&self) {
+        self.tray
+            .set_tooltip(Some(&quota_tooltip(&self.get_quota())));
+    }
+
+    /// Marks the app as quitting; true only for the first call, which must then run
+    /// `shutdown`.
+    pub fn begin_shutdown(&self) -> bool {
+        !self.shutting_down.swap(true, Ordering::SeqCst)
+    }
+
+    /// Finishes the dictation in progress within `SHUTDOWN_GRACE`, otherwise keeps its
+    /// audio for `retranscribe_last`, then stops the transcriber and flushes what is only
+    /// journaled so far.
+    pub async fn shutdown(&self, app: &AppHandle) {
+        let finish = async {
+            if self.recorder.is_recording() {
+                let _ = self.stop_recording(app).await;
+            }
+            while self.in_flight.lock().unwrap().is_some() {
+                tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        };
+        if tokio::time::timeout(SHUTDOWN_GRACE, finish).await.is_err() {
+            if let Some(samples) = self.in_flight.lock().unwrap().take() {
+                let saved =
+                    retention::failed_recording_path().and_then(|path| write_wav(&path, &samples));
+                if let Err(err) = saved {
+                    eprintln!("saving interrupted dictation failed: {err:#}");
+                }
+            }
+        }
+        self.cancel_processing();
+        terminate_transcribers();
+        if let Err(err) = self.flush_usage_journal() {
+            eprintln!("flushing usage journal failed: {err:#}");
+        }
+        if let Err(err) = latency::save_benchmarks(&self.benchmarks.lock().unwrap()) {
+            eprintln!("saving benchmarks failed: {err:#}");
+        }
+        self.tray.set_mode(TrayMode::Idle);
+    }
+
+    /// Kills the transcriber mid-request; returns false when nothing was processing or the
+    /// model runs in-process, where a decode cannot be interrupted.
+    pub fn cancel_processing(&self) -> bool {
+        let config = self.config.snapshot();
+        if ServerOptions::from_config(&config, &config.active_model).in_process {
+            return false;
+        }
+        self.running_child.cancel()
+    }
+
+    fn escalate_slow_processing(&self, app: &AppHandle, model_id: &str, recording_ms: u64) {
+        self.events.emit(
+            app,
+            "processing:slow",
+            serde_json::json!({ "modelId": model_id, "recordingMs": recording_ms }),
+        );
+        self.tray.set_tooltip(Some(
+            "Whisperdict: transcription is taking longer than usual",
+        ));
+        if self.config.snapshot().presentation_mode {
+            return;
+        }
+        let handle = app.clone();
+        app.dialog()
+            .message("Transcription is taking longer than usual. The machine may be overloaded or the model too large for it.")
+            .title("Whisperdict")
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "Keep waiting".to_string(),
+                "Cancel".to_string(),
+            ))
+            .show(move |keep_waiting| {
+                if !keep_waiting {
+                    handle.state::<AppState>().cancel_processing();
+                }
+            });
+    }
+
+    pub fn record_correction(
+        &self,
+        app: &AppHandle,
+        original: &str,
+        corrected: &str,
+    ) -> Result<Vec<CorrectionSuggestion>> {
+        let mut store = self.corrections.lock().unwrap();
+        let reached = store.record(original, corrected, unix_timestamp());
+        corrections::save_store(&store)?;
+        if !reached.is_empty() {
+            self.events
+                .emit(app, "corrections:suggested", reached.clone());
+        }
+        Ok(reached)
+    }
+
+    pub fn list_correction_suggestions(&self) -> Result<Vec<CorrectionSuggestion>> {
+        let config = self.config.snapshot();
+        let store = self.corrections.lock().unwrap();
+        Ok(store
+            .suggestions()
+            .into_iter()
+            .filter(|suggestion| {
+                !config
+                    .replacements
+                    .iter()
+                    .any(|rule| rule.from.eq_ignore_ascii_case(&suggestion.from))
+            })
+            .collect())
+    }
+
+    pub fn accept_correction_suggestion(&self, from: &str, to: &str) -> Result<()> {
+        self.config.update(|config| {
+            dictionary::set_replacement(config, from, to, false, unix_timestamp());
+        })?;
+        let mut store = self.corrections.lock().unwrap();
+        store.remove(from, to);
+        corrections::save_store(&store)?;
+        Ok(())
+    }
+
+    pub fn list_replacements(&self) -> Vec<ReplacementRule> {
+        self.config.snapshot().replacements.clone()
+    }
+
+    pub fn set_replacement(&self, from: &str, to: &str, regex: bool) -> Result<()> {
+        // Leading and trailing whitespace can be meaningful in a pattern.
+        let from = if regex { from } else { from.trim() };
+        post_processing::validate_rule(from, regex)?;
+        self.config.update(|config| {
+            dictionary::set_replacement(config, from, to, regex, unix_timestamp());
+        })
+    }
+
+    pub fn remove_replacement(&self, from: &str) -> Result<()> {
+        self.config.update(|config| {
+            dictionary::remove_replacement(config, from, unix_timestamp());
+        })
+    }
+
+    pub fn list_app_rules(&self) -> Vec<AppRule> {
+        self.config.snapshot().app_rules.clone()
+    }
+
+    /// Adds `rule`, replacing the one for the same app and title.
+    pub fn set_app_rule(&self, mut rule: AppRule) -> Result<()> {
+        rule.app = rule.app.trim().to_string();
+        rule.title = rule.title.trim().to_string();
+        if rule.app.is_empty() && rule.title.is_empty() {
+            anyhow::bail!("application name and window title are empty");
+        }
+        rule.paste_keys = rule.paste_keys.filter(|keys| keys != PASTE_KEYS_AUTO);
+        if let Some(keys) = &rule.paste_keys {
+            if !paste::is_paste_keys(keys) {
+                anyhow::bail!("unknown paste keys {keys}");
+            }
+        }
+        if let Some(mode) = &rule.output_mode {
+            let modes = [
+                OUTPUT_PASTE,
+                OUTPUT_PROGRESSIVE,
+                OUTPUT_TYPE,
+                OUTPUT_CLIPBOARD,
+            ];
+            if !modes.contains(&mode.as_str()) {
+                anyhow::bail!("unknown output mode {mode}");
+            }
+        }
+        if let Some(append) = &rule.append_after {
+            if ![APPEND_NONE, APPEND_SPACE, APPEND_NEWLINE].contains(&append.as_str()) {
+                anyhow::bail!("unknown text to append {append}");
+            }
+        }
+        rule.language = rule
+            .language
+            .map(|language| language.trim().to_string())
+            .filter(|language| !language.is_empty());
+        self.config.update(|config| {
+            config
+                .app_rules
+                .retain(|existing| !existing.same_target(&rule));
+            config.app_rules.push(rule);
+        })
+    }
+
+    pub fn remove_app_rule(&self, app: &str, title: &str) -> Result<()> {
+        let target = AppRule {
+            app: app.to_string(),
+            title: title.to_string(),
+            send_enter: false,
+            paste_keys: None,
+            output_mode: None,
+            append_after: None,
+            language: None,
+        };
+        self.config.update(|config| {
+            config.app_rules.retain(|rule| !rule.same_target(&target));
+        })
+    }
+
+    /// The window dictation would go to now; its title only when `title` asks for it, as
+    /// reading it may need extra permissions.
+    pub async fn focused_window(&self, title: bool) -> Option<FocusedWindow> {
+        task::spawn_blocking(move || focused_window::focused(title))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub fn export_dictionary(&self, path: &str) -> Result<()> {
+        let file = dictionary::export(&self.config.snapshot(), unix_timestamp());
+        dictionary::write_file(Path::new(path), &file)
+    }
+
+    pub fn import_dictionary(&self, path: &str) -> Result<MergeSummary> {
+        let incoming = dictionary::read_file(Path::new(path))?;
+        self.config
+            .update(|config| dictionary::merge(config, &incoming))
+    }
+
+    /// Two-way sync through a shared file (e.g. in a synced folder): merge what other
+    /// machines wrote, then write the merged dictionary back.
+    pub fn sync_dictionary(&self, path: &str) -> Result<MergeSummary> {
+        let path = Path::new(path);
+        let summary = if path.exists() {
+            let incoming = dictionary::read_file(path)?;
+            self.config
+                .update(|config| dictionary::merge(config, &incoming))?
+        } else {
+            MergeSummary::default()
+        };
+        let file = dictionary::export(&self.config.snapshot(), unix_timestamp());
+        dictionary::write_file(path, &file)?;
+        Ok(summary)
+    }
+
+    pub fn dismiss_correction_suggestion(&self, from: &str, to: &str) -> Result<()> {
+        let mut store = self.corrections.lock().unwrap();
+        store.dismiss(from, to);
+        corrections::save_store(&store)?;
+        Ok(())
+    }
+
+    pub fn list_history(
+        &self,
+        query: Option<&str>,
+        tag: Option<&str>,
+        page: usize,
+        page_size: usize,
+    ) -> Result<HistoryPage> {
+        let entries = self.history.lock().unwrap();
+        Ok(history::paginate(&entries, query, tag, page, page_size))
+    }
+
+    pub fn export_history(&self, path: &str, tag: Option<&str>) -> Result<usize> {
+        let entries = self.history.lock().unwrap().clone();
+        history::export(Path::new(path), &entries, tag)
+    }
+
+    fn history_segment(&self, id: u64, index: usize) -> Result<String> {
+        let entries = self.history.lock().unwrap();
+        let entry = entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .context("history entry not found")?;
+        let segment = entry
+            .segments
+            .get(index)
+            .context("history segment not found")?;
+        Ok(segment.text.clone())
+    }
+
+    pub fn copy_history_segment(&self, id: u64, index: usize) -> Result<()> {
+        let text = self.history_segment(id, index)?;
+        Clipboard::new()?.set_text(text)?;
+        Ok(())
+    }
+
+    /// Pastes a history segment into the app behind the history window, which is hidden
+    /// first so that app has focus again when its rules are looked up and the keys sent.
+    pub async fn paste_history_segment(
+        &self,
+        app: &AppHandle,
+        id: u64,
+        index: usize,
+    ) -> Result<()> {
+        let text = self.history_segment(id, index)?;
+        windows::hide_history_window(app)?;
+        let snapshot = self.config.snapshot();
+        task::spawn_blocking(move || -> Result<()> {
+            std::thread::sleep(FOCUS_RETURN_DELAY);
+            // Asked for from the history window itself, so never hold it back there.
+            let guard = if snapshot.clipboard_guard == CLIPBOARD_GUARD_OFF {
+                CLIPBOARD_GUARD_OFF
+            } else {
+                CLIPBOARD_GUARD_RESTORE
+            };
+            let window = lookup_focused_window(&snapshot);
+            let (config, _) = config_for_window(snapshot, window.as_ref());
+            let app = window.as_ref().map(|window| window.app.as_str());
+            let keys = paste::resolve_paste_keys(&config.paste_keys, app);
+            paste_guarded(&text, guard, config.restore_clipboard, keys)?;
+            Ok(())
+        })
+        .await
+        .context("paste task")?
+    }
+
+    pub fn delete_history_entry(&self, id: u64) -> Result<()> {
+        let mut entries = self.history.lock().unwrap();
+        entries.retain(|entry| entry.id != id);
+        history::rewrite_history(&entries)?;
+        Ok(())
+    }
+
+    pub fn clear_history(&self) -> Result<()> {
+        let mut entries = self.history.lock().unwrap();
+        entries.clear();
+        history::rewrite_history(&entries)?;
+        Ok(())
+    }
+
+    /// Transcribes the most recent of the recording kept from a dictation that failed
+    /// every retry and the last successful dictation, with `model_id` or the active model,
+    /// and copies the text. A recovered dictation is recorded in history and charged like
+    /// a new one, the recording deleted once it succeeds; a repeated one revises its
+    /// history entry and is charged to the quota but not counted again.
+    pub async fn retranscribe_last(
+        &self,
+        app: &AppHandle,
+        model_id: Option<String>,
+    ) -> Result<String> {
+        if !self.is_entitled()? {
+            return Err(CommandError::free_limit_reached().into());
+        }
+        let path = retention::failed_recording_path()?;
+        let failed_at = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|since| since.as_millis() as u64);
+        let last_at = self
+            .last_audio
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|last| last.created_at);
+        // A failed recording left over from an earlier session must not shadow the
+        // dictation that came after it.
+        let recovering = match (failed_at, last_at) {
+            (Some(failed_at), Some(last_at)) => failed_at > last_at,
+            (failed_at, _) => failed_at.is_some(),
+        };
+        let (audio, revises) = if recovering {
+            let wav_path = path.clone();
+            let audio = task::spawn_blocking(move || retention::read_recording(&wav_path))
+                .await
+                .context("read recording task")??;
+            (Arc::new(audio), None)
+        } else {
+            let last = self.last_audio.lock().unwrap().clone();
+            let last = last.context("no dictation to transcribe again")?;
+            (last.samples, Some(last.history_id))
+        };
+        let config = self.config.snapshot();
+        let model_id = model_id.unwrap_or_else(|| config.active_model.clone());
+        if !managed_config::policy().model_allowed(&model_id) {
+            return Err(CommandError::model_not_allowed().into());
+        }
+        if !models::model_is_valid(&model_id)? {
+            self.download_model(app, &model_id).await?;
+        }
+        let audio_len = audio.len();
+        let start = Instant::now();
+        let transcript = self
+            .transcribe_with_model(
+                &config,
+                &model_id,
+                audio,
+                transcribe_options(&config),
+                self.running_child.clone(),
+            )
+            .await
+            .context("transcribe task");
+        self.release_other(&config, &model_id);
+        let transcript = transcript??;
+        let audio_ms = audio_len as u64 * 1_000 / 16_000;
+        self.record_usage(audio_ms, revises.is_none())?;
+        self.emit_quota(app);
+        let language = if transcript.language.is_empty() {
+            config.language.clone()
+        } else {
+            transcript.language
+        };
+        let (text, segments) =
+            post_process(&transcript.text, transcript.segments, &language, &config);
+        if let Some(id) = revises {
+            if !text.is_empty() {
+                copy_text(&text)?;
+                self.revise_history(app, id, model_id, text.clone(), segments)?;
+            }
+            return Ok(text);
+        }
+        if !text.is_empty() {
+            copy_text(&text)?;
+            let created_at = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            let _ = self.record_history(
+                app,
+                HistoryEntry {
+                    id: self.history_ids.next(created_at),
+                    text: text.clone(),
+                    model_id,
+                    language,
+                    created_at,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    segments,
+                    revision: None,
+                    tag: None,
+                },
+            );
+        }
+        let _ = fs::remove_file(&path);
+        Ok(text)
+    }
+
+    pub fn get_last_transcription(&self) -> Option<LastTranscription> {
+        self.last_transcription.lock().unwrap().clone()
+    }
+
+    pub fn copy_last_transcription(&self) -> Result<()> {
+        let text = self
+            .get_last_transcription()
+            .map(|last| last.text)
+            .context("nothing has been transcribed yet")?;
+        copy_text(&text)
+    }
+
+    fn record_history(&self, app: &AppHandle, entry: HistoryEntry) -> Result<()> {
+        history::append_entry(&entry)?;
+        self.history.lock().unwrap().push(entry.clone());
+        self.events.emit(app, "history:added", entry);
+        Ok(())
+    }
+
+    /// Checks, optionally downloads, then loads the active model, reporting each stage
+    /// as `models:preload`. A missing model stops at `awaiting_download` unless
+    /// `download` is set, so large downloads only start once the user agrees.
+    /// Starting a new preload or calling `cancel_preload` abandons the current one.
+    pub async fn preload_transcribe_server(&self, app: &AppHandle, download: bool) -> Result<()> {
+        let config = self.config.snapshot();
+        let model_id = config.active_model.clone();
+        if model_id == "none" {
+            return Ok(());
+        }
+        let cancel = Arc::new(Notify::new());
+        if let Some(previous) = self.preload.lock().unwrap().replace(cancel.clone()) {
+            previous.notify_one();
+        }
+        let result = tokio::select! {
+            result = self.run_preload(app, &config, download) => result,
+            _ = cancel.notified() => {
+                emit_preload(&self.events, app, &model_id, "cancelled", None);
+                Ok(())
+            }
+        };
+        if let Err(err) = &result {
+            emit_preload(
+                &self.events,
+                app,
+                &model_id,
+                "failed",
+                Some(err.to_string()),
+            );
+        }
+        let mut slot = self.preload.lock().unwrap();
+        if slot
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &cancel))
+        {
+            *slot = None;
+        }
+        result
+    }
+
+    pub fn cancel_preload(&self) {
+        if let Some(cancel) = self.preload.lock().unwrap().take() {
+            cancel.notify_one();
+        }
+    }
+
+    async fn run_preload(&self, app: &AppHandle, config: &AppConfig, download: bool) -> Result<()> {
+        let model_id = config.active_model.clone();
+        emit_preload(&self.events, app, &model_id, "checking", None);
+        if !models::model_is_valid(&model_id)? {
+            if !download {
+                emit_preload(&self.events, app, &model_id, "awaiting_download", None);
+                return Ok(());
+            }
+            emit_preload(&self.events, app, &model_id, "downloading", None);
+            self.download_model(app, &model_id).await?;
+        }
+
+        emit_preload(&self.events, app, &model_id, "loading", None);
+        let options = ServerOptions::from_config(config, &model_id);
+        let server = self.transcribe.clone();
+        let loading_id = model_id.clone();
+        // Cancelling only stops waiting here; the child finishes loading in the
+        // background and is reused by the next transcription.
+        task::spawn_blocking(move || -> Result<()> {
+            let mut guard = server.lock().unwrap();
+            let needs_restart = guard
+                .as_ref()
+                .map(|s| s.model_id != loading_id || s.options != options)
+                .unwrap_or(true);
+            if needs_restart {
+                // Frees the old model's memory before the new one is checked against it.
+                guard.take();
+                *guard = Some(spawn_server(&loading_id, &options)?);
+            }
+            guard
+                .as_mut()
+                .context("missing server")?
+                .wait_until_loaded()
+        })
+        .await
+        .context("preload task")??;
+        emit_preload(&self.events, app, &model_id, "ready", None);
+        Ok(())
+    }
+
+    pub fn set_schedule(&self, schedule: ScheduleConfig) -> Result<()> {
+        for time in [&schedule.warm_at, &schedule.unload_at] {
+            if schedule::parse_time(time).is_none() {
+                anyhow::bail!("invalid schedule time {time}, expected HH:MM");
+            }
+        }
+        self.config.update(|config| {
+            config.schedule = schedule;
+        })
+    }
+
+    pub fn set_retranscription(&self, retranscribe: RetranscribeConfig) -> Result<()> {
+        if !retranscribe.model_id.is_empty()
+            && models::get_model_info(&retranscribe.model_id).is_none()
+        {
+            anyhow::bail!("unknown model {}", retranscribe.model_id);
+        }
+        self.config.update(|config| {
+            config.retranscribe = RetranscribeConfig {
+                max_confidence: retranscribe.max_confidence.clamp(0.0, 1.0),
+                ..retranscribe
+            };
+        })
+    }
+
+    /// Serves `local_api` requests on loopback while `local_api` is on, rebinding when
+    /// the port changes. Each connection gets a thread of its own, up to
+    /// `local_api::MAX_CONNECTIONS`.
+    pub fn start_local_api(&self, app: &AppHandle) {
+        let state = self.clone();
+        let app = app.clone();
+        self.supervisor.spawn_thread("local_api", move || {
+            let connections = Arc::new(AtomicUsize::new(0));
+            let mut port = None;
+            let mut listener = None;
+            loop {
+                let config = state.config.snapshot();
+                let wanted = config.local_api.then_some(config.local_api_port);
+                if wanted != port {
+                    port = wanted;
+                    listener = port.and_then(|port| match local_api::bind(port) {
+                        Ok(listener) => Some(listener),
+                        Err(err) => {
+                            eprintln!("local API unavailable: {err:#}");
+                            None
+                        }
+                    });
+                }
+                let accepted = listener.as_ref().map(|listener| listener.accept());
+                let Some(Ok((stream, _))) = accepted else {
+                    std::thread::sleep(LOCAL_API_POLL_INTERVAL);
+                    continue;
+                };
+                if connections.fetch_add(1, Ordering::SeqCst) >= local_api::MAX_CONNECTIONS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    if let Err(err) = local_api::refuse_busy(stream) {
+                        eprintln!("local API request failed: {err:#}");
+                    }
+                    continue;
+                }
+                let state = state.clone();
+                let app = app.clone();
+                let token = config.local_api_token.clone();
+                let connections = connections.clone();
+                std::thread::spawn(move || {
+                    let handled = local_api::handle(stream, &token, |audio, request| {
+                        tauri::async_runtime::block_on(state.transcribe_external(
+                            &app,
+                            audio,
+                            request.language,
+                            request.model,
+                        ))
+                        .map(|(text, _)| text)
+                    });
+                    if let Err(err) = handled {
+                        eprintln!("local API request failed: {err:#}");
+                    }
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+    }
+
+    /// Transcribes audio that was not dictated, from another app over the local API or
+    /// from a file, with `model` or the active model. Entitlement and quota apply as for
+    /// dictation, but nothing is pasted, kept or added to history.
+    pub async fn transcribe_external(
+        &self,
+        app: &AppHandle,
+        audio: AudioBuffer,
+        language: Option<String>,
+        model: Option<String>,
+    ) -> Result<(String, Vec<Segment>)> {
+        if !self.is_entitled()? {
+            return Err(CommandError::free_limit_reached().into());
+        }
+        let config = self.config.snapshot();
+        let audio = resample_for_whisper(audio, &config.resampler);
+        if audio.samples.is_empty() {
+            return Ok((String::new(), Vec::new()));
+        }
+        let audio_ms = audio.samples.len() as u64 * 1_000 / u64::from(audio.sample_rate.max(1));
+        let model_id = model.unwrap_or_else(|| config.active_model.clone());
+        if !managed_config::policy().model_allowed(&model_id) {
+            return Err(CommandError::model_not_allowed().into());
+        }
+        if !models::model_is_valid(&model_id)? {
+            anyhow::bail!("model {model_id} is not downloaded");
+        }
+        let mut transcribe = transcribe_options(&config);
+        if let Some(language) = language {
+            transcribe.language = language;
+        }
+        // Piece by piece, so a long file leaves the transcriber to dictation in between.
+        let running = Arc::new(Running::default());
+        let mut transcript = Transcript::default();
+        for piece in streaming::split_at_pauses(&audio.samples, audio.sample_rate, EXTERNAL_CHUNK) {
+            let offset_ms = piece.start as u64 * 1_000 / u64::from(audio.sample_rate);
+            let part = self
+                .transcribe_with_model(
+                    &config,
+                    &model_id,
+                    Arc::new(audio.samples[piece].to_vec()),
+                    transcribe.clone(),
+                    running.clone(),
+                )
+                .await
+                .context("transcribe task")
+                .and_then(|part| part);
+            let part = match part {
+                Ok(part) => part,
+                Err(err) => {
+                    self.release_other(&config, &model_id);
+                    return Err(err);
+                }
+            };
+            // Later pieces keep the language the first one was heard in.
+            if transcribe.language == AUTO_LANGUAGE && !part.language.is_empty() {
+                transcribe.language = part.language.clone();
+            }
+            append_transcript(&mut transcript, part, offset_ms);
+        }
+        self.release_other(&config, &model_id);
+        let language = if transcript.language.is_empty() {
+            config.language.clone()
+        } else {
+            transcript.language
+        };
+        let processed = post_process(&transcript.text, transcript.segments, &language, &config);
+        self.record_usage(audio_ms, true)?;
+        self.emit_quota(app);
+        Ok(processed)
+    }
+
+    /// Transcribes a WAV file and writes the result next to it as plain text or SRT/WebVTT
+    /// subtitles (`subtitles::FORMAT_*`); returns the path written.
+    pub async fn transcribe_file(
+        &self,
+        app: &AppHandle,
+        path: &Path,
+        format: &str,
+        max_line_len: Option<usize>,
+    ) -> Result<PathBuf> {
+        let extension = subtitles::extension(format)
+            .with_context(|| format!("unsupported output format {format}"))?;
+        let bytes = fs::read(path).context("read audio file")?;
+        let audio = local_api::decode_audio(local_api::FORMAT_WAV, &bytes)?;
+        let (_, segments) = self.transcribe_external(app, audio, None, None).await?;
+        let max_line_len = max_line_len.unwrap_or(subtitles::DEFAULT_MAX_LINE_LEN);
+        let output = path.with_extension(extension);
+        fs::write(&output, subtitles::render(format, &segments, max_line_len))
+            .context("write transcription")?;
+        Ok(output)
+    }
+
+    /// A token is generated the first time the API is turned on; clients must send it.
+    pub fn set_local_api(&self, enabled: bool, port: Option<u16>) -> Result<()> {
+        let token = local_api::generate_token()?;
+        self.config.update(|config| {
+            config.local_api = enabled;
+            if let Some(port) = port.filter(|port| *port != 0) {
+                config.local_api_port = port;
+            }
+            if enabled && config.local_api_token.is_empty() {
+                config.local_api_token = token;
+            }
+        })
+    }
+
+    /// Re-runs low-confidence history entries through a larger model on a transcriber of
+    /// its own, one entry per tick and only while idle on AC power, so it never adds
+    /// latency to dictation. The model is unloaded as soon as there is nothing to do.
+    pub fn start_retranscription(&self, app: &AppHandle) {
+        let state = self.clone();
+        let app = app.clone();
+        self.supervisor.spawn_task("retranscription", move || {
+            let state = state.clone();
+            let app = app.clone();
+            async move {
+                let server = Arc::new(Mutex::new(None));
+                let mut skipped = HashSet::new();
+                loop {
+                    tokio::time::sleep(RETRANSCRIBE_TICK).await;
+                    let busy = match state.retranscribe_next(&app, &server, &mut skipped).await {
+                        Ok(busy) => busy,
+                        Err(err) => {
+                            eprintln!("retranscription failed: {err:#}");
+                            false
+                        }
+                    };
+                    if !busy {
+                        server.lock().unwrap().take();
+                    }
+                }
+            }
+        });
+    }
+
+    /// Re-runs the newest queued entry; false when the job has nothing to do right now.
+    async fn retranscribe_next(
+        &self,
+        app: &AppHandle,
+        server: &Arc<Mutex<Option<TranscribeServer>>>,
+        skipped: &mut HashSet<u64>,
+    ) -> Result<bool> {
+        let config = self.config.snapshot();
+        let job = &config.retranscribe;
+        if !job.enabled || !config.keep_recordings || self.recorder.is_recording() {
+            return Ok(false);
+        }
+        let now_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let last_dictation = self
+            .history
+            .lock()
+            .unwrap()
+            .last()
+            .map_or(0, |entry| entry.created_at);
+        if now_ms.saturating_sub(last_dictation) < u64::from(job.idle_minutes) * 60_000 {
+            return Ok(false);
+        }
+        if !task::spawn_blocking(retranscribe::on_ac_power)
+            .await
+            .unwrap_or(false)
+        {
+            return Ok(false);
+        }
+        let Some(model_id) =
+            retranscribe::pick_model(&job.model_id, &config.active_model, &models::list_models()?)
+        else {
+            return Ok(false);
+        };
+        let entry = {
+            let entries = self.history.lock().unwrap();
+            retranscribe::queue(&entries, &model_id, job.max_confidence)
+                .into_iter()
+                .find(|id| !skipped.contains(id))
+                .and_then(|id| entries.iter().find(|entry| entry.id == id).cloned())
+        };
+        let Some(entry) = entry else {
+            return Ok(false);
+        };
+        // Whatever happens below, an entry is tried once per run of the app.
+        skipped.insert(entry.id);
+        let path = retention::recording_path(&recordings_dir(&config)?, entry.created_at);
+        let Ok(audio) = retention::read_recording(&path) else {
+            return Ok(true);
+        };
+        let transcribe = TranscribeOptions {
+            language: entry.language.clone(),
+            ..transcribe_options(&config)
+        };
+        let options = ServerOptions::from_config(&config, &model_id);
+        let decoding = decoding_params(&config);
+        let server = server.clone();
+        let model = model_id.clone();
+        let running = self.retranscribing.clone();
+        running.arm();
+        let transcript = task::spawn_blocking(move || {
+            let result = transcribe_with_server(
+                server,
+                &running,
+                &model,
+                &audio,
+                &transcribe,
+                &options,
+                &decoding,
+            );
+            running.disarm();
+            result
+        })
+        .await
+        .context("retranscription task")?;
+        let transcript = match transcript {
+            // A dictation started; the entry is tried again once idle.
+            Err(err) if is_cancelled(&err) => {
+                skipped.remove(&entry.id);
+                return Ok(false);
+            }
+            transcript => transcript?,
+        };
+        let (text, segments) = post_process(
+            &transcript.text,
+            transcript.segments,
+            &entry.language,
+            &config,
+        );
+        if text.is_empty() {
+            return Ok(true);
+        }
+        self.revise_history(app, entry.id, model_id, text, segments)?;
+        Ok(true)
+    }
+
+    /// Replaces the text of history entry `id` with a new transcription, keeping the old
+    /// one as its revision. An entry deleted meanwhile is left alone.
+    fn revise_history(
+        &self,
+        app: &AppHandle,
+        id: u64,
+        model_id: String,
+        text: String,
+        segments: Vec<Segment>,
+    ) -> Result<()> {
+        let updated = {
+            let mut entries = self.history.lock().unwrap();
+            let Some(stored) = entries.iter_mut().find(|stored| stored.id == id) else {
+                return Ok(());
+            };
+            stored.revision = Some(Revision {
+                model_id: model_id.clone(),
+                previous_text: stored.text.clone(),
+                changes: retranscribe::diff_words(&stored.text, &text),
+                revised_at: unix_timestamp(),
+            });
+            stored.text = text;
+            stored.segments = segments;
+            stored.model_id = model_id;
+            let updated = stored.clone();
+            history::rewrite_history(&entries)?;
+            updated
+        };
+        self.events.emit(app, "history:updated", updated);
+        Ok(())
+    }
+
+    /// Follows `schedule`: the model is preloaded and warmed when the window opens and
+    /// unloaded when it closes. Only those transitions act, so dictating outside the
+    /// window still loads the model on demand.
+    pub fn start_schedule(&self, app: &AppHandle) {
+        let state = self.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut offset = 0;
+            let mut was_warm = None;
+            let mut tick = 0u64;
+            loop {
+                if tick % SCHEDULE_OFFSET_REFRESH_TICKS == 0 {
+                    offset = task::spawn_blocking(schedule::utc_offset_secs)
+                        .await
+                        .unwrap_or(offset);
+                }
+                tick += 1;
+                let config = state.config.snapshot();
+                let now = unix_timestamp();
+                let status = schedule::status(&config.schedule, now, now as i64 + offset);
+                *state.schedule_status.lock().unwrap() = status;
+                let warm = status.map(|status| status.warm);
+                match (was_warm, warm) {
+                    (Some(false) | None, Some(true)) => state.warm_up(&app).await,
+                    (Some(true), Some(false)) => state.unload_transcribe_server(&app),
+                    _ => {}
+                }
+                was_warm = warm;
+                tokio::time::sleep(SCHEDULE_TICK).await;
+            }
+        });
+    }
+
+    /// Preloads the active model, then decodes a second of silence so GPU kernels and
+    /// buffers are ready before the first dictation.
+    async fn warm_up(&self, app: &AppHandle) {
+        let config = self.config.snapshot();
+        if config.active_model == "none"
+            || !models::model_is_valid(&config.active_model).unwrap_or(false)
+        {
+            return;
+        }
+        if self.preload_transcribe_server(app, false).await.is_err() {
+            return;
+        }
+        let server = self.transcribe.clone();
+        let result = task::spawn_blocking(move || -> Result<()> {
+            let silence = vec![0.0; 16_000];
+            let request = RequestBody::Transcribe {
+                samples: silence.len(),
+                partial: false,
+                options: transcribe_options(&config),
+            };
+            let mut guard = server.lock().unwrap();
+            let server = guard.as_mut().context("missing server")?;
+            server.apply_decoding(&decoding_params(&config))?;
+            server
+                .transcribe(&Running::default(), &request, &silence)
+                .map(|_| ())
+        })
+        .await;
+        if let Ok(Err(err)) = result {
+            eprintln!("model warm-up failed: {err}");
+        }
+    }
+
+    /// Stops the transcriber unless it is in use; the next transcription loads it again.
+    fn unload_transcribe_server(&self, app: &AppHandle) {
+        if self.recorder.is_recording() {
+            return;
+        }
+        let Ok(mut guard) = self.transcribe.try_lock() else {
+            return;
+        };
+        if let Some(server) = guard.take() {
+            emit_preload(&self.events, app, &server.model_id, "unloaded", None);
+        }
+    }
+
+    /// Takes effect on the next transcription without restarting the transcriber.
+    pub fn set_decoding_params(&self, beam_size: u32, temperature: f32) -> Result<()> {
+        self.config.update(|config| {
+            config.beam_size = beam_size.clamp(1, MAX_BEAM_SIZE);
+            config.temperature = temperature.clamp(0.0, 1.0);
+        })
+    }
+
+    /// 0 uses every core; applied on the next transcription without reloading the model.
+    pub fn set_transcription_threads(&self, threads: u32) -> Result<()> {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+        self.config.update(|config| {
+            config.transcription_threads = threads.min(cores);
+        })
+    }
+
+    /// Decode options apply on the next transcription; toggling flash attention reloads
+    /// the model.
+    pub fn set_advanced_decoding(&self, advanced: AdvancedDecoding) -> Result<()> {
+        self.config.update(|config| {
+            config.advanced_decoding = advanced;
+        })
+    }
+
+    /// A zero `temperature_inc` turns the fallback off.
+    pub fn set_fallback_params(
+        &self,
+        temperature_inc: f32,
+        entropy_threshold: f32,
+        logprob_threshold: f32,
+    ) -> Result<()> {
+        if !(temperature_inc.is_finite()
+            && entropy_threshold.is_finite()
+            && logprob_threshold.is_finite())
+        {
+            anyhow::bail!("fallback thresholds must be finite numbers");
+        }
+        self.config.update(|config| {
+            config.temperature_inc = temperature_inc.clamp(0.0, 1.0);
+            config.entropy_threshold = entropy_threshold.max(0.0);
+            config.logprob_threshold = logprob_threshold.min(0.0);
+        })
+    }
+
+    pub fn set_initial_prompt(&self, prompt: &str) -> Result<()> {
+        let prompt = clamp_prompt(prompt);
+        self.config.update(|config| {
+            dictionary::set_initial_prompt(config, prompt, unix_timestamp());
+        })
+    }
+
+    pub fn set_word_timestamps(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.word_timestamps = enabled;
+        })
+    }
+
+    pub fn set_restore_punctuation(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.restore_punctuation = enabled;
+        })
+    }
+
+    /// Languages whose dictations get spoken numbers and symbols formatted.
+    pub fn set_spoken_formatting(&self, languages: Vec<String>) -> Result<()> {
+        let mut languages: Vec<String> = languages
+            .iter()
+            .map(|language| language.trim().to_ascii_lowercase())
+            .collect();
+        languages.sort();
+        languages.dedup();
+        if let Some(language) = languages
+            .iter()
+            .find(|language| !normalize::SUPPORTED_LANGUAGES.contains(&language.as_str()))
+        {
+            anyhow::bail!("spoken formatting is not available for {language}");
+        }
+        self.config.update(|config| {
+            config.spoken_formatting = languages;
+        })
+    }
+
+    pub fn set_remove_fillers(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.remove_fillers = enabled;
+        })
+    }
+
+    pub fn set_profanity_filter(&self, filter: ProfanityFilter) -> Result<()> {
+        if ![PROFANITY_OFF, PROFANITY_MASK, PROFANITY_REMOVE].contains(&filter.mode.as_str()) {
+            anyhow::bail!("unknown profanity filter mode: {}", filter.mode);
+        }
+        let words = filter
+            .words
+            .iter()
+            .map(|word| word.trim().to_string())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+        if words.iter().any(|word| word.contains(char::is_whitespace)) {
+            anyhow::bail!("profanity filter entries must be single words");
+        }
+        self.config.update(|config| {
+            config.profanity_filter = ProfanityFilter { words, ..filter };
+        })
+    }
+
+    /// Only takes effect with a model that marks speaker turns (`small.en-tdrz`).
+    pub fn set_diarize(&self, enabled: bool) -> Result<()> {
+        self.config.update(|config| {
+            config.diarize = enabled;
+        })
+    }
+
+    pub fn set_prompt_override(&self, prompt: Option<String>) {
+        *self.prompt_override.lock().unwrap() = prompt.map(|prompt| clamp_prompt(&prompt));
+    }
+
+    pub fn set_shortcut(&self, shortcut: &str) -> Result<()> {
+        self.config.update(|config| {
+            config.shortcut = shortcut.to_string();
+        })?;
+        if let Some(parsed) = Hotkey::parse(shortcut) {
+            let mut hk = self.hotkey.lock().unwrap();
+            *hk = parsed;
+        }
+        if let Some(wayland) = &self.wayland_hotkeys {
+            wayland.update(shortcut.to_string());
+        }
+        Ok(())
+    }
+
+    /// An empty shortcut unbinds the incognito hotkey.
+    pub fn set_incognito_shortcut(&self, shortcut: &str) -> Result<()> {
+        let parsed = Hotkey::parse(shortcut);
+        if parsed.is_none() && !shortcut.trim().is_empty() {
+            anyhow::bail!("invalid shortcut: {shortcut}");
+        }
+        self.config.update(|config| {
+            config.incognito_shortcut = shortcut.to_string();
+        })?;
+        *self.incognito_hotkey.lock().unwrap() = parsed;
+        if let Some(wayland) = &self.wayland_hotkeys {
+            wayland.update_incognito(shortcut.to_string());
+        }
+        Ok(())
+    }
+
+    /// Each tag needs a shortcut of its own, unused by the other dictation shortcuts.
+    pub fn set_tag_shortcuts(&self, shortcuts: BTreeMap<String, String>) -> Result<()> {
+        let config = self.config.snapshot();
+        let mut bound: Vec<(String, Hotkey)> = [
+            ("dictation", &config.shortcut),
+            ("incognito", &config.incognito_shortcut),
+            ("quick model", &config.quick_shortcut),
+        ]
+        .into_iter()
+        .filter_map(|(name, shortcut)| {
+            Some((format!("the {name} shortcut"), Hotkey::parse(shortcut)?))
+        })
+        .collect();
+        let mut cleaned = BTreeMap::new();
+        for (tag, shortcut) in shortcuts {
+            let tag = tag.trim().to_string();
+            if tag.is_empty() {
+                anyhow::bail!("tag is empty");
+            }
+            let Some(hotkey) = Hotkey::parse(&shortcut) else {
+                anyhow::bail!("invalid shortcut for {tag}: {shortcut}");
+            };
+            if let Some((owner, _)) = bound.iter().find(|(_, other)| *other == hotkey) {
+                anyhow::bail!("shortcut for {tag} is already {owner}: {shortcut}");
+            }
+            bound.push((format!("the shortcut for {tag}"), hotkey));
+            cleaned.insert(tag, shortcut);
+        }
+        *self.tag_hotkeys.lock().unwrap() = parse_tag_hotkeys(&cleaned);
+        if let Some(wayland) = &self.wayland_hotkeys {
+            wayland.update_tags(cleaned.clone());
+        }
+        self.config.update(|config| {
+            config.tag_shortcuts = cleaned;
+        })
+    }
+
+    pub fn set_dictation_tag(&self, tag: Option<String>) {
+        *self.dictation_tag.lock().unwrap() = tag
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty());
+    }
+
+    /// Starts a dictation tagged `tag`, or stops the one in progress, which keeps the tag
+    /// it was started with.
+    pub async fn toggle_tagged(&self, app: &AppHandle, tag: String) -> Result<()> {
+        if self.recorder.is_recording() {
+            return self.stop_recording(app).await.map(|_| ());
+        }
+        self.set_dictation_tag(Some(tag));
+        let started = self.start_recording(app);
+        if started.is_err() {
+            self.set_dictation_tag(None);
+        }
+        started
+    }
+
+    /// Starts a dictation with the quick model, or stops the one in progress.
+    pub async fn toggle_quick(&self, app: &AppHandle) -> Result<()> {
+        if self.recorder.is_recording() {
+            return self.stop_recording(app).await.map(|_| ());
+        }
+        let quick_model = self.config.snapshot().quick_model.clone();
+        if quick_model.is_empty() {
+            anyhow::bail!("no quick model is set");
+        }
+        *self.dictation_model.lock().unwrap() = Some(quick_model);
+        let started = self.start_recording(app);
+        if started.is_err() {
+            self.dictation_model.lock().unwrap().take();
+        }
+        started
+    }
+
+    /// Empty turns the quick model off and frees its transcriber.
+    pub fn set_quick_model(&self, model_id: &str) -> Result<()> {
+        let model_id = model_id.trim();
+        if !model_id.is_empty() {
+            if models::get_model_info(model_id).is_none() {
+                anyhow::bail!("unknown model {model_id}");
+            }
+            if !managed_config::policy().model_allowed(model_id) {
+                return Err(CommandError::model_not_allowed().into());
+            }
+        }
+        self.config.update(|config| {
+            config.quick_model = model_id.to_string();
+        })?;
+        self.preload_quick_model();
+        Ok(())
+    }
+
+    pub fn set_quick_shortcut(&self, shortcut: &str) -> Result<()> {
+        let parsed = Hotkey::parse(shortcut);
+        if parsed.is_none() && !shortcut.trim().is_empty() {
+            anyhow::bail!("invalid shortcut: {shortcut}");
+        }
+        self.config.update(|config| {
+            config.quick_shortcut = shortcut.to_string();
+        })?;
+        *self.quick_hotkey.lock().unwrap() = parsed;
+        if let Some(wayland) = &self.wayland_hotkeys {
+            wayland.update_quick(shortcut.to_string());
+        }
+        Ok(())
+    }
+
+    /// Loads the quick model in the background, replacing a transcriber left from a
+    /// previous quick model; the first quick dictation then starts warm.
+    pub fn preload_quick_model(&self) {
+        let config = self.config.snapshot();
+        let model_id = config.quick_model.clone();
+        let options = ServerOptions::from_config(&config, &model_id);
+        let server = self.quick.clone();
+        let wanted = !model_id.is_empty()
+            && model_id != config.active_model
+            && models::model_is_valid(&model_id).unwrap_or(false);
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut guard = server.lock().unwrap();
+            if guard.as_ref().is_some_and(|server| {
+                wanted && server.model_id == model_id && server.options == options
+            }) {
+                return;
+            }
+            guard.take();
+            if !wanted {
+                return;
+            }
+            let loaded = spawn_server(&model_id, &options).and_then(|mut server| {
+                server.wait_until_loaded()?;
+                Ok(server)
+            });
+            match loaded {
+                Ok(server) => *guard = Some(server),
+                Err(err) => eprintln!("quick model warm-up failed: {err:#}"),
+            }
+        });
+    }
+
+    /// The transcriber kept for `model_id`: the active and quick models have one each, so
+    /// alternating between them never reloads either; any other model gets `other`.
+    fn server_for(
+        &self,
+        config: &AppConfig,
+        model_id: &str,
+    ) -> Arc<Mutex<Option<TranscribeServer>>> {
+        if model_id == config.active_model {
+            self.transcribe.clone()
+        } else if model_id == config.quick_model {
+            self.quick.clone()
+        } else {
+            self.other.clone()
+        }
+    }
+
+    /// Frees the transcriber of a one-off model once the job that needed it is done.
+    fn release_other(&self, config: &AppConfig, model_id: &str) {
+        if model_id != config.active_model && model_id != config.quick_model {
+            self.other.lock().unwrap().take();
+        }
+    }
+
+    /// Transcribes on the transcriber `server_for` picks for `model_id`.
+    fn transcribe_with_model(
+        &self,
+        config: &AppConfig,
+        model_id: &str,
+        audio: Arc<Vec<f32>>,
+        transcribe: TranscribeOptions,
+        running: Arc<Running>,
+    ) -> task::JoinHandle<Result<Transcript>> {
+        let server = self.server_for(config, model_id);
+        let options = ServerOptions::from_config(config, model_id);
+        let decoding = decoding_params(config);
+        let model_id = model_id.to_string();
+        running.arm();
+        task::spawn_blocking(move || {
+            let result = transcribe_with_server(
+                server,
+                &running,
+                &model_id,
+                &audio,
+                &transcribe,
+                &options,
+                &decoding,
+            );
+            running.disarm();
+            result
+        })
+    }
+
+    pub fn set_incognito(&self, app: &AppHandle, enabled: bool) {
+        self.incognito.store(enabled, Ordering::Relaxed);
+        self.tray.set_incognito(enabled);
+        self.events.emit(
+            app,
+            "incognito:changed",
+            serde_json::json!({ "enabled": enabled }),
+        );
+    }
+
+    pub fn toggle_incognito(&self, app: &AppHandle) {
+        self.set_incognito(app, !self.incognito.load(Ordering::Relaxed));
+    }
+
+    pub fn status(&self) -> StatusResponse {
+        let recording = self.recorder.is_recording();
+        let paused = recording && self.recorder.is_paused();
+        // A held lock means the transcriber is busy, so it is loaded.
+        let model_loaded = self
+            .transcribe
+            .try_lock()
+            .map_or(true, |server| server.is_some());
+        StatusResponse {
+            recording,
+            paused,
+            model_loaded,
+            schedule: *self.schedule_status.lock().unwrap(),
+            incognito: self.incognito.load(Ordering::Relaxed),
+            recoverable: retention::failed_recording_path().is_ok_and(|path| path.exists()),
+            degraded: self.supervisor.degraded(),
+        }
+    }
+
+    /// Whether a pro license or the remaining free quota allows another transcription.
+    fn is_entitled(&self) -> Result<bool> {
+        let (validation, remaining) = self.config.update(|config| {
+            licensing::validate_current_license(
+                config,
+                &self.license_public_keys,
+                &self.license_issuer,
+            )
+            .map(|validation| {
+                let remaining =
+                    quota::has_remaining(config, global_config::free_quota(), unix_timestamp());
+                (validation, remaining)
+            })
+        })??;
+        Ok(validation.is_pro() || remaining)
+    }
+
+    fn validate_recording_entitlement(&self, app: &AppHandle) -> Result<()> {
+        if self.is_entitled()? {
+            return Ok(());
+        }
+
+        self.tray.set_mode(TrayMode::Error);
+        let error = CommandError::free_limit_reached();
+        self.events.emit(
+            app,
+            "status:changed",
+            serde_json::json!({
+                "status": "error",
+                "code": error.code,
+                "message": error.message,
+            }),
+        );
+        Err(error.into())
+    }
+
+    pub fn start_recording(&self, app: &AppHandle) -> Result<()> {
+        let requested_at = Instant::now();
+        if self.recorder.is_recording() {
+            return Ok(());
+        }
+        if self.shutting_down.load(Ordering::SeqCst) {
+            anyhow::bail!("Whisperdict is quitting");
+        }
+        self.validate_recording_entitlement(app)?;
+        // Background retranscription yields the machine to the dictation.
+        self.retranscribing.cancel();
+        let max_duration = self.max_recording_duration();
+        if let Err(err) = self.recorder.start(requested_at, max_duration) {
+            self.tray.set_mode(TrayMode::Error);
+            self.events.emit(
+                app,
+                "status:changed",
+                serde_json::json!({ "status": "error", "message": err.to_string() }),
+            );
+            return Err(err);
+        }
+        self.spawn_recording_monitor(app);
+        let config = self.config.snapshot();
+        if config.streaming_partials {
+            self.spawn_partial_transcription(app, &config);
+        }
+        if config.cue_on_start {
+            cues::play(Cue::Start, config.cue_volume, &config.audio_host);
+        }
+        self.tray.set_mode(TrayMode::Recording);
+        self.events.emit(
+            app,
+            "status:changed",
+            serde_json::json!({ "status": "recording", "message": null }),
+        );
+        Ok(())
+    }
+
+    pub fn pause_recording(&self, app: &AppHandle) -> Result<()> {
+        if !self.recorder.is_recording() || self.recorder.is_paused() {
+            return Ok(());
+        }
+        self.recorder.pause().context("pause recorder")?;
+        self.tray.set_mode(TrayMode::Paused);
+        self.events.emit(
+            app,
+            "status:changed",
+            serde_json::json!({ "status": "paused", "message": null }),
+        );
+        Ok(())
+    }
+
+    pub fn resume_recording(&self, app: &AppHandle) -> Result<()> {
+        if !self.recorder.is_recording() || !self.recorder.is_paused() {
+            return Ok(());
+        }
+        self.recorder.resume().context("resume recorder")?;
+        self.tray.set_mode(TrayMode::Recording);
+        self.events.emit(
+            app,
+            "status:changed",
+            serde_json::json!({ "status": "recording", "message": null }),
+        );
+        Ok(())
+    }
+
+    fn max_recording_duration(&self) -> Option<Duration> {
+        let secs = self.config.snapshot().max_recording_secs;
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    fn spawn_recording_monitor(&self, app: &AppHandle) {
+        let session = self.recording_session.fetch_add(1, Ordering::SeqCst) + 1;
+        let session_ref = self.recording_session.clone();
+        let recorder = self.recorder.clone();
+        let config = self.config.snapshot();
+        let app = app.clone();
+        let events = self.events.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut started = false;
+            for _ in 0..MONITOR_START_TICKS {
+                if recorder.is_recording() {
+                    started = true;
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(MONITOR_INTERVAL_MS)).await;
+            }
+            if !started {
+                return;
+            }
+            let mut deadline = (config.max_recording_secs > 0)
+                .then(|| Instant::now() + Duration::from_secs(config.max_recording_secs));
+            let mut vad = config.vad_auto_stop.then(|| {
+                EnergyVad::new(
+                    config.vad_threshold,
+                    Duration::from_millis(config.vad_silence_ms),
+                )
+            });
+            let mut clipping = ClippingDetector::new(CLIPPING_WINDOW);
+            while recorder.is_recording() && session_ref.load(Ordering::SeqCst) == session {
+                if let Some(bluetooth) = recorder.take_bluetooth_input() {
+                    events.emit(&app, "audio:bluetooth_input", &bluetooth);
+                }
+                if let Some(reason) = recorder.take_echo_unavailable() {
+                    events.emit(
+                        &app,
+                        "audio:echo_unavailable",
+                        serde_json::json!({ "reason": reason }),
+                    );
+                }
+                if let Some(lost) = recorder.take_device_lost() {
+                    events.emit(&app, "audio:device_lost", &lost);
+                    if lost.fallback.is_none() {
+                        let state = app.state::<AppState>();
+                        let _ = state.stop_recording(&app).await;
+                        return;
+                    }
+                }
+                if recorder.is_paused() {
+                    // Paused time counts towards neither the length cap nor trailing silence.
+                    let interval = Duration::from_millis(MONITOR_INTERVAL_MS);
+                    deadline = deadline.map(|deadline| deadline + interval);
+                    if let Some(vad) = vad.as_mut() {
+                        vad.reset();
+                    }
+                    clipping.reset();
+                    tokio::time::sleep(interval).await;
+                    continue;
+                }
+                let level = recorder.level();
+                events.emit(&app, "audio:level", level);
+                let now = Instant::now();
+                let (clipped, frames) = recorder.take_clipping();
+                if let Some(warning) = clipping.observe(clipped, frames, now) {
+                    app.state::<AppState>().set_clipping_tooltip(warning.active);
+                    events.emit(&app, "audio:clipping", warning);
+                }
+                let reason = if deadline.is_some_and(|deadline| now >= deadline) {
+                    Some("max_duration")
+                } else if vad.as_mut().is_some_and(|vad| vad.observe(level, now)) {
+                    Some("silence")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    events.emit(
+                        &app,
+                        "recording:auto_stopped",
+                        serde_json::json!({ "reason": reason }),
+                    );
+                    let state = app.state::<AppState>();
+                    let _ = state.stop_recording(&app).await;
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(MONITOR_INTERVAL_MS)).await;
+            }
+        });
+    }
+
+    /// Decodes overlapping chunks while recording and emits the stitched text as
+    /// `transcription:partial`; in progressive output mode it is typed as it grows.
+    fn spawn_partial_transcription(&self, app: &AppHandle, config: &Arc<AppConfig>) {
+        let session = Arc::new(Mutex::new(PartialSession {
+            typer: (config.output_mode == OUTPUT_PROGRESSIVE && !config.presentation_mode)
+                .then(|| Arc::new(Mutex::new(ProgressiveTyper::new()))),
+            ..PartialSession::default()
+        }));
+        *self.partial.lock().unwrap() = Some(session.clone());
+        let recorder = self.recorder.clone();
+        // A quick dictation is previewed with the quick model, the one it ends with.
+        let model_id = self
+            .dictation_model
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| config.active_model.clone());
+        let server = self.server_for(config, &model_id);
+        let events = self.events.clone();
+        let config = config.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut cursor = ChunkCursor::default();
+            loop {
+                tokio::time::sleep(PARTIAL_POLL_INTERVAL).await;
+                if session.lock().unwrap().stopped {
+                    break;
+                }
+                let audio = match recorder.snapshot(cursor.start()) {
+                    Ok(Some(audio)) => audio,
+                    _ => break,
+                };
+                if !cursor.is_ready(audio.samples.len(), audio.sample_rate) {
+                    continue;
+                }
+                cursor.advance(audio.samples.len(), audio.sample_rate);
+                let server = server.clone();
+                let chunk_config = config.clone();
+                let model_id = model_id.clone();
+                let decoded = task::spawn_blocking(move || {
+                    transcribe_partial(&server, &model_id, &chunk_config, audio)
+                })
+                .await;
+                let transcript = match decoded {
+                    Ok(Ok(Some(transcript))) => transcript,
+                    Ok(Err(err)) => {
+                        eprintln!("partial transcription failed: {err:#}");
+                        continue;
+                    }
+                    _ => continue,
+                };
+                // With automatic detection only the model knows the language.
+                let language = if transcript.language.is_empty() {
+                    config.language.clone()
+                } else {
+                    transcript.language
+                };
+                let chunk = if config.remove_fillers {
+                    post_processing::remove_fillers(&transcript.text, &language)
+                } else {
+                    transcript.text
+                };
+                let chunk = filter_profanity(
+                    &apply_replacements(&chunk, &config.replacements),
+                    &language,
+                    &config.profanity_filter,
+                );
+                let (text, typer) = {
+                    let mut session = session.lock().unwrap();
+                    if session.stopped {
+                        break;
+                    }
+                    session.text = streaming::stitch(&session.text, &chunk);
+                    (session.text.clone(), session.typer.clone())
+                };
+                if let Some(typer) = typer {
+                    let typed = text.clone();
+                    let _ =
+                        task::spawn_blocking(move || typer.lock().unwrap().update(&typed)).await;
+                }
+                events.emit(
+                    &app,
+                    "transcription:partial",
+                    serde_json::json!({ "text": text }),
+                );
+            }
+        });
+    }
+
+    pub async fn stop_recording(&self, app: &AppHandle) -> Result<String> {
+        if !self.recorder.is_recording() {
+            return Ok(String::new());
+        }
+        self.tray.set_mode(TrayMode::Processing);
+        self.events.emit(
+            app,
+            "status:changed",
+            serde_json::json!({ "status": "processing", "message": null }),
+        );
+        let config = self.config.snapshot();
+        let incognito = self.incognito.load(Ordering::Relaxed);
+        let prompt_override = self.prompt_override.lock().unwrap().take();
+        let tag = self.dictation_tag.lock().unwrap().take();
+        let model_override = self.dictation_model.lock().unwrap().take();
+        // Looked up while the audio is prepared: the focused window's rule can change
+        // the language and how the text is delivered.
+        let lookup = config.clone();
+        let focused = task::spawn_blocking(move || lookup_focused_window(&lookup));
+        // Joining or decoding a long recording takes a while; keep it off the async
+        // workers.
+        let recorder = self.recorder.clone();
+        let mut captured = task::spawn_blocking(move || recorder.stop())
+            .await
+            .context("stop recording task")??;
+        if captured.dropped_ms > 0 {
+            self.events.emit(
+                app,
+                "audio:dropped",
+                serde_json::json!({ "droppedMs": captured.dropped_ms }),
+            );
+        }
+        let partial_typer = self.partial.lock().unwrap().take().and_then(|session| {
+            let mut session = session.lock().unwrap();
+            session.stopped = true;
+            session.typer.take()
+        });
+        self.set_clipping_tooltip(false);
+        if config.cue_on_stop {
+            cues::play(Cue::Stop, config.cue_volume, &config.audio_host);
+        }
+        let recording_ms = if captured.buffer.sample_rate > 0 {
+            captured.buffer.samples.len() as u64 * 1000 / captured.buffer.sample_rate as u64
+        } else {
+            0
+        };
+        let start_latency_ms = captured
+            .start_latency
+            .map(|latency| latency.as_millis() as u64);
+        let device = captured.device.as_deref().filter(|_| !incognito);
+        if let (Some(device), Some(latency_ms)) = (device, start_latency_ms) {
+            self.diagnostics
+                .lock()
+                .unwrap()
+                .record_start_latency(device, latency_ms);
+        }
+        if let Some(reference) = captured.echo_reference.take() {
+            let resampler = config.resampler.clone();
+            captured.buffer = task::spawn_blocking(move || {
+                echo::cancel_echo(captured.buffer, reference, &resampler)
+            })
+            .await
+            .context("echo cancellation task")?;
+        }
+        let mut audio = resample_for_whisper(captured.buffer, &config.resampler);
+        apply_gain(&mut audio, &config.gain_mode);
+        // Nothing was said (e.g. an accidental activation): skip the model and keep the
+        // free quota intact. Checked after gain, so a quiet microphone is not mistaken
+        // for silence.
+        if !audio.samples.is_empty() && recording::is_silent(&audio, config.vad_threshold) {
+            self.tray.set_mode(TrayMode::Idle);
+            self.events.emit(
+                app,
+                "status:changed",
+                serde_json::json!({ "status": "silence", "message": null }),
+            );
+            return Ok(String::new());
+        }
+        if audio.samples.is_empty() {
+            self.tray.set_mode(TrayMode::Idle);
+            return Ok(String::new());
+        }
+        let window = focused.await.ok().flatten();
+        let (config, rule) = config_for_window(config, window.as_ref());
+        let model_id = model_override.unwrap_or_else(|| config.active_model.clone());
+        if !models::model_is_valid(&model_id)? {
+            self.download_model(app, &model_id).await?;
+        }
+        let samples = Arc::new(audio.samples);
+        let start = std::time::Instant::now();
+        let mut transcribe = transcribe_options(&config);
+        if let Some(prompt) = prompt_override {
+            transcribe.initial_prompt = prompt;
+        }
+        let greedy = decoding_params(&config).beam_size <= 1;
+        if !incognito {
+            *self.in_flight.lock().unwrap() = Some(samples.clone());
+        }
+        let mut transcription = self.transcribe_with_model(
+            &config,
+            &model_id,
+            samples.clone(),
+            transcribe,
+            self.running_child.clone(),
+        );
+        let slow_after =
+            (Duration::from_millis(recording_ms) * SLOW_PROCESSING_FACTOR).max(MIN_SLOW_PROCESSING);
+        let mut escalated = false;
+        let joined = tokio::select! {
+            result = &mut transcription => result,
+            _ = tokio::time::sleep(slow_after) => {
+                escalated = true;
+                self.escalate_slow_processing(app, &model_id, recording_ms);
+                transcription.await
+            }
+        };
+        self.in_flight.lock().unwrap().take();
+        let text_result = joined.context("transcribe task")?;
+        let cancelled = text_result.as_ref().is_err_and(is_cancelled);
+        if escalated {
+            self.reset_tooltip();
+        }
+        if escalated && !incognito {
+            self.diagnostics.lock().unwrap().record_slow_run(SlowRun {
+                model_id: model_id.clone(),
+                recording_ms,
+                processing_ms: start.elapsed().as_millis() as u64,
+                cancelled,
+                at: unix_timestamp(),
+            });
+        }
+        let transcript = match text_result {
+            Ok(transcript) => transcript,
+            Err(_) if cancelled => {
+                self.tray.set_mode(TrayMode::Idle);
+                self.events.emit(
+                    app,
+                    "status:changed",
+                    serde_json::json!({ "status": "cancelled", "message": null }),
+                );
+                return Ok(String::new());
+            }
+            Err(err) => {
+                if is_timed_out(&err) {
+                    self.events.emit(
+                        app,
+                        "transcription:timeout",
+                        serde_json::json!({ "modelId": model_id, "recordingMs": recording_ms }),
+                    );
+                    // The hung transcriber is gone; load a fresh one for the next dictation.
+                    let state = self.clone();
+                    let handle = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let _ = state.preload_transcribe_server(&handle, false).await;
+                    });
+                }
+                // Kept so `retranscribe_last` can recover the dictation once the
+                // transcriber works again.
+                let recoverable = !incognito && {
+                    let saved = retention::failed_recording_path()
+                        .and_then(|path| write_wav(&path, &samples));
+                    if let Err(save_err) = &saved {
+                        eprintln!("saving failed recording failed: {save_err:#}");
+                    }
+                    saved.is_ok()
+                };
+                self.tray.set_mode(TrayMode::Error);
+                self.events.emit(
+                    app,
+                    "status:changed",
+                    serde_json::json!({
+                        "status": "error",
+                        "message": err.to_string(),
+                        "recoverable": recoverable,
+                    }),
+                );
+                return Err(err);
+            }
+        };
+        if !incognito {
+            let diagnostics = self.diagnostics.clone();
+            let (config_store, benchmarks) = (self.config.clone(), self.benchmarks.clone());
+            let telemetry_model = model_id.clone();
+            let (tokens, decode_ms) = (transcript.tokens, transcript.decode_ms);
+            task::spawn_blocking(move || {
+                let run = RunTelemetry::new(
+                    &telemetry_model,
+                    recording_ms,
+                    decode_ms,
+                    tokens,
+                    thermal::read(),
+                    unix_timestamp(),
+                );
+                diagnostics.lock().unwrap().record_run(run);
+                {
+                    let mut benchmarks = benchmarks.lock().unwrap();
+                    benchmarks.record(&telemetry_model, greedy, recording_ms, decode_ms);
+                    if let Err(err) = latency::save_benchmarks(&benchmarks) {
+                        eprintln!("saving benchmarks failed: {err:#}");
+                    }
+                }
+                if let Err(err) = apply_latency_budget(&config_store, &benchmarks) {
+                    eprintln!("latency budget failed: {err:#}");
+                }
+            });
+        }
+        let created_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let history_id = self.history_ids.next(created_at);
+        if !incognito {
+            *self.last_audio.lock().unwrap() = Some(LastAudio {
+                created_at,
+                history_id,
+                samples: samples.clone(),
+            });
+        }
+        if config.keep_recordings && !incognito {
+            // Only recordings the user asked to keep are written to disk.
+            let kept = write_temp_wav(&samples).and_then(|wav_path| {
+                let kept = recordings_dir(&config).and_then(|dir| {
+                    retention::keep_recording(
+                        &wav_path,
+                        &dir,
+                        created_at,
+                        config.recordings_keep_count,
+                        config.recordings_keep_days,
+                    )
+                });
+                if kept.is_err() {
+                    let _ = fs::remove_file(&wav_path);
+                }
+                kept
+            });
+            if let Err(err) = kept {
+                eprintln!("keeping recording failed: {err:#}");
+            }
+        }
+        let language = if transcript.language.is_empty() {
+            config.language.clone()
+        } else {
+            transcript.language
+        };
+        let (text, segments) =
+            post_process(&transcript.text, transcript.segments, &language, &config);
+        let timed_segments = config.word_timestamps.then(|| segments.clone());
+        let segment_confidence: Vec<f32> =
+            segments.iter().map(|segment| segment.confidence).collect();
+        let confidence = (!segment_confidence.is_empty())
+            .then(|| segment_confidence.iter().sum::<f32>() / segment_confidence.len() as f32);
+        let duration_ms = start.elapsed().as_millis() as u64;
+        if !text.is_empty() {
+            if config.read_aloud == READ_ALOUD_BEFORE {
+                let spoken = text.clone();
+                let result = task::spawn_blocking(move || speech::speak_blocking(&spoken)).await;
+                if let Ok(Err(err)) = result {
+                    eprintln!("read aloud failed: {err}");
+                }
+            }
+            // Typed partials cannot be wrapped after the fact, so only whole-text
+            // deliveries get direction marks.
+            let trailing = paste::appended(&config.append_after);
+            let wrapped = format!(
+                "{}{trailing}",
+                post_processing::wrap_direction(&text, &language, &config.bidi_marks)
+            );
+            let target_app = window
+                .as_ref()
+                .map(|window| window.app.clone())
+                .filter(|app| !app.is_empty());
+            let send = rule.as_ref().is_some_and(|rule| rule.send_enter);
+            let typed = format!("{text}{trailing}");
+            let (delivery_config, delivery_app) = (config.clone(), target_app.clone());
+            // Typing sleeps between keys and pasting waits for the target, so neither
+            // holds an async worker.
+            let delivery = task::spawn_blocking(move || {
+                let config = delivery_config;
+                let (backend, output) =
+                    if config.presentation_mode || config.output_mode == OUTPUT_CLIPBOARD {
+                        (
+                            "clipboard_copy",
+                            copy_text(&wrapped).map(|_| PasteOutcome::Copied),
+                        )
+                    } else if config.output_mode == OUTPUT_PROGRESSIVE {
+                        // Reuses the streaming typer so partial text already typed gets corrected.
+                        (
+                            "progressive_typing",
+                            match partial_typer {
+                                Some(typer) => typer.lock().unwrap().update(&typed),
+                                None => ProgressiveTyper::new().update(&typed),
+                            }
+                            .map(|_| PasteOutcome::Pasted),
+                        )
+                    } else if config.output_mode == OUTPUT_TYPE {
+                        (
+                            "keyboard_typing",
+                            paste::type_out(&wrapped).map(|_| PasteOutcome::Pasted),
+                        )
+                    } else {
+                        let keys =
+                            paste::resolve_paste_keys(&config.paste_keys, delivery_app.as_deref());
+                        (
+                            "clipboard_paste",
+                            paste_guarded(
+                                &wrapped,
+                                &config.clipboard_guard,
+                                config.restore_clipboard,
+                                keys,
+                            ),
+                        )
+                    };
+                if send && matches!(output, Ok(PasteOutcome::Pasted)) {
+                    if let Err(err) = paste::press_enter() {
+                        eprintln!("send after paste failed: {err}");
+                    }
+                }
+                (backend, output)
+            })
+            .await;
+            let (backend, output) = match delivery {
+                Ok(delivery) => delivery,
+                Err(err) => (
+                    "none",
+                    Err(anyhow::Error::new(err).context("delivery task")),
+                ),
+            };
+            match output {
+                Ok(PasteOutcome::Pasted) if config.cue_on_paste => {
+                    cues::play(Cue::Pasted, config.cue_volume, &config.audio_host);
+                }
+                Ok(PasteOutcome::Held) => {
+                    self.events.emit(
+                        app,
+                        "paste:held",
+                        serde_json::json!({ "reason": "clipboard_not_text" }),
+                    );
+                    let _ = windows::show_history_window(app);
+                }
+                Ok(PasteOutcome::Copied) if !config.presentation_mode => {
+                    self.events.emit(
+                        app,
+                        "paste:copied",
+                        serde_json::json!({ "chars": text.chars().count() }),
+                    );
+                }
+                _ => {}
+            }
+            let last = LastTranscription {
+                text: text.clone(),
+                target_app,
+                backend,
+                outcome: match output {
+                    Ok(PasteOutcome::Pasted) => "pasted",
+                    Ok(PasteOutcome::Held) => "held",
+                    Ok(PasteOutcome::Copied) => "copied",
+                    Err(_) => "failed",
+                },
+                created_at,
+                history_id,
+                recording_ms,
+                processing_ms: duration_ms,
+            };
+            if !incognito {
+                *self.last_transcription.lock().unwrap() = Some(last);
+            }
+            if config.read_aloud == READ_ALOUD_AFTER {
+                speech::speak(&text);
+            }
+            // Quota still counts incognito dictations; only what would leave a trace is
+            // skipped.
+            if let Err(err) = self.record_usage(recording_ms, !incognito) {
+                eprintln!("recording usage failed: {err:#}");
+            }
+            self.emit_quota(app);
+        }
+        if !text.is_empty() && !incognito {
+            let _ = self.record_history(
+                app,
+                HistoryEntry {
+                    id: history_id,
+                    text: text.clone(),
+                    model_id: model_id.clone(),
+                    language,
+                    created_at,
+                    duration_ms,
+                    segments,
+                    revision: None,
+                    tag,
+                },
+            );
+        }
+        if !incognito {
+            self.events.emit(
+                app,
+                "session:stats",
+                SessionStats {
+                    device: captured.device,
+                    start_latency_ms,
+                    recording_ms,
+                    transcription_ms: duration_ms,
+                    dropped_ms: captured.dropped_ms,
+                },
+            );
+        }
+        self.events.emit(
+            app,
+            "transcription:result",
+            TranscriptionEvent {
+                text: text.clone(),
+                model_id: model_id.clone(),
+                duration_ms,
+                segments: timed_segments,
+                segment_confidence,
+                confidence,
+            },
+        );
+        self.tray.set_mode(TrayMode::Idle);
+        self.events.emit(
+            app,
+            "status:changed",
+            serde_json::json!({ "status": "idle", "message": null }),
+        );
+        Ok(text)
+    }
+}
+
+fn parse_tag_hotkeys(shortcuts: &BTreeMap<String, String>) -> Vec<(Hotkey, String)> {
+    shortcuts
+        .iter()
+        .filter_map(|(tag, shortcut)| Some((Hotkey::parse(shortcut)?, tag.clone())))
+        .collect()
+}
+
+fn recordings_dir(config: &AppConfig) -> Result<PathBuf> {
+    match config
+        .recordings_dir
+        .as_deref()
+        .filter(|dir| !dir.is_empty())
+    {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => retention::default_recordings_dir(),
+    }
+}
+
+/// The post-processing pipeline between the model and delivery: filler removal, spoken
+/// number and symbol formatting and punctuation restoration when enabled, then
+/// replacements, so user rules get the last word among edits, and the profanity filter
+/// last so no rule can reintroduce a filtered word. Segments skip punctuation
+/// restoration. When the model marked speaker turns, the text becomes one labeled line
+/// per speaker.
+fn post_process(
+    text: &str,
+    segments: Vec<Segment>,
+    language: &str,
+    config: &AppConfig,
+) -> (String, Vec<Segment>) {
+    let unfill = |text: &str| {
+        let text = if config.remove_fillers {
+            post_processing::remove_fillers(text, language)
+        } else {
+            text.to_string()
+        };
+        if config
+            .spoken_formatting
+            .iter()
+            .any(|enabled| enabled == language)
+        {
+            normalize::format_spoken(&text, language)
+        } else {
+            text
+        }
+    };
+    let punctuate = |text: &str| {
+        if config.restore_punctuation {
+            post_processing::restore_punctuation(text, language)
+        } else {
+            text.to_string()
+        }
+    };
+    let rules = &config.replacements;
+    let clean = |text: &str| {
+        filter_profanity(
+            &apply_replacements(text, rules),
+            language,
+            &config.profanity_filter,
+        )
+    };
+    let text = if segments.iter().any(|segment| segment.speaker_turn) {
+        let turns: Vec<Segment> = segments
+            .iter()
+            .map(|segment| Segment {
+                text: clean(&punctuate(&unfill(&segment.text))),
+                ..segment.clone()
+            })
+            .collect();
+        post_processing::mark_speaker_turns(&turns)
+    } else {
+        clean(&punctuate(&unfill(text)))
+    };
+    let segments = segments
+        .into_iter()
+        .map(|segment| Segment {
+            text: clean(&unfill(&segment.text)),
+            ..segment
+        })
+        .collect();
+    (text, segments)
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn write_temp_wav(samples: &[f32]) -> Result<PathBuf> {
+    let mut path = env::temp_dir();
+    let stamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    path.push(format!("whisperdict-{}.wav", stamp));
+    write_wav(&path, samples)?;
+    Ok(path)
+}
+
+fn write_wav(path: &Path, samples: &[f32]) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16_000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).context("create wav")?;
+    for &sample in samples {
+        let clamped = sample.clamp(-1.0, 1.0);
+        let value = (clamped * i16::MAX as f32) as i16;
+        writer.write_sample(value).context("write wav sample")?;
+    }
+    writer.finalize().context("finalize wav")?;
+    Ok(())
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct ServerOptions {
+    sandbox: bool,
+    in_process: bool,
+    context: ContextOptions,
+    /// Least time a transcription gets before the watchdog kills the child; 0 for none.
+    timeout_secs: u64,
+}
+
+impl ServerOptions {
+    /// Options for serving `model_id`, with its compute override applied.
+    fn from_config(config: &AppConfig, model_id: &str) -> Self {
+        let compute = config
+            .model_compute
+            .get(model_id)
+            .copied()
+            .unwrap_or_default();
+        let backend = if compute.gpu {
+            config.compute_backend.clone()
+        } else {
+            compute::BACKEND_CPU.to_string()
+        };
+        Self {
+            sandbox: config.sandbox_transcriber,
+            // A config edited by hand may still combine them; the child wins.
+            in_process: config.in_process_transcription
+                && !config.sandbox_transcriber
+                && config.transcription_timeout_secs == 0,
+            context: ContextOptions {
+                // Models without a published encoder, such as imported ones, would only
+                // fail to find one.
+                coreml: compute::coreml_enabled(&backend)
+                    && models::coreml_encoder_name(model_id).is_some(),
+                backend,
+                gpu_device: compute.device,
+                flash_attn: config.advanced_decoding.flash_attn,
+            },
+            timeout_secs: config.transcription_timeout_secs,
+        }
+    }
+
+    /// How long transcribing `samples` of 16 kHz audio may take.
+    fn timeout_for(&self, samples: usize) -> Option<Duration> {
+        if self.timeout_secs == 0 {
+            return None;
+        }
+        let audio_secs = samples as u64 / 16_000;
+        Some(Duration::from_secs(
+            self.timeout_secs.max(audio_secs * TIMEOUT_AUDIO_FACTOR),
+        ))
+    }
+}
+
+/// Kills a child that has not answered within its limit, so a decode wedged on bad audio
+/// cannot hold a blocking thread forever.
+struct Watchdog {
+    done: mpsc::Sender<()>,
+    fired: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    fn start(child: ChildHandle, limit: Duration) -> Self {
+        let (done, finished) = mpsc::channel();
+        let fired = Arc::new(AtomicBool::new(false));
+        let fired_flag = fired.clone();
+        std::thread::spawn(move || {
+            if finished.recv_timeout(limit) == Err(RecvTimeoutError::Timeout) {
+                fired_flag.store(true, Ordering::SeqCst);
+                child.kill();
+            }
+        });
+        Self { done, fired }
+    }
+
+    /// Stops watching; true when the child had already been killed.
+    fn finish(self) -> bool {
+        let _ = self.done.send(());
+        self.fired.load(Ordering::SeqCst)
+    }
+}
+
+fn emit_preload(
+    events: &EventBus,
+    app: &AppHandle,
+    model_id: &str,
+    stage: &'static str,
+    message: Option<String>,
+) {
+    events.emit(
+        app,
+        "models:preload",
+        PreloadEvent {
+            model_id: model_id.to_string(),
+            stage,
+            message,
+        },
+    );
+}
+
+fn quota_tooltip(quota: &QuotaState) -> String {
+    let tooltip = match (quota.warning, quota.free_seconds_left) {
+        (Some("exhausted"), Some(_)) => {
+            Some("Whisperdict: free dictation time used up".to_string())
+        }
+        (Some("exhausted"), None) => Some("Whisperdict: free transcriptions used up".to_string()),
+        (Some(_), Some(seconds)) => Some(format!(
+            "Whisperdict: {}:{:02} of free dictation left",
+            seconds / 60,
+            seconds % 60
+        )),
+        (Some(_), None) => Some(format!(
+            "Whisperdict: {} free transcription{} left",
+            quota.free_transcriptions_left,
+            if quota.free_transcriptions_left == 1 {
+                ""
+            } else {
+                "s"
+            }
+        )),
+        (None, _) => None,
+    };
+    tooltip.unwrap_or_else(|| "Whisperdict".to_string())
+}
+
+/// Switches to the model the latency budget picked for the configured language, if it has
+/// measurements to go on. Benchmarks from other hardware are dropped first.
+fn apply_latency_budget(config: &ConfigStore, benchmarks: &Mutex<Benchmarks>) -> Result<()> {
+    let snapshot = config.snapshot();
+    if snapshot.max_latency_ms == 0 {
+        if snapshot.latency_pick.is_some() {
+            config.update(|config| config.latency_pick = None)?;
+        }
+        return Ok(());
+    }
+    let policy = managed_config::policy();
+    let models: Vec<models::ModelStatus> = models::list_models()?
+        .into_iter()
+        .map(|model| models::ModelStatus {
+            installed: model.installed && policy.model_allowed(&model.id),
+            ..model
+        })
+        .collect();
+    let hardware = latency::hardware_fingerprint(&snapshot.compute_backend);
+    let pick = {
+        let mut benchmarks = benchmarks.lock().unwrap();
+        if benchmarks.hardware != hardware {
+            *benchmarks = std::mem::take(&mut *benchmarks).for_hardware(&hardware);
+            latency::save_benchmarks(&benchmarks)?;
+        }
+        benchmarks.pick(
+            snapshot.max_latency_ms,
+            snapshot.beam_size > 1,
+            &snapshot.language,
+            &models,
+        )
+    };
+    if pick == snapshot.latency_pick {
+        return Ok(());
+    }
+    config.update(|config| {
+        if let Some(pick) = &pick {
+            config.active_model = pick.model_id.clone();
+        }
+        config.latency_pick = pick;
+    })
+}
+
+/// The focused window, when an app rule or the "auto" paste shortcut depends on it; titles
+/// are only read when a rule matches on one. Blocking.
+fn lookup_focused_window(config: &AppConfig) -> Option<FocusedWindow> {
+    if config.app_rules.is_empty() && !paste::keys_follow_app(&config.paste_keys) {
+        return None;
+    }
+    let title = config
+        .app_rules
+        .iter()
+        .any(|rule| !rule.title.trim().is_empty());
+    focused_window::focused(title)
+}
+
+/// `config` with the rule for `window` applied, and that rule.
+fn config_for_window(
+    mut config: Arc<AppConfig>,
+    window: Option<&FocusedWindow>,
+) -> (Arc<AppConfig>, Option<AppRule>) {
+    let rule = window.and_then(|window| app_rules::matching(&config.app_rules, window).cloned());
+    if let Some(rule) = &rule {
+        rule.apply(Arc::make_mut(&mut config));
+    }
+    (config, rule)
+}
+
+fn decoding_params(config: &AppConfig) -> DecodingParams {
+    let greedy = config
+        .latency_pick
+        .as_ref()
+        .is_some_and(|pick| pick.greedy && pick.model_id == config.active_model);
+    DecodingParams {
+        beam_size: if greedy { 1 } else { config.beam_size },
+        temperature: config.temperature,
+        temperature_inc: config.temperature_inc,
+        entropy_threshold: config.entropy_threshold,
+        logprob_threshold: config.logprob_threshold,
+        threads: config.transcription_threads,
+        no_context: config.advanced_decoding.no_context,
+        suppress_blank: config.advanced_decoding.suppress_blank,
+        max_segment_len: config.advanced_decoding.max_segment_len,
+        split_on_word: config.advanced_decoding.split_on_word,
+    }
+}
+
+fn transcribe_options(config: &AppConfig) -> TranscribeOptions {
+    TranscribeOptions {
+        language: config.language.clone(),
+        translate: false,
+        initial_prompt: config.initial_prompt.clone(),
+        word_timestamps: config.word_timestamps,
+        diarize: config.diarize && models::supports_speaker_turns(&config.active_model),
+    }
+}
+
+fn clamp_prompt(prompt: &str) -> String {
+    prompt
+        .trim()
+        .chars()
+        .take(MAX_INITIAL_PROMPT_CHARS)
+        .collect()
+}
+
+/// Shared with `AppState` while a request is in flight so it can be killed without the
+/// server lock, which the blocked request holds.
+#[derive(Clone)]
+struct ChildHandle(Arc<Mutex<Child>>);
+
+#[derive(Default)]
+struct RunningState {
+    child: Option<ChildHandle>,
+    active: bool,
+    cancelled: bool,
+}
+
+/// The child working on the current dictation, for `cancel_processing`. A cancel sticks
+/// until the next dictation arms the slot, so it also stops a retry that has no child yet.
+#[derive(Default)]
+struct Running(Mutex<RunningState>);
+
+impl Running {
+    fn arm(&self) {
+        *self.0.lock().unwrap() = RunningState {
+            active: true,
+            ..RunningState::default()
+        };
+    }
+
+    fn disarm(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.active = false;
+        state.child = None;
+    }
+
+    /// Exposes `child` to `cancel`; fails when the dictation was cancelled already.
+    fn hold(&self, child: &ChildHandle) -> Result<()> {
+        let mut state = self.0.lock().unwrap();
+        if state.cancelled {
+            return Err(TranscriptionCancelled.into());
+        }
+        state.child = Some(child.clone());
+        Ok(())
+    }
+
+    /// Takes the child back; false when `cancel` got to it first.
+    fn release(&self) -> bool {
+        self.0.lock().unwrap().child.take().is_some()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.lock().unwrap().cancelled
+    }
+
+    /// Kills the child in flight; false when nothing was processing.
+    fn cancel(&self) -> bool {
+        let child = {
+            let mut state = self.0.lock().unwrap();
+            let busy = state.active || state.child.is_some();
+            state.cancelled |= busy;
+            match state.child.take() {
+                Some(child) => child,
+                None => return busy,
+            }
+        };
+        child.kill();
+        true
+    }
+}
+
+impl ChildHandle {
+    fn kill(&self) {
+        let mut child = self.0.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(self.0.lock().unwrap().try_wait(), Ok(None))
+    }
+}
+
+enum TranscribeBackend {
+    Child {
+        child: ChildHandle,
+        link: ChildLink,
+    },
+    /// The model loaded into this process: no spawn or socket, but also no isolation from
+    /// whisper.cpp crashes and no way to cancel a decode.
+    InProcess(WhisperContext),
+}
+
+/// Dropping a server, e.g. when a new one replaces it, kills and reaps its child; an
+/// in-process model is simply freed.
+struct TranscribeServer {
+    model_id: String,
+    options: ServerOptions,
+    decoding: DecodingParams,
+    backend: TranscribeBackend,
+}
+
+impl Drop for TranscribeServer {
+    fn drop(&mut self) {
+        if let TranscribeBackend::Child { child, .. } = &self.backend {
+            child.kill();
+        }
+    }
+}
+
+impl TranscribeServer {
+    /// Swaps decoding parameters in the running child instead of respawning it.
+    fn apply_decoding(&mut self, decoding: &DecodingParams) -> Result<()> {
+        if self.decoding == *decoding {
+            return Ok(());
+        }
+        self.send_params(decoding)
+    }
+
+    /// The child connects only after the model is loaded, so an answered ping means it is
+    /// ready.
+    fn wait_until_loaded(&mut self) -> Result<()> {
+        if matches!(self.backend, TranscribeBackend::InProcess(_)) {
+            return Ok(());
+        }
+        self.request(&Running::default(), &RequestBody::Ping, &[])?
+            .into_transcript()
+            .map(|_| ())
+    }
+
+    /// Sends one request with the audio it announces and reads its reply; `running`
+    /// exposes the child meanwhile.
+    fn request(
+        &mut self,
+        running: &Running,
+        request: &RequestBody,
+        audio: &[f32],
+    ) -> Result<Response> {
+        // Loading a model can legitimately take long, so only decodes are watched.
+        let limit = match request {
+            RequestBody::Transcribe { samples, .. } => self.options.timeout_for(*samples),
+            RequestBody::SetParams { .. } | RequestBody::Ping => None,
+        };
+        let TranscribeBackend::Child { child, link } = &mut self.backend else {
+            anyhow::bail!("transcriber runs in-process");
+        };
+        let id = link.next_id();
+        let frame = Request::to_frame(id, request.clone())?;
+        running.hold(child)?;
+        let alive = child.clone();
+        let stream = link.stream(|| alive.is_running())?;
+        // Watched from the first byte: a child that stops reading wedges the write too.
+        let watchdog = limit.map(|limit| Watchdog::start(child.clone(), limit));
+        let read = (|| {
+            write_frame(stream, &frame)?;
+            if matches!(request, RequestBody::Transcribe { .. }) {
+                write_pcm(stream, audio)?;
+            }
+            stream.flush().context("flush request")?;
+            let response = Response::parse(&read_frame(stream)?)?;
+            if response.id != id {
+                anyhow::bail!("reply {} does not answer request {id}", response.id);
+            }
+            anyhow::Ok(response)
+        })();
+        if watchdog.is_some_and(Watchdog::finish) {
+            running.release();
+            return Err(TranscriptionTimedOut.into());
+        }
+        // `cancel_processing` takes the handle before killing the child.
+        if !running.release() {
+            return Err(TranscriptionCancelled.into());
+        }
+        read.context("read child")
+    }
+
+    /// An empty transcript when nothing was heard.
+    fn transcribe(
+        &mut self,
+        running: &Running,
+        request: &RequestBody,
+        audio: &[f32],
+    ) -> Result<Transcript> {
+        if let TranscribeBackend::InProcess(ctx) = &self.backend {
+            let RequestBody::Transcribe {
+                partial, options, ..
+            } = request
+            else {
+                anyhow::bail!("not a transcribe request");
+            };
+            let decoding = if *partial {
+                self.decoding.for_partial()
+            } else {
+                self.decoding.clone()
+            };
+            return transcription::transcribe_with_context(ctx, audio, options, &decoding);
+        }
+        Ok(self
+            .request(running, request, audio)?
+            .into_transcript()?
+            .unwrap_or_default())
+    }
+
+    fn send_params(&mut self, decoding: &DecodingParams) -> Result<()> {
+        if matches!(self.backend, TranscribeBackend::InProcess(_)) {
+            self.decoding = decoding.clone();
+            return Ok(());
+        }
+        let request = RequestBody::SetParams {
+            params: decoding.clone(),
+        };
+        self.request(&Running::default(), &request, &[])?
+            .into_transcript()
+            .context("transcriber rejected decoding parameters")?;
+        self.decoding = decoding.clone();
+        Ok(())
+    }
+}
+
+/// Decodes an in-progress chunk on the already running server of `model_id`; `None` when
+/// there is no matching server yet or nothing was heard. Never spawns one, so partials
+/// cannot delay the final transcription with a model load.
+fn transcribe_partial(
+    server: &Mutex<Option<TranscribeServer>>,
+    model_id: &str,
+    config: &AppConfig,
+    audio: AudioBuffer,
+) -> Result<Option<Transcript>> {
+    let audio = resample_for_whisper(audio, &config.resampler);
+    let request = RequestBody::Transcribe {
+        samples: audio.samples.len(),
+        partial: true,
+        options: TranscribeOptions {
+            word_timestamps: false,
+            diarize: false,
+            ..transcribe_options(config)
+        },
+    };
+    let transcript = {
+        let mut guard = server.lock().unwrap();
+        let options = ServerOptions::from_config(config, model_id);
+        match guard
+            .as_mut()
+            .filter(|srv| srv.model_id == model_id && srv.options == options)
+        {
+            Some(srv) => srv
+                .apply_decoding(&decoding_params(config))
+                .and_then(|_| srv.transcribe(&Running::default(), &request, &audio.samples))
+                .map(Some),
+            None => Ok(None),
+        }
+    };
+    Ok(transcript?.filter(|transcript| !transcript.text.is_empty()))
+}
+
+fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TranscriptionCancelled>().is_some()
+}
+
+fn is_timed_out(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TranscriptionTimedOut>().is_some()
+}
+
+/// Adds the transcript of a piece of audio starting `offset_ms` into the whole.
+fn append_transcript(whole: &mut Transcript, part: Transcript, offset_ms: u64) {
+    if !part.text.is_empty() {
+        if !whole.text.is_empty() {
+            whole.text.push(' ');
+        }
+        whole.text.push_str(part.text.trim());
+    }
+    whole
+        .segments
+        .extend(part.segments.into_iter().map(|mut segment| {
+            segment.t0 += offset_ms;
+            segment.t1 += offset_ms;
+            for word in &mut segment.words {
+                word.t0 += offset_ms;
+                word.t1 += offset_ms;
+            }
+            segment
+        }));
+    whole.tokens += part.tokens;
+    whole.decode_ms += part.decode_ms;
+    if whole.language.is_empty() {
+        whole.language = part.language;
+    }
+}
+
+/// Whether a fresh transcriber could succeed where this one failed. A timed-out child was
+/// killed by its watchdog, and the same audio would likely wedge a fresh one too; a model
+/// too large to load, missing files or an unavailable sandbox fail the same way again.
+fn is_retryable(err: &anyhow::Error) -> bool {
+    !(is_cancelled(err)
+        || is_timed_out(err)
+        || err.downcast_ref::<ModelTooLarge>().is_some()
+        || err.downcast_ref::<TranscriberUnavailable>().is_some())
+}
+
+fn transcribe_with_server(
+    server: Arc<Mutex<Option<TranscribeServer>>>,
+    running: &Running,
+    model_id: &str,
+    audio: &[f32],
+    transcribe: &TranscribeOptions,
+    options: &ServerOptions,
+    decoding: &DecodingParams,
+) -> Result<Transcript> {
+    let request = RequestBody::Transcribe {
+        samples: audio.len(),
+        partial: false,
+        options: transcribe.clone(),
+    };
+    let mut attempt = 1;
+    loop {
+        if running.is_cancelled() {
+            return Err(TranscriptionCancelled.into());
+        }
+        let mut guard = server.lock().unwrap();
+        let needs_restart = guard
+            .as_ref()
+            .map(|s| s.model_id != model_id || s.options != *options)
+            .unwrap_or(true);
+        let result = (|| {
+            if needs_restart {
+                // Frees the old model's memory before the new one is checked against it.
+                guard.take();
+                *guard = Some(spawn_server(model_id, options)?);
+            }
+            let srv = guard.as_mut().context("missing server")?;
+            srv.apply_decoding(decoding)?;
+            srv.transcribe(running, &request, audio)
+        })();
+        match result {
+            Err(err) if !is_retryable(&err) => {
+                *guard = None;
+                return Err(err);
+            }
+            Err(err) => {
+                // A crashed or failing child is replaced by a fresh one.
+                guard.take();
+                if attempt == TRANSCRIBE_ATTEMPTS {
+                    return Err(err)
+                        .context(format!("transcription failed after {attempt} attempts"));
+                }
+                eprintln!("transcriber failed (attempt {attempt}), restarting: {err:#}");
+                // Other transcriptions may use the server while this one waits.
+                drop(guard);
+                std::thread::sleep(TRANSCRIBE_RETRY_BACKOFF * 2u32.pow(attempt - 1));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Checks the model fits in memory first, so a too large one fails with `ModelTooLarge`
+/// instead of an opaque mmap failure in the child.
+fn spawn_server(model_id: &str, options: &ServerOptions) -> Result<TranscribeServer> {
+    if let Some(info) = models::get_model_info(model_id) {
+        memory::check_model_fits(
+            model_id,
+            info.size_mb,
+            &options.context.backend,
+            options.context.gpu_device,
+            0,
+        )?;
+    }
+    if options.context.coreml && !models::coreml_encoder_ready(model_id) {
+        return Err(TranscriberUnavailable(format!(
+            "the Core ML encoder for {model_id} is missing; download the model again"
+        ))
+        .into());
+    }
+    let model_path = models::model_path(model_id)?;
+    if !model_path.exists() {
+        return Err(TranscriberUnavailable(format!("model {model_id} is not downloaded")).into());
+    }
+    let backend = if options.in_process {
+        let model_path = model_path.to_str().context("model path is not UTF-8")?;
+        TranscribeBackend::InProcess(transcription::load_context(model_path, &options.context)?)
+    } else {
+        spawn_child(&model_path, options)?
+    };
+    Ok(TranscribeServer {
+        model_id: model_id.to_string(),
+        options: options.clone(),
+        decoding: DecodingParams::default(),
+        backend,
+    })
+}
+
+fn spawn_child(model_path: &Path, options: &ServerOptions) -> Result<TranscribeBackend> {
+    let link = ChildLink::expect()?;
+    let exe = env::current_exe().context("current exe")?;
+    let mut command = Command::new(exe);
+    command
+        .arg("--transcribe-server")
+        .arg("--model")
+        .arg(model_path)
+        .arg("--connect")
+        .arg(link.address()?)
+        .env(child_socket::TOKEN_ENV, link.token());
+    command
+        .arg("--backend")
+        .arg(&options.context.backend)
+        .arg("--gpu-device")
+        .arg(options.context.gpu_device.to_string());
+    if compute::gpu_mode(&options.context.backend)? != Some(false) {
+        if let Some((name, value)) = compute::device_env(options.context.gpu_device) {
+            command.env(name, value);
+        }
+    }
+    if options.context.flash_attn {
+        command.arg("--flash-attn");
+    }
+    if !options.context.coreml {
+        command.arg("--no-coreml");
+    }
+    if options.sandbox {
+        command.arg("--sandbox");
+    }
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .context("spawn server")?;
+
+    let child = ChildHandle(Arc::new(Mutex::new(child)));
+    let mut live = live_children().lock().unwrap();
+    live.retain(ChildHandle::is_running);
+    live.push(child.clone());
+    Ok(TranscribeBackend::Child { child, link })
+}
+
+/// Every transcriber child spawned and not yet reaped, including those held by a busy or
+/// background transcriber, so quitting never orphans one.
+fn live_children() -> &'static Mutex<Vec<ChildHandle>> {
+    static CHILDREN: OnceLock<Mutex<Vec<ChildHandle>>> = OnceLock::new();
+    CHILDREN.get_or_init(Mutex::default)
+}
+
+/// Kills and reaps every transcriber child; the last thing done before the app exits.
+pub fn terminate_transcribers() {
+    for child in live_children().lock().unwrap().drain(..) {
+        child.kill();
+    }
+    child_socket::shutdown();
 }
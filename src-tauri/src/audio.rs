@@ -1,7 +1,13 @@
+use crate::compressed::CompressedAudio;
 use anyhow::{Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{FromSample, Sample, SampleFormat, SizedSample, Stream};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Clone)]
 pub struct AudioBuffer {
@@ -9,50 +15,967 @@ pub struct AudioBuffer {
     pub sample_rate: u32,
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AudioLevel {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// Samples at or above this magnitude count as clipped.
+pub const CLIP_LEVEL: f32 = 0.99;
+
+#[derive(Default)]
+pub struct LevelMeter {
+    rms: AtomicU32,
+    peak: AtomicU32,
+    frames: AtomicU64,
+    clipped_frames: AtomicU64,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(&self, rms: f32, peak: f32, frames: usize, clipped: usize) {
+        self.rms.store(rms.to_bits(), Ordering::Relaxed);
+        // Non-negative f32 values order the same way as their bit patterns.
+        self.peak.fetch_max(peak.to_bits(), Ordering::Relaxed);
+        self.frames.fetch_add(frames as u64, Ordering::Relaxed);
+        self.clipped_frames
+            .fetch_add(clipped as u64, Ordering::Relaxed);
+    }
+
+    pub fn take(&self) -> AudioLevel {
+        AudioLevel {
+            rms: f32::from_bits(self.rms.load(Ordering::Relaxed)),
+            peak: f32::from_bits(self.peak.swap(0, Ordering::Relaxed)),
+        }
+    }
+
+    /// Returns `(clipped, total)` frame counts since the previous call.
+    pub fn take_clipping(&self) -> (u64, u64) {
+        (
+            self.clipped_frames.swap(0, Ordering::Relaxed),
+            self.frames.swap(0, Ordering::Relaxed),
+        )
+    }
+
+    pub fn reset(&self) {
+        self.rms.store(0, Ordering::Relaxed);
+        self.peak.store(0, Ordering::Relaxed);
+        self.frames.store(0, Ordering::Relaxed);
+        self.clipped_frames.store(0, Ordering::Relaxed);
+    }
+}
+
+pub const SOURCE_MICROPHONE: &str = "microphone";
+pub const SOURCE_SYSTEM: &str = "system";
+pub const SOURCE_MIXED: &str = "mixed";
+
 pub struct Recorder {
-    stream: Stream,
-    samples: Arc<Mutex<Vec<f32>>>,
+    streams: Vec<CaptureStream>,
+    has_microphone: bool,
+    host_id: cpal::HostId,
+    bluetooth: Option<BluetoothInput>,
+    device_name: String,
+    requested_at: Option<Instant>,
+    /// The last stream is a playback reference for echo cancellation, not recorded audio.
+    echo_reference: bool,
+    /// Why echo cancellation was requested but could not capture the playback.
+    echo_unavailable: Option<String>,
+}
+
+/// What `Recorder::stop` hands back.
+pub struct StoppedCapture {
+    pub buffer: AudioBuffer,
+    /// Playback recorded for echo cancellation, when it was on.
+    pub echo_reference: Option<AudioBuffer>,
+    /// Audio lost because the recorder fell behind the device.
+    pub dropped_ms: u64,
+}
+
+struct CaptureStream {
+    source: Source,
+    capture: Arc<Capture>,
+    chunks: Receiver<Vec<f32>>,
+    recorded: RecordedAudio,
     sample_rate: u32,
 }
 
-impl Recorder {
-    pub fn start() -> Result<Self> {
-        let host = cpal::default_host();
-        let device = host.default_input_device().context("no input device")?;
+/// Where a capture stream's samples come from.
+enum Source {
+    Device(Stream),
+    /// A sound server source recorded by a `parec` child, which is told the source on
+    /// its command line; ALSA's `pulse` plugin would only read it from the process
+    /// environment, which cannot be changed safely while other threads run.
+    #[cfg(target_os = "linux")]
+    Server(ServerRecorder),
+}
+
+impl Source {
+    fn play(&self) -> Result<()> {
+        match self {
+            Source::Device(stream) => stream.play()?,
+            #[cfg(target_os = "linux")]
+            Source::Server(recorder) => recorder.play()?,
+        }
+        Ok(())
+    }
+
+    fn pause(&self) {
+        match self {
+            Source::Device(stream) => {
+                let _ = stream.pause();
+            }
+            #[cfg(target_os = "linux")]
+            Source::Server(recorder) => recorder.pause(),
+        }
+    }
+}
+
+/// Rate `parec` is asked for, so the sound server resamples instead of us.
+#[cfg(target_os = "linux")]
+const SERVER_RATE: u32 = 16_000;
+
+#[cfg(target_os = "linux")]
+struct ServerRecorder {
+    source: String,
+    capture: Arc<Capture>,
+    meter: Arc<LevelMeter>,
+    running: Mutex<Option<ServerChild>>,
+}
+
+#[cfg(target_os = "linux")]
+struct ServerChild {
+    child: std::process::Child,
+    reader: std::thread::JoinHandle<()>,
+    stopping: Arc<AtomicBool>,
+}
+
+#[cfg(target_os = "linux")]
+impl ServerRecorder {
+    /// Starts `parec`; samples flow from a reader thread like device callbacks.
+    fn play(&self) -> Result<()> {
+        use std::io::Read;
+        use std::process::{Command, Stdio};
+
+        let mut running = self.running.lock().unwrap();
+        if running.is_some() {
+            return Ok(());
+        }
+        let mut child = Command::new("parec")
+            .arg(format!("--device={}", self.source))
+            .arg(format!("--rate={SERVER_RATE}"))
+            .args([
+                "--raw",
+                "--format=float32le",
+                "--channels=1",
+                "--latency-msec=20",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("start parec")?;
+        let mut output = child.stdout.take().context("parec output")?;
+        let stopping = Arc::new(AtomicBool::new(false));
+        let (capture, meter, stopped) =
+            (self.capture.clone(), self.meter.clone(), stopping.clone());
+        let reader = std::thread::spawn(move || {
+            let mut bytes = [0u8; 4096];
+            let mut pending = Vec::new();
+            let mut samples = Vec::new();
+            loop {
+                let read = match output.read(&mut bytes) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                pending.extend_from_slice(&bytes[..read]);
+                let whole = pending.len() / 4 * 4;
+                samples.clear();
+                samples.extend(pending[..whole].chunks_exact(4).map(|sample| {
+                    f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])
+                }));
+                pending.drain(..whole);
+                push_samples(&samples, 1, None, &capture, &meter);
+            }
+            if !stopped.load(Ordering::SeqCst) {
+                capture.lost.store(true, Ordering::SeqCst);
+            }
+        });
+        *running = Some(ServerChild {
+            child,
+            reader,
+            stopping,
+        });
+        Ok(())
+    }
+
+    /// Stops `parec` and waits for the reader, so no samples arrive afterwards.
+    fn pause(&self) {
+        let Some(mut running) = self.running.lock().unwrap().take() else {
+            return;
+        };
+        running.stopping.store(true, Ordering::SeqCst);
+        let _ = running.child.kill();
+        let _ = running.child.wait();
+        let _ = running.reader.join();
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for ServerRecorder {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
+
+/// Callback chunks queued between the audio thread and the recorder; at typical
+/// 10-20 ms callbacks this is several seconds of slack between drains.
+const CHUNK_QUEUE_LEN: usize = 512;
+
+struct Capture {
+    active: AtomicBool,
+    max_samples: AtomicUsize,
+    captured_samples: AtomicUsize,
+    dropped_samples: AtomicUsize,
+    preroll_samples: AtomicUsize,
+    preroll: Mutex<VecDeque<f32>>,
+    queue: SyncSender<Vec<f32>>,
+    /// Downmixed frames of the current callback, reused so the audio thread does not
+    /// allocate on every callback.
+    scratch: Mutex<Vec<f32>>,
+    /// Chunk buffers the recorder has copied out, handed back to the audio thread.
+    spare: Mutex<Vec<Vec<f32>>>,
+    first_sample_at: Mutex<Option<Instant>>,
+    monitor: Mutex<Option<Arc<MonitorTap>>>,
+    lost: AtomicBool,
+}
+
+impl Capture {
+    fn new(queue: SyncSender<Vec<f32>>) -> Self {
+        Self {
+            active: AtomicBool::new(false),
+            max_samples: AtomicUsize::new(0),
+            captured_samples: AtomicUsize::new(0),
+            dropped_samples: AtomicUsize::new(0),
+            preroll_samples: AtomicUsize::new(0),
+            preroll: Mutex::new(VecDeque::new()),
+            queue,
+            scratch: Mutex::new(Vec::new()),
+            spare: Mutex::new(Vec::new()),
+            first_sample_at: Mutex::new(None),
+            monitor: Mutex::new(None),
+            lost: AtomicBool::new(false),
+        }
+    }
+
+    fn recycle(&self, mut chunk: Vec<f32>) {
+        let mut spare = self.spare.lock().unwrap();
+        if spare.len() < CHUNK_QUEUE_LEN {
+            chunk.clear();
+            spare.push(chunk);
+        }
+    }
+}
+
+/// Samples per stored chunk, about a second and a half at 48 kHz.
+const STORED_CHUNK_LEN: usize = 1 << 16;
+
+/// Recorded audio kept in fixed-size chunks, so long sessions grow without repeatedly
+/// reallocating and copying one large buffer.
+#[derive(Default)]
+struct ChunkedAudio {
+    chunks: Vec<Vec<f32>>,
+    len: usize,
+}
+
+impl ChunkedAudio {
+    fn push(&mut self, mut samples: &[f32]) {
+        self.len += samples.len();
+        while !samples.is_empty() {
+            if !matches!(self.chunks.last(), Some(chunk) if chunk.len() < STORED_CHUNK_LEN) {
+                self.chunks.push(Vec::with_capacity(STORED_CHUNK_LEN));
+            }
+            let chunk = self.chunks.last_mut().expect("chunk just pushed");
+            let (head, rest) =
+                samples.split_at((STORED_CHUNK_LEN - chunk.len()).min(samples.len()));
+            chunk.extend_from_slice(head);
+            samples = rest;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.chunks.clear();
+        self.len = 0;
+    }
+
+    /// Copies everything recorded from sample `offset` on.
+    fn copy_from(&self, offset: usize) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(self.len.saturating_sub(offset));
+        let mut start = 0;
+        for chunk in &self.chunks {
+            let end = start + chunk.len();
+            if end > offset {
+                samples.extend_from_slice(&chunk[offset.saturating_sub(start)..]);
+            }
+            start = end;
+        }
+        samples
+    }
+
+    /// Joins the chunks into one buffer for the model, freeing each as it is copied so
+    /// memory peaks one chunk above the recording rather than at twice it.
+    fn into_samples(self) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(self.len);
+        for chunk in self.chunks {
+            samples.extend_from_slice(&chunk);
+        }
+        samples
+    }
+}
+
+enum RecordedAudio {
+    Raw(ChunkedAudio),
+    Compressed(CompressedAudio),
+}
+
+impl RecordedAudio {
+    fn new(compress: bool) -> Self {
+        if compress {
+            RecordedAudio::Compressed(CompressedAudio::default())
+        } else {
+            RecordedAudio::Raw(ChunkedAudio::default())
+        }
+    }
+
+    fn push(&mut self, chunk: &[f32]) {
+        match self {
+            RecordedAudio::Raw(audio) => audio.push(chunk),
+            RecordedAudio::Compressed(audio) => audio.push(chunk),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            RecordedAudio::Raw(audio) => audio.clear(),
+            RecordedAudio::Compressed(audio) => audio.clear(),
+        }
+    }
+
+    fn copy_from(&self, offset: usize) -> Vec<f32> {
+        match self {
+            RecordedAudio::Raw(audio) => audio.copy_from(offset),
+            RecordedAudio::Compressed(audio) => audio.copy_from(offset),
+        }
+    }
+
+    fn into_samples(self) -> Vec<f32> {
+        match self {
+            RecordedAudio::Raw(audio) => audio.into_samples(),
+            RecordedAudio::Compressed(audio) => audio.into_samples(),
+        }
+    }
+}
+
+const MONITOR_MAX_LATENCY_MS: u32 = 100;
+
+/// Hands live microphone audio to the monitoring output stream.
+#[derive(Default)]
+pub struct MonitorTap {
+    queue: Mutex<VecDeque<f32>>,
+    input_rate: AtomicU32,
+    volume: AtomicU32,
+}
+
+impl MonitorTap {
+    pub fn new(volume: f32) -> Self {
+        let tap = Self::default();
+        tap.set_volume(volume);
+        tap
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.volume
+            .store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.queue.lock().unwrap().clear();
+    }
+
+    fn push(&self, frames: &[f32]) {
+        let max_len = (self.input_rate.load(Ordering::Relaxed) * MONITOR_MAX_LATENCY_MS / 1000)
+            .max(1) as usize;
+        let mut queue = self.queue.lock().unwrap();
+        queue.extend(frames.iter().copied());
+        // Drop the oldest audio rather than let monitoring drift behind the speaker.
+        let excess = queue.len().saturating_sub(max_len);
+        queue.drain(..excess);
+    }
+}
+
+/// Plays a `MonitorTap` on the default output device while it is alive.
+pub struct MonitorOutput {
+    _stream: Stream,
+}
+
+impl MonitorOutput {
+    pub fn start(tap: Arc<MonitorTap>, host_id: cpal::HostId) -> Result<Self> {
+        let host = cpal::host_from_id(host_id)?;
+        let device = host.default_output_device().context("no output device")?;
         let supported = device
-            .supported_input_configs()
-            .context("no input configs")?;
+            .default_output_config()
+            .context("default output config")?;
+        let sample_format = supported.sample_format();
+        let config = supported.config();
+        let output_rate = config.sample_rate.0;
+        let channels = config.channels;
+        let err_fn = move |err| {
+            eprintln!("monitor stream error: {err}");
+        };
+
+        let stream = match sample_format {
+            SampleFormat::I16 => {
+                let mut state = MonitorState::new(tap, output_rate);
+                device.build_output_stream(
+                    &config,
+                    move |data: &mut [i16], _| state.fill(data, channels),
+                    err_fn,
+                    None,
+                )?
+            }
+            SampleFormat::U16 => {
+                let mut state = MonitorState::new(tap, output_rate);
+                device.build_output_stream(
+                    &config,
+                    move |data: &mut [u16], _| state.fill(data, channels),
+                    err_fn,
+                    None,
+                )?
+            }
+            _ => {
+                let mut state = MonitorState::new(tap, output_rate);
+                device.build_output_stream(
+                    &config,
+                    move |data: &mut [f32], _| state.fill(data, channels),
+                    err_fn,
+                    None,
+                )?
+            }
+        };
+        stream.play()?;
+        Ok(Self { _stream: stream })
+    }
+}
+
+struct MonitorState {
+    tap: Arc<MonitorTap>,
+    output_rate: u32,
+    position: f64,
+}
+
+impl MonitorState {
+    fn new(tap: Arc<MonitorTap>, output_rate: u32) -> Self {
+        Self {
+            tap,
+            output_rate: output_rate.max(1),
+            position: 0.0,
+        }
+    }
+
+    fn fill<T: Sample + FromSample<f32>>(&mut self, data: &mut [T], channels: u16) {
+        let step = self.tap.input_rate.load(Ordering::Relaxed) as f64 / self.output_rate as f64;
+        let volume = f32::from_bits(self.tap.volume.load(Ordering::Relaxed));
+        let mut queue = self.tap.queue.lock().unwrap();
+        for frame in data.chunks_mut(channels.max(1) as usize) {
+            while self.position >= 1.0 {
+                queue.pop_front();
+                self.position -= 1.0;
+            }
+            let value = queue.front().copied().unwrap_or(0.0) * volume;
+            if !queue.is_empty() {
+                self.position += step;
+            }
+            for sample in frame.iter_mut() {
+                *sample = T::from_sample(value);
+            }
+        }
+    }
+}
+
+/// Plays `buffer` on the default output of the preferred host, blocking until done.
+pub fn play_buffer(buffer: AudioBuffer, host_preference: &str) -> Result<()> {
+    let host = select_host(host_preference);
+    let device = host.default_output_device().context("no output device")?;
+    let supported = device
+        .default_output_config()
+        .context("default output config")?;
+    let sample_format = supported.sample_format();
+    let config = supported.config();
+    let output_rate = config.sample_rate.0;
+    let samples = resample_linear(buffer, output_rate).samples;
+    let duration = Duration::from_millis(samples.len() as u64 * 1000 / output_rate.max(1) as u64);
+    let channels = config.channels;
+    let err_fn = |err| eprintln!("playback stream error: {err}");
+
+    let stream = match sample_format {
+        SampleFormat::I16 => {
+            let mut source = samples.into_iter();
+            device.build_output_stream(
+                &config,
+                move |data: &mut [i16], _| fill_output(data, channels, &mut source),
+                err_fn,
+                None,
+            )?
+        }
+        SampleFormat::U16 => {
+            let mut source = samples.into_iter();
+            device.build_output_stream(
+                &config,
+                move |data: &mut [u16], _| fill_output(data, channels, &mut source),
+                err_fn,
+                None,
+            )?
+        }
+        _ => {
+            let mut source = samples.into_iter();
+            device.build_output_stream(
+                &config,
+                move |data: &mut [f32], _| fill_output(data, channels, &mut source),
+                err_fn,
+                None,
+            )?
+        }
+    };
+    stream.play()?;
+    // Leave room for the device buffer to drain before the stream is dropped.
+    std::thread::sleep(duration + Duration::from_millis(100));
+    Ok(())
+}
+
+fn fill_output<T: Sample + FromSample<f32>>(
+    data: &mut [T],
+    channels: u16,
+    source: &mut impl Iterator<Item = f32>,
+) {
+    for frame in data.chunks_mut(channels.max(1) as usize) {
+        let value = T::from_sample(source.next().unwrap_or(0.0));
+        for sample in frame.iter_mut() {
+            *sample = value;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDevice {
+    pub name: String,
+    pub channels: u16,
+}
+
+pub const HOST_AUTO: &str = "auto";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioHost {
+    pub name: String,
+    pub default_input: Option<String>,
+}
+
+pub fn list_hosts() -> Vec<AudioHost> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| AudioHost {
+            name: id.name().to_string(),
+            default_input: cpal::host_from_id(id)
+                .ok()
+                .and_then(|host| host.default_input_device())
+                .and_then(|device| device.name().ok()),
+        })
+        .collect()
+}
+
+/// Returns the host named by `preference`, or for `auto` (and unknown names) the
+/// first host that owns a default input, starting with cpal's platform default.
+pub fn select_host(preference: &str) -> cpal::Host {
+    let hosts = cpal::available_hosts();
+    if let Some(id) = hosts
+        .iter()
+        .find(|id| id.name().eq_ignore_ascii_case(preference))
+    {
+        if let Ok(host) = cpal::host_from_id(*id) {
+            return host;
+        }
+    }
+    let default = cpal::default_host();
+    if default.default_input_device().is_some() {
+        return default;
+    }
+    hosts
+        .into_iter()
+        .filter(|id| *id != default.id())
+        .filter_map(|id| cpal::host_from_id(id).ok())
+        .find(|host| host.default_input_device().is_some())
+        .unwrap_or(default)
+}
+
+/// Describes the default input so the UI can offer one entry per channel.
+pub fn default_input_device(host_preference: &str) -> Result<InputDevice> {
+    let device = select_host(host_preference)
+        .default_input_device()
+        .context("no input device")?;
+    let config = device
+        .default_input_config()
+        .context("default input config")?;
+    Ok(InputDevice {
+        name: device.name().unwrap_or_else(|_| "unknown".to_string()),
+        channels: config.channels(),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureOptions {
+    pub source: String,
+    /// Zero-based channel to capture instead of the downmix, keyed by device name.
+    pub input_channels: BTreeMap<String, u16>,
+    /// Record from another input rather than a Bluetooth headset when one exists.
+    pub avoid_bluetooth: bool,
+    pub host: String,
+    /// Also capture playback as a reference and cancel it out of the microphone.
+    pub echo_cancellation: bool,
+    /// Keep the recording compressed in memory, for long sessions.
+    pub compress: bool,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            source: SOURCE_MICROPHONE.to_string(),
+            input_channels: BTreeMap::new(),
+            avoid_bluetooth: false,
+            host: HOST_AUTO.to_string(),
+            echo_cancellation: false,
+            compress: false,
+        }
+    }
+}
+
+/// A Bluetooth headset was the default input; `fallback` names the device used
+/// instead, or is `None` when recording went ahead on the headset.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BluetoothInput {
+    pub device: String,
+    pub fallback: Option<String>,
+}
+
+/// Opening a headset's microphone switches it from A2DP to the HFP/HSP call profile,
+/// which records narrowband 8-16 kHz audio.
+pub fn is_bluetooth_input(name: &str) -> bool {
+    let name = name.to_lowercase();
+    [
+        "bluez",
+        "bluetooth",
+        "hands-free",
+        "handsfree",
+        "headset_head_unit",
+        "airpods",
+    ]
+    .iter()
+    .any(|needle| name.contains(needle))
+}
+
+struct InputChoice {
+    device: cpal::Device,
+    pulse_source: Option<String>,
+    bluetooth: Option<BluetoothInput>,
+}
+
+fn choose_input(host: &cpal::Host, avoid_bluetooth: bool) -> Result<InputChoice> {
+    let device = host.default_input_device().context("no input device")?;
+    let name = device.name().unwrap_or_else(|_| "unknown".to_string());
+    let server_source = sound_server_source(&name);
+    let resolved = server_source.clone().unwrap_or(name);
+    if !is_bluetooth_input(&resolved) {
+        return Ok(InputChoice {
+            device,
+            pulse_source: None,
+            bluetooth: None,
+        });
+    }
+
+    if avoid_bluetooth {
+        if let Some(source) = server_source.and_then(|_| alternative_sound_server_source()) {
+            return Ok(InputChoice {
+                device,
+                pulse_source: Some(source.clone()),
+                bluetooth: Some(BluetoothInput {
+                    device: resolved,
+                    fallback: Some(source),
+                }),
+            });
+        }
+        if let Some(other) = alternative_input_device(host) {
+            let fallback = other.name().ok();
+            return Ok(InputChoice {
+                device: other,
+                pulse_source: None,
+                bluetooth: Some(BluetoothInput {
+                    device: resolved,
+                    fallback,
+                }),
+            });
+        }
+    }
+    Ok(InputChoice {
+        device,
+        pulse_source: None,
+        bluetooth: Some(BluetoothInput {
+            device: resolved,
+            fallback: None,
+        }),
+    })
+}
+
+impl Recorder {
+    /// Builds the input stream(s) for `options.source` without capturing so a later
+    /// `begin` skips device setup.
+    pub fn open(meter: Arc<LevelMeter>, options: &CaptureOptions) -> Result<Self> {
+        let host = select_host(&options.host);
+        meter.reset();
+        let mut streams = Vec::new();
+        let mut names = Vec::new();
+        let mut bluetooth = None;
+        if options.source != SOURCE_SYSTEM {
+            let input = choose_input(&host, options.avoid_bluetooth)?;
+            let name = input
+                .device
+                .name()
+                .unwrap_or_else(|_| "unknown".to_string());
+            let channel = options.input_channels.get(&name).copied();
+            let stream = match &input.pulse_source {
+                Some(source) => open_server(source, meter.clone())?,
+                None => CaptureStream::open(&input.device, false, channel, meter.clone())?,
+            };
+            streams.push(stream);
+            names.push(name);
+            bluetooth = input.bluetooth;
+        }
+        let mut echo_reference = false;
+        let mut echo_unavailable = None;
+        if options.echo_cancellation && options.source == SOURCE_MICROPHONE {
+            // Playback must not move the level meter, which drives VAD and clipping.
+            match open_loopback(&host, Arc::new(LevelMeter::new())) {
+                Ok((_, stream)) => {
+                    streams.push(stream);
+                    echo_reference = true;
+                }
+                Err(err) => echo_unavailable = Some(format!("{err:#}")),
+            }
+        }
+        if options.source == SOURCE_SYSTEM || options.source == SOURCE_MIXED {
+            let (name, stream) = open_loopback(&host, meter)?;
+            names.push(name);
+            streams.push(stream);
+        }
+        for stream in &mut streams {
+            stream.recorded = RecordedAudio::new(options.compress);
+        }
+
+        Ok(Self {
+            streams,
+            has_microphone: options.source != SOURCE_SYSTEM,
+            host_id: host.id(),
+            device_name: names.join(" + "),
+            bluetooth,
+            requested_at: None,
+            echo_reference,
+            echo_unavailable,
+        })
+    }
+
+    pub fn host_id(&self) -> cpal::HostId {
+        self.host_id
+    }
+
+    pub fn bluetooth_input(&self) -> Option<&BluetoothInput> {
+        self.bluetooth.as_ref()
+    }
+
+    pub fn echo_unavailable(&self) -> Option<&str> {
+        self.echo_unavailable.as_deref()
+    }
+
+    /// Audio of the first stream recorded so far, from sample `offset` on, at its
+    /// native rate. Used for partial transcription while recording continues.
+    pub fn snapshot(&mut self, offset: usize) -> Option<AudioBuffer> {
+        self.drain();
+        let stream = self.streams.first()?;
+        Some(AudioBuffer {
+            samples: stream.recorded.copy_from(offset),
+            sample_rate: stream.sample_rate,
+        })
+    }
+
+    /// Starts capturing; samples beyond `max_duration` are dropped so a forgotten
+    /// recording cannot grow without bound.
+    pub fn begin(&mut self, requested_at: Instant, max_duration: Option<Duration>) -> Result<()> {
+        self.requested_at = Some(requested_at);
+        for stream in &mut self.streams {
+            stream.begin(max_duration)?;
+        }
+        Ok(())
+    }
+
+    /// Moves audio queued by the device callbacks into the recording; call it
+    /// regularly while capturing so the bounded queue never fills.
+    pub fn drain(&mut self) {
+        for stream in &mut self.streams {
+            stream.drain();
+        }
+    }
+
+    /// Keeps the stream running before `begin`, retaining only the most recent
+    /// `duration` of audio so words spoken as the hotkey goes down are not clipped.
+    pub fn listen(&mut self, duration: Duration) -> Result<()> {
+        for stream in &self.streams {
+            stream.listen(duration)?;
+        }
+        Ok(())
+    }
+
+    /// Stops capturing without discarding what has been recorded so far.
+    pub fn pause(&self) -> Result<()> {
+        for stream in &self.streams {
+            stream.pause()?;
+        }
+        Ok(())
+    }
 
-        let mut chosen_config = None;
-        for config in supported {
-            let config = config.with_max_sample_rate();
-            if config.channels() == 1 && config.sample_rate().0 == 16_000 {
-                chosen_config = Some(config);
-                break;
+    pub fn resume(&self) -> Result<()> {
+        for stream in &self.streams {
+            stream.resume()?;
+        }
+        Ok(())
+    }
+
+    /// Routes the microphone (never loopback audio, which would feed back) to `tap`.
+    pub fn attach_monitor(&self, tap: Option<Arc<MonitorTap>>) {
+        if !self.has_microphone {
+            return;
+        }
+        if let Some(stream) = self.streams.first() {
+            if let Some(tap) = tap.as_ref() {
+                tap.input_rate.store(stream.sample_rate, Ordering::Relaxed);
             }
+            *stream.capture.monitor.lock().unwrap() = tap;
         }
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.streams
+            .iter()
+            .any(|stream| stream.capture.lost.load(Ordering::SeqCst))
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    /// Time from the start request (hotkey press) to the first captured sample.
+    pub fn start_latency(&self) -> Option<Duration> {
+        let requested_at = self.requested_at?;
+        let recorded = self.streams.len() - usize::from(self.echo_reference);
+        let first = self.streams[..recorded]
+            .iter()
+            .filter_map(|stream| *stream.capture.first_sample_at.lock().unwrap())
+            .min()?;
+        Some(first.saturating_duration_since(requested_at))
+    }
+
+    /// The recording and, with echo cancellation, what the speakers played meanwhile. The
+    /// caller cancels the echo with `echo::cancel_echo`, which is too slow for this path.
+    pub fn stop(mut self) -> Result<StoppedCapture> {
+        let mut dropped_ms = 0;
+        let echo_reference = if self.echo_reference {
+            self.streams.pop().map(|stream| stream.stop().0)
+        } else {
+            None
+        };
+        let mut buffers = self.streams.into_iter().map(|stream| {
+            let (buffer, dropped) = stream.stop();
+            dropped_ms = dropped_ms.max(dropped);
+            buffer
+        });
+        let first = buffers.next().context("no capture stream")?;
+        let buffer = buffers.fold(first, mix);
+        Ok(StoppedCapture {
+            buffer,
+            echo_reference,
+            dropped_ms,
+        })
+    }
+}
+
+impl CaptureStream {
+    fn open(
+        device: &cpal::Device,
+        loopback: bool,
+        channel: Option<u16>,
+        meter: Arc<LevelMeter>,
+    ) -> Result<Self> {
+        let chosen = if loopback {
+            device
+                .default_output_config()
+                .context("default output config")?
+        } else if channel.is_some() {
+            // A mono config would already be a downmix, so keep the device's own layout.
+            device
+                .default_input_config()
+                .context("default input config")?
+        } else {
+            let supported = device
+                .supported_input_configs()
+                .context("no input configs")?;
 
-        let default_config = device
-            .default_input_config()
-            .context("default input config")?;
-        let chosen = chosen_config.unwrap_or(default_config);
+            let mut chosen_config = None;
+            for config in supported {
+                let config = config.with_max_sample_rate();
+                if config.channels() == 1 && config.sample_rate().0 == 16_000 {
+                    chosen_config = Some(config);
+                    break;
+                }
+            }
+
+            let default_config = device
+                .default_input_config()
+                .context("default input config")?;
+            chosen_config.unwrap_or(default_config)
+        };
         let sample_format = chosen.sample_format();
         let config = chosen.config();
 
         let sample_rate = config.sample_rate.0;
         let channels = config.channels;
-        let samples = Arc::new(Mutex::new(Vec::new()));
+        let channel = channel.filter(|index| *index < channels);
+        let (queue, chunks) = mpsc::sync_channel(CHUNK_QUEUE_LEN);
+        let capture = Arc::new(Capture::new(queue));
 
-        let samples_ref = samples.clone();
+        let capture_ref = capture.clone();
+        let capture_err = capture.clone();
         let err_fn = move |err| {
             eprintln!("audio stream error: {err}");
+            // Unplugging a device surfaces here rather than as a failed read.
+            capture_err.lost.store(true, Ordering::SeqCst);
         };
 
         let stream = match sample_format {
             SampleFormat::F32 => device.build_input_stream(
                 &config,
                 move |data: &[f32], _| {
-                    push_samples(data, channels, &samples_ref);
+                    push_samples(data, channels, channel, &capture_ref, &meter);
                 },
                 err_fn,
                 None,
@@ -60,7 +983,7 @@ impl Recorder {
             SampleFormat::I16 => device.build_input_stream(
                 &config,
                 move |data: &[i16], _| {
-                    push_samples(data, channels, &samples_ref);
+                    push_samples(data, channels, channel, &capture_ref, &meter);
                 },
                 err_fn,
                 None,
@@ -68,7 +991,7 @@ impl Recorder {
             SampleFormat::U16 => device.build_input_stream(
                 &config,
                 move |data: &[u16], _| {
-                    push_samples(data, channels, &samples_ref);
+                    push_samples(data, channels, channel, &capture_ref, &meter);
                 },
                 err_fn,
                 None,
@@ -76,59 +999,543 @@ impl Recorder {
             _ => device.build_input_stream(
                 &config,
                 move |data: &[f32], _| {
-                    push_samples(data, channels, &samples_ref);
+                    push_samples(data, channels, channel, &capture_ref, &meter);
                 },
                 err_fn,
                 None,
             )?,
         };
+        // Some backends start streams on creation; keep it idle until `begin`.
+        let _ = stream.pause();
 
-        stream.play()?;
+        Ok(Self {
+            source: Source::Device(stream),
+            capture,
+            chunks,
+            recorded: RecordedAudio::new(false),
+            sample_rate,
+        })
+    }
 
+    /// Records the sound server's `source` (a source name or `@DEFAULT_MONITOR@`)
+    /// through `parec`; nothing runs until `begin` or `listen`.
+    #[cfg(target_os = "linux")]
+    fn open_server(source: &str, meter: Arc<LevelMeter>) -> Result<Self> {
+        which::which("parec").context("parec not found (install pulseaudio-utils)")?;
+        let (queue, chunks) = mpsc::sync_channel(CHUNK_QUEUE_LEN);
+        let capture = Arc::new(Capture::new(queue));
+        let recorder = ServerRecorder {
+            source: source.to_string(),
+            capture: capture.clone(),
+            meter,
+            running: Mutex::new(None),
+        };
         Ok(Self {
-            stream,
-            samples,
+            source: Source::Server(recorder),
+            capture,
+            chunks,
+            recorded: RecordedAudio::new(false),
+            sample_rate: SERVER_RATE,
+        })
+    }
+
+    fn begin(&mut self, max_duration: Option<Duration>) -> Result<()> {
+        let max_samples = max_duration
+            .map(|limit| (limit.as_secs_f64() * self.sample_rate as f64) as usize)
+            .unwrap_or(0);
+        self.capture
+            .max_samples
+            .store(max_samples, Ordering::SeqCst);
+        self.capture.dropped_samples.store(0, Ordering::SeqCst);
+        *self.capture.first_sample_at.lock().unwrap() = None;
+        while self.chunks.try_recv().is_ok() {}
+        self.recorded.clear();
+        {
+            let mut preroll = self.capture.preroll.lock().unwrap();
+            self.capture
+                .captured_samples
+                .store(preroll.len(), Ordering::SeqCst);
+            self.recorded.push(preroll.make_contiguous());
+            preroll.clear();
+            self.capture.active.store(true, Ordering::SeqCst);
+        }
+        self.source.play()
+    }
+
+    fn drain(&mut self) {
+        while let Ok(chunk) = self.chunks.try_recv() {
+            self.recorded.push(&chunk);
+            self.capture.recycle(chunk);
+        }
+    }
+
+    fn pause(&self) -> Result<()> {
+        self.capture.active.store(false, Ordering::SeqCst);
+        self.capture.preroll_samples.store(0, Ordering::SeqCst);
+        self.source.pause();
+        Ok(())
+    }
+
+    fn resume(&self) -> Result<()> {
+        self.capture.preroll.lock().unwrap().clear();
+        self.capture.active.store(true, Ordering::SeqCst);
+        self.source.play()
+    }
+
+    fn listen(&self, duration: Duration) -> Result<()> {
+        let capacity = (duration.as_secs_f64() * self.sample_rate as f64) as usize;
+        self.capture
+            .preroll_samples
+            .store(capacity, Ordering::SeqCst);
+        self.source.play()
+    }
+
+    /// The recording and how many milliseconds of it were dropped because the recorder
+    /// fell behind.
+    fn stop(self) -> (AudioBuffer, u64) {
+        let Self {
+            source,
+            capture,
+            chunks,
+            mut recorded,
             sample_rate,
+        } = self;
+        capture.active.store(false, Ordering::SeqCst);
+        // Dropping the source ends the callbacks, so everything left is in the queue.
+        drop(source);
+        while let Ok(chunk) = chunks.try_recv() {
+            recorded.push(&chunk);
+        }
+        let dropped = capture.dropped_samples.load(Ordering::SeqCst) as u64;
+        let buffer = AudioBuffer {
+            samples: recorded.into_samples(),
+            sample_rate,
+        };
+        (buffer, dropped * 1_000 / u64::from(sample_rate.max(1)))
+    }
+}
+
+/// WASAPI records what an output device plays when an input stream is built on it.
+#[cfg(target_os = "windows")]
+fn open_loopback(host: &cpal::Host, meter: Arc<LevelMeter>) -> Result<(String, CaptureStream)> {
+    let device = host.default_output_device().context("no output device")?;
+    let stream = CaptureStream::open(&device, true, None, meter)?;
+    Ok((device_name(&device), stream))
+}
+
+/// PipeWire and PulseAudio expose playback through monitor sources. Use one directly
+/// if ALSA lists it, otherwise record the default monitor through the sound server.
+#[cfg(target_os = "linux")]
+fn open_loopback(host: &cpal::Host, meter: Arc<LevelMeter>) -> Result<(String, CaptureStream)> {
+    let devices: Vec<cpal::Device> = host
+        .input_devices()
+        .context("list input devices")?
+        .collect();
+    let named = |needle: &str| {
+        devices.iter().find(|device| {
+            device
+                .name()
+                .map(|name| name.to_lowercase().contains(needle))
+                .unwrap_or(false)
         })
+    };
+    if let Some(device) = named("monitor") {
+        let stream = CaptureStream::open(device, false, None, meter)?;
+        return Ok((device_name(device), stream));
     }
 
-    pub fn stop(self) -> Result<AudioBuffer> {
-        drop(self.stream);
-        let samples = self.samples.lock().unwrap().clone();
-        Ok(AudioBuffer {
-            samples,
-            sample_rate: self.sample_rate,
+    if named("pulse").or_else(|| named("pipewire")).is_none() {
+        anyhow::bail!("no PipeWire/PulseAudio monitor source found");
+    }
+    let stream = CaptureStream::open_server("@DEFAULT_MONITOR@", meter)?;
+    Ok(("system audio".to_string(), stream))
+}
+
+#[cfg(target_os = "linux")]
+fn open_server(source: &str, meter: Arc<LevelMeter>) -> Result<CaptureStream> {
+    CaptureStream::open_server(source, meter)
+}
+
+/// How long `pactl` answers are reused; every recorder open would otherwise spawn it,
+/// while a newly plugged headset still shows up within this long.
+#[cfg(target_os = "linux")]
+const PACTL_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// The output of `pactl args`, reused for `PACTL_CACHE_TTL`; `None` when it failed.
+#[cfg(target_os = "linux")]
+fn pactl(args: &'static [&'static str]) -> Option<String> {
+    type Answers = Vec<(&'static [&'static str], Instant, Option<String>)>;
+    static ANSWERS: Mutex<Answers> = Mutex::new(Vec::new());
+    let mut answers = ANSWERS.lock().unwrap();
+    let cached = answers
+        .iter()
+        .find(|(cached, at, _)| *cached == args && at.elapsed() < PACTL_CACHE_TTL);
+    if let Some((.., output)) = cached {
+        return output.clone();
+    }
+    let output = std::process::Command::new("pactl")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned());
+    answers.retain(|(cached, ..)| *cached != args);
+    answers.push((args, Instant::now(), output.clone()));
+    output
+}
+
+/// ALSA only sees the sound server's `default`/`pulse` PCM, so ask it which source
+/// that actually is.
+#[cfg(target_os = "linux")]
+fn sound_server_source(name: &str) -> Option<String> {
+    if !matches!(name, "default" | "pulse" | "pipewire") {
+        return None;
+    }
+    let source = pactl(&["get-default-source"])?.trim().to_string();
+    (!source.is_empty()).then_some(source)
+}
+
+#[cfg(target_os = "linux")]
+fn alternative_sound_server_source() -> Option<String> {
+    // Recording another source goes through parec.
+    which::which("parec").ok()?;
+    pactl(&["list", "short", "sources"])?
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .find(|source| !source.ends_with(".monitor") && !is_bluetooth_input(source))
+        .map(str::to_string)
+}
+
+/// ALSA's raw device list is mostly plugins and hardware aliases; picking another
+/// input goes through the sound server instead.
+#[cfg(target_os = "linux")]
+fn alternative_input_device(_host: &cpal::Host) -> Option<cpal::Device> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_server(_source: &str, _meter: Arc<LevelMeter>) -> Result<CaptureStream> {
+    anyhow::bail!("sound server sources are only recorded on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sound_server_source(_name: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn alternative_sound_server_source() -> Option<String> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn alternative_input_device(host: &cpal::Host) -> Option<cpal::Device> {
+    host.input_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|name| !is_bluetooth_input(&name)))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn open_loopback(_host: &cpal::Host, _meter: Arc<LevelMeter>) -> Result<(String, CaptureStream)> {
+    anyhow::bail!("system audio capture is not supported on this platform")
+}
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn device_name(device: &cpal::Device) -> String {
+    device.name().unwrap_or_else(|_| "system audio".to_string())
+}
+
+/// Concatenates two recordings, converting `next` to the sample rate of `base`.
+pub fn append(mut base: AudioBuffer, next: AudioBuffer) -> AudioBuffer {
+    if base.samples.is_empty() {
+        return next;
+    }
+    let next = resample_linear(next, base.sample_rate);
+    base.samples.extend(next.samples);
+    base
+}
+
+/// Sums two captures after bringing `other` to `base`'s rate, scaled to avoid clipping.
+fn mix(base: AudioBuffer, other: AudioBuffer) -> AudioBuffer {
+    if other.samples.is_empty() {
+        return base;
+    }
+    let other = resample_linear(other, base.sample_rate);
+    let len = base.samples.len().max(other.samples.len());
+    let samples = (0..len)
+        .map(|i| {
+            let a = base.samples.get(i).copied().unwrap_or(0.0);
+            let b = other.samples.get(i).copied().unwrap_or(0.0);
+            ((a + b) * 0.5).clamp(-1.0, 1.0)
         })
+        .collect();
+    AudioBuffer {
+        samples,
+        sample_rate: base.sample_rate,
     }
 }
 
-fn push_samples<T: Sample + SizedSample>(data: &[T], channels: u16, buffer: &Arc<Mutex<Vec<f32>>>)
-where
+fn push_samples<T: Sample + SizedSample>(
+    data: &[T],
+    channels: u16,
+    channel: Option<u16>,
+    capture: &Capture,
+    meter: &LevelMeter,
+) where
     f32: FromSample<T>,
 {
-    let mut guard = buffer.lock().unwrap();
-    if channels == 1 {
-        guard.extend(data.iter().map(|s| s.to_sample::<f32>()));
+    let preroll_samples = capture.preroll_samples.load(Ordering::Relaxed);
+    if !capture.active.load(Ordering::Relaxed) && preroll_samples == 0 {
+        return;
+    }
+    let channels = channels.max(1) as usize;
+    let mut frames = capture.scratch.lock().unwrap();
+    frames.clear();
+    frames.extend(data.chunks_exact(channels).map(|frame| match channel {
+        Some(index) => frame[index as usize].to_sample::<f32>(),
+        None => {
+            let sum: f32 = frame.iter().map(|s| s.to_sample::<f32>()).sum();
+            sum / channels as f32
+        }
+    }));
+
+    if preroll_samples > 0 {
+        // `begin` flips `active` while holding this lock, so no chunk is lost between
+        // the pre-roll and the session.
+        let mut preroll = capture.preroll.lock().unwrap();
+        if !capture.active.load(Ordering::Relaxed) {
+            preroll.extend(frames.iter().copied());
+            let excess = preroll.len().saturating_sub(preroll_samples);
+            preroll.drain(..excess);
+            return;
+        }
+    }
+
+    let mut sum_squares = 0.0f32;
+    let mut peak = 0.0f32;
+    let mut clipped = 0;
+    for &sample in frames.iter() {
+        sum_squares += sample * sample;
+        peak = peak.max(sample.abs());
+        if sample.abs() >= CLIP_LEVEL {
+            clipped += 1;
+        }
+    }
+    if !frames.is_empty() {
+        meter.update(
+            (sum_squares / frames.len() as f32).sqrt(),
+            peak,
+            frames.len(),
+            clipped,
+        );
+        capture
+            .first_sample_at
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+    }
+
+    if let Some(tap) = capture.monitor.lock().unwrap().as_ref() {
+        tap.push(&frames);
+    }
+
+    let max_samples = capture.max_samples.load(Ordering::Relaxed);
+    let mut len = frames.len();
+    if max_samples > 0 {
+        let room = max_samples.saturating_sub(capture.captured_samples.load(Ordering::Relaxed));
+        len = len.min(room);
+    }
+    if len == 0 {
+        return;
+    }
+    let mut chunk = capture.spare.lock().unwrap().pop().unwrap_or_default();
+    chunk.extend_from_slice(&frames[..len]);
+    // Never block the audio thread; a full queue means the recorder stalled.
+    match capture.queue.try_send(chunk) {
+        Ok(()) => {
+            capture.captured_samples.fetch_add(len, Ordering::Relaxed);
+        }
+        Err(TrySendError::Full(chunk) | TrySendError::Disconnected(chunk)) => {
+            capture.dropped_samples.fetch_add(len, Ordering::Relaxed);
+            capture.recycle(chunk);
+        }
+    }
+}
+
+pub const GAIN_OFF: &str = "off";
+pub const GAIN_PEAK: &str = "peak";
+pub const GAIN_AGC: &str = "agc";
+
+const NORMALIZE_PEAK: f32 = 0.9;
+const MAX_GAIN: f32 = 20.0;
+const AGC_TARGET_RMS: f32 = 0.1;
+const AGC_WINDOW_MS: u32 = 50;
+const AGC_ATTACK: f32 = 0.5;
+const AGC_RELEASE: f32 = 0.05;
+const AGC_NOISE_FLOOR: f32 = 0.002;
+
+pub fn apply_gain(buffer: &mut AudioBuffer, mode: &str) {
+    match mode {
+        GAIN_PEAK => normalize_peak(&mut buffer.samples),
+        GAIN_AGC => automatic_gain(&mut buffer.samples, buffer.sample_rate),
+        _ => {}
+    }
+}
+
+fn normalize_peak(samples: &mut [f32]) {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak <= f32::EPSILON {
         return;
     }
+    let gain = (NORMALIZE_PEAK / peak).min(MAX_GAIN);
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+/// Block-wise gain riding towards a target RMS. Gain drops quickly on loud blocks and
+/// rises slowly on quiet ones, and near-silent blocks keep the previous gain so room
+/// noise between words is not pumped up.
+fn automatic_gain(samples: &mut [f32], sample_rate: u32) {
+    let window = ((sample_rate * AGC_WINDOW_MS / 1000) as usize).max(1);
+    let mut gain = 1.0f32;
+    for block in samples.chunks_mut(window) {
+        let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+        if rms > AGC_NOISE_FLOOR {
+            let desired = (AGC_TARGET_RMS / rms).min(MAX_GAIN);
+            let rate = if desired < gain {
+                AGC_ATTACK
+            } else {
+                AGC_RELEASE
+            };
+            gain += (desired - gain) * rate;
+        }
+        for sample in block.iter_mut() {
+            *sample = (*sample * gain).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+pub const RESAMPLER_SINC: &str = "sinc";
+pub const RESAMPLER_LINEAR: &str = "linear";
 
-    let mut idx = 0;
-    while idx + channels as usize <= data.len() {
-        let mut sum = 0.0f32;
-        for channel in 0..channels as usize {
-            sum += data[idx + channel].to_sample::<f32>();
+const SINC_ZERO_CROSSINGS: f32 = 16.0;
+const SINC_CUTOFF: f32 = 0.95;
+/// Most kernel phases worth tabulating; common rates need 1 (48 kHz) to 320 (22.05 kHz),
+/// while odd ones would need up to 16000 and get their kernel built per output sample.
+const MAX_SINC_PHASES: u64 = 1024;
+
+pub fn resample_for_whisper(buffer: AudioBuffer, resampler: &str) -> AudioBuffer {
+    if resampler == RESAMPLER_LINEAR {
+        resample_to_16k(buffer)
+    } else {
+        resample_to_16k_sinc(buffer)
+    }
+}
+
+/// Band-limited windowed-sinc resampling; avoids the aliasing linear interpolation
+/// introduces when downsampling 44.1/48 kHz input.
+pub fn resample_to_16k_sinc(buffer: AudioBuffer) -> AudioBuffer {
+    if buffer.sample_rate == 16_000 || buffer.sample_rate == 0 || buffer.samples.is_empty() {
+        return resample_to_16k(buffer);
+    }
+
+    // Output sample `i` sits at input position `i * step / phases`, so its fractional
+    // part, and with it the kernel, repeats every `phases` outputs.
+    let divisor = gcd(u64::from(buffer.sample_rate), 16_000);
+    let step = u64::from(buffer.sample_rate) / divisor;
+    let phases = 16_000 / divisor;
+    let cutoff = (16_000.0 / buffer.sample_rate as f32).min(1.0) * SINC_CUTOFF;
+    let half_width = (SINC_ZERO_CROSSINGS / cutoff).ceil() as isize;
+    let taps = 2 * half_width as usize;
+    let kernel = |phase: u64, weights: &mut [f32]| {
+        let offset = phase as f32 / phases as f32 + (half_width - 1) as f32;
+        for (tap, weight) in weights.iter_mut().enumerate() {
+            let x = offset - tap as f32;
+            *weight = cutoff * sinc(cutoff * x) * blackman(x / half_width as f32);
+        }
+    };
+    let table = (phases <= MAX_SINC_PHASES).then(|| {
+        let mut table = vec![0.0f32; phases as usize * taps];
+        for (phase, weights) in table.chunks_mut(taps).enumerate() {
+            kernel(phase as u64, weights);
+        }
+        table
+    });
+    let mut scratch = vec![0.0f32; taps];
+    let input = &buffer.samples;
+    let out_len = (input.len() as u64 * phases / step) as usize;
+    let mut out = Vec::with_capacity(out_len);
+
+    for i in 0..out_len {
+        let position = i as u64 * step;
+        let phase = position % phases;
+        let weights = match &table {
+            Some(table) => &table[phase as usize * taps..][..taps],
+            None => {
+                kernel(phase, &mut scratch);
+                &scratch[..]
+            }
+        };
+        let first = (position / phases) as isize - half_width + 1;
+        let mut acc = 0.0f32;
+        let mut weight_sum = 0.0f32;
+        for (tap, &weight) in weights.iter().enumerate() {
+            let j = first + tap as isize;
+            if j < 0 || j as usize >= input.len() {
+                continue;
+            }
+            acc += input[j as usize] * weight;
+            weight_sum += weight;
         }
-        guard.push(sum / channels as f32);
-        idx += channels as usize;
+        out.push(if weight_sum.abs() > f32::EPSILON {
+            acc / weight_sum
+        } else {
+            0.0
+        });
+    }
+
+    AudioBuffer {
+        samples: out,
+        sample_rate: 16_000,
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
 }
 
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        let px = std::f32::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+fn blackman(position: f32) -> f32 {
+    if position.abs() >= 1.0 {
+        return 0.0;
+    }
+    let phase = std::f32::consts::PI * (position + 1.0);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
+
 pub fn resample_to_16k(buffer: AudioBuffer) -> AudioBuffer {
-    if buffer.sample_rate == 16_000 {
+    resample_linear(buffer, 16_000)
+}
+
+pub fn resample_linear(buffer: AudioBuffer, sample_rate: u32) -> AudioBuffer {
+    if buffer.sample_rate == sample_rate || buffer.sample_rate == 0 {
         return buffer;
     }
 
-    let ratio = 16_000.0 / buffer.sample_rate as f32;
+    let ratio = sample_rate as f32 / buffer.sample_rate as f32;
     let out_len = (buffer.samples.len() as f32 * ratio) as usize;
     let mut out = Vec::with_capacity(out_len);
     for i in 0..out_len {
@@ -142,6 +1549,211 @@ pub fn resample_to_16k(buffer: AudioBuffer) -> AudioBuffer {
 
     AudioBuffer {
         samples: out,
-        sample_rate: 16_000,
+        sample_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_gain, is_bluetooth_input, push_samples, resample_to_16k, resample_to_16k_sinc,
+        AudioBuffer, Capture, ChunkedAudio, LevelMeter, MonitorState, MonitorTap, GAIN_AGC,
+        GAIN_PEAK, STORED_CHUNK_LEN,
+    };
+    use std::sync::atomic::Ordering;
+    use std::sync::{mpsc, Arc};
+
+    fn tone(frequency: f32, sample_rate: u32, seconds: f32) -> AudioBuffer {
+        let len = (sample_rate as f32 * seconds) as usize;
+        let samples = (0..len)
+            .map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect();
+        AudioBuffer {
+            samples,
+            sample_rate,
+        }
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        let trimmed = &samples[200..samples.len() - 200];
+        (trimmed.iter().map(|s| s * s).sum::<f32>() / trimmed.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn sinc_keeps_speech_band_and_length() {
+        let out = resample_to_16k_sinc(tone(1_000.0, 48_000, 0.5));
+        assert_eq!(out.sample_rate, 16_000);
+        assert_eq!(out.samples.len(), 8_000);
+        assert!((rms(&out.samples) - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.05);
+    }
+
+    #[test]
+    fn sinc_handles_tabulated_and_odd_rates() {
+        // 44.1 kHz tabulates 160 kernel phases; 44.101 kHz would need 16000 and builds
+        // each kernel as it goes.
+        for rate in [44_100, 44_101] {
+            let out = resample_to_16k_sinc(tone(1_000.0, rate, 0.5));
+            assert!(out.samples.len().abs_diff(8_000) <= 1);
+            assert!((rms(&out.samples) - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn sinc_suppresses_aliasing_compared_to_linear() {
+        let input = tone(12_000.0, 48_000, 0.5);
+        let linear = resample_to_16k(input.clone());
+        let sinc = resample_to_16k_sinc(input);
+        assert!(rms(&sinc.samples) < 0.05);
+        assert!(rms(&sinc.samples) < rms(&linear.samples));
+    }
+
+    #[test]
+    fn gain_modes_lift_quiet_input() {
+        let mut quiet = tone(440.0, 16_000, 1.0);
+        for sample in quiet.samples.iter_mut() {
+            *sample *= 0.01;
+        }
+
+        let mut peak = quiet.clone();
+        apply_gain(&mut peak, GAIN_PEAK);
+        let max = peak.samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((max - 0.2).abs() < 0.01);
+
+        let mut agc = quiet.clone();
+        apply_gain(&mut agc, GAIN_AGC);
+        assert!(rms(&agc.samples[8_000..]) > 0.05);
+        assert!(agc.samples.iter().all(|s| s.abs() <= 1.0));
+    }
+
+    #[test]
+    fn detects_bluetooth_headsets_by_name() {
+        assert!(is_bluetooth_input("bluez_input.AA_BB_CC_DD_EE_FF.0"));
+        assert!(is_bluetooth_input(
+            "Headset (WH-1000XM4 Hands-Free AG Audio)"
+        ));
+        assert!(!is_bluetooth_input(
+            "alsa_input.usb-Blue_Yeti-00.analog-stereo"
+        ));
+    }
+
+    #[test]
+    fn copies_recorded_audio_across_chunk_boundaries() {
+        let mut recorded = ChunkedAudio::default();
+        recorded.push(&vec![0.0; STORED_CHUNK_LEN - 2]);
+        recorded.push(&[1.0, 2.0, 3.0]);
+        recorded.push(&[4.0]);
+        assert_eq!(recorded.chunks.len(), 2);
+        let start = STORED_CHUNK_LEN - 3;
+        assert_eq!(recorded.copy_from(start), vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(recorded.copy_from(start + 3), vec![3.0, 4.0]);
+        assert!(recorded.copy_from(STORED_CHUNK_LEN + 9).is_empty());
+        assert_eq!(recorded.into_samples().len(), STORED_CHUNK_LEN + 2);
+    }
+
+    #[test]
+    fn monitor_keeps_only_the_latest_audio_and_plays_it_at_the_output_rate() {
+        let tap = Arc::new(MonitorTap::new(0.5));
+        // At 1 kHz the latency cap is 100 samples, so the oldest 50 are dropped.
+        tap.input_rate.store(1_000, Ordering::Relaxed);
+        let frames: Vec<f32> = (0..150).map(|n| n as f32).collect();
+        tap.push(&frames);
+        assert_eq!(tap.queue.lock().unwrap().len(), 100);
+
+        // Twice the input rate plays every sample twice, on every channel, at half volume.
+        let mut state = MonitorState::new(tap.clone(), 2_000);
+        let mut output = [0.0f32; 8];
+        state.fill(&mut output, 2);
+        assert_eq!(output, [25.0, 25.0, 25.0, 25.0, 25.5, 25.5, 25.5, 25.5]);
+
+        // Running dry plays silence without skipping audio that arrives later.
+        tap.clear();
+        let mut output = [1.0f32; 2];
+        state.fill(&mut output, 1);
+        assert_eq!(output, [0.0, 0.0]);
+        tap.push(&[4.0, 6.0]);
+        let mut output = [0.0f32; 4];
+        state.fill(&mut output, 1);
+        assert_eq!(output, [2.0, 2.0, 3.0, 3.0]);
+    }
+
+    #[test]
+    fn monitor_volume_is_clamped() {
+        let tap = MonitorTap::new(3.0);
+        assert_eq!(f32::from_bits(tap.volume.load(Ordering::Relaxed)), 1.0);
+        tap.set_volume(-1.0);
+        assert_eq!(f32::from_bits(tap.volume.load(Ordering::Relaxed)), 0.0);
+    }
+
+    #[test]
+    fn queued_chunk_buffers_are_reused() {
+        let (queue, chunks) = mpsc::sync_channel(1);
+        let capture = Capture::new(queue);
+        let meter = LevelMeter::new();
+        capture.active.store(true, Ordering::SeqCst);
+
+        push_samples(&[0.1f32; 480], 1, None, &capture, &meter);
+        let chunk = chunks.try_recv().unwrap();
+        let buffer = chunk.as_ptr();
+        capture.recycle(chunk);
+        push_samples(&[0.2f32; 480], 1, None, &capture, &meter);
+        let chunk = chunks.try_recv().unwrap();
+        assert_eq!(chunk.as_ptr(), buffer);
+        assert_eq!(chunk, vec![0.2f32; 480]);
+    }
+
+    #[test]
+    fn level_meter_reports_and_resets_levels() {
+        let (queue, _chunks) = mpsc::sync_channel(4);
+        let capture = Capture::new(queue);
+        let meter = LevelMeter::new();
+
+        // Nothing is metered while the capture is idle without a pre-roll.
+        push_samples(&[1.0f32; 4], 1, None, &capture, &meter);
+        assert_eq!(meter.take_clipping(), (0, 0));
+
+        capture.active.store(true, Ordering::SeqCst);
+        // Stereo frames are averaged: (0.6, 0.4), (-1.0, -1.0), (0.0, 0.0), (0.2, -0.2).
+        push_samples(
+            &[0.6f32, 0.4, -1.0, -1.0, 0.0, 0.0, 0.2, -0.2],
+            2,
+            None,
+            &capture,
+            &meter,
+        );
+        let level = meter.take();
+        assert!((level.rms - (1.25f32 / 4.0).sqrt()).abs() < 1e-6);
+        assert_eq!(level.peak, 1.0);
+        assert_eq!(meter.take_clipping(), (1, 4));
+
+        // Peak and clipping counts start over once taken; the rms is the latest callback's.
+        push_samples(&[0.0f32, 0.5], 2, Some(1), &capture, &meter);
+        let level = meter.take();
+        assert_eq!(level.peak, 0.5);
+        assert_eq!(level.rms, 0.5);
+        assert_eq!(meter.take_clipping(), (0, 1));
+
+        meter.reset();
+        let level = meter.take();
+        assert_eq!((level.rms, level.peak), (0.0, 0.0));
+    }
+
+    #[test]
+    fn capture_queue_respects_cap_and_counts_overflow() {
+        let (queue, chunks) = mpsc::sync_channel(1);
+        let capture = Capture::new(queue);
+        let meter = LevelMeter::new();
+        capture.active.store(true, Ordering::SeqCst);
+        capture.max_samples.store(5, Ordering::SeqCst);
+
+        push_samples(&[0.1f32; 3], 1, None, &capture, &meter);
+        // The single slot is taken, so this chunk is dropped rather than blocking.
+        push_samples(&[0.1f32; 3], 1, None, &capture, &meter);
+        assert_eq!(chunks.try_recv().unwrap().len(), 3);
+        assert_eq!(capture.dropped_samples.load(Ordering::SeqCst), 2);
+
+        push_samples(&[0.1f32; 4], 1, None, &capture, &meter);
+        assert_eq!(chunks.try_recv().unwrap().len(), 2);
+        push_samples(&[0.1f32; 4], 1, None, &capture, &meter);
+        assert!(chunks.try_recv().is_err());
     }
 }
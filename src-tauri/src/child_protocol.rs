@@ -0,0 +1,268 @@
+use crate::transcription::{DecodingParams, TranscribeOptions, Transcript};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Bumped on incompatible changes; a child answers requests of another version with an
+/// error instead of guessing.
+pub const PROTOCOL_VERSION: u32 = 4;
+/// Samples per audio frame, so recordings of any length fit in frames of bounded size.
+const PCM_CHUNK: usize = 16_384;
+/// Largest frame either side accepts; only JSON gets near it, audio is chunked.
+pub const MAX_FRAME: usize = 64 * 1024 * 1024;
+
+/// The first frame a child sends on connecting, so the parent can tell which spawn it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u32,
+    pub token: String,
+    /// Why the child could not sandbox itself as asked; it exits right after saying so.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox_error: Option<String>,
+}
+
+impl Hello {
+    pub fn new(token: String) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            token,
+            sandbox_error: None,
+        }
+    }
+
+    pub fn parse(frame: &[u8]) -> Result<Self> {
+        let hello: Hello = serde_json::from_slice(frame).context("parse hello")?;
+        check_version(hello.version)?;
+        Ok(hello)
+    }
+}
+
+/// One JSON frame from the parent to the transcriber child. The child answers one
+/// request at a time, in order; replies carry the same `id`, so a reply that does not
+/// match the request just sent is an error rather than a wrong transcript.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+    pub id: u64,
+    #[serde(flatten)]
+    pub body: RequestBody,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum RequestBody {
+    /// Replaces the decoding parameters for every later request.
+    SetParams { params: DecodingParams },
+    /// A health check, answered with an empty reply.
+    Ping,
+    /// Followed by `samples` little-endian `f32`s of 16 kHz mono audio, in frames of at
+    /// most `PCM_CHUNK` samples, so recordings never touch the disk.
+    Transcribe {
+        samples: usize,
+        /// A quick greedy decode of an in-progress chunk.
+        #[serde(default)]
+        partial: bool,
+        #[serde(flatten)]
+        options: TranscribeOptions,
+    },
+}
+
+/// The child's reply to one request: a transcript for `transcribe`, neither field for an
+/// accepted `set_params` or `ping`, or `error`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    pub version: u32,
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<Transcript>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Request {
+    pub fn to_frame(id: u64, body: RequestBody) -> Result<Vec<u8>> {
+        serde_json::to_vec(&Request {
+            version: PROTOCOL_VERSION,
+            id,
+            body,
+        })
+        .context("serialize request")
+    }
+
+    /// The request's id and body. The id is also returned, as 0, for a request of another
+    /// version, so the error reply can still be sent.
+    pub fn parse(frame: &[u8]) -> (u64, Result<RequestBody>) {
+        match serde_json::from_slice::<Request>(frame).context("parse request") {
+            Ok(request) => (
+                request.id,
+                check_version(request.version).map(|_| request.body),
+            ),
+            Err(err) => (0, Err(err)),
+        }
+    }
+}
+
+impl Response {
+    pub fn ok(id: u64, transcript: Option<Transcript>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            id,
+            transcript,
+            error: None,
+        }
+    }
+
+    pub fn error(id: u64, message: String) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            id,
+            transcript: None,
+            error: Some(message),
+        }
+    }
+
+    pub fn parse(frame: &[u8]) -> Result<Self> {
+        let response: Response = serde_json::from_slice(frame).context("parse response")?;
+        check_version(response.version)?;
+        Ok(response)
+    }
+
+    /// The transcript carried by the reply; an `error` reply is an error.
+    pub fn into_transcript(self) -> Result<Option<Transcript>> {
+        match self.error {
+            Some(error) => Err(anyhow::anyhow!("transcriber error: {error}")),
+            None => Ok(self.transcript),
+        }
+    }
+}
+
+/// A frame is a little-endian `u32` length followed by that many bytes.
+pub fn write_frame(writer: &mut impl Write, bytes: &[u8]) -> Result<()> {
+    if bytes.len() > MAX_FRAME {
+        anyhow::bail!("frame of {} bytes is too large", bytes.len());
+    }
+    writer
+        .write_all(&(bytes.len() as u32).to_le_bytes())
+        .context("write frame")?;
+    writer.write_all(bytes).context("write frame")
+}
+
+/// Fails on a closed connection as on a malformed frame.
+pub fn read_frame(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader
+        .read_exact(&mut len)
+        .context("transcriber closed the connection")?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME {
+        anyhow::bail!("frame of {len} bytes is too large");
+    }
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes).context("read frame")?;
+    Ok(bytes)
+}
+
+/// Writes `samples` as frames of `PCM_CHUNK` samples; none when there are none.
+pub fn write_pcm(writer: &mut impl Write, samples: &[f32]) -> Result<()> {
+    let mut bytes = Vec::with_capacity(PCM_CHUNK.min(samples.len()) * 4);
+    for chunk in samples.chunks(PCM_CHUNK) {
+        bytes.clear();
+        bytes.extend(chunk.iter().flat_map(|sample| sample.to_le_bytes()));
+        write_frame(writer, &bytes).context("write audio")?;
+    }
+    Ok(())
+}
+
+/// Reads the frames `write_pcm` makes of `samples` samples.
+pub fn read_pcm(reader: &mut impl Read, samples: usize) -> Result<Vec<f32>> {
+    let mut audio = Vec::with_capacity(samples.min(PCM_CHUNK * 64));
+    while audio.len() < samples {
+        let bytes = read_frame(reader).context("read audio")?;
+        if bytes.is_empty() || bytes.len() % 4 != 0 || audio.len() + bytes.len() / 4 > samples {
+            anyhow::bail!(
+                "expected {samples} samples, got a frame of {} bytes after {}",
+                bytes.len(),
+                audio.len()
+            );
+        }
+        audio.extend(
+            bytes
+                .chunks_exact(4)
+                .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])),
+        );
+    }
+    Ok(audio)
+}
+
+fn check_version(version: u32) -> Result<()> {
+    if version != PROTOCOL_VERSION {
+        anyhow::bail!("unsupported protocol version {version}, expected {PROTOCOL_VERSION}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_frame, read_pcm, write_frame, write_pcm, Hello, Request, RequestBody, Response,
+        PCM_CHUNK,
+    };
+    use crate::transcription::{TranscribeOptions, Transcript};
+
+    #[test]
+    fn requests_and_responses_round_trip_as_versioned_frames() {
+        let body = RequestBody::Transcribe {
+            samples: 16_000,
+            partial: true,
+            options: TranscribeOptions {
+                language: "auto".to_string(),
+                translate: false,
+                initial_prompt: "Tauri, whisper.cpp".to_string(),
+                word_timestamps: true,
+                diarize: false,
+            },
+        };
+        let frame = Request::to_frame(7, body.clone()).unwrap();
+        let json = String::from_utf8(frame.clone()).unwrap();
+        assert!(json.starts_with(r#"{"version":4,"id":7,"type":"transcribe","samples":16000"#));
+        let (id, parsed) = Request::parse(&frame);
+        assert_eq!((id, parsed.unwrap()), (7, body));
+        let (id, parsed) =
+            Request::parse(json.replace(r#""version":4"#, r#""version":3"#).as_bytes());
+        assert_eq!(id, 7);
+        assert!(parsed.is_err());
+        assert!(Request::parse(b"{}").1.is_err());
+
+        let audio: Vec<f32> = (0..PCM_CHUNK + 5).map(|n| n as f32 / 1e4 - 0.5).collect();
+        let mut pipe = Vec::new();
+        write_frame(&mut pipe, &frame).unwrap();
+        write_pcm(&mut pipe, &audio).unwrap();
+        assert_eq!(pipe.len(), 4 + frame.len() + 2 * 4 + audio.len() * 4);
+        let mut reader = pipe.as_slice();
+        assert_eq!(read_frame(&mut reader).unwrap(), frame);
+        assert_eq!(read_pcm(&mut reader, audio.len()).unwrap(), audio);
+        assert!(read_frame(&mut reader).is_err());
+        assert!(read_pcm(&mut &pipe[4 + frame.len()..], audio.len() + 1).is_err());
+
+        let transcript = Transcript {
+            text: "hola".to_string(),
+            ..Transcript::default()
+        };
+        let reply = serde_json::to_vec(&Response::ok(7, Some(transcript))).unwrap();
+        let reply = Response::parse(&reply).unwrap();
+        assert_eq!(reply.id, 7);
+        assert_eq!(reply.into_transcript().unwrap().unwrap().text, "hola");
+        let ack = serde_json::to_string(&Response::ok(8, None)).unwrap();
+        assert_eq!(ack, r#"{"version":4,"id":8}"#);
+        let failed = serde_json::to_vec(&Response::error(9, "bad wav".to_string())).unwrap();
+        assert!(Response::parse(&failed).unwrap().into_transcript().is_err());
+
+        let hello = serde_json::to_vec(&Hello::new("secret".to_string())).unwrap();
+        assert_eq!(Hello::parse(&hello).unwrap().token, "secret");
+    }
+}
@@ -0,0 +1,479 @@
+use crate::child_protocol::{read_frame, write_frame, Hello};
+use crate::command_errors::TranscriberUnavailable;
+use crate::local_api;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+#[cfg(windows)]
+use std::fs::File;
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Carries a child's token; kept off the command line, where other users could read it.
+pub const TOKEN_ENV: &str = "WHISPERDICT_CHILD_TOKEN";
+#[cfg(unix)]
+const UNIX_PREFIX: &str = "unix:";
+#[cfg(windows)]
+const PIPE_PREFIX: &str = "pipe:";
+/// Bounds how long a connection may take to say hello.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A connection between the parent and one transcriber child.
+pub enum Stream {
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(windows)]
+    Pipe(File),
+}
+
+impl Stream {
+    /// The first frame, which must arrive within `HELLO_TIMEOUT`.
+    fn read_hello(&mut self) -> Result<Hello> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => {
+                stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+                let hello = Hello::parse(&read_frame(stream)?)?;
+                stream.set_read_timeout(None)?;
+                Ok(hello)
+            }
+            #[cfg(windows)]
+            Self::Pipe(pipe) => {
+                pipe::wait_for_frame(pipe, HELLO_TIMEOUT)?;
+                Hello::parse(&read_frame(pipe)?)
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+            #[cfg(windows)]
+            Self::Pipe(pipe) => pipe.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+            #[cfg(windows)]
+            Self::Pipe(pipe) => pipe.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+            #[cfg(windows)]
+            Self::Pipe(pipe) => pipe.flush(),
+        }
+    }
+}
+
+/// The one socket every transcriber child connects back to, living as long as the app,
+/// so replacing a crashed child never rebuilds anything on the parent's side. Each spawn
+/// waits for the connection that says hello with its token.
+struct Rendezvous {
+    address: String,
+    pending: Mutex<HashMap<String, mpsc::Sender<Admitted>>>,
+    /// Holds the private directory of the Unix socket until `shutdown`.
+    #[cfg(unix)]
+    dir: Mutex<Option<tempfile::TempDir>>,
+}
+
+/// A child's connection, or why it could not sandbox itself.
+type Admitted = std::result::Result<Stream, String>;
+
+static RENDEZVOUS: OnceLock<std::result::Result<Rendezvous, String>> = OnceLock::new();
+
+fn rendezvous() -> Result<&'static Rendezvous> {
+    RENDEZVOUS
+        .get_or_init(|| start().map_err(|err| format!("{err:#}")))
+        .as_ref()
+        .map_err(|err| anyhow::anyhow!("transcriber socket unavailable: {err}"))
+}
+
+/// A Unix domain socket in a directory only this user can enter.
+#[cfg(unix)]
+fn start() -> Result<Rendezvous> {
+    let dir = tempfile::Builder::new()
+        .prefix("whisperdict-")
+        .tempdir()
+        .context("create socket dir")?;
+    let path = dir.path().join("transcriber.sock");
+    let listener = UnixListener::bind(&path).context("bind transcriber socket")?;
+    std::thread::Builder::new()
+        .name("transcriber-socket".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => admit_in_background(Stream::Unix(stream)),
+                    Err(err) => eprintln!("transcriber socket accept failed: {err}"),
+                }
+            }
+        })
+        .context("spawn transcriber socket thread")?;
+    Ok(Rendezvous {
+        address: format!("{UNIX_PREFIX}{}", path.display()),
+        pending: Mutex::default(),
+        dir: Mutex::new(Some(dir)),
+    })
+}
+
+/// A named pipe with a random name that only this user can open and no other machine can
+/// reach. An instance is always waiting for the next child.
+#[cfg(windows)]
+fn start() -> Result<Rendezvous> {
+    let name = format!(r"\\.\pipe\whisperdict-{}", local_api::generate_token()?);
+    let mut instance = pipe::create(&name, true)?;
+    let listening = name.clone();
+    std::thread::Builder::new()
+        .name("transcriber-socket".to_string())
+        .spawn(move || loop {
+            let connected = pipe::accept(&instance);
+            // A failed instance is replaced as well, as it cannot be connected again.
+            let next = match pipe::create(&listening, false) {
+                Ok(next) => next,
+                Err(err) => {
+                    eprintln!("transcriber pipe failed: {err:#}");
+                    return;
+                }
+            };
+            let instance = std::mem::replace(&mut instance, next);
+            match connected {
+                Ok(()) => admit_in_background(Stream::Pipe(instance)),
+                Err(err) => eprintln!("transcriber pipe accept failed: {err:#}"),
+            }
+        })
+        .context("spawn transcriber socket thread")?;
+    Ok(Rendezvous {
+        address: format!("{PIPE_PREFIX}{name}"),
+        pending: Mutex::default(),
+    })
+}
+
+/// Reads the hello on a thread of its own, so a connection that is slow to say it never
+/// holds up the next child.
+fn admit_in_background(stream: Stream) {
+    let spawned = std::thread::Builder::new()
+        .name("transcriber-hello".to_string())
+        .spawn(move || admit(stream));
+    if let Err(err) = spawned {
+        eprintln!("transcriber socket admit failed: {err}");
+    }
+}
+
+/// Hands a new connection to the spawn whose token it presents; anything else is dropped.
+fn admit(mut stream: Stream) {
+    let hello = stream.read_hello();
+    let Ok(rendezvous) = rendezvous() else {
+        return;
+    };
+    match hello {
+        Ok(hello) => {
+            let waiting = rendezvous.pending.lock().unwrap().remove(&hello.token);
+            if let Some(waiting) = waiting {
+                let _ = waiting.send(hello.sandbox_error.map_or(Ok(stream), Err));
+            }
+        }
+        Err(err) => eprintln!("rejected transcriber connection: {err:#}"),
+    }
+}
+
+/// Removes the socket, if one was made; called as the app exits.
+pub fn shutdown() {
+    #[cfg(unix)]
+    if let Some(Ok(rendezvous)) = RENDEZVOUS.get() {
+        rendezvous.dir.lock().unwrap().take();
+    }
+}
+
+/// The parent's side of one child: registered before the spawn, connected once the
+/// child has loaded its model and said hello.
+pub struct ChildLink {
+    token: String,
+    waiting: Option<mpsc::Receiver<Admitted>>,
+    stream: Option<Stream>,
+    next_id: u64,
+}
+
+impl ChildLink {
+    /// Registers a child about to be spawned; it is to be told `address()` and the token.
+    pub fn expect() -> Result<Self> {
+        let rendezvous = rendezvous()?;
+        let token = local_api::generate_token()?;
+        let (sender, receiver) = mpsc::channel();
+        rendezvous
+            .pending
+            .lock()
+            .unwrap()
+            .insert(token.clone(), sender);
+        Ok(Self {
+            token,
+            waiting: Some(receiver),
+            stream: None,
+            next_id: 1,
+        })
+    }
+
+    pub fn address(&self) -> Result<&'static str> {
+        Ok(&rendezvous()?.address)
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// The connection, waiting for the child to connect while `alive` holds; it connects
+    /// only once its model is loaded, so this also waits for that.
+    pub fn stream(&mut self, alive: impl Fn() -> bool) -> Result<&mut Stream> {
+        if let Some(waiting) = &self.waiting {
+            let admitted = loop {
+                match waiting.recv_timeout(POLL_INTERVAL) {
+                    Ok(admitted) => break admitted,
+                    Err(RecvTimeoutError::Timeout) if alive() => continue,
+                    Err(_) => anyhow::bail!("transcriber exited before it was ready"),
+                }
+            };
+            self.waiting = None;
+            match admitted {
+                Ok(stream) => self.stream = Some(stream),
+                Err(reason) => {
+                    return Err(TranscriberUnavailable(format!(
+                        "transcriber could not sandbox itself: {reason}"
+                    ))
+                    .into())
+                }
+            }
+        }
+        self.stream.as_mut().context("transcriber is not connected")
+    }
+}
+
+impl Drop for ChildLink {
+    fn drop(&mut self) {
+        if let Ok(rendezvous) = rendezvous() {
+            rendezvous.pending.lock().unwrap().remove(&self.token);
+        }
+    }
+}
+
+/// The child's side: connects to `address`. It is not admitted until `introduce`.
+pub fn connect(address: &str) -> Result<Stream> {
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+        return Ok(Stream::Unix(
+            UnixStream::connect(path).context("connect to parent")?,
+        ));
+    }
+    #[cfg(windows)]
+    if let Some(name) = address.strip_prefix(PIPE_PREFIX) {
+        return Ok(Stream::Pipe(pipe::open(name)?));
+    }
+    anyhow::bail!("unknown parent address {address}")
+}
+
+/// Says `hello`, which must come within `HELLO_TIMEOUT` of connecting.
+pub fn introduce(stream: &mut Stream, hello: &Hello) -> Result<()> {
+    let hello = serde_json::to_vec(hello).context("serialize hello")?;
+    write_frame(stream, &hello)?;
+    stream.flush().context("flush hello")
+}
+
+#[cfg(windows)]
+mod pipe {
+    use super::POLL_INTERVAL;
+    use crate::child_protocol::MAX_FRAME;
+    use anyhow::{bail, Context, Result};
+    use std::fs::{File, OpenOptions};
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+    use std::time::{Duration, Instant};
+    use windows_sys::Win32::Foundation::{
+        GetLastError, LocalFree, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+    };
+    use windows_sys::Win32::Security::{PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+    };
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PeekNamedPipe, WaitNamedPipeW, PIPE_READMODE_BYTE,
+        PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    /// Full access for the system and the pipe's owner, this user; nobody else.
+    const OWNER_ONLY: &str = "D:P(A;;GA;;;SY)(A;;GA;;;OW)";
+    const BUFFER_SIZE: u32 = 64 * 1024;
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// A new instance of pipe `name`; `first` fails if another process already made one.
+    pub fn create(name: &str, first: bool) -> Result<File> {
+        let sddl = wide(OWNER_ONLY);
+        let name = wide(name);
+        unsafe {
+            let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+            if ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                std::ptr::null_mut(),
+            ) == 0
+            {
+                bail!("build transcriber pipe security failed");
+            }
+            let attributes = SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: descriptor,
+                bInheritHandle: 0,
+            };
+            let open_mode = if first {
+                PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE
+            } else {
+                PIPE_ACCESS_DUPLEX
+            };
+            let handle = CreateNamedPipeW(
+                name.as_ptr(),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                &attributes,
+            );
+            LocalFree(descriptor);
+            if handle == INVALID_HANDLE_VALUE {
+                return Err(std::io::Error::last_os_error()).context("create transcriber pipe");
+            }
+            Ok(File::from_raw_handle(handle))
+        }
+    }
+
+    /// Blocks until a child opens `instance`.
+    pub fn accept(instance: &File) -> Result<()> {
+        let connected =
+            unsafe { ConnectNamedPipe(instance.as_raw_handle(), std::ptr::null_mut()) } != 0;
+        if !connected && unsafe { GetLastError() } != ERROR_PIPE_CONNECTED {
+            return Err(std::io::Error::last_os_error()).context("accept transcriber pipe");
+        }
+        Ok(())
+    }
+
+    /// Opens pipe `name`, waiting while every instance is taken.
+    pub fn open(name: &str) -> Result<File> {
+        for _ in 0..3 {
+            match OpenOptions::new().read(true).write(true).open(name) {
+                Ok(pipe) => return Ok(pipe),
+                Err(err) if err.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) => unsafe {
+                    WaitNamedPipeW(wide(name).as_ptr(), 5_000);
+                },
+                Err(err) => return Err(err).context("connect to parent"),
+            }
+        }
+        bail!("transcriber pipe stayed busy")
+    }
+
+    /// Waits until a whole frame can be read without blocking, as pipes have no read
+    /// timeout.
+    pub fn wait_for_frame(pipe: &File, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut len = [0u8; 4];
+            let mut read = 0u32;
+            let mut available = 0u32;
+            let peeked = unsafe {
+                PeekNamedPipe(
+                    pipe.as_raw_handle(),
+                    len.as_mut_ptr().cast(),
+                    len.len() as u32,
+                    &mut read,
+                    &mut available,
+                    std::ptr::null_mut(),
+                )
+            };
+            if peeked == 0 {
+                return Err(std::io::Error::last_os_error()).context("peek transcriber pipe");
+            }
+            if read == 4 {
+                let needed = 4 + u32::from_le_bytes(len) as usize;
+                if needed > MAX_FRAME {
+                    bail!("frame of {needed} bytes is too large");
+                }
+                if available as usize >= needed {
+                    return Ok(());
+                }
+            }
+            if Instant::now() >= deadline {
+                bail!("no complete frame within {timeout:?}");
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{connect, introduce, ChildLink, Stream};
+    use crate::child_protocol::{read_frame, write_frame, Hello};
+    use std::io::Write;
+
+    fn connect_as(address: &str, hello: Hello) -> Stream {
+        let mut stream = connect(address).unwrap();
+        introduce(&mut stream, &hello).unwrap();
+        stream
+    }
+
+    #[test]
+    fn hands_each_child_the_connection_with_its_token() {
+        let mut link = ChildLink::expect().unwrap();
+        let address = link.address().unwrap();
+        connect_as(address, Hello::new("stranger".to_string()));
+        let token = link.token().to_string();
+        let child = std::thread::spawn(move || {
+            let mut stream = connect_as(address, Hello::new(token));
+            write_frame(&mut stream, b"ready").unwrap();
+            stream.flush().unwrap();
+            read_frame(&mut stream).unwrap()
+        });
+        let stream = link.stream(|| true).unwrap();
+        assert_eq!(read_frame(stream).unwrap(), b"ready");
+        write_frame(stream, b"bye").unwrap();
+        assert_eq!(child.join().unwrap(), b"bye");
+
+        let mut orphan = ChildLink::expect().unwrap();
+        assert!(orphan.stream(|| false).is_err());
+
+        let mut unsandboxed = ChildLink::expect().unwrap();
+        let _stream = connect_as(
+            address,
+            Hello {
+                sandbox_error: Some("no landlock".to_string()),
+                ..Hello::new(unsandboxed.token().to_string())
+            },
+        );
+        let err = unsandboxed.stream(|| true).err().unwrap();
+        assert!(format!("{err:#}").contains("no landlock"));
+    }
+}
@@ -1,13 +1,22 @@
-use crate::transcription::transcribe_with_context;
+use crate::child_protocol::{
+    read_frame, read_pcm, write_frame, Hello, Request, RequestBody, Response,
+};
+use crate::child_socket::{self, TOKEN_ENV};
+use crate::sandbox;
+use crate::transcription::{load_context, transcribe_with_context, ContextOptions, DecodingParams};
 use anyhow::{Context, Result};
 use std::env;
-use std::io::{self, BufRead, Write};
+use std::io::Write;
+use std::path::Path;
 
 pub fn run_if_child() -> Result<bool> {
     let mut args = env::args().skip(1);
     let mut is_child = false;
     let mut is_server = false;
     let mut model_path = None;
+    let mut address = None;
+    let mut sandboxed = false;
+    let mut context = ContextOptions::default();
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
@@ -17,6 +26,17 @@ pub fn run_if_child() -> Result<bool> {
                 is_server = true;
             }
             "--model" => model_path = args.next(),
+            "--connect" => address = args.next(),
+            "--sandbox" => sandboxed = true,
+            "--backend" => context.backend = args.next().unwrap_or(context.backend),
+            "--gpu-device" => {
+                context.gpu_device = args
+                    .next()
+                    .and_then(|device| device.parse().ok())
+                    .unwrap_or(0)
+            }
+            "--flash-attn" => context.flash_attn = true,
+            "--no-coreml" => context.coreml = false,
             _ => {}
         }
     }
@@ -27,69 +47,77 @@ pub fn run_if_child() -> Result<bool> {
 
     let model_path = model_path.context("missing model path")?;
     if is_server {
-        run_server(&model_path)?;
+        let address = address.context("missing parent address")?;
+        run_server(&model_path, &address, &context, sandboxed)?;
         return Ok(true);
     }
 
     Ok(true)
 }
 
-fn run_server(model_path: &str) -> Result<()> {
-    let mut ctx_params = whisper_rs::WhisperContextParameters::default();
-    ctx_params.use_gpu(true);
-    let ctx = match whisper_rs::WhisperContext::new_with_params(model_path, ctx_params) {
-        Ok(ctx) => ctx,
-        Err(err) => {
-            eprintln!("Whisperdict-child: GPU init failed ({err}), falling back to CPU");
-            let mut cpu_params = whisper_rs::WhisperContextParameters::default();
-            cpu_params.use_gpu(false);
-            whisper_rs::WhisperContext::new_with_params(model_path, cpu_params)
-                .context("load model (cpu)")?
-        }
+/// Connects to the parent only once the model is loaded, so the connection itself tells
+/// the parent the child is ready.
+fn run_server(
+    model_path: &str,
+    address: &str,
+    context: &ContextOptions,
+    sandboxed: bool,
+) -> Result<()> {
+    let ctx = load_context(model_path, context)?;
+    let token = env::var(TOKEN_ENV).context("missing child token")?;
+    let mut stream = child_socket::connect(address)?;
+    // The sandbox denies new connections, so it goes up between connecting and saying
+    // hello; a failure is reported to the parent instead of running unsandboxed.
+    let sandbox_error = sandboxed
+        .then(|| sandbox::restrict_child(Path::new(model_path)).err())
+        .flatten()
+        .map(|err| format!("{err:#}"));
+    let hello = Hello {
+        sandbox_error,
+        ..Hello::new(token)
     };
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    for line in stdin.lock().lines() {
-        let line = line.context("read line")?;
-        if line.trim().is_empty() {
-            continue;
-        }
-        let (language, wav_path) = if let Some((lang, path)) = line.split_once('\t') {
-            (lang.trim().to_string(), path.trim().to_string())
-        } else {
-            ("en".to_string(), line.trim().to_string())
-        };
-        let text = match transcribe_wav_with_ctx(&ctx, &wav_path, &language) {
-            Ok(text) => text,
+    child_socket::introduce(&mut stream, &hello)?;
+    if let Some(err) = hello.sandbox_error {
+        anyhow::bail!("sandbox unavailable: {err}");
+    }
+    let mut decoding = DecodingParams::default();
+    // The parent closing the connection ends the loop.
+    while let Ok(frame) = read_frame(&mut stream) {
+        let (id, request) = Request::parse(&frame);
+        let response = match request {
+            Ok(RequestBody::SetParams { params }) => {
+                decoding = params;
+                Response::ok(id, None)
+            }
+            Ok(RequestBody::Ping) => Response::ok(id, None),
+            Ok(RequestBody::Transcribe {
+                samples,
+                partial,
+                options,
+            }) => {
+                // A short read means the parent went away mid-request.
+                let audio = read_pcm(&mut stream, samples)?;
+                let params = if partial {
+                    decoding.for_partial()
+                } else {
+                    decoding.clone()
+                };
+                match transcribe_with_context(&ctx, &audio, &options, &params) {
+                    Ok(transcript) => Response::ok(id, Some(transcript)),
+                    Err(err) => {
+                        eprintln!("Whisperdict-child: error {err}");
+                        Response::error(id, format!("{err:#}"))
+                    }
+                }
+            }
             Err(err) => {
-                eprintln!("Whisperdict-child: error {err}");
-                String::new()
+                eprintln!("Whisperdict-child: invalid request {err:#}");
+                Response::error(id, format!("{err:#}"))
             }
         };
-        writeln!(stdout, "{}", text).context("write stdout")?;
-        stdout.flush().context("flush stdout")?;
+        let frame = serde_json::to_vec(&response).context("serialize response")?;
+        write_frame(&mut stream, &frame)?;
+        stream.flush().context("flush response")?;
     }
     Ok(())
 }
-
-fn transcribe_wav_with_ctx(
-    ctx: &whisper_rs::WhisperContext,
-    wav_path: &str,
-    language: &str,
-) -> Result<String> {
-    let reader = hound::WavReader::open(wav_path).context("open wav")?;
-    let spec = reader.spec();
-    if spec.channels != 1 || spec.sample_rate != 16000 {
-        return Err(anyhow::anyhow!("unexpected wav format"));
-    }
-
-    let mut samples = Vec::new();
-    for sample in reader.into_samples::<i16>() {
-        let sample = sample.context("read sample")? as f32 / 32768.0;
-        samples.push(sample);
-    }
-
-    let lang = if language.is_empty() { "en" } else { language };
-    let text = transcribe_with_context(ctx, &samples, Some(lang), false).context("transcribe")?;
-    Ok(text)
-}
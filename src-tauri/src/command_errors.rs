@@ -3,6 +3,9 @@ use thiserror::Error;
 
 pub const FREE_LIMIT_REACHED_CODE: &str = "FREE_LIMIT_REACHED";
 pub const LICENSE_INVALID_CODE: &str = "LICENSE_INVALID";
+pub const MANAGED_POLICY_CODE: &str = "MANAGED_POLICY";
+pub const AUDIO_DEVICE_UNAVAILABLE_CODE: &str = "AUDIO_DEVICE_UNAVAILABLE";
+pub const MODEL_TOO_LARGE_CODE: &str = "MODEL_TOO_LARGE_FOR_DEVICE";
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -31,6 +34,27 @@ impl CommandError {
         Self::new(LICENSE_INVALID_CODE, "License file is invalid")
     }
 
+    pub const fn model_not_allowed() -> Self {
+        Self::new(
+            MANAGED_POLICY_CODE,
+            "This model is not allowed by your administrator",
+        )
+    }
+
+    pub const fn checkout_disabled() -> Self {
+        Self::new(
+            MANAGED_POLICY_CODE,
+            "Purchases are disabled by your administrator",
+        )
+    }
+
+    pub const fn audio_device_unavailable() -> Self {
+        Self::new(
+            AUDIO_DEVICE_UNAVAILABLE_CODE,
+            "No microphone could be opened. Check that one is connected and that Whisperdict may use it",
+        )
+    }
+
     pub fn payload(&self) -> CommandErrorPayload {
         CommandErrorPayload {
             code: self.code.to_string(),
@@ -39,7 +63,50 @@ impl CommandError {
     }
 }
 
+/// A model that needs more free memory than the device has; serialized with the
+/// requirements so the UI can show them.
+#[derive(Debug, Error, Clone, Serialize)]
+#[error(
+    "{model_id} needs about {required_mb} MB of free {memory}, but only {available_mb} MB is free"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelTooLarge {
+    pub model_id: String,
+    /// `memory::MEMORY_RAM` or `memory::MEMORY_VRAM`.
+    pub memory: &'static str,
+    pub required_mb: u64,
+    pub available_mb: u64,
+}
+
+/// The user cancelled the transcription in progress.
+#[derive(Debug, Error, Clone)]
+#[error("transcription cancelled")]
+pub struct TranscriptionCancelled;
+
+/// The watchdog killed a transcriber that took longer than its limit.
+#[derive(Debug, Error, Clone)]
+#[error("transcription timed out")]
+pub struct TranscriptionTimedOut;
+
+/// A transcriber that cannot start for a reason a fresh one would run into again, such as
+/// a missing model file, so it is never retried.
+#[derive(Debug, Error, Clone)]
+#[error("{0}")]
+pub struct TranscriberUnavailable(pub String);
+
 pub fn map_error(error: anyhow::Error) -> String {
+    if let Some(too_large) = error.downcast_ref::<ModelTooLarge>() {
+        let mut payload = serde_json::json!({
+            "code": MODEL_TOO_LARGE_CODE,
+            "message": too_large.to_string(),
+        });
+        if let (Some(payload), Ok(serde_json::Value::Object(details))) =
+            (payload.as_object_mut(), serde_json::to_value(too_large))
+        {
+            payload.extend(details);
+        }
+        return payload.to_string();
+    }
     if let Some(command_error) = error.downcast_ref::<CommandError>() {
         return match serde_json::to_string(&command_error.payload()) {
             Ok(payload) => payload,
@@ -0,0 +1,344 @@
+use anyhow::Result;
+use serde::Serialize;
+
+pub const BACKEND_AUTO: &str = "auto";
+pub const BACKEND_CUDA: &str = "cuda";
+pub const BACKEND_VULKAN: &str = "vulkan";
+pub const BACKEND_METAL: &str = "metal";
+pub const BACKEND_CPU: &str = "cpu";
+/// Whether this build can run the encoder through Core ML, on top of whichever backend
+/// runs the decoder. OpenVINO, the other encoder accelerator whisper.cpp has, is not
+/// offered: whisper-rs does not expose it.
+pub const COREML: bool = cfg!(feature = "coreml");
+pub const ACCELERATOR_COREML: &str = "coreml";
+
+/// Whether models load with their Core ML encoder under `backend`: always in a Core ML
+/// build, unless the CPU is chosen.
+pub fn coreml_enabled(backend: &str) -> bool {
+    COREML && backend != BACKEND_CPU
+}
+
+/// A compute backend whisper.cpp can run on. GPU backends are compiled in through the
+/// `cuda`, `vulkan` and `metal` cargo features, so a build offers at most what it was
+/// built with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeBackend {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub available: bool,
+}
+
+/// The GPU backend this build links against, if any.
+fn compiled_gpu() -> Option<&'static str> {
+    if cfg!(feature = "cuda") {
+        Some(BACKEND_CUDA)
+    } else if cfg!(feature = "vulkan") {
+        Some(BACKEND_VULKAN)
+    } else if cfg!(feature = "metal") {
+        Some(BACKEND_METAL)
+    } else {
+        None
+    }
+}
+
+pub fn list_backends() -> Vec<ComputeBackend> {
+    let gpu = compiled_gpu();
+    [
+        (BACKEND_AUTO, "Automatic", true),
+        (BACKEND_CUDA, "CUDA", gpu == Some(BACKEND_CUDA)),
+        (BACKEND_VULKAN, "Vulkan", gpu == Some(BACKEND_VULKAN)),
+        (BACKEND_METAL, "Metal", gpu == Some(BACKEND_METAL)),
+        (BACKEND_CPU, "CPU", true),
+    ]
+    .into_iter()
+    .map(|(id, label, available)| ComputeBackend {
+        id,
+        label,
+        available,
+    })
+    .collect()
+}
+
+/// A graphics adapter found on this machine. Memory is in MB and only known where the
+/// platform reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuDevice {
+    pub index: u32,
+    pub name: String,
+    /// The backend that can drive it in a build with that backend compiled in.
+    pub backend: &'static str,
+    pub vram_mb: Option<u64>,
+    pub free_mb: Option<u64>,
+}
+
+/// What transcription actually runs on and why, for the settings screen.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeReport {
+    /// The GPU backend this build links against, if any.
+    pub compiled: Option<&'static str>,
+    pub configured: String,
+    /// `BACKEND_CPU` or the compiled GPU backend, as expected for the next load.
+    pub effective: &'static str,
+    pub reason: Option<String>,
+    /// An encoder accelerator used alongside `effective`, such as `ACCELERATOR_COREML`.
+    pub accelerator: Option<&'static str>,
+    pub gpus: Vec<GpuDevice>,
+}
+
+/// Describes how `backend` (the setting for the active model) will load it, given the
+/// GPUs found. Shells out on most platforms, so call it off the async runtime.
+pub fn report(backend: &str) -> ComputeReport {
+    let gpus = detect_gpus();
+    let compiled = compiled_gpu();
+    let usable = |gpu: &str| gpus.iter().any(|device| device.backend == gpu);
+    let (effective, reason) = match (gpu_mode(backend), compiled) {
+        (Err(err), _) => (BACKEND_CPU, Some(format!("{err:#}"))),
+        (Ok(Some(false)), None) => (
+            BACKEND_CPU,
+            Some("this build has no GPU support".to_string()),
+        ),
+        (Ok(Some(false)), Some(_)) => (BACKEND_CPU, Some("CPU selected in settings".to_string())),
+        // Vulkan drives any vendor's adapter, so any detected GPU will do.
+        (Ok(_), Some(gpu)) if usable(gpu) || (gpu == BACKEND_VULKAN && !gpus.is_empty()) => {
+            (gpu, None)
+        }
+        (Ok(None), Some(gpu)) => (
+            BACKEND_CPU,
+            Some(format!(
+                "no {gpu} device found, so automatic falls back to the CPU"
+            )),
+        ),
+        (Ok(_), Some(gpu)) => (
+            gpu,
+            Some(format!("no {gpu} device was detected; loading may fail")),
+        ),
+        (Ok(_), None) => (BACKEND_CPU, None),
+    };
+    ComputeReport {
+        compiled,
+        configured: backend.to_string(),
+        effective,
+        reason,
+        accelerator: coreml_enabled(backend).then_some(ACCELERATOR_COREML),
+        gpus,
+    }
+}
+
+/// NVIDIA GPUs as reported by `nvidia-smi`, which ships with the driver.
+pub fn nvidia_gpus() -> Vec<GpuDevice> {
+    let mut command = std::process::Command::new("nvidia-smi");
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+        .args([
+            "--query-gpu=index,name,memory.total,memory.free",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .map(|output| parse_nvidia_smi(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+fn parse_nvidia_smi(output: &str) -> Vec<GpuDevice> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [index, name, total, free] = fields[..] else {
+                return None;
+            };
+            Some(GpuDevice {
+                index: index.parse().ok()?,
+                name: name.to_string(),
+                backend: BACKEND_CUDA,
+                vram_mb: total.parse().ok(),
+                free_mb: free.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+/// NVIDIA GPUs come from `nvidia-smi`; other adapters from what the platform lists.
+fn detect_gpus() -> Vec<GpuDevice> {
+    let mut gpus = nvidia_gpus();
+    let has_nvidia = !gpus.is_empty();
+    gpus.extend(
+        platform_gpus()
+            .into_iter()
+            .filter(|gpu| !(has_nvidia && gpu.name.to_ascii_lowercase().contains("nvidia"))),
+    );
+    gpus
+}
+
+/// DRM cards in sysfs; amdgpu also reports VRAM there.
+#[cfg(target_os = "linux")]
+fn platform_gpus() -> Vec<GpuDevice> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/drm") else {
+        return Vec::new();
+    };
+    let mut cards: Vec<(u32, std::path::PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let index = name.strip_prefix("card")?.parse().ok()?;
+            Some((index, entry.path().join("device")))
+        })
+        .collect();
+    cards.sort();
+    cards
+        .into_iter()
+        .filter_map(|(index, device)| {
+            let read = |file: &str| std::fs::read_to_string(device.join(file)).ok();
+            let vendor = match read("vendor")?.trim() {
+                "0x10de" => "NVIDIA",
+                "0x1002" => "AMD",
+                "0x8086" => "Intel",
+                _ => "Unknown",
+            };
+            let mb = |file: &str| -> Option<u64> {
+                Some(read(file)?.trim().parse::<u64>().ok()? / (1024 * 1024))
+            };
+            let total = mb("mem_info_vram_total");
+            let used = mb("mem_info_vram_used");
+            Some(GpuDevice {
+                index,
+                name: format!("{vendor} GPU"),
+                backend: BACKEND_VULKAN,
+                vram_mb: total,
+                free_mb: total
+                    .zip(used)
+                    .map(|(total, used)| total.saturating_sub(used)),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn platform_gpus() -> Vec<GpuDevice> {
+    let Ok(output) = std::process::Command::new("system_profiler")
+        .arg("SPDisplaysDataType")
+        .output()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Chipset Model:"))
+        .enumerate()
+        .map(|(index, name)| GpuDevice {
+            index: index as u32,
+            name: name.trim().to_string(),
+            backend: BACKEND_METAL,
+            vram_mb: None,
+            free_mb: None,
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn platform_gpus() -> Vec<GpuDevice> {
+    use std::os::windows::process::CommandExt;
+    use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
+    // Without CREATE_NO_WINDOW a console flashes up whenever the devices are listed.
+    let Ok(output) = std::process::Command::new("powershell")
+        .creation_flags(CREATE_NO_WINDOW)
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "(Get-CimInstance Win32_VideoController).Name",
+        ])
+        .output()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .enumerate()
+        .map(|(index, name)| GpuDevice {
+            index: index as u32,
+            name: name.to_string(),
+            backend: BACKEND_VULKAN,
+            vram_mb: None,
+            free_mb: None,
+        })
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn platform_gpus() -> Vec<GpuDevice> {
+    Vec::new()
+}
+
+/// How a backend setting loads the model: `Some(true)` on the GPU only, `Some(false)` on
+/// the CPU only, `None` on the GPU with a CPU fallback. Errors for backends this build
+/// lacks, so an explicit choice never silently ends up on the CPU.
+pub fn gpu_mode(backend: &str) -> Result<Option<bool>> {
+    match backend {
+        "" | BACKEND_AUTO => Ok(compiled_gpu().map(|_| None).unwrap_or(Some(false))),
+        BACKEND_CPU => Ok(Some(false)),
+        _ => match list_backends()
+            .iter()
+            .find(|candidate| candidate.id == backend)
+        {
+            Some(candidate) if candidate.available => Ok(Some(true)),
+            Some(candidate) => anyhow::bail!("{} is not supported by this build", candidate.label),
+            None => anyhow::bail!("unknown compute backend {backend}"),
+        },
+    }
+}
+
+/// The environment variable that limits this build's GPU backend to adapter `device`.
+/// whisper.cpp ignores `gpu_device` and takes the first GPU it sees, so the transcription
+/// child is started with only the chosen one visible.
+pub fn device_env(device: u32) -> Option<(&'static str, String)> {
+    match compiled_gpu()? {
+        BACKEND_CUDA => Some(("CUDA_VISIBLE_DEVICES", device.to_string())),
+        BACKEND_VULKAN => Some(("GGML_VK_VISIBLE_DEVICES", device.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compiled_gpu, gpu_mode, parse_nvidia_smi, BACKEND_AUTO, BACKEND_CPU, BACKEND_CUDA,
+    };
+
+    #[test]
+    fn explicit_backends_never_fall_back() {
+        assert_eq!(gpu_mode(BACKEND_CPU).unwrap(), Some(false));
+        assert!(gpu_mode("directml").is_err());
+        match compiled_gpu() {
+            Some(gpu) => {
+                assert_eq!(gpu_mode(BACKEND_AUTO).unwrap(), None);
+                assert_eq!(gpu_mode(gpu).unwrap(), Some(true));
+            }
+            None => {
+                assert_eq!(gpu_mode(BACKEND_AUTO).unwrap(), Some(false));
+                assert!(gpu_mode(BACKEND_CUDA).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn parses_nvidia_smi_devices() {
+        let gpus = parse_nvidia_smi("0, NVIDIA GeForce RTX 3060, 12288, 11050\n1, Tesla T4, [N/A], 15000\nNo devices were found\n");
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(gpus[0].name, "NVIDIA GeForce RTX 3060");
+        assert_eq!(
+            (gpus[0].vram_mb, gpus[0].free_mb),
+            (Some(12_288), Some(11_050))
+        );
+        assert_eq!((gpus[1].index, gpus[1].vram_mb), (1, None));
+    }
+}
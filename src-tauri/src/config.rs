@@ -1,37 +1,255 @@
+use crate::app_rules::AppRule;
+use crate::compute;
+use crate::dictionary::RemovedReplacement;
+use crate::latency::LatencyPick;
+use crate::local_api;
+use crate::managed_config;
+use crate::models::ModelCompute;
+use crate::post_processing::{ProfanityFilter, ReplacementRule, BIDI_OFF};
+use crate::retranscribe::RetranscribeConfig;
+use crate::schedule::ScheduleConfig;
+use crate::transcription::{
+    AdvancedDecoding, DEFAULT_ENTROPY_THRESHOLD, DEFAULT_LOGPROB_THRESHOLD, DEFAULT_TEMPERATURE_INC,
+};
 use anyhow::{Context, Result};
 use directories::BaseDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::watch;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AppConfig {
     pub shortcut: String,
+    /// Toggles incognito dictation; empty leaves it unbound.
+    pub incognito_shortcut: String,
+    /// A second model kept loaded for quick notes, dictated with `quick_shortcut`.
+    pub quick_model: String,
+    pub quick_shortcut: String,
+    /// Tag to shortcut; a dictation started with one of these is stored with its tag.
+    pub tag_shortcuts: BTreeMap<String, String>,
     pub active_model: String,
     pub preferred_model: String,
+    /// Budget for decoding a typical dictation; non-zero lets the app pick the model.
+    pub max_latency_ms: u64,
+    /// The latest choice made for `max_latency_ms`.
+    pub latency_pick: Option<LatencyPick>,
     pub language: String,
     pub free_transcriptions_left: u32,
     pub total_transcriptions_count: u64,
+    /// Audio charged against a duration-based free tier.
+    pub free_audio_ms_used: u64,
+    /// Month (see `quota::month_of`) the monthly free tier was last counted in.
+    pub quota_period: Option<u32>,
+    /// Last usage journal entry applied to the counters above.
+    pub usage_seq: u64,
     pub entitlement: String,
     pub license_file_path: Option<String>,
     pub license_status: String,
     pub license_last_validated_at: Option<u64>,
+    pub replacements: Vec<ReplacementRule>,
+    pub removed_replacements: Vec<RemovedReplacement>,
+    pub app_rules: Vec<AppRule>,
+    pub vad_auto_stop: bool,
+    pub vad_silence_ms: u64,
+    pub vad_threshold: f32,
+    pub sandbox_transcriber: bool,
+    /// Serve transcription of raw audio to other apps on this machine.
+    pub local_api: bool,
+    pub local_api_port: u16,
+    pub local_api_token: String,
+    /// Load the model into the app itself instead of a transcriber child process. Such a
+    /// model cannot be sandboxed, timed out, cancelled or put on a GPU other than the
+    /// first, so it is ignored while the sandbox or a timeout is on.
+    pub in_process_transcription: bool,
+    /// Least seconds a transcription may take before its transcriber is killed; 0 for
+    /// no limit. Long recordings get proportionally more.
+    pub transcription_timeout_secs: u64,
+    /// Parallel ranged connections per model download.
+    pub download_connections: u32,
+    /// http(s) or SOCKS5 proxy URL; empty uses the proxy environment variables.
+    pub proxy_url: String,
+    /// A `compute::BACKEND_*` id.
+    pub compute_backend: String,
+    /// Per-model overrides of `compute_backend`, by model id.
+    pub model_compute: BTreeMap<String, ModelCompute>,
+    pub resampler: String,
+    pub output_mode: String,
+    pub clipboard_guard: String,
+    /// Put back the text that was on the clipboard after pasting a transcription.
+    pub restore_clipboard: bool,
+    /// A `paste::PASTE_KEYS_*` shortcut; app rules can override it.
+    pub paste_keys: String,
+    /// Restore token of the RemoteDesktop portal session used to type on Wayland.
+    pub remote_desktop_token: String,
+    /// A `paste::APPEND_*` choice of what follows each delivered transcript.
+    pub append_after: String,
+    /// How right-to-left dictation is wrapped for pasting, a `post_processing::BIDI_*` mode.
+    pub bidi_marks: String,
+    /// Speak the transcription "before" or "after" pasting it, or "off".
+    pub read_aloud: String,
+    /// Transcribe overlapping chunks while recording and emit partial text.
+    pub streaming_partials: bool,
+    /// Minimum spacing between emits of the same event to the webview, by event name.
+    pub event_throttle_ms: BTreeMap<String, u64>,
+    /// Longest string sent in an event payload before it is cut; zero disables the limit.
+    pub max_event_string_len: usize,
+    pub gain_mode: String,
+    pub low_latency: bool,
+    pub max_recording_secs: u64,
+    pub preroll_enabled: bool,
+    pub preroll_ms: u64,
+    pub capture_source: String,
+    /// Zero-based input channel to capture, keyed by device name; others are downmixed.
+    pub input_channels: BTreeMap<String, u16>,
+    pub avoid_bluetooth_input: bool,
+    /// Cancel speaker playback out of the microphone using a loopback reference.
+    pub echo_cancellation: bool,
+    /// Hold recordings losslessly compressed (16-bit) instead of as raw samples.
+    pub compress_recording: bool,
+    /// While screen sharing: copy instead of pasting or typing, and show no dialogs.
+    pub presentation_mode: bool,
+    /// cpal host name (e.g. "ALSA", "JACK", "WASAPI") or "auto".
+    pub audio_host: String,
+    pub beam_size: u32,
+    pub cue_on_start: bool,
+    pub cue_on_stop: bool,
+    pub cue_on_paste: bool,
+    pub cue_volume: f32,
+    pub temperature: f32,
+    pub temperature_inc: f32,
+    pub entropy_threshold: f32,
+    pub logprob_threshold: f32,
+    /// 0 = one per core.
+    pub transcription_threads: u32,
+    pub advanced_decoding: AdvancedDecoding,
+    /// Text Whisper is primed with to bias it towards domain terms and spellings.
+    pub initial_prompt: String,
+    /// When `initial_prompt` last changed, so dictionary sync keeps the newest.
+    pub initial_prompt_updated_at: u64,
+    /// Include timed segments and words in `transcription:result`.
+    pub word_timestamps: bool,
+    /// Label speakers when the active model supports it.
+    pub diarize: bool,
+    /// Capitalize and punctuate text from models that emit it bare.
+    pub restore_punctuation: bool,
+    pub remove_fillers: bool,
+    /// Languages with spoken number and symbol formatting on.
+    pub spoken_formatting: Vec<String>,
+    pub profanity_filter: ProfanityFilter,
+    pub keep_recordings: bool,
+    pub recordings_dir: Option<String>,
+    pub recordings_keep_count: u32,
+    pub recordings_keep_days: u32,
+    pub pinned_languages: Vec<String>,
+    pub tray_middle_click_action: String,
+    /// Keeps the main window hidden at launch, leaving only the tray icon.
+    pub start_hidden: bool,
+    pub monitor_input: bool,
+    pub monitor_volume: f32,
+    pub schedule: ScheduleConfig,
+    pub retranscribe: RetranscribeConfig,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             shortcut: "Ctrl+Alt+Space".to_string(),
+            incognito_shortcut: String::new(),
+            quick_model: String::new(),
+            quick_shortcut: String::new(),
+            tag_shortcuts: BTreeMap::new(),
             active_model: "base".to_string(),
             preferred_model: "base".to_string(),
+            max_latency_ms: 0,
+            latency_pick: None,
             language: "en".to_string(),
             free_transcriptions_left: 50,
             total_transcriptions_count: 0,
+            free_audio_ms_used: 0,
+            quota_period: None,
+            usage_seq: 0,
             entitlement: "free".to_string(),
             license_file_path: None,
             license_status: "none".to_string(),
             license_last_validated_at: None,
+            replacements: Vec::new(),
+            removed_replacements: Vec::new(),
+            app_rules: Vec::new(),
+            vad_auto_stop: false,
+            vad_silence_ms: 1500,
+            vad_threshold: 0.015,
+            sandbox_transcriber: false,
+            local_api: false,
+            local_api_port: local_api::DEFAULT_PORT,
+            local_api_token: String::new(),
+            in_process_transcription: false,
+            transcription_timeout_secs: 120,
+            download_connections: 1,
+            proxy_url: String::new(),
+            compute_backend: compute::BACKEND_AUTO.to_string(),
+            model_compute: BTreeMap::new(),
+            resampler: "sinc".to_string(),
+            output_mode: "paste".to_string(),
+            clipboard_guard: "restore".to_string(),
+            restore_clipboard: true,
+            paste_keys: "auto".to_string(),
+            remote_desktop_token: String::new(),
+            append_after: "none".to_string(),
+            bidi_marks: BIDI_OFF.to_string(),
+            read_aloud: "off".to_string(),
+            streaming_partials: false,
+            event_throttle_ms: BTreeMap::from([
+                ("audio:level".to_string(), 50),
+                ("models:progress".to_string(), 100),
+            ]),
+            max_event_string_len: 16_384,
+            gain_mode: "off".to_string(),
+            low_latency: false,
+            max_recording_secs: 300,
+            preroll_enabled: false,
+            preroll_ms: 1500,
+            capture_source: "microphone".to_string(),
+            input_channels: BTreeMap::new(),
+            avoid_bluetooth_input: false,
+            echo_cancellation: false,
+            compress_recording: false,
+            presentation_mode: false,
+            audio_host: "auto".to_string(),
+            beam_size: 1,
+            cue_on_start: false,
+            cue_on_stop: false,
+            cue_on_paste: false,
+            cue_volume: 0.4,
+            temperature: 0.0,
+            temperature_inc: DEFAULT_TEMPERATURE_INC,
+            entropy_threshold: DEFAULT_ENTROPY_THRESHOLD,
+            logprob_threshold: DEFAULT_LOGPROB_THRESHOLD,
+            transcription_threads: 0,
+            advanced_decoding: AdvancedDecoding::default(),
+            initial_prompt: String::new(),
+            initial_prompt_updated_at: 0,
+            word_timestamps: false,
+            diarize: false,
+            restore_punctuation: false,
+            remove_fillers: false,
+            spoken_formatting: Vec::new(),
+            profanity_filter: ProfanityFilter::default(),
+            keep_recordings: false,
+            recordings_dir: None,
+            recordings_keep_count: 50,
+            recordings_keep_days: 30,
+            pinned_languages: Vec::new(),
+            tray_middle_click_action: "toggle_recording".to_string(),
+            start_hidden: false,
+            monitor_input: false,
+            monitor_volume: 0.8,
+            schedule: ScheduleConfig::default(),
+            retranscribe: RetranscribeConfig::default(),
         }
     }
 }
@@ -45,17 +263,74 @@ pub fn config_path() -> Result<PathBuf> {
 
 pub fn load_config() -> Result<AppConfig> {
     let path = config_path()?;
-    if !path.exists() {
-        return Ok(AppConfig::default());
-    }
-    let data = fs::read_to_string(&path).context("read config")?;
-    let config = serde_json::from_str(&data).context("parse config")?;
+    let user = if path.exists() {
+        let data = fs::read_to_string(&path).context("read config")?;
+        serde_json::from_str(&data).context("parse config")?
+    } else {
+        serde_json::Value::Object(Default::default())
+    };
+    let merged = match managed_config::system_defaults() {
+        Some(defaults) => managed_config::merge_beneath(defaults, user),
+        None => user,
+    };
+    let config = serde_json::from_value(merged).context("parse config")?;
     Ok(config)
 }
 
 pub fn save_config(config: &AppConfig) -> Result<()> {
     let path = config_path()?;
-    let data = serde_json::to_string_pretty(config).context("serialize config")?;
-    fs::write(path, data).context("write config")?;
+    let mut value = serde_json::to_value(config).context("serialize config")?;
+    if let Some(defaults) = managed_config::system_defaults() {
+        value = managed_config::strip_beneath(defaults, value);
+    }
+    let data = serde_json::to_string_pretty(&value).context("serialize config")?;
+    let temp_path = path.with_extension("json.tmp");
+    // Synced before the rename, so the usage journal is never compacted away while the
+    // counters it fed are still only in the page cache.
+    let mut file = fs::File::create(&temp_path).context("write config")?;
+    file.write_all(data.as_bytes()).context("write config")?;
+    file.sync_all().context("sync config")?;
+    fs::rename(&temp_path, &path).context("replace config")?;
     Ok(())
 }
+
+/// Shared configuration as immutable snapshots. Readers grab an `Arc` and never hold a
+/// lock across awaits or I/O; all changes go through `update`, which serializes writers,
+/// persists the result and only then publishes it, so a failed save changes nothing.
+#[derive(Clone)]
+pub struct ConfigStore {
+    current: Arc<RwLock<Arc<AppConfig>>>,
+    writer: Arc<Mutex<()>>,
+    changes: watch::Sender<Arc<AppConfig>>,
+}
+
+impl ConfigStore {
+    pub fn new(config: AppConfig) -> Self {
+        let config = Arc::new(config);
+        let (changes, _) = watch::channel(config.clone());
+        Self {
+            current: Arc::new(RwLock::new(config)),
+            writer: Arc::new(Mutex::new(())),
+            changes,
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<AppConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn update<R>(&self, apply: impl FnOnce(&mut AppConfig) -> R) -> Result<R> {
+        let _writer = self.writer.lock().unwrap();
+        let mut next = (*self.snapshot()).clone();
+        let result = apply(&mut next);
+        save_config(&next)?;
+        let next = Arc::new(next);
+        *self.current.write().unwrap() = next.clone();
+        self.changes.send_replace(next);
+        Ok(result)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<AppConfig>> {
+        self.changes.subscribe()
+    }
+}
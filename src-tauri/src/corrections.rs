@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+pub const SUGGESTION_THRESHOLD: u32 = 3;
+const MAX_PHRASE_WORDS: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionPair {
+    pub from: String,
+    pub to: String,
+    pub count: u32,
+    pub dismissed: bool,
+    pub last_seen_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorrectionStore {
+    pub pairs: Vec<CorrectionPair>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrectionSuggestion {
+    pub from: String,
+    pub to: String,
+    pub count: u32,
+}
+
+impl CorrectionStore {
+    pub fn record(
+        &mut self,
+        original: &str,
+        corrected: &str,
+        now: u64,
+    ) -> Vec<CorrectionSuggestion> {
+        let mut reached = Vec::new();
+        let Some((from, to)) = changed_phrase(original, corrected) else {
+            return reached;
+        };
+
+        let index = match self
+            .pairs
+            .iter()
+            .position(|pair| pair.from.eq_ignore_ascii_case(&from) && pair.to == to)
+        {
+            Some(index) => index,
+            None => {
+                self.pairs.push(CorrectionPair {
+                    from,
+                    to,
+                    count: 0,
+                    dismissed: false,
+                    last_seen_at: now,
+                });
+                self.pairs.len() - 1
+            }
+        };
+        let pair = &mut self.pairs[index];
+        pair.count = pair.count.saturating_add(1);
+        pair.last_seen_at = now;
+        if pair.count == SUGGESTION_THRESHOLD && !pair.dismissed {
+            reached.push(CorrectionSuggestion::from(&*pair));
+        }
+        reached
+    }
+
+    pub fn suggestions(&self) -> Vec<CorrectionSuggestion> {
+        self.pairs
+            .iter()
+            .filter(|pair| pair.count >= SUGGESTION_THRESHOLD && !pair.dismissed)
+            .map(CorrectionSuggestion::from)
+            .collect()
+    }
+
+    pub fn remove(&mut self, from: &str, to: &str) {
+        self.pairs
+            .retain(|pair| !(pair.from.eq_ignore_ascii_case(from) && pair.to == to));
+    }
+
+    pub fn dismiss(&mut self, from: &str, to: &str) {
+        for pair in self.pairs.iter_mut() {
+            if pair.from.eq_ignore_ascii_case(from) && pair.to == to {
+                pair.dismissed = true;
+            }
+        }
+    }
+}
+
+impl From<&CorrectionPair> for CorrectionSuggestion {
+    fn from(pair: &CorrectionPair) -> Self {
+        Self {
+            from: pair.from.clone(),
+            to: pair.to.clone(),
+            count: pair.count,
+        }
+    }
+}
+
+/// Reduces an edit to the span of words that changed; large rewrites are ignored.
+pub fn changed_phrase(original: &str, corrected: &str) -> Option<(String, String)> {
+    let a: Vec<&str> = original.split_whitespace().collect();
+    let b: Vec<&str> = corrected.split_whitespace().collect();
+
+    let prefix = a
+        .iter()
+        .zip(b.iter())
+        .take_while(|(left, right)| left == right)
+        .count();
+    let max_suffix = a.len().min(b.len()) - prefix;
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take(max_suffix)
+        .take_while(|(left, right)| left == right)
+        .count();
+
+    let from = &a[prefix..a.len() - suffix];
+    let to = &b[prefix..b.len() - suffix];
+    if from.is_empty() || to.is_empty() {
+        return None;
+    }
+    if from.len() > MAX_PHRASE_WORDS || to.len() > MAX_PHRASE_WORDS {
+        return None;
+    }
+    Some((from.join(" "), to.join(" ")))
+}
+
+pub fn corrections_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("missing base dirs")?;
+    let dir = dirs.data_local_dir().join("Whisperdict");
+    fs::create_dir_all(&dir).context("create data dir")?;
+    Ok(dir.join("corrections.json"))
+}
+
+pub fn load_store() -> Result<CorrectionStore> {
+    let path = corrections_path()?;
+    if !path.exists() {
+        return Ok(CorrectionStore::default());
+    }
+    let data = fs::read_to_string(&path).context("read corrections")?;
+    let store = serde_json::from_str(&data).context("parse corrections")?;
+    Ok(store)
+}
+
+pub fn save_store(store: &CorrectionStore) -> Result<()> {
+    let path = corrections_path()?;
+    let data = serde_json::to_string_pretty(store).context("serialize corrections")?;
+    fs::write(path, data).context("write corrections")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_phrase, CorrectionStore, SUGGESTION_THRESHOLD};
+
+    #[test]
+    fn extracts_changed_phrase() {
+        let pair = changed_phrase("I use whisper dict daily", "I use Whisperdict daily");
+        assert_eq!(
+            pair,
+            Some(("whisper dict".to_string(), "Whisperdict".to_string()))
+        );
+    }
+
+    #[test]
+    fn ignores_unchanged_and_rewritten_text() {
+        assert_eq!(changed_phrase("same text", "same text"), None);
+        assert_eq!(
+            changed_phrase(
+                "one two three four five six",
+                "completely different sentence with many words"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn suggests_after_threshold() {
+        let mut store = CorrectionStore::default();
+        for round in 1..SUGGESTION_THRESHOLD {
+            let reached = store.record(
+                "open whisper dict now",
+                "open Whisperdict now",
+                round as u64,
+            );
+            assert!(reached.is_empty());
+        }
+        let reached = store.record("whisper dict rocks", "Whisperdict rocks", 10);
+        assert_eq!(reached.len(), 1);
+        assert_eq!(reached[0].to, "Whisperdict");
+        assert_eq!(store.suggestions().len(), 1);
+
+        store.dismiss("whisper dict", "Whisperdict");
+        assert!(store.suggestions().is_empty());
+    }
+}
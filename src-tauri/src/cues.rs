@@ -0,0 +1,69 @@
+use crate::audio::{self, AudioBuffer};
+use anyhow::Result;
+use std::thread;
+
+const CUE_SAMPLE_RATE: u32 = 48_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    Start,
+    Stop,
+    Pasted,
+}
+
+/// (frequency in Hz, duration in ms) per note.
+fn notes(cue: Cue) -> &'static [(f32, u32)] {
+    match cue {
+        Cue::Start => &[(660.0, 70), (880.0, 90)],
+        Cue::Stop => &[(880.0, 70), (660.0, 90)],
+        Cue::Pasted => &[(1320.0, 45)],
+    }
+}
+
+/// Renders the cue as mono samples with short fades so notes do not click.
+pub fn render(cue: Cue, sample_rate: u32, volume: f32) -> Vec<f32> {
+    let volume = volume.clamp(0.0, 1.0);
+    let fade = (sample_rate / 200).max(1) as usize;
+    let mut samples = Vec::new();
+    for &(frequency, ms) in notes(cue) {
+        let len = (sample_rate as u64 * ms as u64 / 1000) as usize;
+        for i in 0..len {
+            let envelope = (i.min(len - 1 - i) as f32 / fade as f32).min(1.0);
+            let phase = 2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32;
+            samples.push(phase.sin() * envelope * volume);
+        }
+    }
+    samples
+}
+
+/// Plays `cue` on the default output device without blocking the caller.
+pub fn play(cue: Cue, volume: f32, host: &str) {
+    let host = host.to_string();
+    thread::spawn(move || {
+        if let Err(err) = play_blocking(cue, volume, &host) {
+            eprintln!("sound cue failed: {err}");
+        }
+    });
+}
+
+fn play_blocking(cue: Cue, volume: f32, host: &str) -> Result<()> {
+    let buffer = AudioBuffer {
+        samples: render(cue, CUE_SAMPLE_RATE, volume),
+        sample_rate: CUE_SAMPLE_RATE,
+    };
+    audio::play_buffer(buffer, host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Cue};
+
+    #[test]
+    fn cues_are_short_and_respect_volume() {
+        let samples = render(Cue::Start, 48_000, 0.5);
+        assert_eq!(samples.len(), 48_000 * 160 / 1000);
+        let peak = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        assert!(peak > 0.45 && peak <= 0.5);
+        assert!(render(Cue::Pasted, 48_000, 0.0).iter().all(|s| *s == 0.0));
+    }
+}
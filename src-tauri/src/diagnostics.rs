@@ -0,0 +1,200 @@
+use crate::supervisor::ComponentHealth;
+use crate::thermal::ThermalSnapshot;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceLatency {
+    pub device: String,
+    pub samples: u32,
+    pub last_ms: u64,
+    pub average_ms: u64,
+    pub max_ms: u64,
+}
+
+/// A transcription that ran past the slow-processing threshold.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowRun {
+    pub model_id: String,
+    pub recording_ms: u64,
+    pub processing_ms: u64,
+    pub cancelled: bool,
+    pub at: u64,
+}
+
+const MAX_SLOW_RUNS: usize = 20;
+
+/// Decode speed and thermal state of one transcription, to tell throttling apart from
+/// a slow model or backend when runs get slower over a session.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunTelemetry {
+    pub model_id: String,
+    pub audio_ms: u64,
+    pub decode_ms: u64,
+    pub tokens: u32,
+    pub tokens_per_second: f32,
+    /// Decode time over audio length; above 1.0 the model is slower than real time.
+    pub realtime_factor: f32,
+    pub thermal: ThermalSnapshot,
+    pub at: u64,
+}
+
+impl RunTelemetry {
+    pub fn new(
+        model_id: &str,
+        audio_ms: u64,
+        decode_ms: u64,
+        tokens: u32,
+        thermal: ThermalSnapshot,
+        at: u64,
+    ) -> Self {
+        let seconds = decode_ms as f32 / 1000.0;
+        Self {
+            model_id: model_id.to_string(),
+            audio_ms,
+            decode_ms,
+            tokens,
+            tokens_per_second: if seconds > 0.0 {
+                tokens as f32 / seconds
+            } else {
+                0.0
+            },
+            realtime_factor: if audio_ms > 0 {
+                decode_ms as f32 / audio_ms as f32
+            } else {
+                0.0
+            },
+            thermal,
+            at,
+        }
+    }
+}
+
+const MAX_RUNS: usize = 50;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub start_latency: Vec<DeviceLatency>,
+    pub slow_runs: Vec<SlowRun>,
+    pub runs: Vec<RunTelemetry>,
+    /// Supervised workers and any panics they recovered from.
+    pub components: Vec<ComponentHealth>,
+}
+
+impl Diagnostics {
+    pub fn record_start_latency(&mut self, device: &str, latency_ms: u64) {
+        match self
+            .start_latency
+            .iter_mut()
+            .find(|entry| entry.device == device)
+        {
+            Some(entry) => {
+                let total = entry.average_ms * entry.samples as u64 + latency_ms;
+                entry.samples = entry.samples.saturating_add(1);
+                entry.average_ms = total / entry.samples as u64;
+                entry.last_ms = latency_ms;
+                entry.max_ms = entry.max_ms.max(latency_ms);
+            }
+            None => self.start_latency.push(DeviceLatency {
+                device: device.to_string(),
+                samples: 1,
+                last_ms: latency_ms,
+                average_ms: latency_ms,
+                max_ms: latency_ms,
+            }),
+        }
+    }
+
+    pub fn record_slow_run(&mut self, run: SlowRun) {
+        self.slow_runs.push(run);
+        let excess = self.slow_runs.len().saturating_sub(MAX_SLOW_RUNS);
+        self.slow_runs.drain(..excess);
+    }
+
+    pub fn record_run(&mut self, mut run: RunTelemetry) {
+        let previous_events = self
+            .runs
+            .last()
+            .and_then(|last| last.thermal.cpu_throttle_events);
+        if let (None, Some(before), Some(now)) = (
+            run.thermal.cpu_throttled,
+            previous_events,
+            run.thermal.cpu_throttle_events,
+        ) {
+            run.thermal.cpu_throttled = Some(now > before);
+        }
+        self.runs.push(run);
+        let excess = self.runs.len().saturating_sub(MAX_RUNS);
+        self.runs.drain(..excess);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStats {
+    pub device: Option<String>,
+    pub start_latency_ms: Option<u64>,
+    pub recording_ms: u64,
+    pub transcription_ms: u64,
+    /// Audio lost because the recorder fell behind the device.
+    pub dropped_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostics, RunTelemetry, SlowRun, MAX_SLOW_RUNS};
+    use crate::thermal::ThermalSnapshot;
+
+    #[test]
+    fn tracks_latency_per_device() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.record_start_latency("USB Mic", 300);
+        diagnostics.record_start_latency("USB Mic", 100);
+        diagnostics.record_start_latency("Built-in", 20);
+
+        let usb = &diagnostics.start_latency[0];
+        assert_eq!(usb.samples, 2);
+        assert_eq!(usb.average_ms, 200);
+        assert_eq!(usb.last_ms, 100);
+        assert_eq!(usb.max_ms, 300);
+        assert_eq!(diagnostics.start_latency.len(), 2);
+    }
+
+    #[test]
+    fn keeps_only_recent_slow_runs() {
+        let mut diagnostics = Diagnostics::default();
+        for at in 0..MAX_SLOW_RUNS as u64 + 5 {
+            diagnostics.record_slow_run(SlowRun {
+                model_id: "base".to_string(),
+                recording_ms: 2_000,
+                processing_ms: 12_000,
+                cancelled: false,
+                at,
+            });
+        }
+        assert_eq!(diagnostics.slow_runs.len(), MAX_SLOW_RUNS);
+        assert_eq!(diagnostics.slow_runs[0].at, 5);
+    }
+
+    #[test]
+    fn flags_throttling_between_runs() {
+        let thermal = |events| ThermalSnapshot {
+            cpu_throttle_events: Some(events),
+            ..ThermalSnapshot::default()
+        };
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.record_run(RunTelemetry::new("base", 4_000, 2_000, 60, thermal(3), 1));
+        diagnostics.record_run(RunTelemetry::new("base", 4_000, 2_000, 60, thermal(3), 2));
+        diagnostics.record_run(RunTelemetry::new("base", 4_000, 8_000, 60, thermal(9), 3));
+
+        let runs = &diagnostics.runs;
+        assert_eq!(runs[0].thermal.cpu_throttled, None);
+        assert_eq!(runs[1].thermal.cpu_throttled, Some(false));
+        assert_eq!(runs[2].thermal.cpu_throttled, Some(true));
+        assert_eq!(runs[0].tokens_per_second, 30.0);
+        assert_eq!(runs[2].realtime_factor, 2.0);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::AppConfig;
+use crate::post_processing::ReplacementRule;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+const DICTIONARY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedReplacement {
+    pub from: String,
+    pub removed_at: u64,
+}
+
+/// Portable copy of the user dictionary: replacements and the vocabulary prompt.
+/// Deletions travel as tombstones so merging two copies converges regardless of which
+/// machine syncs first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DictionaryFile {
+    pub version: u32,
+    pub exported_at: u64,
+    pub replacements: Vec<ReplacementRule>,
+    pub removed_replacements: Vec<RemovedReplacement>,
+    pub initial_prompt: String,
+    pub initial_prompt_updated_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+pub fn set_replacement(config: &mut AppConfig, from: &str, to: &str, regex: bool, now: u64) {
+    config
+        .replacements
+        .retain(|rule| !rule.from.eq_ignore_ascii_case(from));
+    config
+        .removed_replacements
+        .retain(|removed| !removed.from.eq_ignore_ascii_case(from));
+    config.replacements.push(ReplacementRule {
+        from: from.to_string(),
+        to: to.to_string(),
+        updated_at: now,
+        regex,
+    });
+}
+
+pub fn remove_replacement(config: &mut AppConfig, from: &str, now: u64) {
+    config
+        .replacements
+        .retain(|rule| !rule.from.eq_ignore_ascii_case(from));
+    config
+        .removed_replacements
+        .retain(|removed| !removed.from.eq_ignore_ascii_case(from));
+    config.removed_replacements.push(RemovedReplacement {
+        from: from.to_string(),
+        removed_at: now,
+    });
+}
+
+pub fn set_initial_prompt(config: &mut AppConfig, prompt: String, now: u64) {
+    config.initial_prompt = prompt;
+    config.initial_prompt_updated_at = now;
+}
+
+pub fn export(config: &AppConfig, now: u64) -> DictionaryFile {
+    DictionaryFile {
+        version: DICTIONARY_VERSION,
+        exported_at: now,
+        replacements: config.replacements.clone(),
+        removed_replacements: config.removed_replacements.clone(),
+        initial_prompt: config.initial_prompt.clone(),
+        initial_prompt_updated_at: config.initial_prompt_updated_at,
+    }
+}
+
+/// Whether `incoming` replaces `local`: the later edit wins, and edits made in the same
+/// second are ordered by content so both machines pick the same one.
+fn wins(incoming: &ReplacementRule, local: &ReplacementRule) -> bool {
+    let key = |rule: &ReplacementRule| {
+        (
+            rule.updated_at,
+            rule.to.clone(),
+            rule.regex,
+            rule.from.clone(),
+        )
+    };
+    key(incoming) > key(local)
+}
+
+/// Last writer wins per phrase and for the prompt, comparing rule edits against
+/// tombstones by timestamp; a removal wins a tie with an edit.
+pub fn merge(config: &mut AppConfig, incoming: &DictionaryFile) -> MergeSummary {
+    let mut summary = MergeSummary::default();
+    for rule in &incoming.replacements {
+        let removed_locally = config.removed_replacements.iter().any(|removed| {
+            removed.from.eq_ignore_ascii_case(&rule.from) && removed.removed_at >= rule.updated_at
+        });
+        if removed_locally {
+            continue;
+        }
+        match config
+            .replacements
+            .iter_mut()
+            .find(|local| local.from.eq_ignore_ascii_case(&rule.from))
+        {
+            Some(local) if wins(rule, local) => {
+                if local != rule {
+                    summary.updated += 1;
+                }
+                *local = rule.clone();
+            }
+            Some(_) => {}
+            None => {
+                config
+                    .removed_replacements
+                    .retain(|removed| !removed.from.eq_ignore_ascii_case(&rule.from));
+                config.replacements.push(rule.clone());
+                summary.added += 1;
+            }
+        }
+    }
+
+    for removed in &incoming.removed_replacements {
+        let before = config.replacements.len();
+        config.replacements.retain(|rule| {
+            !(rule.from.eq_ignore_ascii_case(&removed.from)
+                && rule.updated_at <= removed.removed_at)
+        });
+        summary.removed += before - config.replacements.len();
+        match config
+            .removed_replacements
+            .iter_mut()
+            .find(|local| local.from.eq_ignore_ascii_case(&removed.from))
+        {
+            Some(local) => local.removed_at = local.removed_at.max(removed.removed_at),
+            None => config.removed_replacements.push(removed.clone()),
+        }
+    }
+
+    let prompt = |prompt: &str, at: u64| (at, prompt.to_string());
+    if prompt(&incoming.initial_prompt, incoming.initial_prompt_updated_at)
+        > prompt(&config.initial_prompt, config.initial_prompt_updated_at)
+    {
+        config.initial_prompt = incoming.initial_prompt.clone();
+        config.initial_prompt_updated_at = incoming.initial_prompt_updated_at;
+        summary.updated += 1;
+    }
+    summary
+}
+
+pub fn read_file(path: &Path) -> Result<DictionaryFile> {
+    let data = fs::read_to_string(path).context("read dictionary")?;
+    let file: DictionaryFile = serde_json::from_str(&data).context("parse dictionary")?;
+    if file.version > DICTIONARY_VERSION {
+        bail!("dictionary was written by a newer version of Whisperdict");
+    }
+    Ok(file)
+}
+
+pub fn write_file(path: &Path, file: &DictionaryFile) -> Result<()> {
+    let data = serde_json::to_string_pretty(file).context("serialize dictionary")?;
+    fs::write(path, data).context("write dictionary")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, merge, remove_replacement, set_initial_prompt, set_replacement};
+    use crate::config::AppConfig;
+
+    #[test]
+    fn merges_by_timestamp_in_both_directions() {
+        let mut laptop = AppConfig::default();
+        let mut desktop = AppConfig::default();
+        set_replacement(&mut laptop, "whisper dict", "Whisperdict", false, 10);
+        set_replacement(&mut laptop, "gonna", "going to", false, 10);
+        set_replacement(&mut desktop, "whisper dict", "WhisperDict", false, 20);
+        remove_replacement(&mut desktop, "gonna", 30);
+
+        merge(&mut laptop, &export(&desktop, 40));
+        merge(&mut desktop, &export(&laptop, 50));
+
+        for config in [&laptop, &desktop] {
+            assert_eq!(config.replacements.len(), 1);
+            assert_eq!(config.replacements[0].to, "WhisperDict");
+        }
+    }
+
+    #[test]
+    fn same_second_edits_and_prompts_converge() {
+        let mut laptop = AppConfig::default();
+        let mut desktop = AppConfig::default();
+        set_replacement(&mut laptop, "gonna", "going to", false, 10);
+        set_replacement(&mut desktop, "Gonna", "gonna", false, 10);
+        set_initial_prompt(&mut laptop, "Tauri, whisper.cpp".to_string(), 20);
+        set_initial_prompt(&mut desktop, "Kubernetes".to_string(), 20);
+
+        let from_laptop = export(&laptop, 30);
+        merge(&mut laptop, &export(&desktop, 30));
+        merge(&mut desktop, &from_laptop);
+
+        assert_eq!(laptop.replacements, desktop.replacements);
+        assert_eq!(laptop.initial_prompt, desktop.initial_prompt);
+        set_initial_prompt(&mut desktop, "Whisperdict".to_string(), 40);
+        merge(&mut laptop, &export(&desktop, 50));
+        assert_eq!(laptop.initial_prompt, "Whisperdict");
+    }
+
+    #[test]
+    fn newer_edit_beats_older_removal() {
+        let mut local = AppConfig::default();
+        remove_replacement(&mut local, "teh", 5);
+        let mut remote = AppConfig::default();
+        set_replacement(&mut remote, "teh", "the", false, 9);
+
+        let summary = merge(&mut local, &export(&remote, 10));
+        assert_eq!(summary.added, 1);
+        assert!(local.removed_replacements.is_empty());
+    }
+}
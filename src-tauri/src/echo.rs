@@ -0,0 +1,151 @@
+use crate::audio::{self, AudioBuffer};
+use std::collections::VecDeque;
+
+/// Cancellation runs at Whisper's rate so the filter stays cheap and the output needs
+/// no further resampling.
+const RATE: u32 = 16_000;
+/// Adaptive filter length (32 ms at 16 kHz) covering the room response after the bulk delay.
+const TAPS: usize = 512;
+/// Longest playback-to-microphone delay searched for before adapting.
+const MAX_DELAY_MS: usize = 400;
+/// Samples per envelope block used by the delay search.
+const BLOCK: usize = 16;
+const STEP_SIZE: f32 = 0.4;
+/// Adaptation pauses while the microphone is louder than this share of the recent
+/// playback peak, so the user's own voice does not train the filter away (Geigel).
+const DOUBLE_TALK: f32 = 0.6;
+
+/// Removes `reference` (what the speakers played) from `mic` with a normalized LMS
+/// filter aligned on the estimated playback delay. Both are brought to 16 kHz with
+/// `resampler`; this takes a while on long recordings, so call it off the async runtime.
+pub fn cancel_echo(mic: AudioBuffer, reference: AudioBuffer, resampler: &str) -> AudioBuffer {
+    let mic = audio::resample_for_whisper(mic, resampler);
+    if reference.samples.is_empty() || mic.samples.is_empty() {
+        return mic;
+    }
+    let reference = audio::resample_for_whisper(reference, resampler);
+    let delay = estimate_delay(&mic.samples, &reference.samples).saturating_sub(TAPS / 4);
+    AudioBuffer {
+        samples: nlms(&mic.samples, &reference.samples, delay),
+        sample_rate: RATE,
+    }
+}
+
+fn envelope(samples: &[f32]) -> Vec<f32> {
+    samples
+        .chunks(BLOCK)
+        .map(|block| block.iter().map(|s| s.abs()).sum::<f32>() / block.len() as f32)
+        .collect()
+}
+
+/// Lag (in samples) at which the reference envelope best matches the microphone's.
+fn estimate_delay(mic: &[f32], reference: &[f32]) -> usize {
+    let mic = envelope(mic);
+    let reference = envelope(reference);
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len().max(1) as f32;
+    let mic_mean = mean(&mic);
+    let reference_mean = mean(&reference);
+    let max_lag = MAX_DELAY_MS * RATE as usize / 1000 / BLOCK;
+    let mut best = (0, f32::MIN);
+    for lag in 0..=max_lag.min(mic.len().saturating_sub(1)) {
+        let score: f32 = mic[lag..]
+            .iter()
+            .zip(&reference)
+            .map(|(m, r)| (m - mic_mean) * (r - reference_mean))
+            .sum::<f32>()
+            / (mic.len() - lag) as f32;
+        if score > best.1 {
+            best = (lag, score);
+        }
+    }
+    best.0 * BLOCK
+}
+
+fn nlms(mic: &[f32], reference: &[f32], delay: usize) -> Vec<f32> {
+    // Weights are stored oldest tap first so they line up with `aligned[n..n + TAPS]`,
+    // the window ending at the current reference sample; no per-sample shifting.
+    let mut weights = vec![0.0f32; TAPS];
+    let mut aligned = vec![0.0f32; TAPS + mic.len()];
+    let start = (TAPS + delay).min(aligned.len());
+    let len = (aligned.len() - start).min(reference.len());
+    aligned[start..start + len].copy_from_slice(&reference[..len]);
+    // Indices into `aligned` of a decreasing run of magnitudes: the window's peak in O(1).
+    let mut peaks = VecDeque::new();
+    let mut power = 0.0f32;
+    let mut output = Vec::with_capacity(mic.len());
+    for (n, &sample) in mic.iter().enumerate() {
+        let newest = n + TAPS;
+        let incoming = aligned[newest];
+        let outgoing = aligned[n];
+        power = (power + incoming * incoming - outgoing * outgoing).max(0.0);
+        while peaks
+            .back()
+            .is_some_and(|&i: &usize| aligned[i].abs() <= incoming.abs())
+        {
+            peaks.pop_back();
+        }
+        peaks.push_back(newest);
+        if peaks.front().is_some_and(|&i| i <= n) {
+            peaks.pop_front();
+        }
+        let window = &aligned[n + 1..=newest];
+
+        let estimate: f32 = weights.iter().zip(window).map(|(w, x)| w * x).sum();
+        let error = sample - estimate;
+        output.push(error.clamp(-1.0, 1.0));
+
+        let far_peak = peaks.front().map_or(0.0, |&i| aligned[i].abs());
+        if power > 1e-6 && sample.abs() < DOUBLE_TALK * far_peak {
+            let gain = STEP_SIZE * error / (power + 1e-6);
+            for (w, x) in weights.iter_mut().zip(window) {
+                *w += gain * x;
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cancel_echo, RATE};
+    use crate::audio::{AudioBuffer, RESAMPLER_SINC};
+
+    fn energy(samples: &[f32]) -> f32 {
+        samples.iter().map(|s| s * s).sum()
+    }
+
+    #[test]
+    fn removes_delayed_playback_from_the_microphone() {
+        // Deterministic pseudo-random "music" so the filter has broadband input.
+        let mut seed = 0x1234_5678u32;
+        let reference: Vec<f32> = (0..RATE as usize * 4)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                (seed as f32 / u32::MAX as f32 - 0.5) * 0.4
+            })
+            .collect();
+        let delay = 1_600;
+        let mic: Vec<f32> = (0..reference.len())
+            .map(|n| {
+                let echo = |d: usize| n.checked_sub(d).map_or(0.0, |i| reference[i]);
+                0.5 * echo(delay) + 0.2 * echo(delay + 40)
+            })
+            .collect();
+        let cleaned = cancel_echo(
+            AudioBuffer {
+                samples: mic.clone(),
+                sample_rate: RATE,
+            },
+            AudioBuffer {
+                samples: reference,
+                sample_rate: RATE,
+            },
+            RESAMPLER_SINC,
+        );
+        let tail = mic.len() / 2..;
+        let reduction = energy(&mic[tail.clone()]) / energy(&cleaned.samples[tail]);
+        assert!(reduction > 100.0, "echo only reduced {reduction}x");
+    }
+}
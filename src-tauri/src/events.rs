@@ -0,0 +1,218 @@
+use crate::config::ConfigStore;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// Suffix marking a string field cut short by `max_event_string_len`.
+const TRUNCATED: &str = "…";
+
+/// Events delivered whole: the transcript is what the user dictated, not a log line.
+const UNTRUNCATED_EVENTS: &[&str] = &["transcription:result"];
+
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Emit(Value),
+    /// Held as the pending payload; the caller schedules a flush after the delay.
+    Schedule(Duration),
+    /// Replaced the payload already waiting for a scheduled flush.
+    Coalesced,
+}
+
+#[derive(Default)]
+struct Slot {
+    last: Option<Instant>,
+    pending: Option<Value>,
+}
+
+impl Slot {
+    fn offer(&mut self, payload: Value, interval: Duration, now: Instant) -> Decision {
+        if self.pending.is_some() {
+            self.pending = Some(payload);
+            return Decision::Coalesced;
+        }
+        match self.last {
+            Some(last) if now.duration_since(last) < interval => {
+                self.pending = Some(payload);
+                Decision::Schedule(interval - now.duration_since(last))
+            }
+            _ => {
+                self.last = Some(now);
+                Decision::Emit(payload)
+            }
+        }
+    }
+}
+
+/// Throttles events to the webview per name and entity (`event_throttle_ms`), always
+/// delivering the latest payload of a burst, and truncates long strings in payloads.
+#[derive(Clone)]
+pub struct EventBus {
+    config: ConfigStore,
+    slots: Arc<Mutex<HashMap<String, Slot>>>,
+}
+
+impl EventBus {
+    pub fn new(config: ConfigStore) -> Self {
+        Self {
+            config,
+            slots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn emit<S: Serialize>(&self, app: &AppHandle, event: &str, payload: S) {
+        let config = self.config.snapshot();
+        let Ok(mut payload) = serde_json::to_value(payload) else {
+            return;
+        };
+        if !UNTRUNCATED_EVENTS.contains(&event) {
+            truncate_strings(&mut payload, config.max_event_string_len);
+        }
+        let interval = config
+            .event_throttle_ms
+            .get(event)
+            .map(|ms| Duration::from_millis(*ms))
+            .unwrap_or_default();
+        if interval.is_zero() {
+            let _ = app.emit(event, payload);
+            return;
+        }
+        let key = slot_key(event, &payload);
+        if is_terminal(&payload) {
+            // The update it closes must not be flushed after it.
+            if let Some(slot) = self.slots.lock().unwrap().get_mut(&key) {
+                slot.pending = None;
+                slot.last = Some(Instant::now());
+            }
+            let _ = app.emit(event, payload);
+            return;
+        }
+        let decision = self
+            .slots
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .offer(payload, interval, Instant::now());
+        match decision {
+            Decision::Emit(payload) => {
+                let _ = app.emit(event, payload);
+            }
+            Decision::Schedule(delay) => {
+                let bus = self.clone();
+                let app = app.clone();
+                let event = event.to_string();
+                tauri::async_runtime::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    bus.flush(&app, &event, &key);
+                });
+            }
+            Decision::Coalesced => {}
+        }
+    }
+
+    fn flush(&self, app: &AppHandle, event: &str, key: &str) {
+        let pending = {
+            let mut slots = self.slots.lock().unwrap();
+            let Some(slot) = slots.get_mut(key) else {
+                return;
+            };
+            slot.last = Some(Instant::now());
+            slot.pending.take()
+        };
+        if let Some(payload) = pending {
+            let _ = app.emit(event, payload);
+        }
+    }
+}
+
+/// Throttle slot of a payload: progress of two downloads must not replace each other.
+fn slot_key(event: &str, payload: &Value) -> String {
+    match payload.get("modelId").and_then(Value::as_str) {
+        Some(id) => format!("{event}\u{0}{id}"),
+        None => event.to_string(),
+    }
+}
+
+/// Whether the payload ends what the event reports on, so it is never coalesced away.
+fn is_terminal(payload: &Value) -> bool {
+    payload.get("done").and_then(Value::as_bool) == Some(true)
+        || payload.get("error").is_some_and(|error| !error.is_null())
+}
+
+/// Shortens every string in `value` to at most `max_chars` characters; zero disables it.
+fn truncate_strings(value: &mut Value, max_chars: usize) {
+    if max_chars == 0 {
+        return;
+    }
+    match value {
+        Value::String(text) => {
+            if let Some((cut, _)) = text.char_indices().nth(max_chars) {
+                text.truncate(cut);
+                text.push_str(TRUNCATED);
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| truncate_strings(item, max_chars)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| truncate_strings(field, max_chars)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_terminal, slot_key, truncate_strings, Decision, Slot};
+    use serde_json::json;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn coalesces_bursts_into_one_trailing_emit() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(100);
+        let mut slot = Slot::default();
+        assert_eq!(
+            slot.offer(json!(1), interval, start),
+            Decision::Emit(json!(1))
+        );
+        assert_eq!(
+            slot.offer(json!(2), interval, start + Duration::from_millis(30)),
+            Decision::Schedule(Duration::from_millis(70))
+        );
+        assert_eq!(
+            slot.offer(json!(3), interval, start + Duration::from_millis(60)),
+            Decision::Coalesced
+        );
+        assert_eq!(slot.pending, Some(json!(3)));
+    }
+
+    #[test]
+    fn throttles_per_entity_and_never_holds_back_the_end() {
+        let tiny = json!({ "modelId": "tiny", "done": false, "error": null });
+        let base = json!({ "modelId": "base", "done": false, "error": null });
+        assert_ne!(
+            slot_key("models:progress", &tiny),
+            slot_key("models:progress", &base)
+        );
+        assert_eq!(slot_key("audio:level", &json!(0.5)), "audio:level");
+        assert!(!is_terminal(&tiny));
+        assert!(is_terminal(&json!({ "modelId": "tiny", "done": true })));
+        assert!(is_terminal(
+            &json!({ "modelId": "tiny", "error": "disk full" })
+        ));
+    }
+
+    #[test]
+    fn truncates_nested_strings_on_char_boundaries() {
+        let mut value = json!({ "text": "héllo wörld", "segments": [{ "text": "ok" }] });
+        truncate_strings(&mut value, 4);
+        assert_eq!(
+            value,
+            json!({ "text": "héll…", "segments": [{ "text": "ok" }] })
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Serialize;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use std::process::Command;
+
+/// The window that has keyboard focus: its application (process name, or window class
+/// on X11) and title.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusedWindow {
+    pub app: String,
+    pub title: String,
+}
+
+/// The application on the first line, the title on the second.
+#[cfg(any(test, target_os = "macos", target_os = "linux"))]
+fn parse(output: &str) -> Option<FocusedWindow> {
+    let mut lines = output.lines();
+    let app = lines.next()?.trim().to_string();
+    let title = lines.next().unwrap_or_default().trim().to_string();
+    (!app.is_empty() || !title.is_empty()).then_some(FocusedWindow { app, title })
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The focused window, where the platform lets us ask; Wayland compositors generally do
+/// not. The title is only read when `title` asks for it, and is empty otherwise. This may
+/// shell out, so call it off the async runtime.
+///
+/// Asking System Events for the title needs the user's Automation permission, so only
+/// a `title` lookup does; the app comes from `lsappinfo`, which needs none.
+#[cfg(target_os = "macos")]
+pub fn focused(title: bool) -> Option<FocusedWindow> {
+    if !title {
+        let front = command_output("lsappinfo", &["front"])?;
+        let info = command_output("lsappinfo", &["info", "-only", "name", front.trim()])?;
+        let app = info.split_once('=')?.1.trim().trim_matches('"').to_string();
+        return (!app.is_empty()).then_some(FocusedWindow {
+            app,
+            title: String::new(),
+        });
+    }
+    let output = command_output(
+        "osascript",
+        &[
+            "-e",
+            "tell application \"System Events\"",
+            "-e",
+            "set p to first process whose frontmost is true",
+            "-e",
+            "set t to \"\"",
+            "-e",
+            "try",
+            "-e",
+            "set t to name of front window of p",
+            "-e",
+            "end try",
+            "-e",
+            "return (name of p) & linefeed & t",
+            "-e",
+            "end tell",
+        ],
+    )?;
+    parse(&output)
+}
+
+/// The foreground window's own title and its process's executable name.
+#[cfg(target_os = "windows")]
+pub fn focused(title: bool) -> Option<FocusedWindow> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::PathBuf;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId,
+    };
+
+    unsafe {
+        let window = GetForegroundWindow();
+        if window.is_null() {
+            return None;
+        }
+        let title = if title {
+            let mut text = [0u16; 512];
+            let len = GetWindowTextW(window, text.as_mut_ptr(), text.len() as i32);
+            String::from_utf16_lossy(&text[..len.max(0) as usize])
+        } else {
+            String::new()
+        };
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(window, &mut pid);
+        let mut app = String::new();
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if !process.is_null() {
+            let mut path = [0u16; 1024];
+            let mut size = path.len() as u32;
+            if QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut size)
+                != 0
+            {
+                let path = PathBuf::from(OsString::from_wide(&path[..size as usize]));
+                if let Some(stem) = path.file_stem() {
+                    app = stem.to_string_lossy().into_owned();
+                }
+            }
+            CloseHandle(process);
+        }
+        (!app.is_empty() || !title.is_empty()).then_some(FocusedWindow { app, title })
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn focused(title: bool) -> Option<FocusedWindow> {
+    if std::env::var("WAYLAND_DISPLAY").is_ok() && std::env::var("DISPLAY").is_err() {
+        return None;
+    }
+    let xdotool = which::which("xdotool").ok()?;
+    let args: &[&str] = if title {
+        &["getactivewindow", "getwindowclassname", "getwindowname"]
+    } else {
+        &["getactivewindow", "getwindowclassname"]
+    };
+    let output = command_output(xdotool.to_str()?, args)?;
+    parse(&output)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+pub fn focused(_title: bool) -> Option<FocusedWindow> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, FocusedWindow};
+
+    #[test]
+    fn reads_app_and_title_lines() {
+        assert_eq!(
+            parse("Code\nmain.rs - whisperdict - Visual Studio Code\n"),
+            Some(FocusedWindow {
+                app: "Code".to_string(),
+                title: "main.rs - whisperdict - Visual Studio Code".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("Finder\n").map(|window| window.title),
+            Some(String::new())
+        );
+        assert_eq!(parse(""), None);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::quota::QuotaPolicy;
+
 pub const CHECKOUT_ENDPOINT: &str =
     "https://n8n.icordoba.dev/webhook/whisperdict/polar/create-checkout";
 
@@ -5,6 +7,10 @@ pub const CHECKOUT_BEARER_TOKEN: Option<&str> = None;
 
 pub const LICENSE_ISSUER: &str = "whisperdict";
 
+/// How the free tier is counted, set at build time (e.g. `monthly:20`, `duration:600`);
+/// the license server must be configured with the same policy.
+const FREE_QUOTA: Option<&str> = option_env!("WHISPERDICT_FREE_QUOTA");
+
 const BUNDLED_LICENSE_PUBLIC_KEY: &str =
     include_str!("../keys/whisperdict_license_public_kid1.pem");
 
@@ -32,3 +38,9 @@ pub fn trusted_license_public_keys() -> Vec<String> {
         vec![key.to_string()]
     }
 }
+
+pub fn free_quota() -> QuotaPolicy {
+    FREE_QUOTA
+        .and_then(QuotaPolicy::parse)
+        .unwrap_or(QuotaPolicy::Lifetime)
+}
@@ -0,0 +1,256 @@
+use crate::transcription::Segment;
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub text: String,
+    pub model_id: String,
+    pub language: String,
+    pub created_at: u64,
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    /// Set once the background job has re-run the entry through a larger model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<Revision>,
+    /// Label from the tag shortcut that started the dictation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    pub model_id: String,
+    pub previous_text: String,
+    pub changes: Vec<TextChange>,
+    pub revised_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Same,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextChange {
+    pub kind: ChangeKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+}
+
+/// Hands out history ids: the creation time in milliseconds, bumped past the previous
+/// id so two dictations finishing in the same millisecond never share one.
+#[derive(Debug, Default)]
+pub struct IdCounter(AtomicU64);
+
+impl IdCounter {
+    pub fn after(entries: &[HistoryEntry]) -> Self {
+        Self(AtomicU64::new(
+            entries.iter().map(|entry| entry.id).max().unwrap_or(0),
+        ))
+    }
+
+    pub fn next(&self, now_ms: u64) -> u64 {
+        let id = |last: u64| now_ms.max(last + 1);
+        let last = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(id(last)))
+            .unwrap_or_default();
+        id(last)
+    }
+}
+
+pub fn history_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("missing base dirs")?;
+    let dir = dirs.data_local_dir().join("Whisperdict");
+    fs::create_dir_all(&dir).context("create data dir")?;
+    Ok(dir.join("history.jsonl"))
+}
+
+pub fn load_history() -> Result<Vec<HistoryEntry>> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = fs::File::open(&path).context("open history")?;
+    let entries = BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+    Ok(entries)
+}
+
+pub fn append_entry(entry: &HistoryEntry) -> Result<()> {
+    let path = history_path()?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("open history")?;
+    let line = serde_json::to_string(entry).context("serialize history entry")?;
+    writeln!(file, "{line}").context("write history entry")?;
+    Ok(())
+}
+
+pub fn rewrite_history(entries: &[HistoryEntry]) -> Result<()> {
+    let path = history_path()?;
+    let mut data = String::new();
+    for entry in entries {
+        data.push_str(&serde_json::to_string(entry).context("serialize history entry")?);
+        data.push('\n');
+    }
+    fs::write(path, data).context("write history")?;
+    Ok(())
+}
+
+/// Tags compare case-insensitively; `None` matches every entry.
+fn has_tag(entry: &HistoryEntry, tag: Option<&str>) -> bool {
+    match tag.map(str::trim).filter(|tag| !tag.is_empty()) {
+        Some(tag) => entry
+            .tag
+            .as_deref()
+            .is_some_and(|own| own.eq_ignore_ascii_case(tag)),
+        None => true,
+    }
+}
+
+/// Writes the entries carrying `tag` as a JSON array, oldest first; returns how many.
+pub fn export(path: &Path, entries: &[HistoryEntry], tag: Option<&str>) -> Result<usize> {
+    let selected: Vec<&HistoryEntry> = entries.iter().filter(|entry| has_tag(entry, tag)).collect();
+    let data = serde_json::to_string_pretty(&selected).context("serialize history")?;
+    fs::write(path, data).context("write history export")?;
+    Ok(selected.len())
+}
+
+pub fn paginate(
+    entries: &[HistoryEntry],
+    query: Option<&str>,
+    tag: Option<&str>,
+    page: usize,
+    page_size: usize,
+) -> HistoryPage {
+    let page_size = if page_size == 0 {
+        DEFAULT_PAGE_SIZE
+    } else {
+        page_size
+    };
+    let needle = query
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty());
+    let matches: Vec<&HistoryEntry> = entries
+        .iter()
+        .rev()
+        .filter(|entry| has_tag(entry, tag))
+        .filter(|entry| match needle.as_deref() {
+            Some(needle) => entry.text.to_lowercase().contains(needle),
+            None => true,
+        })
+        .collect();
+    let total = matches.len();
+    let entries = matches
+        .into_iter()
+        .skip(page.saturating_mul(page_size))
+        .take(page_size)
+        .cloned()
+        .collect();
+    HistoryPage {
+        entries,
+        total,
+        page,
+        page_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{paginate, HistoryEntry, IdCounter};
+
+    fn entry(id: u64, text: &str) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            text: text.to_string(),
+            model_id: "base".to_string(),
+            language: "en".to_string(),
+            created_at: id,
+            duration_ms: 100,
+            segments: Vec::new(),
+            revision: None,
+            tag: (id > 3).then(|| "Work".to_string()),
+        }
+    }
+
+    #[test]
+    fn ids_stay_unique_within_a_millisecond_and_after_loading() {
+        let ids = IdCounter::after(&[entry(1_000, "a"), entry(1_005, "b")]);
+        assert_eq!(ids.next(900), 1_006);
+        assert_eq!(ids.next(2_000), 2_000);
+        assert_eq!(ids.next(2_000), 2_001);
+        assert_eq!(ids.next(2_000), 2_002);
+        assert_eq!(ids.next(3_000), 3_000);
+    }
+
+    #[test]
+    fn paginates_newest_first_and_filters() {
+        let entries: Vec<HistoryEntry> = (1..=5)
+            .map(|id| {
+                entry(
+                    id,
+                    if id % 2 == 0 {
+                        "Meeting notes"
+                    } else {
+                        "hello"
+                    },
+                )
+            })
+            .collect();
+
+        let page = paginate(&entries, None, None, 0, 2);
+        assert_eq!(page.total, 5);
+        assert_eq!(
+            page.entries.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![5, 4]
+        );
+
+        let page = paginate(&entries, Some("meeting"), None, 0, 10);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.entries[0].id, 4);
+
+        let page = paginate(&entries, None, Some("work"), 0, 10);
+        assert_eq!(
+            page.entries.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![5, 4]
+        );
+    }
+
+    #[test]
+    fn entries_without_segments_still_load() {
+        let line =
+            r#"{"id":1,"text":"hi","modelId":"base","language":"en","createdAt":1,"durationMs":5}"#;
+        let entry: HistoryEntry = serde_json::from_str(line).unwrap();
+        assert!(entry.segments.is_empty());
+    }
+}
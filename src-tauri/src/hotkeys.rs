@@ -1,11 +1,11 @@
 use crate::app_state::AppState;
+use crate::supervisor::Supervisor;
 use anyhow::Result;
 use rdev::{listen, Event, EventType, Key};
 use std::sync::{Arc, Mutex};
-use std::thread;
 use tauri::{AppHandle, Manager};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Hotkey {
     pub ctrl: bool,
     pub alt: bool,
@@ -63,6 +63,13 @@ impl Hotkey {
             key,
         })
     }
+
+    fn matches(&self, key: Key, mods: &Modifiers) -> bool {
+        self.key == key
+            && self.ctrl == mods.ctrl
+            && self.alt == mods.alt
+            && self.shift == mods.shift
+    }
 }
 
 #[derive(Default)]
@@ -72,11 +79,22 @@ struct Modifiers {
     shift: bool,
 }
 
-pub fn start_listener(app: AppHandle, hotkey: Arc<Mutex<Hotkey>>) -> Result<()> {
-    thread::spawn(move || {
+pub fn start_listener(
+    app: AppHandle,
+    hotkey: Arc<Mutex<Hotkey>>,
+    incognito_hotkey: Arc<Mutex<Option<Hotkey>>>,
+    quick_hotkey: Arc<Mutex<Option<Hotkey>>>,
+    tag_hotkeys: Arc<Mutex<Vec<(Hotkey, String)>>>,
+    supervisor: &Supervisor,
+) -> Result<()> {
+    supervisor.spawn_thread("hotkeys", move || {
+        let app = app.clone();
         let modifiers = Arc::new(Mutex::new(Modifiers::default()));
         let mods_ref = modifiers.clone();
         let hotkey_ref = hotkey.clone();
+        let incognito_ref = incognito_hotkey.clone();
+        let quick_ref = quick_hotkey.clone();
+        let tags_ref = tag_hotkeys.clone();
 
         let callback = move |event: Event| {
             if let Ok(mut mods) = mods_ref.lock() {
@@ -84,12 +102,30 @@ pub fn start_listener(app: AppHandle, hotkey: Arc<Mutex<Hotkey>>) -> Result<()>
                     EventType::KeyPress(key) => {
                         update_mods(key, true, &mut mods);
                         let current = hotkey_ref.lock().ok().map(|h| h.clone());
-                        if let Some(hotkey) = current {
-                            if hotkey.key == key
-                                && hotkey.ctrl == mods.ctrl
-                                && hotkey.alt == mods.alt
-                                && hotkey.shift == mods.shift
-                            {
+                        let incognito = incognito_ref.lock().ok().and_then(|h| h.clone());
+                        let quick = quick_ref.lock().ok().and_then(|h| h.clone());
+                        let tag = tags_ref.lock().ok().and_then(|tags| {
+                            tags.iter()
+                                .find(|(hotkey, _)| hotkey.matches(key, &mods))
+                                .map(|(_, tag)| tag.clone())
+                        });
+                        if incognito.is_some_and(|hotkey| hotkey.matches(key, &mods)) {
+                            let state = app.state::<AppState>();
+                            state.toggle_incognito(&app);
+                        } else if quick.is_some_and(|hotkey| hotkey.matches(key, &mods)) {
+                            let app_handle = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let state = app_handle.state::<AppState>();
+                                let _ = state.toggle_quick(&app_handle).await;
+                            });
+                        } else if let Some(tag) = tag {
+                            let app_handle = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let state = app_handle.state::<AppState>();
+                                let _ = state.toggle_tagged(&app_handle, tag).await;
+                            });
+                        } else if let Some(hotkey) = current {
+                            if hotkey.matches(key, &mods) {
                                 let app_handle = app.clone();
                                 tauri::async_runtime::spawn(async move {
                                     let state = app_handle.state::<AppState>();
@@ -0,0 +1,222 @@
+use crate::models::{ModelStatus, MULTILINGUAL, TINYDIARIZE};
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Weight of the newest run in the moving averages.
+const SMOOTHING: f32 = 0.2;
+/// Dictation length assumed until a few have been measured.
+const DEFAULT_AUDIO_MS: f32 = 8_000.0;
+
+/// Decode speed of one model and decoding mode on this machine.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Speed {
+    pub samples: u32,
+    /// Moving average of decode time over audio length.
+    pub realtime_factor: f32,
+}
+
+/// Measured speeds kept across launches, so the latency budget can pick a model without
+/// benchmarking first. Numbers from another machine or backend are thrown away.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Benchmarks {
+    pub hardware: String,
+    /// Moving average of dictation length.
+    pub typical_audio_ms: f32,
+    /// Keyed by `run_key`.
+    pub runs: BTreeMap<String, Speed>,
+}
+
+/// What the budget settled on: the model to use and whether beam search has to give way
+/// to greedy decoding to fit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPick {
+    pub model_id: String,
+    pub greedy: bool,
+    pub predicted_ms: u64,
+    /// No run of this model was measured yet; the prediction is scaled from another one.
+    pub estimated: bool,
+}
+
+fn run_key(model_id: &str, greedy: bool) -> String {
+    format!("{model_id}:{}", if greedy { "greedy" } else { "beam" })
+}
+
+/// Identifies the hardware the numbers hold for: CPU architecture and core count plus the
+/// configured compute backend.
+pub fn hardware_fingerprint(backend: &str) -> String {
+    let cores = std::thread::available_parallelism()
+        .map(|cores| cores.get())
+        .unwrap_or(1);
+    format!("{}/{cores}/{backend}", std::env::consts::ARCH)
+}
+
+impl Benchmarks {
+    pub fn for_hardware(mut self, hardware: &str) -> Self {
+        if self.hardware != hardware {
+            self = Self {
+                hardware: hardware.to_string(),
+                ..Self::default()
+            };
+        }
+        self
+    }
+
+    pub fn record(&mut self, model_id: &str, greedy: bool, audio_ms: u64, decode_ms: u64) {
+        if audio_ms == 0 {
+            return;
+        }
+        let factor = decode_ms as f32 / audio_ms as f32;
+        self.runs
+            .entry(run_key(model_id, greedy))
+            .and_modify(|speed| {
+                speed.samples = speed.samples.saturating_add(1);
+                speed.realtime_factor += (factor - speed.realtime_factor) * SMOOTHING;
+            })
+            .or_insert(Speed {
+                samples: 1,
+                realtime_factor: factor,
+            });
+        self.typical_audio_ms = if self.typical_audio_ms > 0.0 {
+            self.typical_audio_ms + (audio_ms as f32 - self.typical_audio_ms) * SMOOTHING
+        } else {
+            audio_ms as f32
+        };
+    }
+
+    /// Expected decode time of a typical dictation, and whether it had to be scaled from
+    /// the measured model closest in size.
+    fn predict(
+        &self,
+        model: &ModelStatus,
+        greedy: bool,
+        models: &[ModelStatus],
+    ) -> Option<(f32, bool)> {
+        let audio_ms = if self.typical_audio_ms > 0.0 {
+            self.typical_audio_ms
+        } else {
+            DEFAULT_AUDIO_MS
+        };
+        if let Some(speed) = self.runs.get(&run_key(&model.id, greedy)) {
+            return Some((speed.realtime_factor * audio_ms, false));
+        }
+        let (reference, speed) = models
+            .iter()
+            .filter_map(|other| Some((other, self.runs.get(&run_key(&other.id, greedy))?)))
+            .min_by_key(|(other, _)| other.size_mb.abs_diff(model.size_mb))?;
+        let scale = model.size_mb as f32 / reference.size_mb.max(1) as f32;
+        Some((speed.realtime_factor * scale * audio_ms, true))
+    }
+
+    /// The largest installed model that fits `budget_ms` for dictation in `language`,
+    /// keeping beam search (`beam`) when that fits too. Falls back to the fastest
+    /// prediction when nothing fits, and `None` without any measurements.
+    pub fn pick(
+        &self,
+        budget_ms: u64,
+        beam: bool,
+        language: &str,
+        models: &[ModelStatus],
+    ) -> Option<LatencyPick> {
+        let mut candidates: Vec<&ModelStatus> = models
+            .iter()
+            .filter(|model| model.installed && model.tags.family != TINYDIARIZE)
+            .filter(|model| model.tags.language == MULTILINGUAL || model.tags.language == language)
+            .collect();
+        candidates.sort_by_key(|model| std::cmp::Reverse(model.size_mb));
+        let modes: &[bool] = if beam { &[false, true] } else { &[true] };
+        let mut fastest: Option<LatencyPick> = None;
+        for model in candidates {
+            for &greedy in modes {
+                let Some((predicted, estimated)) = self.predict(model, greedy, models) else {
+                    continue;
+                };
+                let pick = LatencyPick {
+                    model_id: model.id.clone(),
+                    greedy,
+                    predicted_ms: predicted.round() as u64,
+                    estimated,
+                };
+                if pick.predicted_ms <= budget_ms {
+                    return Some(pick);
+                }
+                if fastest
+                    .as_ref()
+                    .map_or(true, |fastest| pick.predicted_ms < fastest.predicted_ms)
+                {
+                    fastest = Some(pick);
+                }
+            }
+        }
+        fastest
+    }
+}
+
+pub fn benchmarks_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("missing base dirs")?;
+    let dir = dirs.data_local_dir().join("Whisperdict");
+    fs::create_dir_all(&dir).context("create data dir")?;
+    Ok(dir.join("benchmarks.json"))
+}
+
+pub fn load_benchmarks() -> Result<Benchmarks> {
+    let path = benchmarks_path()?;
+    if !path.exists() {
+        return Ok(Benchmarks::default());
+    }
+    let data = fs::read_to_string(&path).context("read benchmarks")?;
+    serde_json::from_str(&data).context("parse benchmarks")
+}
+
+pub fn save_benchmarks(benchmarks: &Benchmarks) -> Result<()> {
+    let data = serde_json::to_string_pretty(benchmarks).context("serialize benchmarks")?;
+    fs::write(benchmarks_path()?, data).context("write benchmarks")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Benchmarks;
+    use crate::models::{ModelStatus, ModelTags, MULTILINGUAL};
+
+    #[test]
+    fn picks_the_largest_model_within_budget() {
+        let models: Vec<ModelStatus> = [("base", 142), ("small", 466), ("medium", 1460)]
+            .into_iter()
+            .map(|(id, size_mb)| ModelStatus {
+                id: id.to_string(),
+                size_mb,
+                installed: true,
+                partial: false,
+                tags: ModelTags {
+                    family: "whisper",
+                    language: MULTILINGUAL,
+                    domain: "general",
+                    quantization: "f16",
+                },
+                variant_of: None,
+                name: None,
+            })
+            .collect();
+        let mut benchmarks = Benchmarks::default().for_hardware("test");
+        assert!(benchmarks.pick(2_000, true, "es", &models).is_none());
+
+        benchmarks.record("base", false, 10_000, 1_000);
+        benchmarks.record("small", false, 10_000, 2_500);
+        benchmarks.record("small", true, 10_000, 1_800);
+        let pick = benchmarks.pick(2_000, true, "es", &models).unwrap();
+        assert_eq!((pick.model_id.as_str(), pick.greedy), ("small", true));
+        assert!(!pick.estimated);
+
+        let pick = benchmarks.pick(100_000, true, "es", &models).unwrap();
+        assert_eq!(pick.model_id, "medium");
+        assert!(pick.estimated);
+        let pick = benchmarks.pick(10, false, "es", &models).unwrap();
+        assert_eq!(pick.model_id, "base");
+    }
+}
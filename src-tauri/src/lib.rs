@@ -1,20 +1,58 @@
+mod app_rules;
 mod app_state;
 mod audio;
+mod child_protocol;
+mod child_socket;
 mod child_transcribe;
 mod command_errors;
+mod compressed;
+mod compute;
 mod config;
+mod corrections;
+mod cues;
+mod diagnostics;
+mod dictionary;
+mod echo;
+mod events;
+mod focused_window;
 mod global_config;
+mod history;
 mod hotkeys;
+mod latency;
 mod licensing;
+mod local_api;
+mod managed_config;
+mod memory;
+mod migration;
 mod models;
+mod normalize;
 mod paste;
+mod post_processing;
+mod proxy;
+mod quota;
 mod recording;
+mod retention;
+mod retranscribe;
+mod sandbox;
+mod schedule;
+mod self_test;
+mod speech;
+mod streaming;
+mod subtitles;
+mod supervisor;
+mod thermal;
 mod transcription;
 mod tray;
+mod usage;
 mod wayland_hotkeys;
+mod wayland_input;
+mod windows;
 
 use app_state::{AppState, StatusResponse};
+use config::AppConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use tauri::{image::Image, AppHandle, Manager, State};
 use tauri_plugin_updater::UpdaterExt;
 
@@ -29,49 +67,698 @@ struct ModelState {
     installed: bool,
     partial: bool,
     active: bool,
+    tags: models::ModelTags,
+    variant_of: Option<&'static str>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ConfigState {
     shortcut: String,
+    incognito_shortcut: String,
+    quick_model: String,
+    quick_shortcut: String,
+    tag_shortcuts: BTreeMap<String, String>,
     active_model_id: String,
     language: String,
     free_transcriptions_left: u32,
     total_transcriptions_count: u64,
+    free_audio_ms_used: u64,
+    quota_period: Option<u32>,
     entitlement: String,
     license_status: String,
     license_file_path: Option<String>,
     license_last_validated_at: Option<u64>,
+    vad_auto_stop: bool,
+    vad_silence_ms: u64,
+    vad_threshold: f32,
+    sandbox_transcriber: bool,
+    local_api: bool,
+    local_api_port: u16,
+    local_api_token: String,
+    in_process_transcription: bool,
+    transcription_timeout_secs: u64,
+    download_connections: u32,
+    proxy_url: String,
+    compute_backend: String,
+    model_compute: BTreeMap<String, models::ModelCompute>,
+    resampler: String,
+    output_mode: String,
+    clipboard_guard: String,
+    restore_clipboard: bool,
+    paste_keys: String,
+    append_after: String,
+    bidi_marks: String,
+    read_aloud: String,
+    streaming_partials: bool,
+    event_throttle_ms: BTreeMap<String, u64>,
+    max_event_string_len: usize,
+    gain_mode: String,
+    low_latency: bool,
+    max_recording_secs: u64,
+    preroll_enabled: bool,
+    preroll_ms: u64,
+    capture_source: String,
+    input_channels: BTreeMap<String, u16>,
+    avoid_bluetooth_input: bool,
+    echo_cancellation: bool,
+    compress_recording: bool,
+    presentation_mode: bool,
+    audio_host: String,
+    beam_size: u32,
+    max_latency_ms: u64,
+    latency_pick: Option<latency::LatencyPick>,
+    cue_on_start: bool,
+    cue_on_stop: bool,
+    cue_on_paste: bool,
+    cue_volume: f32,
+    temperature: f32,
+    temperature_inc: f32,
+    entropy_threshold: f32,
+    logprob_threshold: f32,
+    transcription_threads: u32,
+    advanced_decoding: transcription::AdvancedDecoding,
+    initial_prompt: String,
+    word_timestamps: bool,
+    diarize: bool,
+    restore_punctuation: bool,
+    remove_fillers: bool,
+    spoken_formatting: Vec<String>,
+    profanity_filter: post_processing::ProfanityFilter,
+    keep_recordings: bool,
+    recordings_dir: Option<String>,
+    recordings_keep_count: u32,
+    recordings_keep_days: u32,
+    pinned_languages: Vec<String>,
+    tray_middle_click_action: String,
+    start_hidden: bool,
+    monitor_input: bool,
+    monitor_volume: f32,
+    schedule: schedule::ScheduleConfig,
+    retranscribe: retranscribe::RetranscribeConfig,
+}
+
+impl From<&AppConfig> for ConfigState {
+    fn from(config: &AppConfig) -> Self {
+        Self {
+            shortcut: config.shortcut.clone(),
+            incognito_shortcut: config.incognito_shortcut.clone(),
+            quick_model: config.quick_model.clone(),
+            quick_shortcut: config.quick_shortcut.clone(),
+            tag_shortcuts: config.tag_shortcuts.clone(),
+            active_model_id: config.active_model.clone(),
+            language: config.language.clone(),
+            free_transcriptions_left: config.free_transcriptions_left,
+            total_transcriptions_count: config.total_transcriptions_count,
+            free_audio_ms_used: config.free_audio_ms_used,
+            quota_period: config.quota_period,
+            entitlement: config.entitlement.clone(),
+            license_status: config.license_status.clone(),
+            license_file_path: config.license_file_path.clone(),
+            license_last_validated_at: config.license_last_validated_at,
+            vad_auto_stop: config.vad_auto_stop,
+            vad_silence_ms: config.vad_silence_ms,
+            vad_threshold: config.vad_threshold,
+            sandbox_transcriber: config.sandbox_transcriber,
+            local_api: config.local_api,
+            local_api_port: config.local_api_port,
+            local_api_token: config.local_api_token.clone(),
+            in_process_transcription: config.in_process_transcription,
+            transcription_timeout_secs: config.transcription_timeout_secs,
+            download_connections: config.download_connections,
+            proxy_url: config.proxy_url.clone(),
+            compute_backend: config.compute_backend.clone(),
+            model_compute: config.model_compute.clone(),
+            resampler: config.resampler.clone(),
+            output_mode: config.output_mode.clone(),
+            clipboard_guard: config.clipboard_guard.clone(),
+            restore_clipboard: config.restore_clipboard,
+            paste_keys: config.paste_keys.clone(),
+            append_after: config.append_after.clone(),
+            bidi_marks: config.bidi_marks.clone(),
+            read_aloud: config.read_aloud.clone(),
+            streaming_partials: config.streaming_partials,
+            event_throttle_ms: config.event_throttle_ms.clone(),
+            max_event_string_len: config.max_event_string_len,
+            gain_mode: config.gain_mode.clone(),
+            low_latency: config.low_latency,
+            max_recording_secs: config.max_recording_secs,
+            preroll_enabled: config.preroll_enabled,
+            preroll_ms: config.preroll_ms,
+            capture_source: config.capture_source.clone(),
+            input_channels: config.input_channels.clone(),
+            avoid_bluetooth_input: config.avoid_bluetooth_input,
+            echo_cancellation: config.echo_cancellation,
+            compress_recording: config.compress_recording,
+            presentation_mode: config.presentation_mode,
+            audio_host: config.audio_host.clone(),
+            beam_size: config.beam_size,
+            max_latency_ms: config.max_latency_ms,
+            latency_pick: config.latency_pick.clone(),
+            cue_on_start: config.cue_on_start,
+            cue_on_stop: config.cue_on_stop,
+            cue_on_paste: config.cue_on_paste,
+            cue_volume: config.cue_volume,
+            temperature: config.temperature,
+            temperature_inc: config.temperature_inc,
+            entropy_threshold: config.entropy_threshold,
+            logprob_threshold: config.logprob_threshold,
+            transcription_threads: config.transcription_threads,
+            advanced_decoding: config.advanced_decoding.clone(),
+            initial_prompt: config.initial_prompt.clone(),
+            word_timestamps: config.word_timestamps,
+            diarize: config.diarize,
+            restore_punctuation: config.restore_punctuation,
+            remove_fillers: config.remove_fillers,
+            spoken_formatting: config.spoken_formatting.clone(),
+            profanity_filter: config.profanity_filter.clone(),
+            keep_recordings: config.keep_recordings,
+            recordings_dir: config.recordings_dir.clone(),
+            recordings_keep_count: config.recordings_keep_count,
+            recordings_keep_days: config.recordings_keep_days,
+            pinned_languages: config.pinned_languages.clone(),
+            tray_middle_click_action: config.tray_middle_click_action.clone(),
+            start_hidden: config.start_hidden,
+            monitor_input: config.monitor_input,
+            monitor_volume: config.monitor_volume,
+            schedule: config.schedule.clone(),
+            retranscribe: config.retranscribe.clone(),
+        }
+    }
+}
+
+#[tauri::command]
+fn get_config(state: State<'_, AppState>) -> Result<ConfigState, String> {
+    let config = state.get_settings().map_err(command_errors::map_error)?;
+    Ok(ConfigState::from(&config))
+}
+
+#[tauri::command]
+fn set_shortcut(state: State<'_, AppState>, shortcut: String) -> Result<(), String> {
+    state
+        .set_shortcut(&shortcut)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_quick_model(state: State<'_, AppState>, model_id: String) -> Result<(), String> {
+    state
+        .set_quick_model(&model_id)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_quick_shortcut(state: State<'_, AppState>, shortcut: String) -> Result<(), String> {
+    state
+        .set_quick_shortcut(&shortcut)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_incognito_shortcut(state: State<'_, AppState>, shortcut: String) -> Result<(), String> {
+    state
+        .set_incognito_shortcut(&shortcut)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_tag_shortcuts(
+    state: State<'_, AppState>,
+    shortcuts: BTreeMap<String, String>,
+) -> Result<(), String> {
+    state
+        .set_tag_shortcuts(shortcuts)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_incognito(state: State<'_, AppState>, app: AppHandle, enabled: bool) {
+    state.set_incognito(&app, enabled);
+}
+
+#[tauri::command]
+fn set_language(state: State<'_, AppState>, language: String) -> Result<(), String> {
+    state
+        .set_language(&language)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_pinned_languages(state: State<'_, AppState>, languages: Vec<String>) -> Result<(), String> {
+    state
+        .set_pinned_languages(languages)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn cycle_language(state: State<'_, AppState>, step: Option<i32>) -> Result<String, String> {
+    state
+        .cycle_language(step.unwrap_or(1))
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_tray_middle_click_action(state: State<'_, AppState>, action: String) -> Result<(), String> {
+    state
+        .set_tray_middle_click_action(&action)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_vad_auto_stop(
+    state: State<'_, AppState>,
+    enabled: bool,
+    silence_ms: Option<u64>,
+    threshold: Option<f32>,
+) -> Result<(), String> {
+    state
+        .set_vad_auto_stop(enabled, silence_ms, threshold)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_local_api(
+    state: State<'_, AppState>,
+    enabled: bool,
+    port: Option<u16>,
+) -> Result<(), String> {
+    state
+        .set_local_api(enabled, port)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_sandbox_transcriber(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_sandbox_transcriber(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn list_gpus(state: State<'_, AppState>) -> Result<compute::ComputeReport, String> {
+    state
+        .compute_report()
+        .await
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn list_compute_backends(state: State<'_, AppState>) -> Vec<compute::ComputeBackend> {
+    state.list_compute_backends()
+}
+
+#[tauri::command]
+fn set_compute_backend(state: State<'_, AppState>, backend: String) -> Result<(), String> {
+    state
+        .set_compute_backend(&backend)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_model_compute(
+    state: State<'_, AppState>,
+    model_id: String,
+    compute: Option<models::ModelCompute>,
+) -> Result<(), String> {
+    state
+        .set_model_compute(&model_id, compute)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_download_connections(state: State<'_, AppState>, connections: u32) -> Result<(), String> {
+    state
+        .set_download_connections(connections)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_proxy(state: State<'_, AppState>, url: String) -> Result<(), String> {
+    state.set_proxy(&url).map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_transcription_timeout(state: State<'_, AppState>, secs: u64) -> Result<(), String> {
+    state
+        .set_transcription_timeout(secs)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_in_process_transcription(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_in_process_transcription(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_output_mode(state: State<'_, AppState>, mode: String) -> Result<(), String> {
+    state
+        .set_output_mode(&mode)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_event_limits(
+    state: State<'_, AppState>,
+    throttle_ms: BTreeMap<String, u64>,
+    max_string_len: usize,
+) -> Result<(), String> {
+    state
+        .set_event_limits(throttle_ms, max_string_len)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_streaming_partials(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_streaming_partials(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_read_aloud(state: State<'_, AppState>, mode: String) -> Result<(), String> {
+    state
+        .set_read_aloud(&mode)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_clipboard_guard(state: State<'_, AppState>, mode: String) -> Result<(), String> {
+    state
+        .set_clipboard_guard(&mode)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_paste_keys(state: State<'_, AppState>, keys: String) -> Result<(), String> {
+    state
+        .set_paste_keys(&keys)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_append_after(state: State<'_, AppState>, append: String) -> Result<(), String> {
+    state
+        .set_append_after(&append)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_restore_clipboard(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_restore_clipboard(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_bidi_marks(state: State<'_, AppState>, mode: String) -> Result<(), String> {
+    state
+        .set_bidi_marks(&mode)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_gain_mode(state: State<'_, AppState>, mode: String) -> Result<(), String> {
+    state
+        .set_gain_mode(&mode)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_preroll(
+    state: State<'_, AppState>,
+    enabled: bool,
+    preroll_ms: Option<u64>,
+) -> Result<(), String> {
+    state
+        .set_preroll(enabled, preroll_ms)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn get_input_device(state: State<'_, AppState>) -> Result<audio::InputDevice, String> {
+    state.get_input_device().map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_input_channel(
+    state: State<'_, AppState>,
+    device: String,
+    channel: Option<u16>,
+) -> Result<(), String> {
+    state
+        .set_input_channel(&device, channel)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_avoid_bluetooth_input(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_avoid_bluetooth_input(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_presentation_mode(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_presentation_mode(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_compress_recording(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_compress_recording(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_echo_cancellation(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_echo_cancellation(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_decoding_params(
+    state: State<'_, AppState>,
+    beam_size: u32,
+    temperature: f32,
+) -> Result<(), String> {
+    state
+        .set_decoding_params(beam_size, temperature)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_transcription_threads(state: State<'_, AppState>, threads: u32) -> Result<(), String> {
+    state
+        .set_transcription_threads(threads)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_advanced_decoding(
+    state: State<'_, AppState>,
+    advanced: transcription::AdvancedDecoding,
+) -> Result<(), String> {
+    state
+        .set_advanced_decoding(advanced)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_fallback_params(
+    state: State<'_, AppState>,
+    temperature_inc: f32,
+    entropy_threshold: f32,
+    logprob_threshold: f32,
+) -> Result<(), String> {
+    state
+        .set_fallback_params(temperature_inc, entropy_threshold, logprob_threshold)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_schedule(
+    state: State<'_, AppState>,
+    schedule: schedule::ScheduleConfig,
+) -> Result<(), String> {
+    state
+        .set_schedule(schedule)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_retranscription(
+    state: State<'_, AppState>,
+    retranscribe: retranscribe::RetranscribeConfig,
+) -> Result<(), String> {
+    state
+        .set_retranscription(retranscribe)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_profanity_filter(
+    state: State<'_, AppState>,
+    filter: post_processing::ProfanityFilter,
+) -> Result<(), String> {
+    state
+        .set_profanity_filter(filter)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_max_latency(state: State<'_, AppState>, max_latency_ms: u64) -> Result<(), String> {
+    state
+        .set_max_latency(max_latency_ms)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_spoken_formatting(state: State<'_, AppState>, languages: Vec<String>) -> Result<(), String> {
+    state
+        .set_spoken_formatting(languages)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_remove_fillers(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_remove_fillers(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_restore_punctuation(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_restore_punctuation(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_diarize(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_diarize(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_word_timestamps(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_word_timestamps(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_initial_prompt(state: State<'_, AppState>, prompt: String) -> Result<(), String> {
+    state
+        .set_initial_prompt(&prompt)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_sound_cues(
+    state: State<'_, AppState>,
+    on_start: bool,
+    on_stop: bool,
+    on_paste: bool,
+    volume: Option<f32>,
+) -> Result<(), String> {
+    state
+        .set_sound_cues(on_start, on_stop, on_paste, volume)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn test_microphone(
+    state: State<'_, AppState>,
+    playback: Option<bool>,
+) -> Result<app_state::MicrophoneTest, String> {
+    state
+        .test_microphone(playback.unwrap_or(false))
+        .await
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn self_test(state: State<'_, AppState>) -> Result<self_test::SelfTestReport, String> {
+    state.self_test().await.map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn list_audio_hosts(state: State<'_, AppState>) -> Vec<audio::AudioHost> {
+    state.list_audio_hosts()
+}
+
+#[tauri::command]
+fn set_audio_host(state: State<'_, AppState>, host: String) -> Result<(), String> {
+    state
+        .set_audio_host(&host)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_capture_source(state: State<'_, AppState>, source: String) -> Result<(), String> {
+    state
+        .set_capture_source(&source)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_recording_retention(
+    state: State<'_, AppState>,
+    enabled: bool,
+    dir: Option<String>,
+    keep_count: Option<u32>,
+    keep_days: Option<u32>,
+) -> Result<(), String> {
+    state
+        .set_recording_retention(enabled, dir, keep_count, keep_days)
+        .map_err(command_errors::map_error)
 }
 
 #[tauri::command]
-fn get_config(state: State<'_, AppState>) -> Result<ConfigState, String> {
-    let config = state.get_settings().map_err(command_errors::map_error)?;
-    Ok(ConfigState {
-        shortcut: config.shortcut,
-        active_model_id: config.active_model,
-        language: config.language,
-        free_transcriptions_left: config.free_transcriptions_left,
-        total_transcriptions_count: config.total_transcriptions_count,
-        entitlement: config.entitlement,
-        license_status: config.license_status,
-        license_file_path: config.license_file_path,
-        license_last_validated_at: config.license_last_validated_at,
-    })
+fn set_input_monitoring(
+    state: State<'_, AppState>,
+    enabled: bool,
+    volume: Option<f32>,
+) -> Result<(), String> {
+    state
+        .set_input_monitoring(enabled, volume)
+        .map_err(command_errors::map_error)
 }
 
 #[tauri::command]
-fn set_shortcut(state: State<'_, AppState>, shortcut: String) -> Result<(), String> {
+fn set_max_recording_secs(state: State<'_, AppState>, seconds: u64) -> Result<(), String> {
     state
-        .set_shortcut(&shortcut)
+        .set_max_recording_secs(seconds)
         .map_err(command_errors::map_error)
 }
 
 #[tauri::command]
-fn set_language(state: State<'_, AppState>, language: String) -> Result<(), String> {
+fn get_quota(state: State<'_, AppState>) -> quota::QuotaState {
+    state.get_quota()
+}
+
+#[tauri::command]
+fn set_low_latency(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
     state
-        .set_language(&language)
+        .set_low_latency(enabled)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn get_diagnostics(state: State<'_, AppState>) -> diagnostics::Diagnostics {
+    state.get_diagnostics()
+}
+
+#[tauri::command]
+fn set_resampler(state: State<'_, AppState>, resampler: String) -> Result<(), String> {
+    state
+        .set_resampler(&resampler)
         .map_err(command_errors::map_error)
 }
 
@@ -105,10 +792,16 @@ fn get_device_mac_address() -> String {
 
 #[tauri::command]
 async fn create_checkout_session() -> Result<CheckoutSession, String> {
+    if managed_config::policy().disable_checkout {
+        return Err(command_errors::map_error(
+            command_errors::CommandError::checkout_disabled().into(),
+        ));
+    }
     let endpoint = global_config::checkout_endpoint()
         .ok_or_else(|| "Checkout endpoint is not configured".to_string())?;
 
-    let client = reqwest::Client::builder()
+    let client = proxy::client_builder()
+        .map_err(|error| error.to_string())?
         .connect_timeout(std::time::Duration::from_secs(10))
         .timeout(std::time::Duration::from_secs(20))
         .build()
@@ -151,10 +844,11 @@ async fn create_checkout_session() -> Result<CheckoutSession, String> {
 #[tauri::command]
 fn import_license_file(
     state: State<'_, AppState>,
+    app: AppHandle,
     path: String,
 ) -> Result<licensing::LicenseImportResponse, String> {
     state
-        .import_license_file(&path)
+        .import_license_file(&app, &path)
         .map_err(command_errors::map_error)
 }
 
@@ -164,11 +858,270 @@ fn get_license_state(state: State<'_, AppState>) -> Result<licensing::LicenseSta
 }
 
 #[tauri::command]
-fn remove_license(state: State<'_, AppState>) -> Result<(), String> {
-    state.remove_license().map_err(command_errors::map_error)
+fn remove_license(state: State<'_, AppState>, app: AppHandle) -> Result<(), String> {
+    state
+        .remove_license(&app)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn record_correction(
+    state: State<'_, AppState>,
+    app: AppHandle,
+    original: String,
+    corrected: String,
+) -> Result<Vec<corrections::CorrectionSuggestion>, String> {
+    state
+        .record_correction(&app, &original, &corrected)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn list_correction_suggestions(
+    state: State<'_, AppState>,
+) -> Result<Vec<corrections::CorrectionSuggestion>, String> {
+    state
+        .list_correction_suggestions()
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn accept_correction_suggestion(
+    state: State<'_, AppState>,
+    from: String,
+    to: String,
+) -> Result<(), String> {
+    state
+        .accept_correction_suggestion(&from, &to)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn list_replacements(state: State<'_, AppState>) -> Vec<post_processing::ReplacementRule> {
+    state.list_replacements()
+}
+
+#[tauri::command]
+fn set_replacement(
+    state: State<'_, AppState>,
+    from: String,
+    to: String,
+    regex: Option<bool>,
+) -> Result<(), String> {
+    state
+        .set_replacement(&from, &to, regex.unwrap_or(false))
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn remove_replacement(state: State<'_, AppState>, from: String) -> Result<(), String> {
+    state
+        .remove_replacement(&from)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn list_app_rules(state: State<'_, AppState>) -> Vec<app_rules::AppRule> {
+    state.list_app_rules()
+}
+
+#[tauri::command]
+fn set_app_rule(state: State<'_, AppState>, rule: app_rules::AppRule) -> Result<(), String> {
+    state.set_app_rule(rule).map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn get_focused_window(
+    state: State<'_, AppState>,
+    title: bool,
+) -> Result<Option<focused_window::FocusedWindow>, String> {
+    Ok(state.focused_window(title).await)
+}
+
+#[tauri::command]
+fn remove_app_rule(
+    state: State<'_, AppState>,
+    app: String,
+    title: Option<String>,
+) -> Result<(), String> {
+    state
+        .remove_app_rule(&app, title.as_deref().unwrap_or_default())
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn export_dictionary(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    state
+        .export_dictionary(&path)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn import_dictionary(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<dictionary::MergeSummary, String> {
+    state
+        .import_dictionary(&path)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn sync_dictionary(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<dictionary::MergeSummary, String> {
+    state
+        .sync_dictionary(&path)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn dismiss_correction_suggestion(
+    state: State<'_, AppState>,
+    from: String,
+    to: String,
+) -> Result<(), String> {
+    state
+        .dismiss_correction_suggestion(&from, &to)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn list_history(
+    state: State<'_, AppState>,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<history::HistoryPage, String> {
+    state
+        .list_history(None, None, page.unwrap_or(0), page_size.unwrap_or(0))
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn search_history(
+    state: State<'_, AppState>,
+    query: String,
+    tag: Option<String>,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<history::HistoryPage, String> {
+    state
+        .list_history(
+            Some(&query),
+            tag.as_deref(),
+            page.unwrap_or(0),
+            page_size.unwrap_or(0),
+        )
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn export_history(
+    state: State<'_, AppState>,
+    path: String,
+    tag: Option<String>,
+) -> Result<usize, String> {
+    state
+        .export_history(&path, tag.as_deref())
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn get_last_transcription(state: State<'_, AppState>) -> Option<app_state::LastTranscription> {
+    state.get_last_transcription()
+}
+
+#[tauri::command]
+fn copy_last_transcription(state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .copy_last_transcription()
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn copy_history_segment(state: State<'_, AppState>, id: u64, index: usize) -> Result<(), String> {
+    state
+        .copy_history_segment(id, index)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn paste_history_segment(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: u64,
+    index: usize,
+) -> Result<(), String> {
+    state
+        .paste_history_segment(&app, id, index)
+        .await
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn delete_history_entry(state: State<'_, AppState>, id: u64) -> Result<(), String> {
+    state
+        .delete_history_entry(id)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn clear_history(state: State<'_, AppState>) -> Result<(), String> {
+    state.clear_history().map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn open_history_window(app: AppHandle) -> Result<(), String> {
+    windows::show_history_window(&app).map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn show_main_window(app: AppHandle) -> Result<(), String> {
+    windows::show_main_window(&app).map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn hide_main_window(app: AppHandle) -> Result<(), String> {
+    windows::hide_main_window(&app).map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn set_start_hidden(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .set_start_hidden(enabled)
+        .map_err(command_errors::map_error)
+}
+
+/// Returns a report only when legacy ECO files were found, so the event fires once.
+fn migrate_legacy_files() -> Option<migration::MigrationReport> {
+    let result = config::config_path().and_then(|path| {
+        let config_dir = path
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .unwrap_or_default();
+        migration::migrate_legacy(&config_dir, &models::models_dir()?)
+    });
+    match result {
+        Ok(report) if report.is_empty() => None,
+        Ok(report) => {
+            eprintln!(
+                "legacy migration: moved {:?}, deleted {:?}, failed {:?}",
+                report.migrated, report.deleted, report.failed
+            );
+            Some(report)
+        }
+        Err(err) => {
+            eprintln!("legacy migration failed: {err:#}");
+            None
+        }
+    }
 }
 
 async fn check_for_updates(app: AppHandle) {
+    if managed_config::policy().disable_update_checks {
+        return;
+    }
     let mut updater = app.updater_builder();
 
     if let Some(pubkey) = UPDATER_PUBKEY {
@@ -204,9 +1157,12 @@ async fn check_for_updates(app: AppHandle) {
 }
 
 #[tauri::command]
-async fn list_models(state: State<'_, AppState>) -> Result<Vec<ModelState>, String> {
+async fn list_models(
+    state: State<'_, AppState>,
+    filter: Option<models::ModelFilter>,
+) -> Result<Vec<ModelState>, String> {
     let response = state
-        .list_models()
+        .list_models(&filter.unwrap_or_default())
         .await
         .map_err(command_errors::map_error)?;
     Ok(response
@@ -214,24 +1170,109 @@ async fn list_models(state: State<'_, AppState>) -> Result<Vec<ModelState>, Stri
         .into_iter()
         .map(|model| ModelState {
             id: model.id.clone(),
-            title: model.id[..1].to_uppercase() + &model.id[1..],
             size_mb: model.size_mb,
             installed: model.installed,
             partial: model.partial,
+            title: model
+                .name
+                .unwrap_or_else(|| model.id[..1].to_uppercase() + &model.id[1..]),
             active: model.id == response.active_model,
+            tags: model.tags,
+            variant_of: model.variant_of,
         })
         .collect())
 }
 
+#[tauri::command]
+async fn transcribe_file(
+    state: State<'_, AppState>,
+    app: AppHandle,
+    path: String,
+    format: String,
+    max_line_len: Option<usize>,
+) -> Result<String, String> {
+    state
+        .transcribe_file(&app, Path::new(&path), &format, max_line_len)
+        .await
+        .map(|output| output.to_string_lossy().into_owned())
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn retranscribe_last(
+    state: State<'_, AppState>,
+    app: AppHandle,
+    model_id: Option<String>,
+) -> Result<String, String> {
+    state
+        .retranscribe_last(&app, model_id)
+        .await
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn preload_model(
+    state: State<'_, AppState>,
+    app: AppHandle,
+    download: bool,
+) -> Result<(), String> {
+    state
+        .preload_transcribe_server(&app, download)
+        .await
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn cancel_preload(state: State<'_, AppState>) {
+    state.cancel_preload();
+}
+
+#[tauri::command]
+fn cancel_processing(state: State<'_, AppState>) -> bool {
+    state.cancel_processing()
+}
+
 #[tauri::command]
 async fn download_model(
     state: State<'_, AppState>,
     app: AppHandle,
     id: String,
-) -> Result<(), String> {
+    quantization: Option<String>,
+) -> Result<String, String> {
+    let id = match quantization.as_deref() {
+        Some(quantization) => models::variant_id(&id, quantization)
+            .ok_or_else(|| format!("no {quantization} build of {id}"))?
+            .to_string(),
+        None => id,
+    };
     state
         .download_model(&app, &id)
         .await
+        .map_err(command_errors::map_error)?;
+    Ok(id)
+}
+
+#[tauri::command]
+async fn import_model(
+    state: State<'_, AppState>,
+    path: String,
+    display_name: String,
+) -> Result<String, String> {
+    state
+        .import_model(PathBuf::from(path), display_name)
+        .await
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+async fn repair_model(
+    state: State<'_, AppState>,
+    app: AppHandle,
+    id: String,
+) -> Result<models::RepairOutcome, String> {
+    state
+        .repair_model(&app, &id)
+        .await
         .map_err(command_errors::map_error)
 }
 
@@ -244,21 +1285,38 @@ async fn delete_model(state: State<'_, AppState>, id: String) -> Result<(), Stri
 }
 
 #[tauri::command]
-fn set_active_model(state: State<'_, AppState>, app: AppHandle, id: String) -> Result<(), String> {
+async fn set_active_model(
+    state: State<'_, AppState>,
+    app: AppHandle,
+    id: String,
+) -> Result<(), String> {
     state
         .set_active_model(&id)
+        .await
         .map_err(command_errors::map_error)?;
     let handle = app.clone();
     tauri::async_runtime::spawn(async move {
         let state = handle.state::<AppState>();
-        let _ = state.preload_transcribe_server(&handle).await;
+        let _ = state.preload_transcribe_server(&handle, false).await;
     });
     Ok(())
 }
 
 #[tauri::command]
-async fn toggle_recording(state: State<'_, AppState>, app: AppHandle) -> Result<(), String> {
+async fn toggle_recording(
+    state: State<'_, AppState>,
+    app: AppHandle,
+    initial_prompt: Option<String>,
+    tag: Option<String>,
+) -> Result<(), String> {
     let recording = state.status().recording;
+    // Starting always replaces the override so one left by an earlier call cannot leak in.
+    if !recording || initial_prompt.is_some() {
+        state.set_prompt_override(initial_prompt);
+    }
+    if !recording {
+        state.set_dictation_tag(tag);
+    }
     if recording {
         state
             .stop_recording(&app)
@@ -272,6 +1330,20 @@ async fn toggle_recording(state: State<'_, AppState>, app: AppHandle) -> Result<
     }
 }
 
+#[tauri::command]
+fn pause_recording(state: State<'_, AppState>, app: AppHandle) -> Result<(), String> {
+    state
+        .pause_recording(&app)
+        .map_err(command_errors::map_error)
+}
+
+#[tauri::command]
+fn resume_recording(state: State<'_, AppState>, app: AppHandle) -> Result<(), String> {
+    state
+        .resume_recording(&app)
+        .map_err(command_errors::map_error)
+}
+
 #[tauri::command]
 fn get_status(state: State<'_, AppState>) -> Result<StatusResponse, String> {
     Ok(state.status())
@@ -293,45 +1365,211 @@ pub fn run() {
             }
         })
         .setup(|app| {
+            let migration = migrate_legacy_files();
             let state = AppState::new(app.handle()).map_err(command_errors::map_error)?;
+            state
+                .tray
+                .set_presentation(state.config.snapshot().presentation_mode);
             state.tray.init(app.handle());
+            state.start_schedule(app.handle());
+            state.start_retranscription(app.handle());
+            state.start_local_api(app.handle());
             let hotkey = state.hotkey.clone();
+            let incognito_hotkey = state.incognito_hotkey.clone();
+            let quick_hotkey = state.quick_hotkey.clone();
+            let tag_hotkeys = state.tag_hotkeys.clone();
             let handle = app.handle().clone();
-            let _ = hotkeys::start_listener(handle, hotkey);
+            let _ = hotkeys::start_listener(
+                handle,
+                hotkey,
+                incognito_hotkey,
+                quick_hotkey,
+                tag_hotkeys,
+                &state.supervisor,
+            );
             app.manage(state);
-            if let Some(window) = app.get_webview_window("main") {
+            if let Some(window) = app.get_webview_window(windows::MAIN_WINDOW) {
                 if let Ok(icon) = Image::from_bytes(include_bytes!("../icons-app/32x32.png")) {
                     let _ = window.set_icon(icon);
                 }
             }
+            let start_hidden = app.state::<AppState>().config.snapshot().start_hidden
+                || std::env::args().any(|arg| arg == windows::HIDDEN_FLAG);
+            if !start_hidden {
+                let _ = windows::show_main_window(app.handle());
+            }
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 let state = handle.state::<AppState>();
-                let _ = state.preload_transcribe_server(&handle).await;
+                let _ = state.preload_transcribe_server(&handle, false).await;
+                state.preload_quick_model();
             });
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 check_for_updates(handle).await;
             });
+            let handle = app.handle().clone();
+            let mut changes = handle.state::<AppState>().config.subscribe();
+            let events = handle.state::<AppState>().events.clone();
+            tauri::async_runtime::spawn(async move {
+                while changes.changed().await.is_ok() {
+                    let config = ConfigState::from(&**changes.borrow_and_update());
+                    events.emit(&handle, "config:changed", config);
+                }
+            });
+            if let Some(error) = managed_config::load_error() {
+                let handle = app.handle().clone();
+                handle.state::<AppState>().events.emit(
+                    &handle,
+                    "managed-config:invalid",
+                    serde_json::json!({ "error": error }),
+                );
+            }
+            if let Some(report) = migration {
+                let handle = app.handle().clone();
+                handle
+                    .state::<AppState>()
+                    .events
+                    .emit(&handle, "migration:completed", report);
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_config,
             set_shortcut,
+            set_incognito_shortcut,
+            set_quick_model,
+            set_quick_shortcut,
+            set_incognito,
+            set_tag_shortcuts,
             set_language,
+            set_vad_auto_stop,
+            set_pinned_languages,
+            cycle_language,
+            set_tray_middle_click_action,
+            set_sandbox_transcriber,
+            set_local_api,
+            set_in_process_transcription,
+            set_transcription_timeout,
+            set_download_connections,
+            set_proxy,
+            list_compute_backends,
+            list_gpus,
+            set_compute_backend,
+            set_model_compute,
+            set_resampler,
+            set_output_mode,
+            set_clipboard_guard,
+            set_restore_clipboard,
+            set_paste_keys,
+            set_append_after,
+            set_bidi_marks,
+            set_read_aloud,
+            set_streaming_partials,
+            set_event_limits,
+            set_gain_mode,
+            set_low_latency,
+            set_max_recording_secs,
+            set_preroll,
+            set_capture_source,
+            get_input_device,
+            set_input_channel,
+            set_avoid_bluetooth_input,
+            set_echo_cancellation,
+            set_compress_recording,
+            set_presentation_mode,
+            test_microphone,
+            self_test,
+            list_audio_hosts,
+            set_audio_host,
+            set_decoding_params,
+            set_fallback_params,
+            set_transcription_threads,
+            set_advanced_decoding,
+            set_schedule,
+            set_retranscription,
+            set_initial_prompt,
+            set_word_timestamps,
+            set_diarize,
+            set_restore_punctuation,
+            set_remove_fillers,
+            set_spoken_formatting,
+            set_max_latency,
+            set_profanity_filter,
+            set_sound_cues,
+            set_recording_retention,
+            set_input_monitoring,
+            get_diagnostics,
+            get_quota,
             create_checkout_session,
             import_license_file,
             get_license_state,
             remove_license,
             list_models,
             download_model,
+            import_model,
+            preload_model,
+            retranscribe_last,
+            transcribe_file,
+            cancel_preload,
+            cancel_processing,
             delete_model,
+            repair_model,
             set_active_model,
             toggle_recording,
-            get_status
+            get_status,
+            pause_recording,
+            resume_recording,
+            record_correction,
+            list_correction_suggestions,
+            accept_correction_suggestion,
+            dismiss_correction_suggestion,
+            list_replacements,
+            set_replacement,
+            remove_replacement,
+            list_app_rules,
+            set_app_rule,
+            remove_app_rule,
+            get_focused_window,
+            export_dictionary,
+            import_dictionary,
+            sync_dictionary,
+            list_history,
+            search_history,
+            export_history,
+            get_last_transcription,
+            copy_last_transcription,
+            copy_history_segment,
+            paste_history_segment,
+            delete_history_entry,
+            clear_history,
+            open_history_window,
+            show_main_window,
+            hide_main_window,
+            set_start_hidden
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running Whisperdict");
+        .build(tauri::generate_context!())
+        .expect("error while building Whisperdict")
+        .run(|app, event| match event {
+            tauri::RunEvent::ExitRequested { api, .. } => {
+                let Some(state) = app.try_state::<AppState>() else {
+                    return;
+                };
+                // The first request holds the exit until in-flight work is flushed; the
+                // `exit` after that goes through.
+                if state.begin_shutdown() {
+                    api.prevent_exit();
+                    let handle = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        handle.state::<AppState>().shutdown(&handle).await;
+                        handle.exit(0);
+                    });
+                }
+            }
+            // However the app ends, no transcriber outlives it.
+            tauri::RunEvent::Exit => app_state::terminate_transcribers(),
+            _ => {}
+        });
 }
 
 pub fn run_child() -> anyhow::Result<bool> {
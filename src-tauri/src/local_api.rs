@@ -0,0 +1,251 @@
+use crate::audio::AudioBuffer;
+use crate::command_errors::CommandError;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::Duration;
+
+pub const API_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 47_611;
+/// Mono little-endian 16-bit samples at 16 kHz.
+pub const FORMAT_PCM_S16LE: &str = "pcm_s16le";
+/// Mono little-endian 32-bit float samples at 16 kHz.
+pub const FORMAT_PCM_F32LE: &str = "pcm_f32le";
+/// A complete WAV file of any rate and channel count.
+pub const FORMAT_WAV: &str = "wav";
+const PCM_RATE: u32 = 16_000;
+const MAX_HEADER_BYTES: u64 = 4_096;
+/// About 30 minutes of 16 kHz float audio.
+const MAX_AUDIO_BYTES: usize = 128 * 1024 * 1024;
+/// Bounds how long a stalled client can hold its connection open.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Connections served at once; more are refused rather than each getting a thread.
+pub const MAX_CONNECTIONS: usize = 4;
+
+/// The JSON line a client sends, followed on the same connection by `bytes` bytes of
+/// audio in `format`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub version: u32,
+    /// Must match `local_api_token` from the settings.
+    pub token: String,
+    pub format: String,
+    pub bytes: usize,
+    /// Overrides the configured language for this request.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// An installed model to use instead of the active one.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// The JSON line answering a request: `text`, or `error` with a `CommandError` code
+/// where there is one.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+}
+
+impl Response {
+    fn from_result(result: Result<String>) -> Self {
+        match result {
+            Ok(text) => Self {
+                version: API_VERSION,
+                text: Some(text),
+                ..Self::default()
+            },
+            Err(err) => Self {
+                version: API_VERSION,
+                code: err.downcast_ref::<CommandError>().map(|err| err.code),
+                error: Some(err.to_string()),
+                ..Self::default()
+            },
+        }
+    }
+}
+
+/// 128 bits from the OS random source, in hex, for `local_api_token` and child tokens.
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|err| anyhow::anyhow!("generate token: {err}"))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Listens on loopback only; other machines can never reach the model.
+pub fn bind(port: u16) -> Result<TcpListener> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .with_context(|| format!("bind local API port {port}"))?;
+    listener
+        .set_nonblocking(true)
+        .context("configure local API listener")?;
+    Ok(listener)
+}
+
+pub fn decode_audio(format: &str, bytes: &[u8]) -> Result<AudioBuffer> {
+    let samples = match format {
+        FORMAT_PCM_S16LE => bytes
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]) as f32 / i16::MAX as f32)
+            .collect(),
+        FORMAT_PCM_F32LE => bytes
+            .chunks_exact(4)
+            .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))
+            .collect(),
+        FORMAT_WAV => return decode_wav(bytes),
+        _ => anyhow::bail!("unsupported audio format {format}"),
+    };
+    Ok(AudioBuffer {
+        samples,
+        sample_rate: PCM_RATE,
+    })
+}
+
+fn decode_wav(bytes: &[u8]) -> Result<AudioBuffer> {
+    let mut reader = hound::WavReader::new(Cursor::new(bytes)).context("open wav")?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .context("read wav samples")?;
+    let channels = usize::from(spec.channels.max(1));
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    Ok(AudioBuffer {
+        samples,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+/// Answers one connection: checks the token, reads the audio and replies with what
+/// `transcribe` makes of it.
+pub fn handle(
+    stream: TcpStream,
+    token: &str,
+    transcribe: impl FnOnce(AudioBuffer, Request) -> Result<String>,
+) -> Result<()> {
+    stream
+        .set_nonblocking(false)
+        .context("configure connection")?;
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .context("configure connection")?;
+    let mut writer = stream.try_clone().context("clone connection")?;
+    let mut reader = BufReader::new(stream);
+    let result = read_request(&mut reader, token).and_then(|(request, bytes)| {
+        let audio = decode_audio(&request.format, &bytes)?;
+        transcribe(audio, request)
+    });
+    let mut line = serde_json::to_string(&Response::from_result(result))
+        .context("serialize local API response")?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .context("write local API response")
+}
+
+/// Answers a connection over `MAX_CONNECTIONS` with an error instead of serving it.
+pub fn refuse_busy(mut stream: TcpStream) -> Result<()> {
+    stream
+        .set_nonblocking(false)
+        .context("configure connection")?;
+    let response = Response::from_result(Err(anyhow::anyhow!(
+        "too many local API requests at once, try again later"
+    )));
+    let mut line = serde_json::to_string(&response).context("serialize local API response")?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .context("write local API response")
+}
+
+/// Compares without stopping at the first differing byte, so the time a rejection takes
+/// does not reveal how much of the token was right.
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn read_request(reader: &mut impl BufRead, token: &str) -> Result<(Request, Vec<u8>)> {
+    let mut line = String::new();
+    reader
+        .take(MAX_HEADER_BYTES)
+        .read_line(&mut line)
+        .context("read request")?;
+    let request: Request = serde_json::from_str(line.trim()).context("parse request")?;
+    if request.version != API_VERSION {
+        anyhow::bail!(
+            "unsupported API version {}, expected {API_VERSION}",
+            request.version
+        );
+    }
+    if token.is_empty() || !token_matches(&request.token, token) {
+        anyhow::bail!("invalid token");
+    }
+    if request.bytes > MAX_AUDIO_BYTES {
+        anyhow::bail!("audio is larger than {MAX_AUDIO_BYTES} bytes");
+    }
+    let mut bytes = vec![0u8; request.bytes];
+    reader.read_exact(&mut bytes).context("read audio")?;
+    Ok((request, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_audio, read_request, token_matches, FORMAT_PCM_S16LE, FORMAT_WAV};
+
+    #[test]
+    fn reads_authenticated_requests_and_decodes_audio() {
+        let header = r#"{"version":1,"token":"secret","format":"pcm_s16le","bytes":4}"#;
+        let mut input = format!("{header}\n").into_bytes();
+        input.extend([0x00, 0x40, 0x00, 0xc0]);
+        let (request, bytes) = read_request(&mut input.as_slice(), "secret").unwrap();
+        assert_eq!(request.format, FORMAT_PCM_S16LE);
+        let audio = decode_audio(&request.format, &bytes).unwrap();
+        assert_eq!(audio.sample_rate, 16_000);
+        assert!((audio.samples[0] - 0.5).abs() < 1e-3 && (audio.samples[1] + 0.5).abs() < 1e-3);
+        assert!(read_request(&mut input.as_slice(), "other").is_err());
+        assert!(read_request(&mut input.as_slice(), "").is_err());
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret", "secret2"));
+
+        let mut wav = std::io::Cursor::new(Vec::new());
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44_100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for sample in [16_384i16, 0, -16_384, -16_384] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        let audio = decode_audio(FORMAT_WAV, wav.get_ref()).unwrap();
+        assert_eq!(audio.sample_rate, 44_100);
+        assert_eq!(audio.samples, vec![0.25, -0.5]);
+        assert!(decode_audio("mp3", &[]).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const SYSTEM_CONFIG_ENV: &str = "WHISPERDICT_SYSTEM_CONFIG";
+const DISABLE_CHECKOUT_ENV: &str = "WHISPERDICT_DISABLE_CHECKOUT";
+const DISABLE_UPDATE_CHECKS_ENV: &str = "WHISPERDICT_DISABLE_UPDATE_CHECKS";
+const MODELS_DIR_ENV: &str = "WHISPERDICT_MODELS_DIR";
+const ALLOWED_MODELS_ENV: &str = "WHISPERDICT_ALLOWED_MODELS";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ManagedPolicy {
+    pub disable_checkout: bool,
+    pub disable_update_checks: bool,
+    pub models_dir: Option<PathBuf>,
+    pub allowed_models: Option<Vec<String>>,
+}
+
+impl ManagedPolicy {
+    pub fn model_allowed(&self, model_id: &str) -> bool {
+        match &self.allowed_models {
+            Some(allowed) => allowed.iter().any(|id| id == model_id),
+            None => true,
+        }
+    }
+
+    pub fn fallback_model(&self) -> String {
+        if self.model_allowed("base") {
+            return "base".to_string();
+        }
+        self.allowed_models
+            .as_ref()
+            .and_then(|allowed| allowed.first().cloned())
+            .unwrap_or_else(|| "base".to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct SystemConfig {
+    defaults: Option<Value>,
+    policy: ManagedPolicy,
+    /// Why the administrator's file could not be used, if it exists but is broken.
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+/// A missing file is no policy; an unreadable or malformed one is an error.
+fn read_system_config(path: &Path) -> Result<Option<SystemConfig>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).context("read system config"),
+    };
+    serde_json::from_str(&data)
+        .map(Some)
+        .context("parse system config")
+}
+
+fn system_config() -> &'static SystemConfig {
+    static SYSTEM_CONFIG: OnceLock<SystemConfig> = OnceLock::new();
+    SYSTEM_CONFIG.get_or_init(|| {
+        let path = system_config_path();
+        let mut config = match path.as_deref().map(read_system_config) {
+            Some(Ok(config)) => config.unwrap_or_default(),
+            Some(Err(err)) => {
+                let error = format!("{}: {err:#}", path.unwrap_or_default().display());
+                eprintln!("managed config ignored, defaults and policy not applied: {error}");
+                SystemConfig {
+                    error: Some(error),
+                    ..SystemConfig::default()
+                }
+            }
+            None => SystemConfig::default(),
+        };
+        apply_env_overrides(&mut config.policy);
+        config
+    })
+}
+
+/// Set when the administrator's config exists but could not be read or parsed.
+pub fn load_error() -> Option<&'static str> {
+    system_config().error.as_deref()
+}
+
+pub fn policy() -> &'static ManagedPolicy {
+    &system_config().policy
+}
+
+/// Settings pre-seeded by the administrator; user config is merged on top of these.
+pub fn system_defaults() -> Option<&'static Value> {
+    system_config().defaults.as_ref()
+}
+
+pub fn merge_beneath(base: &Value, overlay: Value) -> Value {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            let mut merged = base.clone();
+            for (key, value) in overlay {
+                merged.insert(key, value);
+            }
+            Value::Object(merged)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
+/// Drops the values `config` shares with `base`, so saving never copies the
+/// administrator's defaults into the user config and later changes to them still apply.
+pub fn strip_beneath(base: &Value, config: Value) -> Value {
+    match (base, config) {
+        (Value::Object(base), Value::Object(mut config)) => {
+            config.retain(|key, value| base.get(key) != Some(value));
+            Value::Object(config)
+        }
+        (_, config) => config,
+    }
+}
+
+fn system_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(SYSTEM_CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    if cfg!(target_os = "windows") {
+        env::var_os("ProgramData")
+            .map(|dir| PathBuf::from(dir).join("Whisperdict").join("config.json"))
+    } else if cfg!(target_os = "macos") {
+        Some(PathBuf::from(
+            "/Library/Application Support/Whisperdict/config.json",
+        ))
+    } else {
+        Some(PathBuf::from("/etc/whisperdict/config.json"))
+    }
+}
+
+fn apply_env_overrides(policy: &mut ManagedPolicy) {
+    if let Some(value) = env_flag(DISABLE_CHECKOUT_ENV) {
+        policy.disable_checkout = value;
+    }
+    if let Some(value) = env_flag(DISABLE_UPDATE_CHECKS_ENV) {
+        policy.disable_update_checks = value;
+    }
+    if let Some(dir) = env::var_os(MODELS_DIR_ENV).filter(|value| !value.is_empty()) {
+        policy.models_dir = Some(PathBuf::from(dir));
+    }
+    if let Ok(value) = env::var(ALLOWED_MODELS_ENV) {
+        let allowed: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(ToOwned::to_owned)
+            .collect();
+        if !allowed.is_empty() {
+            policy.allowed_models = Some(allowed);
+        }
+    }
+}
+
+fn env_flag(name: &str) -> Option<bool> {
+    let value = env::var(name).ok()?;
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_beneath, read_system_config, strip_beneath, ManagedPolicy};
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn malformed_system_config_is_an_error_and_a_missing_one_is_not() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("config.json");
+        assert!(read_system_config(&path).expect("missing file").is_none());
+
+        fs::write(&path, r#"{ "policy": { "allowedModels": "base" } }"#).expect("write");
+        assert!(read_system_config(&path).is_err());
+
+        fs::write(&path, r#"{ "policy": { "allowedModels": ["base"] } }"#).expect("write");
+        let config = read_system_config(&path)
+            .expect("valid file")
+            .expect("config");
+        assert!(!config.policy.model_allowed("large"));
+    }
+
+    #[test]
+    fn saving_leaves_system_defaults_out_of_the_user_config() {
+        let system = json!({ "language": "es", "shortcut": "Ctrl+Alt+D" });
+        let config = json!({ "language": "en", "shortcut": "Ctrl+Alt+D", "keep_recordings": true });
+        let saved = strip_beneath(&system, config.clone());
+        assert_eq!(saved, json!({ "language": "en", "keep_recordings": true }));
+        assert_eq!(merge_beneath(&system, saved), config);
+    }
+
+    #[test]
+    fn user_values_win_over_system_defaults() {
+        let system = json!({ "language": "es", "shortcut": "Ctrl+Alt+D" });
+        let user = json!({ "language": "en" });
+        let merged = merge_beneath(&system, user);
+        assert_eq!(merged["language"], "en");
+        assert_eq!(merged["shortcut"], "Ctrl+Alt+D");
+    }
+
+    #[test]
+    fn allowed_models_restricts_selection() {
+        let policy = ManagedPolicy {
+            allowed_models: Some(vec!["base".to_string()]),
+            ..ManagedPolicy::default()
+        };
+        assert!(policy.model_allowed("base"));
+        assert!(!policy.model_allowed("large"));
+        assert!(ManagedPolicy::default().model_allowed("large"));
+    }
+}
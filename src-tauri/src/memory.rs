@@ -0,0 +1,138 @@
+use crate::command_errors::ModelTooLarge;
+use crate::compute;
+
+pub const MEMORY_RAM: &str = "ram";
+pub const MEMORY_VRAM: &str = "vram";
+
+/// Rough working set of whisper.cpp for a model file of `size_mb`: the weights plus the
+/// KV cache and compute buffers, in line with the figures whisper.cpp publishes.
+pub fn required_mb(size_mb: u32) -> u64 {
+    u64::from(size_mb) * 13 / 10 + 200
+}
+
+/// Refuses to load `model_id` when the memory it would live in has too little free, once
+/// the caller releases `freed_mb` of it. GPU memory is only checked when the backend is
+/// GPU-only and can be queried; unknown amounts pass, so a failed probe never blocks
+/// loading.
+pub fn check_model_fits(
+    model_id: &str,
+    size_mb: u32,
+    backend: &str,
+    device: u32,
+    freed_mb: u64,
+) -> Result<(), ModelTooLarge> {
+    let gpu_only = compute::gpu_mode(backend).ok().flatten() == Some(true);
+    let (memory, available_mb) = if gpu_only {
+        (MEMORY_VRAM, available_vram_mb(backend, device))
+    } else {
+        (MEMORY_RAM, available_ram_mb())
+    };
+    let required_mb = required_mb(size_mb);
+    match available_mb.map(|available_mb| available_mb + freed_mb) {
+        Some(available_mb) if available_mb < required_mb => Err(ModelTooLarge {
+            model_id: model_id.to_string(),
+            memory,
+            required_mb,
+            available_mb,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// `MemAvailable` from `/proc/meminfo`, in MB.
+#[cfg(any(target_os = "linux", test))]
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024)
+}
+
+/// Free GPU memory of CUDA device `device`, in MB; other backends cannot be queried.
+fn available_vram_mb(backend: &str, device: u32) -> Option<u64> {
+    if backend != compute::BACKEND_CUDA {
+        return None;
+    }
+    compute::nvidia_gpus()
+        .into_iter()
+        .find(|gpu| gpu.index == device)?
+        .free_mb
+}
+
+/// Memory available to new processes without swapping, in MB. This shells out on some
+/// platforms, so call it off the async runtime.
+#[cfg(target_os = "linux")]
+fn available_ram_mb() -> Option<u64> {
+    parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+#[cfg(target_os = "macos")]
+fn available_ram_mb() -> Option<u64> {
+    let output = std::process::Command::new("vm_stat").output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let page_size: u64 = text
+        .lines()
+        .next()?
+        .split("page size of ")
+        .nth(1)?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    let pages = |name: &str| -> Option<u64> {
+        let line = text.lines().find(|line| line.starts_with(name))?;
+        line.split(':')
+            .nth(1)?
+            .trim()
+            .trim_end_matches('.')
+            .parse()
+            .ok()
+    };
+    // What Activity Monitor counts as available: inactive, speculative and purgeable
+    // pages are all reclaimed before anything is swapped.
+    let free = pages("Pages free")?
+        + ["Pages inactive", "Pages speculative", "Pages purgeable"]
+            .iter()
+            .filter_map(|name| pages(name))
+            .sum::<u64>();
+    Some(free * page_size / (1024 * 1024))
+}
+
+#[cfg(target_os = "windows")]
+fn available_ram_mb() -> Option<u64> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return None;
+    }
+    Some(status.ullAvailPhys / (1024 * 1024))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn available_ram_mb() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_model_fits, parse_meminfo, required_mb};
+    use crate::compute::BACKEND_CPU;
+
+    #[test]
+    fn reads_available_memory_and_estimates_requirements() {
+        let meminfo = "MemTotal:       16303428 kB\nMemFree:          512000 kB\nMemAvailable:    2097152 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some(2_048));
+        assert_eq!(parse_meminfo("MemTotal: 1 kB\n"), None);
+        // whisper.cpp lists about 2.1 GB for medium and 3.9 GB for large.
+        assert_eq!(required_mb(1_460), 2_098);
+        assert_eq!(required_mb(2_880), 3_944);
+        if cfg!(target_os = "linux") {
+            assert!(check_model_fits("huge", u32::MAX, BACKEND_CPU, 0, 0).is_err());
+            let freed_mb = required_mb(u32::MAX);
+            assert!(check_model_fits("huge", u32::MAX, BACKEND_CPU, 0, freed_mb).is_ok());
+        }
+    }
+}
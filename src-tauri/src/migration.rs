@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Recordings ECO kept in its data dir, named `ECO-<unix millis>.wav`.
+const LEGACY_WAV_PREFIX: &str = "ECO-";
+const LEGACY_CONFIG_DIR: &str = "ECO";
+const LEGACY_DATA_DIR: &str = "eco";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// Moves a legacy file into the current layout.
+    Move { from: PathBuf, to: PathBuf },
+    /// Deletes a legacy file that is stale or already superseded.
+    Delete(PathBuf),
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub migrated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl MigrationReport {
+    pub fn is_empty(&self) -> bool {
+        self.migrated.is_empty() && self.deleted.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// Files directly inside `dir`; a missing directory has none.
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Moves each legacy file into `target` unless a file of that name already exists there,
+/// in which case the current one wins and the legacy copy is deleted.
+fn plan_dir(files: Vec<PathBuf>, target: &Path, existing: &[PathBuf]) -> Vec<Step> {
+    files
+        .into_iter()
+        .filter_map(|from| {
+            let name = from.file_name()?.to_owned();
+            let to = target.join(&name);
+            Some(if existing.contains(&to) {
+                Step::Delete(from)
+            } else {
+                Step::Move { from, to }
+            })
+        })
+        .collect()
+}
+
+fn is_legacy_wav(name: &str) -> bool {
+    name.strip_prefix(LEGACY_WAV_PREFIX)
+        .and_then(|rest| rest.strip_suffix(".wav"))
+        .is_some_and(|stamp| !stamp.is_empty() && stamp.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Only ECO's own data dir is searched: the shared temp dir may hold other programs'
+/// files that merely share the prefix.
+fn legacy_wavs(legacy_data: &Path) -> Vec<Step> {
+    files_in(legacy_data)
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(is_legacy_wav)
+        })
+        .map(Step::Delete)
+        .collect()
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).context("create target dir")?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // Rename fails across filesystems; fall back to copying.
+    fs::copy(from, to).context("copy file")?;
+    fs::remove_file(from).context("remove legacy file")
+}
+
+fn apply(steps: Vec<Step>, report: &mut MigrationReport) {
+    for step in steps {
+        let (result, path, done) = match &step {
+            Step::Move { from, to } => (move_file(from, to), from, &mut report.migrated),
+            Step::Delete(path) => (
+                fs::remove_file(path).context("remove legacy file"),
+                path,
+                &mut report.deleted,
+            ),
+        };
+        match result {
+            Ok(()) => done.push(path.display().to_string()),
+            Err(err) => {
+                eprintln!("legacy migration: {}: {err:#}", path.display());
+                report.failed.push(path.display().to_string());
+            }
+        }
+    }
+}
+
+/// Removes `dir` and then `parent` when they are left empty.
+fn remove_empty(dir: &Path, parent: Option<&Path>) {
+    if fs::remove_dir(dir).is_ok() {
+        if let Some(parent) = parent {
+            let _ = fs::remove_dir(parent);
+        }
+    }
+}
+
+/// Cleans up what ECO left behind: stale recordings are deleted, and the old
+/// config and model files are moved into the current directories. Runs before the
+/// config is loaded so migrated settings take effect on this launch.
+pub fn migrate_legacy(config_dir: &Path, models_dir: &Path) -> Result<MigrationReport> {
+    let dirs = BaseDirs::new().context("missing base dirs")?;
+    let legacy_config = dirs.config_dir().join(LEGACY_CONFIG_DIR);
+    let legacy_data = dirs.data_local_dir().join(LEGACY_DATA_DIR);
+    let legacy_models = legacy_data.join("models");
+
+    let mut steps = legacy_wavs(&legacy_data);
+    steps.extend(plan_dir(
+        files_in(&legacy_config),
+        config_dir,
+        &files_in(config_dir),
+    ));
+    steps.extend(plan_dir(
+        files_in(&legacy_models),
+        models_dir,
+        &files_in(models_dir),
+    ));
+
+    let mut report = MigrationReport::default();
+    apply(steps, &mut report);
+    remove_empty(&legacy_config, None);
+    remove_empty(&legacy_models, Some(&legacy_data));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{legacy_wavs, plan_dir, Step};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn only_eco_recordings_in_its_own_dir_are_deleted() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        for name in [
+            "ECO-1700000000000.wav",
+            "ECO-notes.wav",
+            "ECO-.wav",
+            "ECO-1700000000000.txt",
+            "other.wav",
+        ] {
+            fs::write(dir.path().join(name), b"").expect("write file");
+        }
+        fs::create_dir(dir.path().join("ECO-1.wav")).expect("create dir");
+        assert_eq!(
+            legacy_wavs(dir.path()),
+            vec![Step::Delete(dir.path().join("ECO-1700000000000.wav"))]
+        );
+        assert!(legacy_wavs(&dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn current_files_win_over_legacy_copies() {
+        let target = PathBuf::from("/data/Whisperdict/models");
+        let legacy = vec![
+            PathBuf::from("/data/eco/models/ggml-base.bin"),
+            PathBuf::from("/data/eco/models/ggml-small.bin"),
+        ];
+        let existing = vec![target.join("ggml-base.bin")];
+        assert_eq!(
+            plan_dir(legacy, &target, &existing),
+            vec![
+                Step::Delete(PathBuf::from("/data/eco/models/ggml-base.bin")),
+                Step::Move {
+                    from: PathBuf::from("/data/eco/models/ggml-small.bin"),
+                    to: target.join("ggml-small.bin"),
+                },
+            ]
+        );
+    }
+}
@@ -1,10 +1,18 @@
+use crate::compute;
+use crate::managed_config;
+use crate::proxy;
 use anyhow::{Context, Result};
 use directories::BaseDirs;
 use futures_util::StreamExt;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
-use tokio::io::AsyncWriteExt;
+use std::io::{Read, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::Instant;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 use tokio::time::{timeout, Duration};
 
 #[derive(Debug, Clone, Serialize)]
@@ -13,6 +21,115 @@ pub struct ModelStatus {
     pub size_mb: u32,
     pub installed: bool,
     pub partial: bool,
+    pub tags: ModelTags,
+    pub variant_of: Option<&'static str>,
+    /// Display name of an imported model.
+    pub name: Option<String>,
+}
+
+/// Describes what a model is for, so stock Whisper checkpoints and fine-tunes can sit
+/// side by side and be chosen by tag rather than by id.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelTags {
+    pub family: &'static str,
+    /// An ISO 639-1 code, or `MULTILINGUAL`.
+    pub language: &'static str,
+    pub domain: &'static str,
+    pub quantization: &'static str,
+}
+
+pub const MULTILINGUAL: &str = "multilingual";
+pub const TINYDIARIZE: &str = "tinydiarize";
+/// Family of models imported from disk; also the prefix of their ids.
+pub const IMPORTED: &str = "imported";
+const IMPORTED_FILE: &str = "imported.json";
+/// Upper bound for `download_connections`; more rarely helps and hosts may throttle it.
+pub const MAX_DOWNLOAD_CONNECTIONS: u32 = 8;
+const COREML_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+const WHISPER_TAGS: ModelTags = ModelTags {
+    family: "whisper",
+    language: MULTILINGUAL,
+    domain: "general",
+    quantization: "f16",
+};
+
+/// Roughly a third of the f16 size. whisper.cpp publishes q5_1 for the smaller models and
+/// q5_0 for the larger ones.
+const Q5_0_TAGS: ModelTags = ModelTags {
+    quantization: "q5_0",
+    ..WHISPER_TAGS
+};
+const Q5_1_TAGS: ModelTags = ModelTags {
+    quantization: "q5_1",
+    ..WHISPER_TAGS
+};
+/// About half the f16 size, with accuracy close to it.
+const Q8_0_TAGS: ModelTags = ModelTags {
+    quantization: "q8_0",
+    ..WHISPER_TAGS
+};
+
+/// English small model fine-tuned to mark speaker turns.
+const TINYDIARIZE_TAGS: ModelTags = ModelTags {
+    family: TINYDIARIZE,
+    language: "en",
+    domain: "meeting",
+    quantization: "f16",
+};
+
+/// Nothing is known about an imported file beyond that it loads.
+const IMPORTED_TAGS: ModelTags = ModelTags {
+    family: IMPORTED,
+    language: MULTILINGUAL,
+    domain: "general",
+    quantization: "unknown",
+};
+
+/// Tags a model must carry; unset fields match anything. Comparison ignores case, and a
+/// multilingual model satisfies any language.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelFilter {
+    pub family: Option<String>,
+    pub language: Option<String>,
+    pub domain: Option<String>,
+    pub quantization: Option<String>,
+}
+
+impl ModelFilter {
+    pub fn matches(&self, tags: &ModelTags) -> bool {
+        let accepts = |wanted: &Option<String>, value: &str| {
+            wanted.as_deref().map(str::trim).map_or(true, |wanted| {
+                wanted.is_empty() || wanted.eq_ignore_ascii_case(value)
+            })
+        };
+        accepts(&self.family, tags.family)
+            && (tags.language == MULTILINGUAL || accepts(&self.language, tags.language))
+            && accepts(&self.domain, tags.domain)
+            && accepts(&self.quantization, tags.quantization)
+    }
+}
+
+/// How one model is loaded, for drivers that handle some models on the GPU but crash
+/// on others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ModelCompute {
+    /// `false` keeps the model on the CPU whatever `compute_backend` says.
+    pub gpu: bool,
+    /// Index of the GPU to load it onto.
+    pub device: u32,
+}
+
+impl Default for ModelCompute {
+    fn default() -> Self {
+        Self {
+            gpu: true,
+            device: 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -22,6 +139,10 @@ pub struct ModelInfo {
     pub filename: &'static str,
     pub url: &'static str,
     pub min_bytes: u64,
+    pub tags: ModelTags,
+    /// The full-precision model this is a quantized build of. The large-v2 builds stand in
+    /// for `large`, which has none of its own; large-v3 has a q5_0 build but no q8_0.
+    pub variant_of: Option<&'static str>,
 }
 
 const MODEL_LIST: &[ModelInfo] = &[
@@ -31,6 +152,8 @@ const MODEL_LIST: &[ModelInfo] = &[
         filename: "ggml-tiny.bin",
         url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin",
         min_bytes: 70 * 1024 * 1024,
+        tags: WHISPER_TAGS,
+        variant_of: None,
     },
     ModelInfo {
         id: "base",
@@ -38,6 +161,8 @@ const MODEL_LIST: &[ModelInfo] = &[
         filename: "ggml-base.bin",
         url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin",
         min_bytes: 135 * 1024 * 1024,
+        tags: WHISPER_TAGS,
+        variant_of: None,
     },
     ModelInfo {
         id: "small",
@@ -45,6 +170,17 @@ const MODEL_LIST: &[ModelInfo] = &[
         filename: "ggml-small.bin",
         url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.bin",
         min_bytes: 440 * 1024 * 1024,
+        tags: WHISPER_TAGS,
+        variant_of: None,
+    },
+    ModelInfo {
+        id: "small.en-tdrz",
+        size_mb: 465,
+        filename: "ggml-small.en-tdrz.bin",
+        url: "https://huggingface.co/akashmjn/tinydiarize-whisper.cpp/resolve/main/ggml-small.en-tdrz.bin",
+        min_bytes: 440 * 1024 * 1024,
+        tags: TINYDIARIZE_TAGS,
+        variant_of: None,
     },
     ModelInfo {
         id: "medium",
@@ -52,6 +188,8 @@ const MODEL_LIST: &[ModelInfo] = &[
         filename: "ggml-medium.bin",
         url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.bin",
         min_bytes: 1400 * 1024 * 1024,
+        tags: WHISPER_TAGS,
+        variant_of: None,
     },
     ModelInfo {
         id: "large",
@@ -59,33 +197,342 @@ const MODEL_LIST: &[ModelInfo] = &[
         filename: "ggml-large.bin",
         url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large.bin",
         min_bytes: 2700 * 1024 * 1024,
+        tags: WHISPER_TAGS,
+        variant_of: None,
+    },
+    ModelInfo {
+        id: "large-v3",
+        size_mb: 3095,
+        filename: "ggml-large-v3.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3.bin",
+        min_bytes: 2900 * 1024 * 1024,
+        tags: WHISPER_TAGS,
+        variant_of: None,
+    },
+    ModelInfo {
+        id: "large-v3-turbo",
+        size_mb: 1620,
+        filename: "ggml-large-v3-turbo.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo.bin",
+        min_bytes: 1500 * 1024 * 1024,
+        tags: WHISPER_TAGS,
+        variant_of: None,
+    },
+    ModelInfo {
+        id: "tiny-q5_1",
+        size_mb: 31,
+        filename: "ggml-tiny-q5_1.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny-q5_1.bin",
+        min_bytes: 30 * 1024 * 1024,
+        tags: Q5_1_TAGS,
+        variant_of: Some("tiny"),
+    },
+    ModelInfo {
+        id: "tiny-q8_0",
+        size_mb: 42,
+        filename: "ggml-tiny-q8_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny-q8_0.bin",
+        min_bytes: 40 * 1024 * 1024,
+        tags: Q8_0_TAGS,
+        variant_of: Some("tiny"),
+    },
+    ModelInfo {
+        id: "base-q5_1",
+        size_mb: 57,
+        filename: "ggml-base-q5_1.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base-q5_1.bin",
+        min_bytes: 55 * 1024 * 1024,
+        tags: Q5_1_TAGS,
+        variant_of: Some("base"),
+    },
+    ModelInfo {
+        id: "base-q8_0",
+        size_mb: 78,
+        filename: "ggml-base-q8_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base-q8_0.bin",
+        min_bytes: 75 * 1024 * 1024,
+        tags: Q8_0_TAGS,
+        variant_of: Some("base"),
+    },
+    ModelInfo {
+        id: "small-q5_1",
+        size_mb: 181,
+        filename: "ggml-small-q5_1.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small-q5_1.bin",
+        min_bytes: 175 * 1024 * 1024,
+        tags: Q5_1_TAGS,
+        variant_of: Some("small"),
+    },
+    ModelInfo {
+        id: "small-q8_0",
+        size_mb: 252,
+        filename: "ggml-small-q8_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small-q8_0.bin",
+        min_bytes: 240 * 1024 * 1024,
+        tags: Q8_0_TAGS,
+        variant_of: Some("small"),
+    },
+    ModelInfo {
+        id: "medium-q5_0",
+        size_mb: 514,
+        filename: "ggml-medium-q5_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium-q5_0.bin",
+        min_bytes: 495 * 1024 * 1024,
+        tags: Q5_0_TAGS,
+        variant_of: Some("medium"),
+    },
+    ModelInfo {
+        id: "medium-q8_0",
+        size_mb: 785,
+        filename: "ggml-medium-q8_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium-q8_0.bin",
+        min_bytes: 750 * 1024 * 1024,
+        tags: Q8_0_TAGS,
+        variant_of: Some("medium"),
+    },
+    ModelInfo {
+        id: "large-v2-q5_0",
+        size_mb: 1030,
+        filename: "ggml-large-v2-q5_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v2-q5_0.bin",
+        min_bytes: 990 * 1024 * 1024,
+        tags: Q5_0_TAGS,
+        variant_of: Some("large"),
+    },
+    ModelInfo {
+        id: "large-v2-q8_0",
+        size_mb: 1570,
+        filename: "ggml-large-v2-q8_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v2-q8_0.bin",
+        min_bytes: 1500 * 1024 * 1024,
+        tags: Q8_0_TAGS,
+        variant_of: Some("large"),
+    },
+    ModelInfo {
+        id: "large-v3-q5_0",
+        size_mb: 1080,
+        filename: "ggml-large-v3-q5_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-q5_0.bin",
+        min_bytes: 1000 * 1024 * 1024,
+        tags: Q5_0_TAGS,
+        variant_of: Some("large-v3"),
+    },
+    ModelInfo {
+        id: "large-v3-turbo-q5_0",
+        size_mb: 574,
+        filename: "ggml-large-v3-turbo-q5_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo-q5_0.bin",
+        min_bytes: 530 * 1024 * 1024,
+        tags: Q5_0_TAGS,
+        variant_of: Some("large-v3-turbo"),
+    },
+    ModelInfo {
+        id: "large-v3-turbo-q8_0",
+        size_mb: 874,
+        filename: "ggml-large-v3-turbo-q8_0.bin",
+        url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo-q8_0.bin",
+        min_bytes: 800 * 1024 * 1024,
+        tags: Q8_0_TAGS,
+        variant_of: Some("large-v3-turbo"),
     },
 ];
 
 pub fn models_dir() -> Result<PathBuf> {
-    let dirs = BaseDirs::new().context("missing base dirs")?;
-    let dir = dirs.data_local_dir().join("Whisperdict").join("models");
+    let dir = match managed_config::policy().models_dir.clone() {
+        Some(dir) => dir,
+        None => {
+            let dirs = BaseDirs::new().context("missing base dirs")?;
+            dirs.data_local_dir().join("Whisperdict").join("models")
+        }
+    };
     fs::create_dir_all(&dir).context("create models dir")?;
     Ok(dir)
 }
 
 pub fn list_models() -> Result<Vec<ModelStatus>> {
     let dir = models_dir()?;
+    let policy = managed_config::policy();
+    let imported: Vec<(&'static ModelInfo, Option<String>)> = imported()
+        .read()
+        .unwrap()
+        .iter()
+        .map(|model| (model.info, Some(model.entry.name.clone())))
+        .collect();
     let items = MODEL_LIST
         .iter()
-        .map(|model| ModelStatus {
+        .map(|model| (model, None))
+        .chain(imported)
+        .filter(|(model, _)| policy.model_allowed(model.id))
+        .map(|(model, name)| ModelStatus {
             id: model.id.to_string(),
             size_mb: model.size_mb,
             installed: dir.join(model.filename).exists()
-                && model_is_valid(model.id).unwrap_or(false),
+                && model_is_valid(model.id).unwrap_or(false)
+                && (!compute::COREML || coreml_encoder_ready(model.id)),
             partial: dir.join(format!("{}.part", model.filename)).exists(),
+            tags: model.tags,
+            variant_of: model.variant_of,
+            name,
         })
         .collect();
     Ok(items)
 }
 
 pub fn get_model_info(model_id: &str) -> Option<&'static ModelInfo> {
-    MODEL_LIST.iter().find(|model| model.id == model_id)
+    MODEL_LIST
+        .iter()
+        .find(|model| model.id == model_id)
+        .or_else(|| {
+            imported()
+                .read()
+                .unwrap()
+                .iter()
+                .find(|model| model.info.id == model_id)
+                .map(|model| model.info)
+        })
+}
+
+/// A model file the user brought, recorded in `imported.json` in the models folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportedEntry {
+    id: String,
+    name: String,
+    filename: String,
+    bytes: u64,
+}
+
+struct Imported {
+    entry: ImportedEntry,
+    info: &'static ModelInfo,
+}
+
+impl Imported {
+    /// Leaks the entry's `ModelInfo` so imported models are looked up like built-in ones.
+    /// Imports are rare, so this stays small.
+    fn new(entry: ImportedEntry) -> Self {
+        let leak = |value: &str| -> &'static str { Box::leak(value.into()) };
+        let info = Box::leak(Box::new(ModelInfo {
+            id: leak(&entry.id),
+            size_mb: entry.bytes.div_ceil(1024 * 1024) as u32,
+            filename: leak(&entry.filename),
+            url: "",
+            min_bytes: entry.bytes,
+            tags: IMPORTED_TAGS,
+            variant_of: None,
+        }));
+        Self { entry, info }
+    }
+}
+
+fn imported() -> &'static RwLock<Vec<Imported>> {
+    static IMPORTED_MODELS: OnceLock<RwLock<Vec<Imported>>> = OnceLock::new();
+    IMPORTED_MODELS.get_or_init(|| {
+        let entries = load_imported().unwrap_or_else(|err| {
+            eprintln!("failed to load imported models: {err:#}");
+            Vec::new()
+        });
+        RwLock::new(entries.into_iter().map(Imported::new).collect())
+    })
+}
+
+fn load_imported() -> Result<Vec<ImportedEntry>> {
+    let path = models_dir()?.join(IMPORTED_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = fs::read_to_string(&path).context("read imported models")?;
+    serde_json::from_str(&data).context("parse imported models")
+}
+
+fn save_imported(imported: &[Imported]) -> Result<()> {
+    let entries: Vec<&ImportedEntry> = imported.iter().map(|model| &model.entry).collect();
+    let data = serde_json::to_string_pretty(&entries).context("serialize imported models")?;
+    fs::write(models_dir()?.join(IMPORTED_FILE), data).context("write imported models")
+}
+
+/// whisper.cpp files open with the `ggml` magic as a little-endian u32, GGUF files with
+/// `GGUF`.
+fn has_model_magic(header: &[u8]) -> bool {
+    header.starts_with(b"lmgg") || header.starts_with(b"GGUF")
+}
+
+/// `imported-` followed by the lowercase letters and digits of `name`, dash separated.
+fn imported_id(name: &str) -> String {
+    let slug: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    format!("{IMPORTED}-{}", slug.join("-"))
+}
+
+/// Links `source` into the models folder, or copies it when it lives on another file
+/// system, and registers it under `name`. Returns the new model's id. The caller still
+/// has to check the file actually loads.
+pub fn import_model(source: &Path, name: &str) -> Result<String> {
+    let name = name.trim();
+    if !name.chars().any(char::is_alphanumeric) {
+        anyhow::bail!("an imported model needs a name");
+    }
+    let mut header = [0u8; 4];
+    fs::File::open(source)
+        .context("open model file")?
+        .read_exact(&mut header)
+        .context("read model header")?;
+    if !has_model_magic(&header) {
+        anyhow::bail!("{} is not a GGML or GGUF model", source.display());
+    }
+
+    let dir = models_dir()?;
+    let mut imported = imported().write().unwrap();
+    let base = imported_id(name);
+    let id = (1..)
+        .map(|n| match n {
+            1 => base.clone(),
+            n => format!("{base}-{n}"),
+        })
+        .find(|id| {
+            !MODEL_LIST.iter().any(|model| model.id == id)
+                && !imported.iter().any(|model| model.info.id == id)
+                && !dir.join(format!("{id}.bin")).exists()
+        })
+        .context("no free model id")?;
+    let filename = format!("{id}.bin");
+    let path = dir.join(&filename);
+    if fs::hard_link(source, &path).is_err() {
+        fs::copy(source, &path).context("copy model")?;
+    }
+    let bytes = fs::metadata(&path).context("model metadata")?.len();
+    imported.push(Imported::new(ImportedEntry {
+        id: id.clone(),
+        name: name.to_string(),
+        filename,
+        bytes,
+    }));
+    if let Err(err) = save_imported(&imported) {
+        imported.pop();
+        let _ = fs::remove_file(&path);
+        return Err(err);
+    }
+    Ok(id)
+}
+
+/// The build of `model_id` in `quantization`, which may be the model itself.
+pub fn variant_id(model_id: &str, quantization: &str) -> Option<&'static str> {
+    let base = get_model_info(model_id)?;
+    let base_id = base.variant_of.unwrap_or(base.id);
+    MODEL_LIST
+        .iter()
+        .find(|model| {
+            (model.id == base_id || model.variant_of == Some(base_id))
+                && model.tags.quantization.eq_ignore_ascii_case(quantization)
+        })
+        .map(|model| model.id)
+}
+
+pub fn supports_speaker_turns(model_id: &str) -> bool {
+    get_model_info(model_id).is_some_and(|model| model.tags.family == TINYDIARIZE)
 }
 
 pub fn model_path(model_id: &str) -> Result<PathBuf> {
@@ -115,10 +562,32 @@ pub fn delete_model(model_id: &str) -> Result<()> {
     if part.exists() {
         let _ = fs::remove_file(&part);
     }
+    // Quantized builds share the encoder, so it goes only with the last of them.
+    if let Some(encoder) = coreml_encoder_name(model_id) {
+        let shared = MODEL_LIST.iter().any(|model| {
+            model.id != model_id
+                && dir.join(model.filename).exists()
+                && coreml_encoder_name(model.id).as_deref() == Some(encoder.as_str())
+        });
+        if !shared && dir.join(&encoder).is_dir() {
+            let _ = fs::remove_dir_all(dir.join(&encoder));
+        }
+    }
+    let mut imported = imported().write().unwrap();
+    if imported.iter().any(|model| model.info.id == model_id) {
+        imported.retain(|model| model.info.id != model_id);
+        save_imported(&imported)?;
+    }
     Ok(())
 }
 
-pub async fn download_model_with_progress<F>(model_id: &str, progress: F) -> Result<PathBuf>
+/// Downloads `model_id` over `connections` ranged requests at once when the host allows
+/// it, otherwise over one.
+pub async fn download_model_with_progress<F>(
+    model_id: &str,
+    connections: u32,
+    progress: F,
+) -> Result<PathBuf>
 where
     F: Fn(u64, Option<u64>) + Send + Sync,
 {
@@ -136,15 +605,28 @@ where
             return Ok(path);
         }
     }
+    if info.url.is_empty() {
+        anyhow::bail!("imported model {model_id} is missing; import it again");
+    }
+    if connections > 1 {
+        match download_ranges(info, &temp_path, connections, &progress).await {
+            Ok(()) => {
+                tokio::fs::rename(&temp_path, &path)
+                    .await
+                    .context("rename model")?;
+                return Ok(path);
+            }
+            Err(err) => {
+                eprintln!("parallel download of {model_id} failed, using one connection: {err:#}");
+                let _ = tokio::fs::remove_file(&temp_path).await;
+            }
+        }
+    }
 
     let mut file = tokio::fs::File::create(&temp_path)
         .await
         .context("create temp")?;
-    let client = reqwest::Client::builder()
-        .connect_timeout(Duration::from_secs(15))
-        .timeout(Duration::from_secs(60 * 60))
-        .build()
-        .context("build client")?;
+    let client = download_client()?;
     let response = client
         .get(info.url)
         .send()
@@ -186,3 +668,624 @@ where
         .context("rename model")?;
     Ok(path)
 }
+
+/// Directory whisper.cpp built with Core ML loads next to the model: the filename with
+/// `.bin` and any `-qX_Y` quantization suffix swapped for `-encoder.mlmodelc`. Only stock
+/// Whisper models have one published.
+pub fn coreml_encoder_name(model_id: &str) -> Option<String> {
+    let info = get_model_info(model_id).filter(|info| info.tags.family == "whisper")?;
+    let stem = info.filename.strip_suffix(".bin")?;
+    let stem = match stem.rsplit_once('-') {
+        Some((base, suffix))
+            if suffix.len() == 4 && suffix.starts_with('q') && suffix.as_bytes()[2] == b'_' =>
+        {
+            base
+        }
+        _ => stem,
+    };
+    Some(format!("{stem}-encoder.mlmodelc"))
+}
+
+/// Whether a model can load in a Core ML build; models without a published encoder load
+/// without Core ML, see `ContextOptions::coreml`.
+pub fn coreml_encoder_ready(model_id: &str) -> bool {
+    coreml_encoder_name(model_id).map_or(true, |name| {
+        models_dir()
+            .map(|dir| dir.join(name).is_dir())
+            .unwrap_or(false)
+    })
+}
+
+/// Fetches and unpacks the Core ML encoder of `model_id` unless it is there already.
+pub async fn download_coreml_encoder<F>(model_id: &str, progress: F) -> Result<()>
+where
+    F: Fn(u64, Option<u64>) + Send + Sync,
+{
+    let Some(name) = coreml_encoder_name(model_id) else {
+        return Ok(());
+    };
+    let dir = models_dir()?;
+    if dir.join(&name).is_dir() {
+        return Ok(());
+    }
+    let archive = dir.join(format!("{name}.zip.part"));
+    let response = download_client()?
+        .get(format!("{COREML_BASE_URL}/{name}.zip"))
+        .send()
+        .await
+        .context("download Core ML encoder")?
+        .error_for_status()
+        .context("bad status")?;
+    let total = response.content_length();
+    let mut file = tokio::fs::File::create(&archive)
+        .await
+        .context("create temp")?;
+    let mut stream = response.bytes_stream();
+    let mut downloaded = 0u64;
+    let fetched: Result<()> = async {
+        while let Some(chunk) = timeout(Duration::from_secs(30), stream.next())
+            .await
+            .context("download stalled")?
+        {
+            let chunk = chunk?;
+            downloaded += chunk.len() as u64;
+            file.write_all(&chunk).await.context("write chunk")?;
+            progress(downloaded, total);
+        }
+        file.flush().await.context("flush temp")
+    }
+    .await;
+    let result = match fetched {
+        Ok(()) => {
+            let (archive, dir) = (archive.clone(), dir.clone());
+            tokio::task::spawn_blocking(move || unpack_zip(&archive, &dir))
+                .await
+                .context("unpack task")
+                .and_then(|result| result)
+        }
+        Err(err) => Err(err),
+    };
+    let _ = tokio::fs::remove_file(&archive).await;
+    result
+}
+
+/// Core ML only exists on macOS, where `ditto` ships with the system.
+#[cfg(target_os = "macos")]
+fn unpack_zip(archive: &Path, dir: &Path) -> Result<()> {
+    let status = std::process::Command::new("ditto")
+        .arg("-x")
+        .arg("-k")
+        .arg(archive)
+        .arg(dir)
+        .status()
+        .context("run ditto")?;
+    if !status.success() {
+        anyhow::bail!("unpacking the Core ML encoder failed ({status})");
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn unpack_zip(_archive: &Path, _dir: &Path) -> Result<()> {
+    anyhow::bail!("Core ML is only available on macOS")
+}
+
+/// Weight of the newest window in the smoothed download speed.
+const SPEED_SMOOTHING: f64 = 0.3;
+const SPEED_WINDOW: Duration = Duration::from_millis(500);
+
+/// Download speed averaged over half-second windows, so progress can show a rate and an
+/// ETA that do not jump with every chunk.
+#[derive(Debug)]
+pub struct DownloadSpeed {
+    window_start: Instant,
+    window_bytes: Option<u64>,
+    bytes_per_sec: Option<f64>,
+}
+
+impl Default for DownloadSpeed {
+    fn default() -> Self {
+        Self {
+            window_start: Instant::now(),
+            window_bytes: None,
+            bytes_per_sec: None,
+        }
+    }
+}
+
+impl DownloadSpeed {
+    /// Records that `downloaded` bytes are done at `now` and returns the speed in bytes
+    /// per second and the seconds left, once known.
+    pub fn sample(
+        &mut self,
+        now: Instant,
+        downloaded: u64,
+        total: Option<u64>,
+    ) -> (Option<u64>, Option<u64>) {
+        let Some(window_bytes) = self.window_bytes else {
+            self.window_start = now;
+            self.window_bytes = Some(downloaded);
+            return (None, None);
+        };
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed >= SPEED_WINDOW {
+            let rate = downloaded.saturating_sub(window_bytes) as f64 / elapsed.as_secs_f64();
+            self.bytes_per_sec = Some(match self.bytes_per_sec {
+                Some(smoothed) => smoothed + (rate - smoothed) * SPEED_SMOOTHING,
+                None => rate,
+            });
+            self.window_start = now;
+            self.window_bytes = Some(downloaded);
+        }
+        let Some(rate) = self.bytes_per_sec.filter(|&rate| rate > 0.0) else {
+            return (None, None);
+        };
+        let eta = total.map(|total| (total.saturating_sub(downloaded) as f64 / rate).ceil() as u64);
+        (Some(rate.round() as u64), eta)
+    }
+}
+
+/// Inclusive byte ranges splitting `total` bytes into at most `parts` near-equal pieces.
+fn split_ranges(total: u64, parts: u32) -> Vec<(u64, u64)> {
+    let size = total.div_ceil(u64::from(parts.max(1))).max(1);
+    (0..u64::from(parts.max(1)))
+        .map(|part| part * size)
+        .take_while(|&start| start < total)
+        .map(|start| (start, (start + size).min(total) - 1))
+        .collect()
+}
+
+/// Fetches the model in `connections` slices at once, each written at its offset in
+/// `temp_path`, reporting their combined progress.
+async fn download_ranges<F>(
+    info: &ModelInfo,
+    temp_path: &Path,
+    connections: u32,
+    progress: &F,
+) -> Result<()>
+where
+    F: Fn(u64, Option<u64>) + Send + Sync,
+{
+    let total = remote_metadata(info)
+        .await?
+        .size
+        .filter(|&size| size > 0)
+        .context("model size unknown")?;
+    tokio::fs::File::create(temp_path)
+        .await
+        .context("create temp")?
+        .set_len(total)
+        .await
+        .context("allocate temp")?;
+    let client = download_client()?;
+    let downloaded = AtomicU64::new(0);
+    let report = |len: u64| {
+        let done = downloaded.fetch_add(len, Ordering::Relaxed) + len;
+        progress(done, Some(total));
+    };
+    let slices = split_ranges(total, connections.min(MAX_DOWNLOAD_CONNECTIONS))
+        .into_iter()
+        .map(|(start, end)| download_range(&client, info, temp_path, (start, end), &report));
+    futures_util::future::try_join_all(slices).await?;
+    Ok(())
+}
+
+async fn download_range(
+    client: &reqwest::Client,
+    info: &ModelInfo,
+    temp_path: &Path,
+    (start, end): (u64, u64),
+    report: &(dyn Fn(u64) + Sync),
+) -> Result<()> {
+    let response = client
+        .get(info.url)
+        .header(reqwest::header::RANGE, format!("bytes={start}-{end}"))
+        .send()
+        .await
+        .context("download range")?
+        .error_for_status()
+        .context("bad status")?;
+    if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        anyhow::bail!("server does not support ranged downloads");
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(temp_path)
+        .await
+        .context("open temp")?;
+    file.seek(SeekFrom::Start(start))
+        .await
+        .context("seek temp")?;
+    let mut stream = response.bytes_stream();
+    let mut written = 0u64;
+    while let Some(chunk) = timeout(Duration::from_secs(30), stream.next())
+        .await
+        .context("download stalled")?
+    {
+        let chunk = chunk?;
+        written += chunk.len() as u64;
+        file.write_all(&chunk).await.context("write chunk")?;
+        report(chunk.len() as u64);
+    }
+    file.flush().await.context("flush temp")?;
+    if written != end - start + 1 {
+        anyhow::bail!("range {start}-{end} ended after {written} bytes");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairOutcome {
+    Valid,
+    Resumed,
+    Redownloaded,
+}
+
+/// A model file on disk measured against the size the server publishes, or against the
+/// model's minimum size when the server does not say.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModelFile {
+    Missing,
+    /// Of the full size; its hash still needs checking.
+    Complete,
+    /// A prefix of the model, which a ranged download can finish.
+    Truncated,
+    Corrupt,
+}
+
+fn classify_model_file(len: Option<u64>, remote_size: Option<u64>, min_bytes: u64) -> ModelFile {
+    match (len, remote_size) {
+        (None, _) => ModelFile::Missing,
+        (Some(len), Some(size)) if len == size => ModelFile::Complete,
+        (Some(len), Some(size)) if len < size => ModelFile::Truncated,
+        (Some(len), None) if len >= min_bytes => ModelFile::Complete,
+        (Some(_), _) => ModelFile::Corrupt,
+    }
+}
+
+/// `(offset, total)` of the ranged download that finishes a partial file of `len` bytes,
+/// when one can.
+fn resume_range(len: u64, remote_size: Option<u64>) -> Option<(u64, u64)> {
+    remote_size
+        .filter(|&size| len > 0 && len <= size)
+        .map(|size| (len, size))
+}
+
+#[derive(Debug, Default)]
+struct RemoteModel {
+    size: Option<u64>,
+    sha256: Option<String>,
+}
+
+fn download_client() -> Result<reqwest::Client> {
+    proxy::client_builder()?
+        .connect_timeout(Duration::from_secs(15))
+        .timeout(Duration::from_secs(60 * 60))
+        .build()
+        .context("build client")
+}
+
+/// Hugging Face answers the unresolved URL with the LFS object's size and sha256.
+async fn remote_metadata(info: &ModelInfo) -> Result<RemoteModel> {
+    let client = proxy::client_builder()?
+        .connect_timeout(Duration::from_secs(15))
+        .timeout(Duration::from_secs(30))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .context("build client")?;
+    let response = client
+        .head(info.url)
+        .send()
+        .await
+        .context("query model metadata")?;
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim_matches('"').to_string())
+    };
+    let size = header("x-linked-size")
+        .and_then(|value| value.parse().ok())
+        .or_else(|| {
+            response
+                .status()
+                .is_success()
+                .then(|| response.content_length())
+                .flatten()
+        });
+    let sha256 = header("x-linked-etag")
+        .filter(|value| value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|value| value.to_lowercase());
+    Ok(RemoteModel { size, sha256 })
+}
+
+async fn file_matches_hash(path: &Path, expected: Option<&str>) -> Result<bool> {
+    let Some(expected) = expected.map(ToOwned::to_owned) else {
+        return Ok(true);
+    };
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = fs::File::open(&path).context("open model")?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            let read = file.read(&mut buffer).context("read model")?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        let digest: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Ok(digest == expected)
+    })
+    .await
+    .context("hash model")?
+}
+
+async fn resume_download<F>(
+    info: &ModelInfo,
+    temp_path: &Path,
+    offset: u64,
+    total: u64,
+    progress: &F,
+) -> Result<()>
+where
+    F: Fn(u64, Option<u64>) + Send + Sync,
+{
+    let response = download_client()?
+        .get(info.url)
+        .header(reqwest::header::RANGE, format!("bytes={offset}-"))
+        .send()
+        .await
+        .context("resume download")?
+        .error_for_status()
+        .context("bad status")?;
+    if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        anyhow::bail!("server does not support ranged downloads");
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .append(true)
+        .open(temp_path)
+        .await
+        .context("open partial model")?;
+    let mut downloaded = offset;
+    let mut stream = response.bytes_stream();
+    loop {
+        let item = timeout(Duration::from_secs(30), stream.next())
+            .await
+            .map_err(|_| anyhow::anyhow!("download stalled for {}", info.id))?;
+        let Some(chunk) = item else {
+            break;
+        };
+        let chunk = chunk?;
+        downloaded += chunk.len() as u64;
+        file.write_all(&chunk).await.context("write chunk")?;
+        progress(downloaded, Some(total));
+    }
+    file.flush().await.context("flush temp")?;
+    if downloaded != total {
+        anyhow::bail!("resumed download ended at {downloaded} of {total} bytes");
+    }
+    Ok(())
+}
+
+/// Re-validates an installed model and fixes it with as little transfer as possible:
+/// a truncated file only fetches its missing tail, anything else is downloaded again.
+pub async fn repair_model_with_progress<F>(
+    model_id: &str,
+    connections: u32,
+    progress: F,
+) -> Result<RepairOutcome>
+where
+    F: Fn(u64, Option<u64>) + Send + Sync,
+{
+    let info = get_model_info(model_id).context("unknown model")?;
+    if info.url.is_empty() {
+        if !model_is_valid(model_id)? {
+            anyhow::bail!("imported model {model_id} is damaged; import it again");
+        }
+        return Ok(RepairOutcome::Valid);
+    }
+    let dir = models_dir()?;
+    let path = dir.join(info.filename);
+    let temp_path = dir.join(format!("{}.part", info.filename));
+    let remote = remote_metadata(info).await.unwrap_or_default();
+    let expected_hash = remote.sha256.as_deref();
+
+    let installed = fs::metadata(&path).ok().map(|meta| meta.len());
+    match classify_model_file(installed, remote.size, info.min_bytes) {
+        ModelFile::Missing => {}
+        ModelFile::Complete if file_matches_hash(&path, expected_hash).await? => {
+            return Ok(RepairOutcome::Valid);
+        }
+        ModelFile::Truncated => {
+            tokio::fs::rename(&path, &temp_path)
+                .await
+                .context("move truncated model")?;
+        }
+        ModelFile::Complete | ModelFile::Corrupt => {
+            tokio::fs::remove_file(&path)
+                .await
+                .context("remove corrupt model")?;
+        }
+    }
+
+    let partial = fs::metadata(&temp_path).map(|meta| meta.len()).unwrap_or(0);
+    if let Some((len, size)) = resume_range(partial, remote.size) {
+        let resumed = len == size
+            || resume_download(info, &temp_path, len, size, &progress)
+                .await
+                .is_ok();
+        if resumed && file_matches_hash(&temp_path, expected_hash).await? {
+            tokio::fs::rename(&temp_path, &path)
+                .await
+                .context("rename model")?;
+            return Ok(RepairOutcome::Resumed);
+        }
+    }
+
+    let path = download_model_with_progress(model_id, connections, progress).await?;
+    if !file_matches_hash(&path, expected_hash).await? {
+        let _ = tokio::fs::remove_file(&path).await;
+        anyhow::bail!("downloaded model {model_id} failed the integrity check");
+    }
+    Ok(RepairOutcome::Redownloaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        classify_model_file, coreml_encoder_name, file_matches_hash, has_model_magic, imported_id,
+        resume_range, split_ranges, variant_id, DownloadSpeed, ModelFile, ModelFilter, ModelTags,
+        MODEL_LIST, MULTILINGUAL,
+    };
+
+    #[test]
+    fn repair_tells_valid_damaged_and_missing_models_apart() {
+        // Valid: the published size, or at least the minimum when none is published.
+        assert_eq!(
+            classify_model_file(Some(100), Some(100), 50),
+            ModelFile::Complete
+        );
+        assert_eq!(classify_model_file(Some(60), None, 50), ModelFile::Complete);
+        // Damaged: a cut-off download is finished, anything else is fetched again.
+        assert_eq!(
+            classify_model_file(Some(40), Some(100), 50),
+            ModelFile::Truncated
+        );
+        assert_eq!(
+            classify_model_file(Some(120), Some(100), 50),
+            ModelFile::Corrupt
+        );
+        assert_eq!(classify_model_file(Some(10), None, 50), ModelFile::Corrupt);
+        assert_eq!(resume_range(40, Some(100)), Some((40, 100)));
+        assert_eq!(resume_range(100, Some(100)), Some((100, 100)));
+        assert_eq!(resume_range(120, Some(100)), None);
+        assert_eq!(resume_range(40, None), None);
+        // Missing: nothing to resume from, so the model is downloaded.
+        assert_eq!(classify_model_file(None, Some(100), 50), ModelFile::Missing);
+        assert_eq!(resume_range(0, Some(100)), None);
+    }
+
+    #[tokio::test]
+    async fn repair_hash_check_catches_damaged_content() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("ggml-test.bin");
+        std::fs::write(&path, b"hello").expect("write model");
+        let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(file_matches_hash(&path, Some(hello)).await.expect("hash"));
+        assert!(file_matches_hash(&path, None).await.expect("no hash"));
+        std::fs::write(&path, b"hellp").expect("damage model");
+        assert!(!file_matches_hash(&path, Some(hello)).await.expect("hash"));
+        std::fs::remove_file(&path).expect("remove model");
+        assert!(file_matches_hash(&path, Some(hello)).await.is_err());
+    }
+
+    #[test]
+    fn filters_models_by_tag() {
+        let spanish_medical = ModelTags {
+            family: "whisper-ft",
+            language: "es",
+            domain: "medical",
+            quantization: "q5_0",
+        };
+        let stock = ModelTags {
+            family: "whisper",
+            language: MULTILINGUAL,
+            domain: "general",
+            quantization: "f16",
+        };
+        let filter = ModelFilter {
+            language: Some("ES".to_string()),
+            domain: Some("medical".to_string()),
+            ..ModelFilter::default()
+        };
+        assert!(filter.matches(&spanish_medical));
+        assert!(!filter.matches(&stock));
+
+        let spanish = ModelFilter {
+            language: Some("es".to_string()),
+            ..ModelFilter::default()
+        };
+        assert!(spanish.matches(&stock));
+        assert!(ModelFilter::default().matches(&spanish_medical));
+    }
+
+    #[test]
+    fn resolves_quantized_variants() {
+        assert_eq!(variant_id("medium", "Q8_0"), Some("medium-q8_0"));
+        assert_eq!(variant_id("base-q5_1", "f16"), Some("base"));
+        assert_eq!(variant_id("large", "q5_0"), Some("large-v2-q5_0"));
+        assert_eq!(variant_id("large-v3", "q5_0"), Some("large-v3-q5_0"));
+        assert_eq!(variant_id("large-v3", "q8_0"), None);
+        assert_eq!(
+            variant_id("large-v3-turbo-q5_0", "q8_0"),
+            Some("large-v3-turbo-q8_0")
+        );
+        assert_eq!(
+            coreml_encoder_name("large-v3-turbo-q8_0").as_deref(),
+            Some("ggml-large-v3-turbo-encoder.mlmodelc")
+        );
+        assert_eq!(variant_id("small.en-tdrz", "q8_0"), None);
+        assert_eq!(
+            coreml_encoder_name("medium-q5_0").as_deref(),
+            Some("ggml-medium-encoder.mlmodelc")
+        );
+        assert_eq!(
+            coreml_encoder_name("large-v2-q8_0").as_deref(),
+            Some("ggml-large-v2-encoder.mlmodelc")
+        );
+        assert_eq!(coreml_encoder_name("small.en-tdrz"), None);
+        for model in MODEL_LIST.iter().filter(|model| model.variant_of.is_some()) {
+            let base = MODEL_LIST
+                .iter()
+                .find(|base| Some(base.id) == model.variant_of)
+                .unwrap();
+            assert!(model.size_mb < base.size_mb && model.min_bytes < base.min_bytes);
+        }
+    }
+
+    #[test]
+    fn names_imported_models_and_checks_their_header() {
+        assert_eq!(
+            imported_id("My Fine-tune (ES) v2"),
+            "imported-my-fine-tune-es-v2"
+        );
+        assert!(has_model_magic(b"lmgg\x01\x00"));
+        assert!(has_model_magic(b"GGUF\x03\x00"));
+        assert!(!has_model_magic(b"PK\x03\x04"));
+        assert!(!has_model_magic(b"lm"));
+    }
+
+    #[test]
+    fn splits_downloads_into_covering_ranges() {
+        assert_eq!(split_ranges(10, 4), vec![(0, 2), (3, 5), (6, 8), (9, 9)]);
+        assert_eq!(
+            split_ranges(5, 8),
+            vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]
+        );
+        assert_eq!(split_ranges(7, 1), vec![(0, 6)]);
+        assert_eq!(split_ranges(7, 0), vec![(0, 6)]);
+    }
+
+    #[test]
+    fn smooths_download_speed_into_an_eta() {
+        let start = std::time::Instant::now();
+        let at = |ms: u64| start + std::time::Duration::from_millis(ms);
+        let mut speed = DownloadSpeed::default();
+        assert_eq!(speed.sample(at(0), 0, Some(10_000)), (None, None));
+        assert_eq!(speed.sample(at(200), 400, Some(10_000)), (None, None));
+        assert_eq!(
+            speed.sample(at(1_000), 1_000, Some(10_000)),
+            (Some(1_000), Some(9))
+        );
+        assert_eq!(speed.sample(at(2_000), 3_000, None), (Some(1_300), None));
+    }
+}
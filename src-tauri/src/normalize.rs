@@ -0,0 +1,560 @@
+pub const SUPPORTED_LANGUAGES: &[&str] = &["en", "es"];
+
+/// Top-level domains a spoken "dot" must end in before words are joined into a domain.
+const TOP_LEVEL_DOMAINS: &[&str] = &[
+    "com", "org", "net", "io", "dev", "app", "ai", "edu", "gov", "co", "uk", "es", "mx", "ar",
+    "de", "fr", "it",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// One to nine.
+    Unit,
+    /// Zero and ten to nineteen (to twenty-nine in Spanish).
+    Teen,
+    Ten,
+    /// "hundred": multiplies what came before.
+    Hundred,
+    /// Spanish "doscientos" and friends: adds a whole hundred.
+    Hundreds,
+    Scale,
+    /// "and" / "y" inside a number.
+    And,
+}
+
+struct Lexicon {
+    words: &'static [(&'static str, Kind, u64)],
+    /// Kinds the joining word may follow: "one hundred and five", "treinta y cinco".
+    and_after: &'static [Kind],
+    percent: &'static [&'static [&'static str]],
+    /// Currency word, symbol, and whether the symbol goes before the number.
+    currencies: &'static [(&'static str, &'static str, bool)],
+    at: &'static str,
+    /// Words that end a clause before a spoken "at" rather than name a mailbox: "look at
+    /// example dot com" is a place to look, not an address.
+    not_mailboxes: &'static [&'static str],
+    dot: &'static str,
+    /// Whether two adjacent two-digit numbers read as one, the way years are spoken:
+    /// "nineteen eighty four", "twenty twenty four".
+    paired_numbers: bool,
+}
+
+const ENGLISH: Lexicon = Lexicon {
+    words: &[
+        ("zero", Kind::Teen, 0),
+        ("one", Kind::Unit, 1),
+        ("two", Kind::Unit, 2),
+        ("three", Kind::Unit, 3),
+        ("four", Kind::Unit, 4),
+        ("five", Kind::Unit, 5),
+        ("six", Kind::Unit, 6),
+        ("seven", Kind::Unit, 7),
+        ("eight", Kind::Unit, 8),
+        ("nine", Kind::Unit, 9),
+        ("ten", Kind::Teen, 10),
+        ("eleven", Kind::Teen, 11),
+        ("twelve", Kind::Teen, 12),
+        ("thirteen", Kind::Teen, 13),
+        ("fourteen", Kind::Teen, 14),
+        ("fifteen", Kind::Teen, 15),
+        ("sixteen", Kind::Teen, 16),
+        ("seventeen", Kind::Teen, 17),
+        ("eighteen", Kind::Teen, 18),
+        ("nineteen", Kind::Teen, 19),
+        ("twenty", Kind::Ten, 20),
+        ("thirty", Kind::Ten, 30),
+        ("forty", Kind::Ten, 40),
+        ("fifty", Kind::Ten, 50),
+        ("sixty", Kind::Ten, 60),
+        ("seventy", Kind::Ten, 70),
+        ("eighty", Kind::Ten, 80),
+        ("ninety", Kind::Ten, 90),
+        ("hundred", Kind::Hundred, 100),
+        ("thousand", Kind::Scale, 1_000),
+        ("million", Kind::Scale, 1_000_000),
+        ("billion", Kind::Scale, 1_000_000_000),
+        ("and", Kind::And, 0),
+    ],
+    and_after: &[Kind::Hundred, Kind::Scale],
+    percent: &[&["percent"], &["per", "cent"]],
+    currencies: &[
+        ("dollars", "$", true),
+        ("dollar", "$", true),
+        ("euros", "€", true),
+        ("euro", "€", true),
+    ],
+    at: "at",
+    not_mailboxes: &[
+        "look",
+        "looks",
+        "looked",
+        "looking",
+        "find",
+        "found",
+        "visit",
+        "see",
+        "check",
+        "go",
+        "read",
+        "online",
+        "available",
+        "here",
+        "there",
+        "live",
+        "posted",
+        "published",
+        "hosted",
+        "listed",
+        "is",
+        "are",
+        "was",
+        "were",
+        "be",
+        "it",
+        "me",
+        "us",
+        "you",
+        "him",
+        "her",
+        "them",
+        "up",
+        "in",
+        "out",
+        "arrive",
+        "arrived",
+        "stay",
+        "meet",
+        "work",
+        "works",
+        "worked",
+        "working",
+        "buy",
+        "order",
+        "download",
+        "register",
+        "apply",
+    ],
+    dot: "dot",
+    paired_numbers: true,
+};
+
+const SPANISH: Lexicon = Lexicon {
+    words: &[
+        ("cero", Kind::Teen, 0),
+        ("un", Kind::Unit, 1),
+        ("uno", Kind::Unit, 1),
+        ("una", Kind::Unit, 1),
+        ("dos", Kind::Unit, 2),
+        ("tres", Kind::Unit, 3),
+        ("cuatro", Kind::Unit, 4),
+        ("cinco", Kind::Unit, 5),
+        ("seis", Kind::Unit, 6),
+        ("siete", Kind::Unit, 7),
+        ("ocho", Kind::Unit, 8),
+        ("nueve", Kind::Unit, 9),
+        ("diez", Kind::Teen, 10),
+        ("once", Kind::Teen, 11),
+        ("doce", Kind::Teen, 12),
+        ("trece", Kind::Teen, 13),
+        ("catorce", Kind::Teen, 14),
+        ("quince", Kind::Teen, 15),
+        ("dieciséis", Kind::Teen, 16),
+        ("dieciseis", Kind::Teen, 16),
+        ("diecisiete", Kind::Teen, 17),
+        ("dieciocho", Kind::Teen, 18),
+        ("diecinueve", Kind::Teen, 19),
+        ("veinte", Kind::Ten, 20),
+        ("veintiuno", Kind::Teen, 21),
+        ("veintiún", Kind::Teen, 21),
+        ("veintidós", Kind::Teen, 22),
+        ("veintidos", Kind::Teen, 22),
+        ("veintitrés", Kind::Teen, 23),
+        ("veintitres", Kind::Teen, 23),
+        ("veinticuatro", Kind::Teen, 24),
+        ("veinticinco", Kind::Teen, 25),
+        ("veintiséis", Kind::Teen, 26),
+        ("veintiseis", Kind::Teen, 26),
+        ("veintisiete", Kind::Teen, 27),
+        ("veintiocho", Kind::Teen, 28),
+        ("veintinueve", Kind::Teen, 29),
+        ("treinta", Kind::Ten, 30),
+        ("cuarenta", Kind::Ten, 40),
+        ("cincuenta", Kind::Ten, 50),
+        ("sesenta", Kind::Ten, 60),
+        ("setenta", Kind::Ten, 70),
+        ("ochenta", Kind::Ten, 80),
+        ("noventa", Kind::Ten, 90),
+        ("cien", Kind::Hundreds, 100),
+        ("ciento", Kind::Hundreds, 100),
+        ("doscientos", Kind::Hundreds, 200),
+        ("doscientas", Kind::Hundreds, 200),
+        ("trescientos", Kind::Hundreds, 300),
+        ("trescientas", Kind::Hundreds, 300),
+        ("cuatrocientos", Kind::Hundreds, 400),
+        ("cuatrocientas", Kind::Hundreds, 400),
+        ("quinientos", Kind::Hundreds, 500),
+        ("quinientas", Kind::Hundreds, 500),
+        ("seiscientos", Kind::Hundreds, 600),
+        ("seiscientas", Kind::Hundreds, 600),
+        ("setecientos", Kind::Hundreds, 700),
+        ("setecientas", Kind::Hundreds, 700),
+        ("ochocientos", Kind::Hundreds, 800),
+        ("ochocientas", Kind::Hundreds, 800),
+        ("novecientos", Kind::Hundreds, 900),
+        ("novecientas", Kind::Hundreds, 900),
+        ("mil", Kind::Scale, 1_000),
+        ("millón", Kind::Scale, 1_000_000),
+        ("millon", Kind::Scale, 1_000_000),
+        ("millones", Kind::Scale, 1_000_000),
+        ("y", Kind::And, 0),
+    ],
+    and_after: &[Kind::Ten],
+    percent: &[&["por", "ciento"]],
+    currencies: &[
+        ("dólares", "$", true),
+        ("dolares", "$", true),
+        ("dólar", "$", true),
+        ("euros", "€", false),
+        ("euro", "€", false),
+    ],
+    at: "arroba",
+    not_mailboxes: &[],
+    dot: "punto",
+    paired_numbers: false,
+};
+
+fn lexicon(language: &str) -> Option<&'static Lexicon> {
+    match language {
+        "en" => Some(&ENGLISH),
+        "es" => Some(&SPANISH),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    /// Sentence punctuation that followed the word; a number or address ends at it.
+    trail: String,
+}
+
+impl Token {
+    fn is(&self, word: &str) -> bool {
+        self.text.to_lowercase() == word
+    }
+}
+
+/// Turns spoken forms in `text` dictated in `language` into written ones: number words to
+/// digits, percent and currency words to symbols, spelled-out domains and email addresses
+/// to the real thing. Languages outside `SUPPORTED_LANGUAGES` pass through unchanged;
+/// words inside a line are re-joined with single spaces.
+pub fn format_spoken(text: &str, language: &str) -> String {
+    let language = language.trim().to_ascii_lowercase();
+    let Some(lexicon) = lexicon(&language) else {
+        return text.to_string();
+    };
+    text.split('\n')
+        .map(|line| {
+            let tokens = tokenize(line, lexicon);
+            let tokens = join_addresses(format_numbers(tokens, lexicon), lexicon);
+            tokens
+                .iter()
+                .map(|token| format!("{}{}", token.text, token.trail))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn tokenize(line: &str, lexicon: &Lexicon) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for word in line.split_whitespace() {
+        let core = word.trim_end_matches([',', '.', ';', ':', '!', '?']);
+        let trail = &word[core.len()..];
+        let parts: Vec<&str> = core.split('-').collect();
+        // "twenty-five" reads as two number words; other hyphenated words stay whole.
+        let numeric = parts.len() > 1
+            && parts.iter().all(|part| {
+                let part = part.to_lowercase();
+                lexicon.words.iter().any(|(word, ..)| *word == part)
+            });
+        let parts = if numeric { parts } else { vec![core] };
+        let last = parts.len() - 1;
+        for (index, part) in parts.into_iter().enumerate() {
+            tokens.push(Token {
+                text: part.to_string(),
+                trail: if index == last {
+                    trail.to_string()
+                } else {
+                    String::new()
+                },
+            });
+        }
+    }
+    tokens
+}
+
+/// The number at the start of `tokens`: its digits, how many tokens it spans and whether
+/// it was already written in digits.
+fn parse_number(tokens: &[Token], lexicon: &Lexicon) -> Option<(String, usize, bool)> {
+    let first = tokens.first()?;
+    if !first.text.is_empty()
+        && first
+            .text
+            .chars()
+            .all(|ch| ch.is_ascii_digit() || ch == '.')
+        && first.text.starts_with(|ch: char| ch.is_ascii_digit())
+    {
+        return Some((first.text.clone(), 1, true));
+    }
+    let (mut total, mut current) = (0u64, 0u64);
+    let mut last: Option<Kind> = None;
+    let mut last_scale = u64::MAX;
+    let mut committed = None;
+    for (index, token) in tokens.iter().enumerate() {
+        let word = token.text.to_lowercase();
+        let Some(&(_, kind, value)) = lexicon.words.iter().find(|(entry, ..)| *entry == word)
+        else {
+            break;
+        };
+        use Kind::*;
+        let allowed = match kind {
+            Unit => matches!(last, None | Some(Ten | Hundred | Hundreds | Scale | And)),
+            Teen | Ten => matches!(last, None | Some(Hundred | Hundreds | Scale | And)),
+            Hundred => matches!(last, Some(Unit | Teen)) && current < 100,
+            Hundreds => matches!(last, None | Some(Scale)),
+            Scale => value < last_scale && last != Some(And),
+            And => last.is_some_and(|last| lexicon.and_after.contains(&last)),
+        };
+        if !allowed {
+            break;
+        }
+        match kind {
+            Hundred => current *= value,
+            Scale => {
+                total += current.max(1) * value;
+                current = 0;
+                last_scale = value;
+            }
+            And => {}
+            _ => current += value,
+        }
+        last = Some(kind);
+        if kind != And {
+            committed = Some((total + current, index + 1));
+        }
+        if !token.trail.is_empty() {
+            break;
+        }
+    }
+    committed.map(|(value, used)| (value.to_string(), used, false))
+}
+
+/// A percent or currency phrase at the start of `tokens`: the formatted number and how
+/// many tokens the phrase spans.
+fn with_unit(number: &str, tokens: &[Token], lexicon: &Lexicon) -> Option<(String, usize)> {
+    for phrase in lexicon.percent {
+        let matches = tokens.len() >= phrase.len()
+            && phrase
+                .iter()
+                .zip(tokens)
+                .enumerate()
+                .all(|(index, (word, token))| {
+                    token.is(word) && (index + 1 == phrase.len() || token.trail.is_empty())
+                });
+        if matches {
+            return Some((format!("{number}%"), phrase.len()));
+        }
+    }
+    let first = tokens.first()?;
+    lexicon
+        .currencies
+        .iter()
+        .find(|(word, ..)| first.is(word))
+        .map(|&(_, symbol, before)| {
+            let text = if before {
+                format!("{symbol}{number}")
+            } else {
+                format!("{number} {symbol}")
+            };
+            (text, 1)
+        })
+}
+
+/// Numbers of two or more words become digits; any number followed by a percent or
+/// currency word gets its symbol. Single number words ("one of them") are left alone.
+fn format_numbers(tokens: Vec<Token>, lexicon: &Lexicon) -> Vec<Token> {
+    let mut output = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        if let Some((mut number, mut used, literal)) = parse_number(&tokens[index..], lexicon) {
+            if !literal && lexicon.paired_numbers {
+                if let Some((pair, pair_used)) = pair_with(&number, &tokens[index..], used, lexicon)
+                {
+                    number = pair;
+                    used += pair_used;
+                }
+            }
+            let last = &tokens[index + used - 1];
+            let rest = &tokens[index + used..];
+            let unit = if last.trail.is_empty() {
+                with_unit(&number, rest, lexicon)
+            } else {
+                None
+            };
+            if let Some((text, unit_used)) = unit {
+                output.push(Token {
+                    text,
+                    trail: rest[unit_used - 1].trail.clone(),
+                });
+                index += used + unit_used;
+                continue;
+            }
+            if used >= 2 && !literal {
+                output.push(Token {
+                    text: number,
+                    trail: last.trail.clone(),
+                });
+                index += used;
+                continue;
+            }
+        }
+        output.push(tokens[index].clone());
+        index += 1;
+    }
+    output
+}
+
+/// Reads `number`, spoken over the first `used` tokens, together with the number right
+/// after it when both are two-digit numbers, the way years are said: the joined digits
+/// and how many extra tokens the second number spans.
+fn pair_with(
+    number: &str,
+    tokens: &[Token],
+    used: usize,
+    lexicon: &Lexicon,
+) -> Option<(String, usize)> {
+    let two_digits = |number: &str| number.parse::<u64>().is_ok_and(|n| (10..100).contains(&n));
+    if !two_digits(number) || !tokens[used - 1].trail.is_empty() {
+        return None;
+    }
+    let (next, next_used, literal) = parse_number(&tokens[used..], lexicon)?;
+    (!literal && two_digits(&next)).then(|| (format!("{number}{next}"), next_used))
+}
+
+/// A spoken domain at the start of `tokens` ("example dot com"), lowercased, and how
+/// many tokens it spans.
+fn parse_domain(tokens: &[Token], lexicon: &Lexicon) -> Option<(String, usize)> {
+    let is_label = |token: &Token| {
+        !token.text.is_empty()
+            && token
+                .text
+                .chars()
+                .all(|ch| ch.is_alphanumeric() || ch == '-')
+    };
+    let first = tokens.first().filter(|token| is_label(token))?;
+    let mut labels = vec![first.text.to_lowercase()];
+    let mut used = 1;
+    let mut end = None;
+    while let (Some(dot), Some(label)) = (tokens.get(used), tokens.get(used + 1)) {
+        let joined = tokens[used - 1].trail.is_empty() && dot.trail.is_empty();
+        if !joined || !dot.is(lexicon.dot) || !is_label(label) {
+            break;
+        }
+        labels.push(label.text.to_lowercase());
+        used += 2;
+        if TOP_LEVEL_DOMAINS.contains(&labels[labels.len() - 1].as_str()) {
+            end = Some((labels.join("."), used));
+        }
+    }
+    end
+}
+
+/// Joins spoken domains, and a preceding "name at" into an email address unless the word
+/// before "at" is one that rarely names a mailbox.
+fn join_addresses(tokens: Vec<Token>, lexicon: &Lexicon) -> Vec<Token> {
+    let mut output: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        let Some((domain, used)) = parse_domain(&tokens[index..], lexicon) else {
+            output.push(tokens[index].clone());
+            index += 1;
+            continue;
+        };
+        let trail = tokens[index + used - 1].trail.clone();
+        let at = output
+            .last()
+            .is_some_and(|token| token.is(lexicon.at) && token.trail.is_empty());
+        let user = output.len().checked_sub(2).map(|user| &output[user]);
+        let mailbox = user.is_some_and(|user| {
+            user.trail.is_empty()
+                && !lexicon
+                    .not_mailboxes
+                    .iter()
+                    .any(|word| user.text.to_lowercase() == *word)
+        });
+        let text = if at && mailbox {
+            output.pop();
+            let user = output.pop().map(|user| user.text).unwrap_or_default();
+            format!("{user}@{domain}")
+        } else {
+            domain
+        };
+        output.push(Token { text, trail });
+        index += used;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_spoken;
+
+    #[test]
+    fn formats_numbers_units_and_addresses() {
+        assert_eq!(
+            format_spoken(
+                "Sales grew twenty five percent to three thousand four hundred and ten dollars.",
+                "en"
+            ),
+            "Sales grew 25% to $3410."
+        );
+        assert_eq!(
+            format_spoken("one of the twenty-one tests, not one two", "en"),
+            "one of the 21 tests, not one two"
+        );
+        assert_eq!(
+            format_spoken(
+                "write to jane at gmail dot com or see example dot co dot uk.",
+                "en"
+            ),
+            "write to jane@gmail.com or see example.co.uk."
+        );
+        assert_eq!(
+            format_spoken(
+                "cuesta ciento treinta y cinco euros, un cinco por ciento más",
+                "es"
+            ),
+            "cuesta 135 €, un 5% más"
+        );
+        assert_eq!(
+            format_spoken(
+                "look at google dot com, then email me at sales at example dot com",
+                "en"
+            ),
+            "look at google.com, then email me at sales@example.com"
+        );
+        assert_eq!(
+            format_spoken(
+                "born in nineteen eighty four, back in twenty twenty four",
+                "en"
+            ),
+            "born in 1984, back in 2024"
+        );
+        assert_eq!(
+            format_spoken("twenty five percent", "fr"),
+            "twenty five percent"
+        );
+    }
+}
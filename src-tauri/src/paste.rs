@@ -1,33 +1,418 @@
-use anyhow::Result;
-use arboard::Clipboard;
+use crate::wayland_input::{
+    self, Stroke, KEY_BACKSPACE, KEY_CONTROL, KEY_INSERT, KEY_RETURN, KEY_SHIFT,
+};
+use anyhow::{Context, Result};
+use arboard::{Clipboard, ImageData};
 use enigo::{
     Direction::{Click, Press, Release},
     Enigo, Key as EnigoKey, Keyboard, Settings,
 };
+use std::path::PathBuf;
 use std::process::Command;
 use std::thread::sleep;
 use std::time::Duration;
 
-pub fn paste_text(text: &str) -> Result<()> {
+pub const OUTPUT_PASTE: &str = "paste";
+pub const OUTPUT_PROGRESSIVE: &str = "progressive";
+/// Types the text key by key, for targets that ignore a synthetic paste (VMs, RDP).
+pub const OUTPUT_TYPE: &str = "type";
+/// Only puts the text on the clipboard and tells the user.
+pub const OUTPUT_CLIPBOARD: &str = "clipboard";
+
+/// What to do when the clipboard holds an image or files (e.g. a pending cut) at paste time.
+pub const CLIPBOARD_GUARD_OFF: &str = "off";
+pub const CLIPBOARD_GUARD_RESTORE: &str = "restore";
+pub const CLIPBOARD_GUARD_REVIEW: &str = "review";
+
+/// What follows each delivered transcript, so consecutive dictations don't run together.
+pub const APPEND_NONE: &str = "none";
+pub const APPEND_SPACE: &str = "space";
+pub const APPEND_NEWLINE: &str = "newline";
+
+/// Which shortcut pastes: "auto" picks one for the focused app, the others force it.
+/// Ctrl means Cmd on macOS.
+pub const PASTE_KEYS_AUTO: &str = "auto";
+pub const PASTE_KEYS_CTRL_V: &str = "ctrl_v";
+pub const PASTE_KEYS_CTRL_SHIFT_V: &str = "ctrl_shift_v";
+pub const PASTE_KEYS_SHIFT_INSERT: &str = "shift_insert";
+
+/// Window classes and process names of terminals, where Ctrl+V does not paste on Linux.
+const TERMINALS: &[&str] = &[
+    "terminal",
+    "konsole",
+    "alacritty",
+    "kitty",
+    "wezterm",
+    "xterm",
+    "urxvt",
+    "tilix",
+    "terminator",
+    "ghostty",
+    "foot",
+    "guake",
+    "yakuake",
+];
+
+/// Time the target app gets to read the pasted text before guarded contents come back.
+const RESTORE_DELAY: Duration = Duration::from_millis(300);
+/// Pause between typed characters; remote sessions drop keys that arrive faster.
+const TYPE_DELAY: Duration = Duration::from_millis(8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteOutcome {
+    Pasted,
+    /// Left the clipboard alone because it held non-text contents.
+    Held,
+    /// Put on the clipboard without pasting (presentation mode or clipboard output).
+    Copied,
+}
+
+enum PendingContents {
+    Image(ImageData<'static>),
+    Files(Vec<PathBuf>),
+    Text(String),
+}
+
+fn pending_contents(clipboard: &mut Clipboard) -> Option<PendingContents> {
+    if let Ok(files) = clipboard.get().file_list() {
+        if !files.is_empty() {
+            return Some(PendingContents::Files(files));
+        }
+    }
+    clipboard.get_image().ok().map(PendingContents::Image)
+}
+
+/// Pastes `text` unless that would clobber an image or file list on the clipboard, in
+/// which case `guard` decides whether to restore it afterwards or hold the text back.
+/// With `restore_text`, text that was on the clipboard comes back after the paste too;
+/// otherwise the transcript stays there. `keys` is a resolved `PASTE_KEYS_*` shortcut.
+pub fn paste_guarded(
+    text: &str,
+    guard: &str,
+    restore_text: bool,
+    keys: &str,
+) -> Result<PasteOutcome> {
+    let pending = if guard == CLIPBOARD_GUARD_OFF {
+        None
+    } else {
+        Clipboard::new()
+            .ok()
+            .and_then(|mut clipboard| pending_contents(&mut clipboard))
+    };
+    if pending.is_some() && guard == CLIPBOARD_GUARD_REVIEW {
+        return Ok(PasteOutcome::Held);
+    }
+    let pending = pending.or_else(|| {
+        if !restore_text {
+            return None;
+        }
+        let previous = Clipboard::new().ok()?.get_text().ok()?;
+        (previous != text).then_some(PendingContents::Text(previous))
+    });
+    paste_text(text, keys)?;
+    let Some(pending) = pending else {
+        return Ok(PasteOutcome::Pasted);
+    };
+    sleep(RESTORE_DELAY);
+    restore_contents(&mut Clipboard::new()?, pending)?;
+    Ok(PasteOutcome::Pasted)
+}
+
+fn restore_contents(clipboard: &mut Clipboard, pending: PendingContents) -> Result<()> {
+    match pending {
+        PendingContents::Image(image) => clipboard.set_image(image)?,
+        // Restores the paths; a platform "cut" marker is not preserved.
+        PendingContents::Files(files) => clipboard.set().file_list(&files)?,
+        PendingContents::Text(previous) => clipboard.set_text(previous)?,
+    }
+    Ok(())
+}
+
+/// Runs `f` on the clipboard, then puts back the image, file list or text it held
+/// before, or empties it if it held nothing.
+pub fn keeping_clipboard<T>(f: impl FnOnce(&mut Clipboard) -> Result<T>) -> Result<T> {
+    let mut clipboard = Clipboard::new()?;
+    let previous = pending_contents(&mut clipboard)
+        .or_else(|| clipboard.get_text().ok().map(PendingContents::Text));
+    let result = f(&mut clipboard);
+    let restored = match previous {
+        Some(previous) => restore_contents(&mut clipboard, previous),
+        None => clipboard.clear().map_err(Into::into),
+    };
+    let value = result?;
+    restored.context("restore clipboard")?;
+    Ok(value)
+}
+
+pub fn copy_text(text: &str) -> Result<()> {
+    Clipboard::new()?.set_text(text.to_string())?;
+    Ok(())
+}
+
+/// The text an `APPEND_*` setting adds after a transcript.
+pub fn appended(setting: &str) -> &'static str {
+    match setting {
+        APPEND_SPACE => " ",
+        APPEND_NEWLINE => "\n",
+        _ => "",
+    }
+}
+
+/// The shortcut `setting` stands for in `app`. "auto" uses Ctrl+V, except in Linux
+/// terminals, and keeps the old Ctrl+Shift+V when Linux cannot tell what has focus.
+pub fn resolve_paste_keys(setting: &str, app: Option<&str>) -> &'static str {
+    match setting {
+        PASTE_KEYS_CTRL_V => return PASTE_KEYS_CTRL_V,
+        PASTE_KEYS_CTRL_SHIFT_V => return PASTE_KEYS_CTRL_SHIFT_V,
+        PASTE_KEYS_SHIFT_INSERT => return PASTE_KEYS_SHIFT_INSERT,
+        _ => {}
+    }
+    if !cfg!(target_os = "linux") {
+        return PASTE_KEYS_CTRL_V;
+    }
+    match app.map(str::to_lowercase) {
+        Some(app) if TERMINALS.iter().any(|terminal| app.contains(terminal)) => {
+            PASTE_KEYS_CTRL_SHIFT_V
+        }
+        Some(_) => PASTE_KEYS_CTRL_V,
+        None => PASTE_KEYS_CTRL_SHIFT_V,
+    }
+}
+
+/// Whether `setting` is one of the `PASTE_KEYS_*` values.
+pub fn is_paste_keys(setting: &str) -> bool {
+    [
+        PASTE_KEYS_AUTO,
+        PASTE_KEYS_CTRL_V,
+        PASTE_KEYS_CTRL_SHIFT_V,
+        PASTE_KEYS_SHIFT_INSERT,
+    ]
+    .contains(&setting)
+}
+
+/// Whether `resolve_paste_keys` needs the focused app to resolve `setting`.
+pub fn keys_follow_app(setting: &str) -> bool {
+    cfg!(target_os = "linux")
+        && ![
+            PASTE_KEYS_CTRL_V,
+            PASTE_KEYS_CTRL_SHIFT_V,
+            PASTE_KEYS_SHIFT_INSERT,
+        ]
+        .contains(&setting)
+}
+
+/// Puts `text` on the clipboard and presses the `keys` shortcut once. On Wayland the
+/// portal and then wtype are tried first; enigo only sends the keys when both could not.
+pub fn paste_text(text: &str, keys: &str) -> Result<()> {
     let mut clipboard = Clipboard::new()?;
     clipboard.set_text(text.to_string())?;
 
     if std::env::var("WAYLAND_DISPLAY").is_ok() {
-        let _ = Command::new("wtype")
-            .args([
+        match wayland_paste(keys) {
+            Ok(()) => return Ok(()),
+            Err(err) => eprintln!("Wayland paste failed, sending keys with enigo: {err:#}"),
+        }
+    }
+
+    let mut enigo = Enigo::new(&Settings::default())?;
+    let (modifiers, key): (&[EnigoKey], EnigoKey) = match keys {
+        PASTE_KEYS_CTRL_SHIFT_V => (&[COMMAND_KEY, EnigoKey::Shift], EnigoKey::Unicode('v')),
+        #[cfg(not(target_os = "macos"))]
+        PASTE_KEYS_SHIFT_INSERT => (&[EnigoKey::Shift], EnigoKey::Insert),
+        _ => (&[COMMAND_KEY], EnigoKey::Unicode('v')),
+    };
+    for modifier in modifiers {
+        enigo.key(*modifier, Press)?;
+    }
+    let clicked = enigo.key(key, Click);
+    for modifier in modifiers.iter().rev() {
+        let _ = enigo.key(*modifier, Release);
+    }
+    clicked?;
+    sleep(Duration::from_millis(20));
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+const COMMAND_KEY: EnigoKey = EnigoKey::Meta;
+#[cfg(not(target_os = "macos"))]
+const COMMAND_KEY: EnigoKey = EnigoKey::Control;
+
+fn wayland_paste(keys: &str) -> Result<()> {
+    let v = 'v' as i32;
+    let (strokes, args): (_, &[&str]) = match keys {
+        PASTE_KEYS_CTRL_SHIFT_V => (
+            wayland_input::chord(&[KEY_CONTROL, KEY_SHIFT], v),
+            &[
                 "-M", "ctrl", "-M", "shift", "-k", "v", "-m", "shift", "-m", "ctrl",
-            ])
-            .status();
+            ],
+        ),
+        PASTE_KEYS_SHIFT_INSERT => (
+            wayland_input::chord(&[KEY_SHIFT], KEY_INSERT),
+            &["-M", "shift", "-k", "Insert", "-m", "shift"],
+        ),
+        _ => (
+            wayland_input::chord(&[KEY_CONTROL], v),
+            &["-M", "ctrl", "-k", "v", "-m", "ctrl"],
+        ),
+    };
+    wayland_keys(strokes, Duration::ZERO, args)
+}
+
+/// Sends keys on Wayland through the RemoteDesktop portal, which stock desktops
+/// provide, falling back to running wtype with `wtype_args`.
+fn wayland_keys(strokes: Vec<Stroke>, delay: Duration, wtype_args: &[&str]) -> Result<()> {
+    let Err(portal_err) = wayland_input::send(strokes, delay) else {
+        return Ok(());
+    };
+    let status = Command::new("wtype")
+        .args(wtype_args)
+        .status()
+        .with_context(|| format!("{portal_err:#}; wtype did not run"))?;
+    if !status.success() {
+        anyhow::bail!("{portal_err:#}; wtype exited with {status}");
+    }
+    Ok(())
+}
+
+/// Types `text` one character at a time instead of pasting it; the clipboard is left
+/// untouched.
+pub fn type_out(text: &str) -> Result<()> {
+    if text.is_empty() {
         return Ok(());
     }
+    if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        let delay = TYPE_DELAY.as_millis().to_string();
+        let strokes = wayland_input::text_strokes(text);
+        return wayland_keys(strokes, TYPE_DELAY, &["-d", delay.as_str(), "--", text]);
+    }
+    let mut enigo = Enigo::new(&Settings::default())?;
+    let mut buf = [0u8; 4];
+    for ch in text.chars() {
+        if ch == '\n' {
+            enigo.key(EnigoKey::Return, Click)?;
+        } else {
+            enigo.text(ch.encode_utf8(&mut buf))?;
+        }
+        sleep(TYPE_DELAY);
+    }
+    Ok(())
+}
 
-    if let Ok(mut enigo) = Enigo::new(&Settings::default()) {
-        let _ = enigo.key(EnigoKey::Control, Press);
-        let _ = enigo.key(EnigoKey::Shift, Press);
-        let _ = enigo.key(EnigoKey::Unicode('v'), Click);
-        let _ = enigo.key(EnigoKey::Shift, Release);
-        let _ = enigo.key(EnigoKey::Control, Release);
-        sleep(Duration::from_millis(20));
+/// Presses Enter in the focused app, e.g. to send a message that was just pasted.
+pub fn press_enter() -> Result<()> {
+    // Gives the target time to take in the paste first.
+    sleep(Duration::from_millis(80));
+    if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        let strokes = wayland_input::tap(KEY_RETURN).to_vec();
+        return wayland_keys(strokes, Duration::ZERO, &["-k", "Return"]);
     }
+    let mut enigo = Enigo::new(&Settings::default())?;
+    enigo.key(EnigoKey::Return, Click)?;
     Ok(())
 }
+
+/// Types finalized segments into the focused app as they arrive, backspacing over the
+/// tail of previously typed text when a revision changes it.
+#[derive(Default)]
+pub struct ProgressiveTyper {
+    typed: String,
+}
+
+impl ProgressiveTyper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, confirmed: &str) -> Result<()> {
+        let (backspaces, suffix) = typing_edit(&self.typed, confirmed);
+        if backspaces == 0 && suffix.is_empty() {
+            return Ok(());
+        }
+        send_backspaces(backspaces)?;
+        type_text(suffix)?;
+        self.typed = confirmed.to_string();
+        Ok(())
+    }
+}
+
+/// Returns how many characters to erase from `typed` and what to type afterwards so the
+/// target app ends up showing `target`.
+pub fn typing_edit<'a>(typed: &str, target: &'a str) -> (usize, &'a str) {
+    let common = typed
+        .char_indices()
+        .zip(target.chars())
+        .take_while(|((_, left), right)| left == right)
+        .last()
+        .map(|((index, ch), _)| index + ch.len_utf8())
+        .unwrap_or(0);
+    let backspaces = typed[common..].chars().count();
+    (backspaces, &target[common..])
+}
+
+fn send_backspaces(count: usize) -> Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        let mut args = Vec::with_capacity(count * 2);
+        for _ in 0..count {
+            args.extend(["-k", "BackSpace"]);
+        }
+        let strokes = wayland_input::tap(KEY_BACKSPACE).repeat(count);
+        return wayland_keys(strokes, Duration::ZERO, &args);
+    }
+
+    let mut enigo = Enigo::new(&Settings::default())?;
+    for _ in 0..count {
+        let _ = enigo.key(EnigoKey::Backspace, Click);
+    }
+    Ok(())
+}
+
+fn type_text(text: &str) -> Result<()> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        let strokes = wayland_input::text_strokes(text);
+        return wayland_keys(strokes, Duration::ZERO, &["--", text]);
+    }
+
+    let mut enigo = Enigo::new(&Settings::default())?;
+    enigo.text(text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_paste_keys, typing_edit, PASTE_KEYS_SHIFT_INSERT};
+
+    #[test]
+    fn appends_when_prefix_is_unchanged() {
+        assert_eq!(typing_edit("hello", "hello world"), (0, " world"));
+        assert_eq!(typing_edit("", "hello"), (0, "hello"));
+    }
+
+    #[test]
+    fn backspaces_over_revised_tail() {
+        assert_eq!(typing_edit("I scream", "Ice cream"), (7, "ce cream"));
+        assert_eq!(typing_edit("café au", "café olé"), (2, "olé"));
+    }
+
+    #[test]
+    fn picks_paste_keys_for_the_focused_app() {
+        assert_eq!(
+            resolve_paste_keys(PASTE_KEYS_SHIFT_INSERT, Some("Firefox")),
+            PASTE_KEYS_SHIFT_INSERT
+        );
+        if cfg!(target_os = "linux") {
+            assert_eq!(resolve_paste_keys("auto", Some("Firefox")), "ctrl_v");
+            assert_eq!(
+                resolve_paste_keys("auto", Some("gnome-terminal-server")),
+                "ctrl_shift_v"
+            );
+            assert_eq!(resolve_paste_keys("auto", None), "ctrl_shift_v");
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use crate::transcription::Segment;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacementRule {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub updated_at: u64,
+    /// `from` is a regular expression and `to` may reference its groups (`$1`, `$name`).
+    #[serde(default)]
+    pub regex: bool,
+}
+
+pub fn validate_rule(from: &str, regex: bool) -> Result<()> {
+    if from.trim().is_empty() {
+        anyhow::bail!("replacement phrase is empty");
+    }
+    if regex {
+        Regex::new(from).context("invalid replacement pattern")?;
+    }
+    Ok(())
+}
+
+pub fn apply_replacements(text: &str, rules: &[ReplacementRule]) -> String {
+    let mut output = text.to_string();
+    for rule in rules {
+        if rule.from.trim().is_empty() {
+            continue;
+        }
+        output = if rule.regex {
+            // Patterns are validated when saved; one arriving broken via an import is skipped.
+            match Regex::new(&rule.from) {
+                Ok(pattern) => pattern.replace_all(&output, rule.to.as_str()).into_owned(),
+                Err(_) => output,
+            }
+        } else {
+            replace_words(&output, &rule.from, &rule.to)
+        };
+    }
+    output
+}
+
+pub const BIDI_OFF: &str = "off";
+/// Right-to-left marks (U+200F) around the text.
+pub const BIDI_MARKS: &str = "marks";
+/// A right-to-left embedding (U+202B) closed by a pop (U+202C).
+pub const BIDI_EMBEDDING: &str = "embedding";
+
+/// Languages whisper transcribes in a right-to-left script.
+const RTL_LANGUAGES: &[&str] = &["ar", "fa", "he", "ps", "sd", "ur", "yi"];
+
+/// Wraps `text` dictated in `language` so it keeps its direction when pasted into a
+/// left-to-right field; left-to-right languages and `BIDI_OFF` pass through unchanged.
+pub fn wrap_direction(text: &str, language: &str, mode: &str) -> String {
+    let language = language.trim().to_ascii_lowercase();
+    if text.is_empty() || !RTL_LANGUAGES.contains(&language.as_str()) {
+        return text.to_string();
+    }
+    match mode {
+        BIDI_MARKS => format!("\u{200F}{text}\u{200F}"),
+        BIDI_EMBEDDING => format!("\u{202B}{text}\u{202C}"),
+        _ => text.to_string(),
+    }
+}
+
+/// Opens each line of a diarized transcript.
+const SPEAKER_TURN_MARK: &str = "- ";
+
+/// Segments joined into one dash-led line per speaker turn. Turns are not numbered: the
+/// model only marks where the speaker changes, and a third voice would get the label of
+/// the first.
+pub fn mark_speaker_turns(segments: &[Segment]) -> String {
+    let mut turns = Vec::new();
+    let mut turn = String::new();
+    for segment in segments {
+        if !turn.is_empty() {
+            turn.push(' ');
+        }
+        turn.push_str(&segment.text);
+        if segment.speaker_turn {
+            turns.push(std::mem::take(&mut turn));
+        }
+    }
+    turns.push(turn);
+    turns
+        .iter()
+        .filter(|turn| !turn.is_empty())
+        .map(|turn| format!("{SPEAKER_TURN_MARK}{turn}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// English words that open a question when they start a sentence.
+const QUESTION_WORDS: &[&str] = &[
+    "what", "why", "how", "when", "where", "who", "which", "is", "are", "can", "could", "would",
+    "should", "do", "does", "did", "will",
+];
+
+/// Rule-based capitalization and end punctuation for models that emit lowercase,
+/// unpunctuated text. Sentences end at end punctuation or a line break, and each gets its
+/// own end mark; spacing and line breaks are kept. Text that already has both capitals
+/// and sentence punctuation is left as the model wrote it.
+pub fn restore_punctuation(text: &str, language: &str) -> String {
+    let text = text.trim();
+    let has_capitals = text.chars().any(char::is_uppercase);
+    let has_punctuation = text.chars().any(|ch| matches!(ch, '.' | '?' | '!' | '。'));
+    if text.is_empty() || (has_capitals && has_punctuation) {
+        return text.to_string();
+    }
+    let language = language.trim().to_ascii_lowercase();
+    let english = language == "en";
+    // Ends the sentence opened by `first` when it has no end punctuation of its own.
+    let end_sentence = |output: &mut String, first: Option<String>| {
+        let Some(first) = first else {
+            return;
+        };
+        if output
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+        {
+            output.push(match language.as_str() {
+                "zh" | "ja" => '。',
+                _ if english && QUESTION_WORDS.contains(&first.as_str()) => '?',
+                _ => '.',
+            });
+        }
+    };
+    let mut output = String::with_capacity(text.len() + 1);
+    let mut sentence_start = true;
+    let mut first_word = None;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let word_start = rest.len() - rest.trim_start().len();
+        let (space, after) = rest.split_at(word_start);
+        if space.contains('\n') {
+            end_sentence(&mut output, first_word.take());
+            sentence_start = true;
+        }
+        output.push_str(space);
+        let (word, after) = after.split_at(after.find(char::is_whitespace).unwrap_or(after.len()));
+        rest = after;
+        // Bullets and dashes leave the next word to open the sentence.
+        if !word.contains(char::is_alphanumeric) && !word.ends_with(['.', '?', '!']) {
+            output.push_str(word);
+            continue;
+        }
+        let lower = word.to_lowercase();
+        let pronoun = english && (lower == "i" || lower.starts_with("i'"));
+        if sentence_start || pronoun {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                output.extend(first.to_uppercase());
+                output.push_str(chars.as_str());
+            }
+        } else {
+            output.push_str(word);
+        }
+        first_word.get_or_insert(lower);
+        sentence_start = word.ends_with(['.', '?', '!']);
+        if sentence_start {
+            first_word = None;
+        }
+    }
+    end_sentence(&mut output, first_word);
+    output
+}
+
+pub const PROFANITY_OFF: &str = "off";
+/// Keeps the first letter and stars out the rest.
+pub const PROFANITY_MASK: &str = "mask";
+/// Drops the word and tidies the spacing it leaves behind.
+pub const PROFANITY_REMOVE: &str = "remove";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProfanityFilter {
+    pub mode: String,
+    /// Use the built-in list for the dictation language on top of `words`.
+    pub builtin: bool,
+    /// Extra single words to filter, matched case-insensitively.
+    pub words: Vec<String>,
+}
+
+impl Default for ProfanityFilter {
+    fn default() -> Self {
+        Self {
+            mode: PROFANITY_OFF.to_string(),
+            builtin: true,
+            words: Vec::new(),
+        }
+    }
+}
+
+/// A deliberately short list of the most common swear words per language; anything
+/// more specific, or also in use as a name (like "dick"), belongs in the user's own list.
+fn builtin_profanity(language: &str) -> &'static [&'static str] {
+    match language {
+        "en" => &[
+            "fuck",
+            "fucking",
+            "fucked",
+            "shit",
+            "shitty",
+            "bitch",
+            "bastard",
+            "asshole",
+            "cunt",
+            "motherfucker",
+            "bullshit",
+            "damn",
+            "crap",
+        ],
+        "es" => &[
+            "mierda",
+            "joder",
+            "coño",
+            "puta",
+            "puto",
+            "cabrón",
+            "gilipollas",
+            "pendejo",
+            "carajo",
+            "hostia",
+        ],
+        "fr" => &[
+            "merde", "putain", "connard", "connasse", "salope", "enculé", "bordel", "chiant",
+        ],
+        "de" => &[
+            "scheiße",
+            "scheisse",
+            "arschloch",
+            "fick",
+            "ficken",
+            "verdammt",
+            "miststück",
+            "wichser",
+        ],
+        "it" => &[
+            "cazzo",
+            "merda",
+            "stronzo",
+            "vaffanculo",
+            "puttana",
+            "coglione",
+        ],
+        "pt" => &["merda", "porra", "caralho", "puta", "foda", "cacete"],
+        _ => &[],
+    }
+}
+
+/// Masks or removes filtered words in `text` dictated in `language`. Words are runs of
+/// letters and digits, so a filtered word inside a longer one is left alone.
+pub fn filter_profanity(text: &str, language: &str, filter: &ProfanityFilter) -> String {
+    let remove = match filter.mode.as_str() {
+        PROFANITY_MASK => false,
+        PROFANITY_REMOVE => true,
+        _ => return text.to_string(),
+    };
+    let language = language.trim().to_ascii_lowercase();
+    let builtin = if filter.builtin {
+        builtin_profanity(&language)
+    } else {
+        &[]
+    };
+    let blocked = |word: &str| {
+        let word = word.to_lowercase();
+        builtin.contains(&word.as_str())
+            || filter
+                .words
+                .iter()
+                .any(|entry| entry.trim().to_lowercase() == word)
+    };
+    edit_words(text, |word, _| {
+        if !blocked(word) {
+            Some(word.to_string())
+        } else if remove {
+            None
+        } else {
+            let mut chars = word.chars();
+            Some(chars.next().into_iter().chain(chars.map(|_| '*')).collect())
+        }
+    })
+}
+
+/// Hesitation sounds whisper writes out, per language. The second list holds fillers
+/// that are also real words, dropped only when set off by a comma.
+fn fillers(language: &str) -> (&'static [&'static str], &'static [&'static str]) {
+    match language {
+        "en" => (
+            &["um", "umm", "uh", "uhh", "uhm", "er", "erm", "hmm", "mm"],
+            &[],
+        ),
+        "es" => (&["eh", "ehm", "em", "mmm", "hmm"], &["este", "bueno"]),
+        "fr" => (&["euh", "heu", "hum", "bah"], &["ben"]),
+        "de" => (&["äh", "ähm", "öh", "öhm", "hm", "hmm"], &["also"]),
+        "it" => (&["ehm", "ehh", "uhm", "mmm"], &["cioè"]),
+        "pt" => (&["ahn", "hum", "humm", "éh"], &["tipo"]),
+        _ => (&["um", "uh", "hmm"], &[]),
+    }
+}
+
+/// Drops filler words from `text` dictated in `language`.
+pub fn remove_fillers(text: &str, language: &str) -> String {
+    let (always, set_off) = fillers(&language.trim().to_ascii_lowercase());
+    edit_words(text, |word, after| {
+        let lower = word.to_lowercase();
+        let filler = always.contains(&lower.as_str())
+            || (set_off.contains(&lower.as_str()) && after.starts_with(','));
+        (!filler).then(|| word.to_string())
+    })
+}
+
+/// Rewrites each word (a run of letters and digits) through `edit`, which gets the word
+/// and the text after it and returns the replacement, or `None` to drop the word. A
+/// dropped word takes a following comma and one of the spaces around it with it, and
+/// hands a leading capital on to the next word; all other spacing is kept.
+fn edit_words(text: &str, edit: impl Fn(&str, &str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut capitalize = false;
+    let mut rest = text;
+    while let Some(start) = rest.find(char::is_alphanumeric) {
+        output.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let end = tail
+            .find(|ch: char| !ch.is_alphanumeric())
+            .unwrap_or(tail.len());
+        let (word, after) = tail.split_at(end);
+        rest = after;
+        match edit(word, after) {
+            Some(replacement) if capitalize => {
+                capitalize = false;
+                let mut chars = replacement.chars();
+                output.extend(chars.next().into_iter().flat_map(char::to_uppercase));
+                output.push_str(chars.as_str());
+            }
+            Some(replacement) => output.push_str(&replacement),
+            None => {
+                capitalize |= word.starts_with(char::is_uppercase);
+                rest = rest.strip_prefix(',').unwrap_or(rest);
+                if output.is_empty() || output.ends_with(char::is_whitespace) {
+                    rest = rest.strip_prefix(' ').unwrap_or(rest);
+                }
+                let closes =
+                    rest.is_empty() || rest.starts_with(['.', ',', '!', '?', ';', ':', '\n']);
+                if closes && output.ends_with(' ') {
+                    output.pop();
+                }
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn replace_words(text: &str, from: &str, to: &str) -> String {
+    let needle = from.to_lowercase();
+    if needle.is_empty() {
+        return text.to_string();
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (start, _) in text.char_indices() {
+        if start < cursor {
+            continue;
+        }
+        let Some(len) = lowercase_prefix_len(&text[start..], &needle) else {
+            continue;
+        };
+        let end = start + len;
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        if is_word_char(before) || is_word_char(after) {
+            continue;
+        }
+        output.push_str(&text[cursor..start]);
+        output.push_str(to);
+        cursor = end;
+    }
+    output.push_str(&text[cursor..]);
+    output
+}
+
+/// Byte length of the start of `text` that lowercases to `needle`. Compares char by char
+/// on the original text, since lowercasing can change byte lengths ("İ" grows, "ẞ"
+/// shrinks) and offsets into a lowercased copy would not line up.
+fn lowercase_prefix_len(text: &str, needle: &str) -> Option<usize> {
+    let mut rest = needle;
+    for (index, ch) in text.char_indices() {
+        if rest.is_empty() {
+            return Some(index);
+        }
+        for lower in ch.to_lowercase() {
+            rest = rest.strip_prefix(lower)?;
+        }
+    }
+    rest.is_empty().then_some(text.len())
+}
+
+fn is_word_char(ch: Option<char>) -> bool {
+    ch.map(char::is_alphanumeric).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_replacements, filter_profanity, mark_speaker_turns, remove_fillers,
+        restore_punctuation, validate_rule, wrap_direction, ProfanityFilter, ReplacementRule,
+        BIDI_EMBEDDING, BIDI_MARKS, BIDI_OFF, PROFANITY_MASK, PROFANITY_REMOVE,
+    };
+    use crate::transcription::Segment;
+
+    fn rule(from: &str, to: &str, regex: bool) -> ReplacementRule {
+        ReplacementRule {
+            from: from.to_string(),
+            to: to.to_string(),
+            updated_at: 0,
+            regex,
+        }
+    }
+
+    #[test]
+    fn applies_literal_and_pattern_rules_in_order() {
+        let rules = vec![
+            rule("whisper dict", "Whisperdict", false),
+            rule(r"(?i)\b(\w+) at (\w+) dot com\b", "$1@$2.com", true),
+        ];
+        assert_eq!(
+            apply_replacements("Whisper dict mailed jane at example dot com", &rules),
+            "Whisperdict mailed jane@example.com"
+        );
+        assert!(validate_rule("(unclosed", true).is_err());
+        assert!(validate_rule("(unclosed", false).is_ok());
+    }
+
+    #[test]
+    fn literal_rules_ignore_case_in_non_ascii_text() {
+        let rules = vec![rule("İstanbul", "Estambul", false)];
+        assert_eq!(
+            apply_replacements("Vuelos a İSTANBUL e İstanbul", &rules),
+            "Vuelos a Estambul e Estambul"
+        );
+        // Characters whose lowercase differs in length must not shift later matches.
+        let rules = vec![rule("whisper dict", "Whisperdict", false)];
+        assert_eq!(
+            apply_replacements("İİİ ẞ says WHISPER DICT works", &rules),
+            "İİİ ẞ says Whisperdict works"
+        );
+        let rules = vec![rule("straße", "Weg", false)];
+        assert_eq!(
+            apply_replacements("STRAẞE, Straßenbahn, straße", &rules),
+            "Weg, Straßenbahn, Weg"
+        );
+        let rules = vec![rule("ärger", "Mühe", false)];
+        assert_eq!(
+            apply_replacements("ÄRGER über Ärgernis", &rules),
+            "Mühe über Ärgernis"
+        );
+    }
+
+    #[test]
+    fn wraps_only_right_to_left_dictation() {
+        assert_eq!(
+            wrap_direction("שלום", "he", BIDI_EMBEDDING),
+            "\u{202B}שלום\u{202C}"
+        );
+        assert_eq!(
+            wrap_direction("مرحبا", "AR", BIDI_MARKS),
+            "\u{200F}مرحبا\u{200F}"
+        );
+        assert_eq!(wrap_direction("hello", "en", BIDI_MARKS), "hello");
+        assert_eq!(wrap_direction("שלום", "he", BIDI_OFF), "שלום");
+    }
+
+    #[test]
+    fn restores_capitals_and_end_punctuation() {
+        assert_eq!(
+            restore_punctuation("so i think we should ship it. tomorrow works", "en"),
+            "So I think we should ship it. Tomorrow works."
+        );
+        assert_eq!(
+            restore_punctuation("can you send me the file", "en"),
+            "Can you send me the file?"
+        );
+        assert_eq!(restore_punctuation("hola a todos", "es"), "Hola a todos.");
+        assert_eq!(
+            restore_punctuation("Already fine, thanks.", "en"),
+            "Already fine, thanks."
+        );
+    }
+
+    #[test]
+    fn punctuates_each_sentence_and_keeps_line_breaks() {
+        assert_eq!(
+            restore_punctuation("can we ship it. we ship it tomorrow", "en"),
+            "Can we ship it. We ship it tomorrow."
+        );
+        assert_eq!(
+            restore_punctuation("we ship it today. why is it late", "en"),
+            "We ship it today. Why is it late?"
+        );
+        assert_eq!(
+            restore_punctuation("dear team\n\nwhere is the report\n- first item", "en"),
+            "Dear team.\n\nWhere is the report?\n- First item."
+        );
+        assert_eq!(
+            restore_punctuation("uno  dos\ttres", "es"),
+            "Uno  dos\ttres."
+        );
+    }
+
+    #[test]
+    fn masks_or_removes_filtered_words() {
+        let mut filter = ProfanityFilter {
+            mode: PROFANITY_MASK.to_string(),
+            words: vec!["Frak".to_string()],
+            ..ProfanityFilter::default()
+        };
+        assert_eq!(
+            filter_profanity("Well, shit. Frak this, Dickens", "en", &filter),
+            "Well, s***. F*** this, Dickens"
+        );
+        assert_eq!(filter_profanity("shit", "es", &filter), "shit");
+        filter.mode = PROFANITY_REMOVE.to_string();
+        assert_eq!(
+            filter_profanity("Damn, that is fucking good.", "en", &filter),
+            "That is good."
+        );
+        assert_eq!(
+            filter_profanity("Dear Dick,\nkeep  this shit.\nBye shit\n", "en", &filter),
+            "Dear Dick,\nkeep  this.\nBye\n"
+        );
+        assert_eq!(
+            filter_profanity("shit", "en", &ProfanityFilter::default()),
+            "shit"
+        );
+    }
+
+    #[test]
+    fn drops_fillers_and_keeps_real_words() {
+        assert_eq!(
+            remove_fillers("Um, I think, uh, we should ship it", "en"),
+            "I think, we should ship it"
+        );
+        assert_eq!(
+            remove_fillers("Este, eh, quiero este libro", "es"),
+            "Quiero este libro"
+        );
+        assert_eq!(remove_fillers("umbrella", "en"), "umbrella");
+    }
+
+    #[test]
+    fn marks_each_speaker_turn() {
+        let segment = |text: &str, speaker_turn| Segment {
+            text: text.to_string(),
+            speaker_turn,
+            ..Segment::default()
+        };
+        let segments = vec![
+            segment("Shall we start?", true),
+            segment("Yes.", false),
+            segment("Go ahead.", true),
+            segment("Thanks.", true),
+            segment("Welcome, everyone.", false),
+        ];
+        assert_eq!(
+            mark_speaker_turns(&segments),
+            "- Shall we start?\n- Yes. Go ahead.\n- Thanks.\n- Welcome, everyone."
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use crate::config::AppConfig;
+use crate::licensing::{ENTITLEMENT_PRO, LICENSE_STATUS_VALID};
+use serde::Serialize;
+
+pub const QUOTA_LOW_THRESHOLD: u32 = 5;
+/// Under the duration strategy, warn once this little audio time is left.
+const LOW_SECONDS: u64 = 5 * 60;
+const LAST_SECONDS: u64 = 60;
+
+pub const STRATEGY_LIFETIME: &str = "lifetime";
+pub const STRATEGY_MONTHLY: &str = "monthly";
+pub const STRATEGY_DURATION: &str = "duration";
+
+/// How the free tier is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaPolicy {
+    /// A one-time allowance of transcriptions (`free_transcriptions_left` counts down).
+    Lifetime,
+    /// `transcriptions` per calendar month (UTC), refilled on the first use of a new month.
+    Monthly { transcriptions: u32 },
+    /// A one-time allowance of recorded audio.
+    Duration { seconds: u64 },
+}
+
+impl QuotaPolicy {
+    /// Parses `lifetime`, `monthly:<transcriptions>` or `duration:<seconds>`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (strategy, limit) = match value.trim().split_once(':') {
+            Some((strategy, limit)) => (strategy, Some(limit.trim().parse::<u64>().ok()?)),
+            None => (value.trim(), None),
+        };
+        match (strategy, limit) {
+            (STRATEGY_LIFETIME, None) => Some(QuotaPolicy::Lifetime),
+            (STRATEGY_MONTHLY, Some(limit)) => Some(QuotaPolicy::Monthly {
+                transcriptions: u32::try_from(limit).ok()?,
+            }),
+            (STRATEGY_DURATION, Some(seconds)) => Some(QuotaPolicy::Duration { seconds }),
+            _ => None,
+        }
+    }
+
+    fn strategy(&self) -> &'static str {
+        match self {
+            QuotaPolicy::Lifetime => STRATEGY_LIFETIME,
+            QuotaPolicy::Monthly { .. } => STRATEGY_MONTHLY,
+            QuotaPolicy::Duration { .. } => STRATEGY_DURATION,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaState {
+    pub unlimited: bool,
+    pub strategy: &'static str,
+    /// Left under the lifetime and monthly strategies; 0 under the duration strategy.
+    pub free_transcriptions_left: u32,
+    /// Audio time left under the duration strategy.
+    pub free_seconds_left: Option<u64>,
+    pub total_transcriptions_count: u64,
+    /// Start of the next month under the monthly strategy, in unix seconds.
+    pub resets_at: Option<u64>,
+    pub warning: Option<&'static str>,
+}
+
+fn is_unlimited(config: &AppConfig) -> bool {
+    config.entitlement == ENTITLEMENT_PRO && config.license_status == LICENSE_STATUS_VALID
+}
+
+/// Transcriptions left, counting a stale month as already refilled.
+fn transcriptions_left(config: &AppConfig, policy: QuotaPolicy, now: u64) -> u32 {
+    match policy {
+        QuotaPolicy::Monthly { transcriptions } if config.quota_period != Some(month_of(now)) => {
+            transcriptions
+        }
+        _ => config.free_transcriptions_left,
+    }
+}
+
+fn seconds_left(config: &AppConfig, seconds: u64) -> u64 {
+    seconds.saturating_sub(config.free_audio_ms_used / 1000)
+}
+
+pub fn has_remaining(config: &AppConfig, policy: QuotaPolicy, now: u64) -> bool {
+    if is_unlimited(config) {
+        return true;
+    }
+    match policy {
+        QuotaPolicy::Duration { seconds } => seconds_left(config, seconds) > 0,
+        _ => transcriptions_left(config, policy, now) > 0,
+    }
+}
+
+/// Charges one finished transcription of `audio_ms` against the free tier.
+pub fn consume(config: &mut AppConfig, policy: QuotaPolicy, audio_ms: u64, now: u64) {
+    if is_unlimited(config) {
+        return;
+    }
+    match policy {
+        QuotaPolicy::Duration { .. } => {
+            config.free_audio_ms_used = config.free_audio_ms_used.saturating_add(audio_ms);
+        }
+        QuotaPolicy::Monthly { .. } => {
+            config.free_transcriptions_left =
+                transcriptions_left(config, policy, now).saturating_sub(1);
+            config.quota_period = Some(month_of(now));
+        }
+        QuotaPolicy::Lifetime => {
+            config.free_transcriptions_left = config.free_transcriptions_left.saturating_sub(1);
+        }
+    }
+}
+
+pub fn state(config: &AppConfig, policy: QuotaPolicy, now: u64) -> QuotaState {
+    let unlimited = is_unlimited(config);
+    let (free_transcriptions_left, free_seconds_left, warning) = match policy {
+        QuotaPolicy::Duration { seconds } => {
+            let left = seconds_left(config, seconds);
+            (0, Some(left), duration_warning(left))
+        }
+        _ => {
+            let left = transcriptions_left(config, policy, now);
+            (left, None, quota_warning(left))
+        }
+    };
+    QuotaState {
+        unlimited,
+        strategy: policy.strategy(),
+        free_transcriptions_left,
+        free_seconds_left,
+        total_transcriptions_count: config.total_transcriptions_count,
+        resets_at: match policy {
+            QuotaPolicy::Monthly { .. } => Some(month_start(month_of(now) + 1)),
+            _ => None,
+        },
+        warning: if unlimited { None } else { warning },
+    }
+}
+
+pub fn quota_warning(free_left: u32) -> Option<&'static str> {
+    match free_left {
+        0 => Some("exhausted"),
+        1 => Some("last"),
+        left if left <= QUOTA_LOW_THRESHOLD => Some("low"),
+        _ => None,
+    }
+}
+
+fn duration_warning(seconds_left: u64) -> Option<&'static str> {
+    match seconds_left {
+        0 => Some("exhausted"),
+        left if left < LAST_SECONDS => Some("last"),
+        left if left <= LOW_SECONDS => Some("low"),
+        _ => None,
+    }
+}
+
+/// Months since year 0 (UTC) containing the unix time `secs`.
+fn month_of(secs: u64) -> u32 {
+    // Civil-from-days (H. Hinnant), shifted so the year starts in March.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year * 12 + month - 1) as u32
+}
+
+/// Unix time of the first second of `month` (as returned by `month_of`).
+fn month_start(month: u32) -> u64 {
+    let (year, month) = ((month / 12) as i64, (month % 12 + 1) as i64);
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    ((era * 146_097 + doe - 719_468) * 86_400) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{consume, has_remaining, month_of, month_start, quota_warning, state, QuotaPolicy};
+    use crate::config::AppConfig;
+
+    /// 2024-02-29T12:00:00Z and 2024-03-01T00:00:00Z.
+    const LEAP_DAY: u64 = 1_709_208_000;
+    const MARCH: u64 = 1_709_251_200;
+
+    #[test]
+    fn quota_warns_at_five_and_one_remaining() {
+        assert_eq!(quota_warning(6), None);
+        assert_eq!(quota_warning(5), Some("low"));
+        assert_eq!(quota_warning(1), Some("last"));
+        assert_eq!(quota_warning(0), Some("exhausted"));
+    }
+
+    #[test]
+    fn monthly_quota_refills_in_a_new_month() {
+        assert_eq!(
+            QuotaPolicy::parse("monthly:2"),
+            Some(QuotaPolicy::Monthly { transcriptions: 2 })
+        );
+        assert_eq!(QuotaPolicy::parse("monthly"), None);
+        assert_eq!(month_of(LEAP_DAY), 2024 * 12 + 1);
+        assert_eq!(month_start(month_of(LEAP_DAY) + 1), MARCH);
+
+        let policy = QuotaPolicy::Monthly { transcriptions: 2 };
+        let mut config = AppConfig::default();
+        consume(&mut config, policy, 1_000, LEAP_DAY);
+        consume(&mut config, policy, 1_000, LEAP_DAY);
+        assert!(!has_remaining(&config, policy, LEAP_DAY));
+        assert_eq!(state(&config, policy, LEAP_DAY).resets_at, Some(MARCH));
+        assert!(has_remaining(&config, policy, MARCH));
+        assert_eq!(state(&config, policy, MARCH).free_transcriptions_left, 2);
+    }
+
+    #[test]
+    fn duration_quota_counts_recorded_audio() {
+        let policy = QuotaPolicy::Duration { seconds: 600 };
+        let mut config = AppConfig::default();
+        consume(&mut config, policy, 330_000, 0);
+        let quota = state(&config, policy, 0);
+        assert_eq!(quota.free_seconds_left, Some(270));
+        assert_eq!(quota.warning, Some("low"));
+        consume(&mut config, policy, 300_000, 0);
+        assert!(!has_remaining(&config, policy, 0));
+    }
+}
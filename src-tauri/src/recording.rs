@@ -1,72 +1,737 @@
-use crate::audio::{AudioBuffer, Recorder};
+use crate::audio::{
+    self, AudioBuffer, AudioLevel, BluetoothInput, CaptureOptions, LevelMeter, MonitorOutput,
+    MonitorTap, Recorder,
+};
+use crate::command_errors::CommandError;
+use crate::supervisor::Supervisor;
 use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{self, Sender};
-use std::sync::Arc;
-use std::thread;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
 enum Command {
-    Start,
-    Stop(Sender<AudioBuffer>),
+    Start(Instant, Option<Duration>, Sender<Result<()>>),
+    Stop(Sender<CapturedAudio>),
+    SetLowLatency(bool),
+    SetPreroll(Option<Duration>),
+    SetSource(String),
+    SetInputChannels(BTreeMap<String, u16>),
+    SetAvoidBluetooth(bool),
+    SetEchoCancellation(bool),
+    SetCompressRecording(bool),
+    SetHost(String),
+    Pause,
+    Resume,
+    Snapshot(usize, Sender<Option<AudioBuffer>>),
+    SetMonitor(bool),
+}
+
+/// How often the worker drains captured audio and checks the device between commands.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct CapturedAudio {
+    pub buffer: AudioBuffer,
+    /// Playback captured alongside `buffer` when echo cancellation is on.
+    pub echo_reference: Option<AudioBuffer>,
+    pub device: Option<String>,
+    pub start_latency: Option<Duration>,
+    /// Audio lost because the recorder fell behind the device.
+    pub dropped_ms: u64,
+}
+
+/// Reported when the capture device disappears mid-recording; `fallback` names the
+/// device recording continued on, or is `None` when the session had to end.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceLost {
+    pub device: String,
+    pub fallback: Option<String>,
+}
+
+/// What the worker has been configured with, kept outside it so a restart after a panic
+/// resumes with the same settings.
+#[derive(Clone, Default)]
+struct WorkerSettings {
+    low_latency: bool,
+    preroll: Option<Duration>,
+    options: CaptureOptions,
+    monitoring: bool,
 }
 
 #[derive(Clone)]
 pub struct RecorderWorker {
     tx: Sender<Command>,
     recording: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    meter: Arc<LevelMeter>,
+    monitor: Arc<MonitorTap>,
+    device_lost: Arc<Mutex<Option<DeviceLost>>>,
+    bluetooth_input: Arc<Mutex<Option<BluetoothInput>>>,
+    echo_unavailable: Arc<Mutex<Option<String>>>,
 }
 
 impl RecorderWorker {
-    pub fn new() -> Self {
+    /// Runs under `supervisor`; after a panic the worker restarts without the recording
+    /// in progress but with the settings it had been given.
+    pub fn new(supervisor: &Supervisor) -> Self {
         let (tx, rx) = mpsc::channel::<Command>();
+        let rx = Arc::new(Mutex::new(rx));
+        let settings = Arc::new(Mutex::new(WorkerSettings::default()));
         let recording = Arc::new(AtomicBool::new(false));
         let recording_flag = recording.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let paused_flag = paused.clone();
+        let meter = Arc::new(LevelMeter::new());
+        let meter_ref = meter.clone();
+        let monitor = Arc::new(MonitorTap::new(1.0));
+        let monitor_ref = monitor.clone();
+        let device_lost = Arc::new(Mutex::new(None));
+        let device_lost_ref = device_lost.clone();
+        let bluetooth_input = Arc::new(Mutex::new(None));
+        let bluetooth_input_ref = bluetooth_input.clone();
+        let echo_unavailable = Arc::new(Mutex::new(None));
+        let echo_unavailable_ref = echo_unavailable.clone();
 
-        thread::spawn(move || {
+        supervisor.spawn_thread("recorder", move || {
+            let rx = rx.lock().unwrap_or_else(PoisonError::into_inner);
+            recording_flag.store(false, Ordering::SeqCst);
+            paused_flag.store(false, Ordering::SeqCst);
+            let WorkerSettings {
+                mut low_latency,
+                mut preroll,
+                mut options,
+                mut monitoring,
+            } = settings
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
             let mut recorder: Option<Recorder> = None;
-            while let Ok(cmd) = rx.recv() {
+            let mut prepared = if low_latency || preroll.is_some() {
+                prepare(&meter_ref, preroll, &options)
+            } else {
+                None
+            };
+            let mut monitor_output: Option<MonitorOutput> = None;
+            let mut session_max: Option<Duration> = None;
+            // Audio from a device that was lost earlier in the current session.
+            let mut carried: Option<CapturedAudio> = None;
+            loop {
+                let cmd = match rx.recv_timeout(POLL_INTERVAL) {
+                    Ok(cmd) => cmd,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Some(active) = recorder.as_mut() {
+                            active.drain();
+                        }
+                        if recorder.as_ref().is_some_and(Recorder::is_lost) {
+                            let lost = recorder.take().unwrap();
+                            lost.attach_monitor(None);
+                            monitor_output = None;
+                            let device = lost.device_name().to_string();
+                            let start_latency = lost.start_latency();
+                            if let Ok(stopped) = lost.stop() {
+                                carried = Some(join_captured(
+                                    carried.take(),
+                                    CapturedAudio {
+                                        buffer: stopped.buffer,
+                                        echo_reference: stopped.echo_reference,
+                                        device: Some(device.clone()),
+                                        start_latency,
+                                        dropped_ms: stopped.dropped_ms,
+                                    },
+                                ));
+                            }
+                            recorder = Recorder::open(meter_ref.clone(), &options).ok().and_then(
+                                |mut r| {
+                                    r.begin(Instant::now(), session_max).ok()?;
+                                    if paused_flag.load(Ordering::SeqCst) {
+                                        let _ = r.pause();
+                                    }
+                                    Some(r)
+                                },
+                            );
+                            if let Some(active) = recorder.as_ref() {
+                                if monitoring {
+                                    monitor_output = start_monitor(active, &monitor_ref);
+                                }
+                                *bluetooth_input_ref.lock().unwrap() =
+                                    active.bluetooth_input().cloned();
+                            }
+                            // `recording` stays set so the session can still be stopped
+                            // and transcribed with what was captured before the loss.
+                            *device_lost_ref.lock().unwrap() = Some(DeviceLost {
+                                device,
+                                fallback: recorder
+                                    .as_ref()
+                                    .map(|active| active.device_name().to_string()),
+                            });
+                        }
+                        if prepared.as_ref().is_some_and(Recorder::is_lost) {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                        continue;
+                    }
+                };
                 match cmd {
-                    Command::Start => {
-                        if recorder.is_none() {
-                            if let Ok(r) = Recorder::start() {
+                    Command::Start(requested_at, max_duration, reply) => {
+                        // Already recording (or holding audio from a lost device): no-op.
+                        if recorder.is_some() || carried.is_some() {
+                            let _ = reply.send(Ok(()));
+                            continue;
+                        }
+                        *device_lost_ref.lock().unwrap() = None;
+                        session_max = max_duration;
+                        let started = match prepared.take() {
+                            Some(ready) => Ok(ready),
+                            None => Recorder::open(meter_ref.clone(), &options),
+                        }
+                        .and_then(|mut r| {
+                            r.begin(requested_at, max_duration)?;
+                            Ok(r)
+                        });
+                        match started {
+                            Ok(r) => {
+                                if monitoring {
+                                    monitor_output = start_monitor(&r, &monitor_ref);
+                                }
+                                *bluetooth_input_ref.lock().unwrap() = r.bluetooth_input().cloned();
+                                *echo_unavailable_ref.lock().unwrap() =
+                                    r.echo_unavailable().map(str::to_string);
                                 recorder = Some(r);
                                 recording_flag.store(true, Ordering::SeqCst);
+                                let _ = reply.send(Ok(()));
+                            }
+                            Err(err) => {
+                                let _ = reply.send(Err(err));
                             }
                         }
                     }
                     Command::Stop(reply) => {
+                        recording_flag.store(false, Ordering::SeqCst);
+                        paused_flag.store(false, Ordering::SeqCst);
+                        let mut captured = carried.take();
                         if let Some(active) = recorder.take() {
-                            recording_flag.store(false, Ordering::SeqCst);
-                            if let Ok(buffer) = active.stop() {
-                                let _ = reply.send(buffer);
+                            active.attach_monitor(None);
+                            monitor_output = None;
+                            let device = Some(active.device_name().to_string());
+                            let start_latency = active.start_latency();
+                            if let Ok(stopped) = active.stop() {
+                                captured = Some(join_captured(
+                                    captured,
+                                    CapturedAudio {
+                                        buffer: stopped.buffer,
+                                        echo_reference: stopped.echo_reference,
+                                        device,
+                                        start_latency,
+                                        dropped_ms: stopped.dropped_ms,
+                                    },
+                                ));
                             }
-                        } else {
-                            let _ = reply.send(AudioBuffer {
+                            if low_latency || preroll.is_some() {
+                                prepared = prepare(&meter_ref, preroll, &options);
+                            }
+                        }
+                        let _ = reply.send(captured.unwrap_or(CapturedAudio {
+                            buffer: AudioBuffer {
                                 samples: Vec::new(),
                                 sample_rate: 16_000,
-                            });
+                            },
+                            echo_reference: None,
+                            device: None,
+                            start_latency: None,
+                            dropped_ms: 0,
+                        }));
+                    }
+                    Command::SetLowLatency(enabled) => {
+                        low_latency = enabled;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                    }
+                    Command::SetPreroll(duration) => {
+                        preroll = duration;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                    }
+                    Command::Snapshot(offset, reply) => {
+                        let _ = reply.send(recorder.as_mut().and_then(|r| r.snapshot(offset)));
+                    }
+                    Command::Pause => {
+                        if let Some(active) = recorder.as_ref() {
+                            if active.pause().is_ok() {
+                                paused_flag.store(true, Ordering::SeqCst);
+                            }
+                        }
+                    }
+                    Command::Resume => {
+                        if let Some(active) = recorder.as_ref() {
+                            if active.resume().is_ok() {
+                                paused_flag.store(false, Ordering::SeqCst);
+                            }
+                        }
+                    }
+                    Command::SetMonitor(enabled) => {
+                        monitoring = enabled;
+                        match recorder.as_ref() {
+                            Some(active) if enabled && monitor_output.is_none() => {
+                                monitor_output = start_monitor(active, &monitor_ref);
+                            }
+                            Some(active) if !enabled => {
+                                active.attach_monitor(None);
+                                monitor_output = None;
+                            }
+                            _ => {}
+                        }
+                    }
+                    Command::SetHost(next) => {
+                        options.host = next;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                    }
+                    Command::SetAvoidBluetooth(enabled) => {
+                        options.avoid_bluetooth = enabled;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                    }
+                    Command::SetCompressRecording(enabled) => {
+                        options.compress = enabled;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                    }
+                    Command::SetEchoCancellation(enabled) => {
+                        options.echo_cancellation = enabled;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                    }
+                    Command::SetInputChannels(next) => {
+                        options.input_channels = next;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
+                        }
+                    }
+                    Command::SetSource(next) => {
+                        options.source = next;
+                        prepared = None;
+                        if (low_latency || preroll.is_some()) && recorder.is_none() {
+                            prepared = prepare(&meter_ref, preroll, &options);
                         }
                     }
                 }
+                *settings.lock().unwrap_or_else(PoisonError::into_inner) = WorkerSettings {
+                    low_latency,
+                    preroll,
+                    options: options.clone(),
+                    monitoring,
+                };
             }
         });
 
-        Self { tx, recording }
+        Self {
+            tx,
+            recording,
+            paused,
+            meter,
+            monitor,
+            device_lost,
+            bluetooth_input,
+            echo_unavailable,
+        }
     }
 
-    pub fn start(&self) -> Result<()> {
-        self.tx.send(Command::Start).context("start recording")?;
-        Ok(())
+    pub fn start(&self, requested_at: Instant, max_duration: Option<Duration>) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        self.tx
+            .send(Command::Start(requested_at, max_duration, tx))
+            .context("start recording")?;
+        rx.recv().context("receive start result")?.map_err(|err| {
+            eprintln!("failed to start recording: {err:#}");
+            anyhow::Error::new(CommandError::audio_device_unavailable())
+        })
     }
 
-    pub fn stop(&self) -> Result<AudioBuffer> {
+    pub fn stop(&self) -> Result<CapturedAudio> {
         let (tx, rx) = mpsc::channel();
         self.tx.send(Command::Stop(tx)).context("stop recording")?;
-        let buffer = rx.recv().context("receive audio")?;
-        Ok(buffer)
+        let captured = rx.recv().context("receive audio")?;
+        Ok(captured)
+    }
+
+    /// Keeps an input stream opened and paused between recordings so slow devices
+    /// don't swallow the first syllable.
+    pub fn set_low_latency(&self, enabled: bool) -> Result<()> {
+        self.tx
+            .send(Command::SetLowLatency(enabled))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    /// `None` turns the always-listening pre-roll buffer off.
+    pub fn set_preroll(&self, duration: Option<Duration>) -> Result<()> {
+        self.tx
+            .send(Command::SetPreroll(duration))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    pub fn set_source(&self, source: &str) -> Result<()> {
+        self.tx
+            .send(Command::SetSource(source.to_string()))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    pub fn set_host(&self, host: &str) -> Result<()> {
+        self.tx
+            .send(Command::SetHost(host.to_string()))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    pub fn set_avoid_bluetooth(&self, enabled: bool) -> Result<()> {
+        self.tx
+            .send(Command::SetAvoidBluetooth(enabled))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    pub fn set_compress_recording(&self, enabled: bool) -> Result<()> {
+        self.tx
+            .send(Command::SetCompressRecording(enabled))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    pub fn set_echo_cancellation(&self, enabled: bool) -> Result<()> {
+        self.tx
+            .send(Command::SetEchoCancellation(enabled))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    pub fn set_input_channels(&self, input_channels: BTreeMap<String, u16>) -> Result<()> {
+        self.tx
+            .send(Command::SetInputChannels(input_channels))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    /// Plays the microphone back through the output device while recording.
+    pub fn set_monitor(&self, enabled: bool) -> Result<()> {
+        self.tx
+            .send(Command::SetMonitor(enabled))
+            .context("configure recorder")?;
+        Ok(())
+    }
+
+    pub fn set_monitor_volume(&self, volume: f32) {
+        self.monitor.set_volume(volume);
+    }
+
+    /// Audio recorded so far from sample `offset` on; `None` when not recording.
+    pub fn snapshot(&self, offset: usize) -> Result<Option<AudioBuffer>> {
+        let (tx, rx) = mpsc::channel();
+        self.tx
+            .send(Command::Snapshot(offset, tx))
+            .context("snapshot recording")?;
+        rx.recv().context("receive snapshot")
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        self.tx.send(Command::Pause).context("pause recording")?;
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        self.tx.send(Command::Resume).context("resume recording")?;
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn take_device_lost(&self) -> Option<DeviceLost> {
+        self.device_lost.lock().unwrap().take()
+    }
+
+    pub fn take_bluetooth_input(&self) -> Option<BluetoothInput> {
+        self.bluetooth_input.lock().unwrap().take()
+    }
+
+    /// Why the recording that just started runs without echo cancellation, once.
+    pub fn take_echo_unavailable(&self) -> Option<String> {
+        self.echo_unavailable.lock().unwrap().take()
     }
 
     pub fn is_recording(&self) -> bool {
         self.recording.load(Ordering::SeqCst)
     }
+
+    pub fn level(&self) -> AudioLevel {
+        self.meter.take()
+    }
+
+    pub fn take_clipping(&self) -> (u64, u64) {
+        self.meter.take_clipping()
+    }
+}
+
+/// Keeps the first device's identity and latency; later audio is appended after it.
+fn join_captured(earlier: Option<CapturedAudio>, later: CapturedAudio) -> CapturedAudio {
+    match earlier {
+        Some(mut earlier) => CapturedAudio {
+            echo_reference: join_references(&mut earlier, &later),
+            buffer: audio::append(earlier.buffer, later.buffer),
+            dropped_ms: earlier.dropped_ms + later.dropped_ms,
+            ..earlier
+        },
+        None => later,
+    }
+}
+
+/// Keeps the playback reference aligned with the joined recording: a part recorded
+/// without one contributes silence of its length.
+fn join_references(earlier: &mut CapturedAudio, later: &CapturedAudio) -> Option<AudioBuffer> {
+    let sample_rate = earlier
+        .echo_reference
+        .as_ref()
+        .or(later.echo_reference.as_ref())?
+        .sample_rate;
+    let silence = |captured: &CapturedAudio| AudioBuffer {
+        samples: vec![
+            0.0;
+            captured.buffer.samples.len() * sample_rate as usize
+                / captured.buffer.sample_rate.max(1) as usize
+        ],
+        sample_rate,
+    };
+    let first = earlier
+        .echo_reference
+        .take()
+        .unwrap_or_else(|| silence(earlier));
+    let second = later
+        .echo_reference
+        .clone()
+        .unwrap_or_else(|| silence(later));
+    Some(audio::append(first, second))
+}
+
+fn start_monitor(recorder: &Recorder, tap: &Arc<MonitorTap>) -> Option<MonitorOutput> {
+    tap.clear();
+    let output = MonitorOutput::start(tap.clone(), recorder.host_id()).ok()?;
+    recorder.attach_monitor(Some(tap.clone()));
+    Some(output)
+}
+
+fn prepare(
+    meter: &Arc<LevelMeter>,
+    preroll: Option<Duration>,
+    options: &CaptureOptions,
+) -> Option<Recorder> {
+    let mut recorder = Recorder::open(meter.clone(), options).ok()?;
+    if let Some(duration) = preroll {
+        recorder.listen(duration).ok()?;
+    }
+    Some(recorder)
+}
+
+pub struct EnergyVad {
+    threshold: f32,
+    silence: Duration,
+    heard_speech: bool,
+    last_voice: Instant,
+}
+
+impl EnergyVad {
+    pub fn new(threshold: f32, silence: Duration) -> Self {
+        Self {
+            threshold,
+            silence,
+            heard_speech: false,
+            last_voice: Instant::now(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.heard_speech = false;
+        self.last_voice = Instant::now();
+    }
+
+    /// Returns true once speech has been heard and was followed by enough silence.
+    pub fn observe(&mut self, level: AudioLevel, now: Instant) -> bool {
+        if level.rms >= self.threshold {
+            self.heard_speech = true;
+            self.last_voice = now;
+            return false;
+        }
+        self.heard_speech && now.duration_since(self.last_voice) >= self.silence
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClippingWarning {
+    pub active: bool,
+    pub percent: f32,
+}
+
+/// Share of clipped frames over the window that starts a warning; it clears below half.
+const CLIPPING_RATIO: f64 = 0.01;
+
+/// Warns once clipping has persisted over a whole window rather than on a single peak.
+pub struct ClippingDetector {
+    window: Duration,
+    ticks: VecDeque<(Instant, u64, u64)>,
+    warning: bool,
+}
+
+impl ClippingDetector {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            ticks: VecDeque::new(),
+            warning: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.ticks.clear();
+    }
+
+    /// Returns a warning when clipping starts or stops being sustained.
+    pub fn observe(&mut self, clipped: u64, frames: u64, now: Instant) -> Option<ClippingWarning> {
+        self.ticks.push_back((now, clipped, frames));
+        while self
+            .ticks
+            .front()
+            .is_some_and(|(at, _, _)| now.duration_since(*at) > self.window)
+        {
+            self.ticks.pop_front();
+        }
+        let (oldest, _, _) = *self.ticks.front()?;
+        if now.duration_since(oldest) < self.window.mul_f32(0.9) {
+            return None;
+        }
+        let (clipped, frames) = self
+            .ticks
+            .iter()
+            .fold((0, 0), |(c, f), (_, clipped, frames)| {
+                (c + clipped, f + frames)
+            });
+        if frames == 0 {
+            return None;
+        }
+        let ratio = clipped as f64 / frames as f64;
+        let active = if self.warning {
+            ratio >= CLIPPING_RATIO / 2.0
+        } else {
+            ratio >= CLIPPING_RATIO
+        };
+        if active == self.warning {
+            return None;
+        }
+        self.warning = active;
+        Some(ClippingWarning {
+            active,
+            percent: (ratio * 100.0) as f32,
+        })
+    }
+}
+
+const SILENCE_WINDOW_MS: u64 = 30;
+
+/// True when no short window of the recording reaches the VAD threshold.
+pub fn is_silent(buffer: &AudioBuffer, threshold: f32) -> bool {
+    let window = ((buffer.sample_rate as u64 * SILENCE_WINDOW_MS / 1000) as usize).max(1);
+    !buffer.samples.chunks(window).any(|block| {
+        let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+        rms >= threshold
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_silent, ClippingDetector, EnergyVad};
+    use crate::audio::AudioBuffer;
+    use crate::audio::AudioLevel;
+    use crate::audio::{apply_gain, GAIN_AGC};
+    use std::time::{Duration, Instant};
+
+    fn level(rms: f32) -> AudioLevel {
+        AudioLevel { rms, peak: rms }
+    }
+
+    #[test]
+    fn stops_after_silence_following_speech() {
+        let start = Instant::now();
+        let mut vad = EnergyVad::new(0.02, Duration::from_millis(1000));
+        assert!(!vad.observe(level(0.0), start + Duration::from_millis(2000)));
+        assert!(!vad.observe(level(0.2), start + Duration::from_millis(2100)));
+        assert!(!vad.observe(level(0.001), start + Duration::from_millis(2600)));
+        assert!(vad.observe(level(0.001), start + Duration::from_millis(3200)));
+    }
+
+    #[test]
+    fn warns_only_on_sustained_clipping() {
+        let start = Instant::now();
+        let tick = |n: u64| start + Duration::from_millis(n * 100);
+        let mut detector = ClippingDetector::new(Duration::from_secs(1));
+        // A single clipped burst is diluted by the rest of the window.
+        assert!(detector.observe(400, 4_800, tick(0)).is_none());
+        for n in 1..=10 {
+            assert!(detector.observe(0, 4_800, tick(n)).is_none());
+        }
+        for n in 11..=21 {
+            if let Some(warning) = detector.observe(240, 4_800, tick(n)) {
+                assert!(warning.active && warning.percent >= 1.0);
+            }
+        }
+        assert!(detector.warning);
+        let cleared = (22..=32).find_map(|n| detector.observe(0, 4_800, tick(n)));
+        assert!(cleared.is_some_and(|warning| !warning.active));
+    }
+
+    #[test]
+    fn detects_all_silent_recordings() {
+        let mut buffer = AudioBuffer {
+            samples: vec![0.001; 16_000],
+            sample_rate: 16_000,
+        };
+        assert!(is_silent(&buffer, 0.015));
+        buffer.samples[8_000..8_480].fill(0.3);
+        assert!(!is_silent(&buffer, 0.015));
+    }
+
+    #[test]
+    fn quiet_speech_is_not_silent_once_gain_lifts_it() {
+        let mut buffer = AudioBuffer {
+            samples: (0..16_000).map(|n| 0.01 * (n as f32 * 0.1).sin()).collect(),
+            sample_rate: 16_000,
+        };
+        assert!(is_silent(&buffer, 0.015));
+        apply_gain(&mut buffer, GAIN_AGC);
+        assert!(!is_silent(&buffer, 0.015));
+        // Room noise below the AGC floor is left alone and still counts as silence.
+        let mut noise = AudioBuffer {
+            samples: vec![0.001; 16_000],
+            sample_rate: 16_000,
+        };
+        apply_gain(&mut noise, GAIN_AGC);
+        assert!(is_silent(&noise, 0.015));
+    }
 }
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const RECORDING_PREFIX: &str = "whisperdict-";
+const RECORDING_EXTENSION: &str = "wav";
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+pub fn default_recordings_dir() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("missing base dirs")?;
+    Ok(dirs.data_local_dir().join("Whisperdict").join("recordings"))
+}
+
+/// Moves a finished dictation's WAV into `dir` and prunes it down to the retention limits
+/// (`0` disables a limit).
+pub fn keep_recording(
+    wav_path: &Path,
+    dir: &Path,
+    created_at_ms: u64,
+    keep_count: u32,
+    keep_days: u32,
+) -> Result<PathBuf> {
+    fs::create_dir_all(dir).context("create recordings dir")?;
+    let target = recording_path(dir, created_at_ms);
+    if fs::rename(wav_path, &target).is_err() {
+        // Temp and recordings dirs may sit on different filesystems.
+        fs::copy(wav_path, &target).context("copy recording")?;
+        let _ = fs::remove_file(wav_path);
+    }
+    prune(dir, created_at_ms, keep_count, keep_days)?;
+    Ok(target)
+}
+
+/// The audio of the last dictation whose transcription failed, kept until it is
+/// recovered.
+pub fn failed_recording_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("missing base dirs")?;
+    let dir = dirs.data_local_dir().join("Whisperdict");
+    fs::create_dir_all(&dir).context("create data dir")?;
+    Ok(dir.join("failed-recording.wav"))
+}
+
+/// Where the recording of the dictation made at `created_at_ms` is kept.
+pub fn recording_path(dir: &Path, created_at_ms: u64) -> PathBuf {
+    dir.join(format!(
+        "{RECORDING_PREFIX}{created_at_ms}.{RECORDING_EXTENSION}"
+    ))
+}
+
+/// Samples of a kept recording, which is already 16 kHz mono.
+pub fn read_recording(path: &Path) -> Result<Vec<f32>> {
+    let mut reader = hound::WavReader::open(path).context("open recording")?;
+    reader
+        .samples::<i16>()
+        .map(|sample| {
+            sample
+                .map(|sample| sample as f32 / i16::MAX as f32)
+                .context("read recording sample")
+        })
+        .collect()
+}
+
+pub fn prune(dir: &Path, now_ms: u64, keep_count: u32, keep_days: u32) -> Result<()> {
+    let mut recordings: Vec<(u64, PathBuf)> = fs::read_dir(dir)
+        .context("read recordings dir")?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            recording_timestamp(&path).map(|stamp| (stamp, path))
+        })
+        .collect();
+    let stamps: Vec<u64> = recordings.iter().map(|(stamp, _)| *stamp).collect();
+    let expired = expired_recordings(&stamps, now_ms, keep_count, keep_days);
+    recordings.retain(|(stamp, _)| expired.contains(stamp));
+    for (_, path) in recordings {
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
+
+fn recording_timestamp(path: &Path) -> Option<u64> {
+    if path.extension()?.to_str()? != RECORDING_EXTENSION {
+        return None;
+    }
+    path.file_stem()?
+        .to_str()?
+        .strip_prefix(RECORDING_PREFIX)?
+        .parse()
+        .ok()
+}
+
+pub fn expired_recordings(
+    stamps: &[u64],
+    now_ms: u64,
+    keep_count: u32,
+    keep_days: u32,
+) -> Vec<u64> {
+    let mut newest_first = stamps.to_vec();
+    newest_first.sort_unstable_by(|a, b| b.cmp(a));
+    let cutoff = (keep_days > 0).then(|| now_ms.saturating_sub(keep_days as u64 * DAY_MS));
+    newest_first
+        .into_iter()
+        .enumerate()
+        .filter(|(index, stamp)| {
+            (keep_count > 0 && *index >= keep_count as usize)
+                || cutoff.is_some_and(|cutoff| *stamp < cutoff)
+        })
+        .map(|(_, stamp)| stamp)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expired_recordings, DAY_MS};
+
+    #[test]
+    fn applies_count_and_age_limits() {
+        let now = 100 * DAY_MS;
+        let stamps = [now - 1, now - 2, now - 3, now - 40 * DAY_MS];
+
+        let mut expired = expired_recordings(&stamps, now, 2, 0);
+        expired.sort_unstable();
+        assert_eq!(expired, vec![now - 40 * DAY_MS, now - 3]);
+
+        assert_eq!(
+            expired_recordings(&stamps, now, 0, 30),
+            vec![now - 40 * DAY_MS]
+        );
+        assert!(expired_recordings(&stamps, now, 0, 0).is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::history::{ChangeKind, HistoryEntry, TextChange};
+use crate::models::ModelStatus;
+use serde::{Deserialize, Serialize};
+
+/// Background job that re-runs low-confidence dictations through a larger model while
+/// the machine is idle and on AC power. Needs `keep_recordings`, since it decodes the
+/// kept WAV of each entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetranscribeConfig {
+    pub enabled: bool,
+    /// Model to re-run with; empty picks the largest installed model above the active one.
+    pub model_id: String,
+    /// Entries whose mean segment confidence is below this are queued.
+    pub max_confidence: f32,
+    /// Minutes without dictating before the job starts.
+    pub idle_minutes: u32,
+}
+
+impl Default for RetranscribeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model_id: String::new(),
+            max_confidence: 0.6,
+            idle_minutes: 15,
+        }
+    }
+}
+
+pub fn mean_confidence(entry: &HistoryEntry) -> Option<f32> {
+    if entry.segments.is_empty() {
+        return None;
+    }
+    let total: f32 = entry
+        .segments
+        .iter()
+        .map(|segment| segment.confidence)
+        .sum();
+    Some(total / entry.segments.len() as f32)
+}
+
+/// Ids of entries still worth re-running, newest first: low confidence, not revised yet
+/// and not already decoded with `model_id`.
+pub fn queue(entries: &[HistoryEntry], model_id: &str, max_confidence: f32) -> Vec<u64> {
+    entries
+        .iter()
+        .rev()
+        .filter(|entry| entry.revision.is_none() && entry.model_id != model_id)
+        .filter(|entry| mean_confidence(entry).is_some_and(|mean| mean < max_confidence))
+        .map(|entry| entry.id)
+        .collect()
+}
+
+/// The configured model when installed, otherwise the largest installed model of the same
+/// family bigger than `active`, so a diarizing or imported model is never picked for it.
+pub fn pick_model(configured: &str, active: &str, models: &[ModelStatus]) -> Option<String> {
+    let installed = models.iter().filter(|model| model.installed);
+    if !configured.is_empty() {
+        return installed
+            .clone()
+            .any(|model| model.id == configured)
+            .then(|| configured.to_string());
+    }
+    let active = models.iter().find(|model| model.id == active)?;
+    installed
+        .filter(|model| model.size_mb > active.size_mb && model.tags.family == active.tags.family)
+        .max_by_key(|model| model.size_mb)
+        .map(|model| model.id.clone())
+}
+
+/// Word-level diff from `before` to `after`, with runs of the same kind merged.
+pub fn diff_words(before: &str, after: &str) -> Vec<TextChange> {
+    let old: Vec<&str> = before.split_whitespace().collect();
+    let new: Vec<&str> = after.split_whitespace().collect();
+    // lcs[i][j]: longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut changes: Vec<TextChange> = Vec::new();
+    let mut push = |kind: ChangeKind, word: &str| match changes.last_mut() {
+        Some(last) if last.kind == kind => {
+            last.text.push(' ');
+            last.text.push_str(word);
+        }
+        _ => changes.push(TextChange {
+            kind,
+            text: word.to_string(),
+        }),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            push(ChangeKind::Same, old[i]);
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            push(ChangeKind::Added, new[j]);
+            j += 1;
+        } else {
+            push(ChangeKind::Removed, old[i]);
+            i += 1;
+        }
+    }
+    changes
+}
+
+/// Whether the machine runs on mains power; machines without a battery count as plugged
+/// in. This shells out on macOS, so call it off the async runtime.
+#[cfg(target_os = "linux")]
+pub fn on_ac_power() -> bool {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return true;
+    };
+    let mut has_battery = false;
+    for supply in supplies.flatten() {
+        let read = |name: &str| std::fs::read_to_string(supply.path().join(name)).ok();
+        match read("type").as_deref().map(str::trim) {
+            Some("Mains") if read("online").as_deref().map(str::trim) == Some("1") => {
+                return true;
+            }
+            Some("Battery") => has_battery = true,
+            _ => {}
+        }
+    }
+    !has_battery
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_ac_power() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .map(|output| !String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+        .unwrap_or(true)
+}
+
+#[cfg(target_os = "windows")]
+pub fn on_ac_power() -> bool {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // SAFETY: the struct is plain data that the call fills in.
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return true;
+    }
+    // 0 is offline; 1 online and 255 unknown.
+    status.ACLineStatus != 0
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn on_ac_power() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_words, pick_model, queue};
+    use crate::history::{ChangeKind, HistoryEntry};
+    use crate::models::{ModelStatus, ModelTags, MULTILINGUAL, TINYDIARIZE};
+    use crate::transcription::Segment;
+
+    #[test]
+    fn picks_a_bigger_installed_model_of_the_same_family() {
+        let model = |id: &str, size_mb, installed, family| ModelStatus {
+            id: id.to_string(),
+            size_mb,
+            installed,
+            partial: false,
+            tags: ModelTags {
+                family,
+                language: MULTILINGUAL,
+                domain: "general",
+                quantization: "f16",
+            },
+            variant_of: None,
+            name: None,
+        };
+        let mut models = vec![
+            model("base", 142, true, "whisper"),
+            model("small.en-tdrz", 465, true, TINYDIARIZE),
+            model("medium", 1533, false, "whisper"),
+        ];
+        assert_eq!(pick_model("", "base", &models), None);
+        models[2].installed = true;
+        assert_eq!(pick_model("", "base", &models).as_deref(), Some("medium"));
+        assert_eq!(pick_model("", "unknown", &models), None);
+        assert_eq!(
+            pick_model("small.en-tdrz", "base", &models).as_deref(),
+            Some("small.en-tdrz")
+        );
+    }
+
+    #[test]
+    fn queues_low_confidence_entries_and_diffs_words() {
+        let entry = |id: u64, confidence: f32| HistoryEntry {
+            id,
+            text: String::new(),
+            model_id: "base".to_string(),
+            language: "en".to_string(),
+            created_at: id,
+            duration_ms: 100,
+            segments: vec![Segment {
+                confidence,
+                ..Segment::default()
+            }],
+            revision: None,
+            tag: None,
+        };
+        let entries = vec![entry(1, 0.3), entry(2, 0.9), entry(3, 0.5)];
+        assert_eq!(queue(&entries, "large-v3", 0.6), vec![3, 1]);
+        assert!(queue(&entries, "base", 0.6).is_empty());
+
+        let changes = diff_words("send the report to anna", "send the reports to Anna today");
+        let kinds: Vec<(ChangeKind, &str)> = changes
+            .iter()
+            .map(|change| (change.kind, change.text.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ChangeKind::Same, "send the"),
+                (ChangeKind::Added, "reports"),
+                (ChangeKind::Removed, "report"),
+                (ChangeKind::Same, "to"),
+                (ChangeKind::Added, "Anna today"),
+                (ChangeKind::Removed, "anna"),
+            ]
+        );
+    }
+}
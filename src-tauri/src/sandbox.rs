@@ -0,0 +1,467 @@
+use anyhow::Result;
+use std::path::Path;
+
+/// Drops filesystem and process privileges the transcribe child no longer needs once the
+/// model is loaded: from then on it only talks over its socket. Any failure is an error,
+/// so a child asked to sandbox itself never runs unsandboxed.
+pub fn restrict_child(model_path: &Path) -> Result<()> {
+    platform::restrict(model_path)
+}
+
+/// Errors when this machine cannot sandbox the child, so the setting is refused up front
+/// rather than failing every transcription.
+pub fn check_available() -> Result<()> {
+    platform::check_available()
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::{bail, Context, Result};
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+    const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+
+    const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+    const ACCESS_FS_ALL_V1: u64 = (1 << 13) - 1;
+    const ACCESS_FS_REFER: u64 = 1 << 13;
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+    const ACCESS_NET_BIND_TCP: u64 = 1 << 0;
+    const ACCESS_NET_CONNECT_TCP: u64 = 1 << 1;
+    const SCOPE_ABSTRACT_UNIX_SOCKET: u64 = 1 << 0;
+    const SCOPE_SIGNAL: u64 = 1 << 1;
+
+    /// Later ABI fields are zero on older kernels, which accept the larger struct then.
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+        handled_access_net: u64,
+        scoped: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+    /// Set in the numbers of x32 syscalls, which share the x86_64 audit architecture.
+    #[cfg(target_arch = "x86_64")]
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    /// Calls that would reach outside the process: new sockets (the socket to the parent
+    /// is already open), running programs and inspecting other processes.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const DENIED_SYSCALLS: [libc::c_long; 11] = [
+        libc::SYS_socket,
+        libc::SYS_connect,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept,
+        libc::SYS_accept4,
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+    ];
+
+    fn landlock_abi() -> i64 {
+        unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        }
+    }
+
+    pub fn check_available() -> Result<()> {
+        if !cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+            bail!("the transcriber sandbox is not supported on this CPU architecture");
+        }
+        if landlock_abi() < 1 {
+            bail!("the transcriber sandbox needs Landlock, which this kernel lacks");
+        }
+        Ok(())
+    }
+
+    pub fn restrict(model_path: &Path) -> Result<()> {
+        check_available()?;
+        let abi = landlock_abi();
+        let mut attr = RulesetAttr {
+            handled_access_fs: ACCESS_FS_ALL_V1,
+            handled_access_net: 0,
+            scoped: 0,
+        };
+        if abi >= 2 {
+            attr.handled_access_fs |= ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            attr.handled_access_fs |= ACCESS_FS_TRUNCATE;
+        }
+        // No network rules are added, so handling these denies all TCP.
+        if abi >= 4 {
+            attr.handled_access_net = ACCESS_NET_BIND_TCP | ACCESS_NET_CONNECT_TCP;
+        }
+        if abi >= 6 {
+            attr.scoped = SCOPE_ABSTRACT_UNIX_SOCKET | SCOPE_SIGNAL;
+        }
+        let ruleset = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0u32,
+            )
+        };
+        if ruleset < 0 {
+            bail!("create landlock ruleset failed");
+        }
+        let ruleset = ruleset as i32;
+
+        let result = (|| {
+            allow_path(ruleset, model_path, ACCESS_FS_READ_FILE)?;
+            // GPU drivers and thread-count detection touch these after model load.
+            allow_path(
+                ruleset,
+                Path::new("/dev"),
+                ACCESS_FS_READ_FILE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_DIR,
+            )?;
+            for dir in ["/proc", "/sys"] {
+                allow_path(
+                    ruleset,
+                    Path::new(dir),
+                    ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR,
+                )?;
+            }
+
+            if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+                bail!("set no_new_privs failed");
+            }
+            if unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) } != 0 {
+                bail!("landlock restrict_self failed");
+            }
+            deny_syscalls()
+        })();
+        unsafe {
+            libc::close(ruleset);
+        }
+        result
+    }
+
+    /// A seccomp filter failing `DENIED_SYSCALLS` with EPERM, synced to every thread, so
+    /// driver threads started while loading the model are covered too. Landlock does not
+    /// handle Unix socket connects, which this closes.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn deny_syscalls() -> Result<()> {
+        let statement = |code: u32, k: u32, jt: u8, jf: u8| libc::sock_filter {
+            code: code as u16,
+            jt,
+            jf,
+            k,
+        };
+        let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+        let jump_eq = libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K;
+        let ret = libc::BPF_RET | libc::BPF_K;
+        let deny = libc::SECCOMP_RET_ERRNO | (libc::EPERM as u32 & libc::SECCOMP_RET_DATA);
+        // `seccomp_data` starts with the syscall number, then the audit architecture.
+        let mut filter = vec![
+            statement(load, 4, 0, 0),
+            statement(jump_eq, AUDIT_ARCH, 1, 0),
+            statement(ret, libc::SECCOMP_RET_KILL_PROCESS, 0, 0),
+            statement(load, 0, 0, 0),
+        ];
+        // x32 numbers would slip past the deny list below; nothing here uses that ABI.
+        #[cfg(target_arch = "x86_64")]
+        filter.extend([
+            statement(
+                libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+                X32_SYSCALL_BIT,
+                0,
+                1,
+            ),
+            statement(ret, libc::SECCOMP_RET_KILL_PROCESS, 0, 0),
+        ]);
+        for syscall in DENIED_SYSCALLS {
+            filter.push(statement(jump_eq, syscall as u32, 0, 1));
+            filter.push(statement(ret, deny, 0, 0));
+        }
+        filter.push(statement(ret, libc::SECCOMP_RET_ALLOW, 0, 0));
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_TSYNC,
+                &program as *const libc::sock_fprog,
+            )
+        };
+        if result != 0 {
+            bail!("install seccomp filter failed");
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    fn deny_syscalls() -> Result<()> {
+        bail!("seccomp filtering is not supported on this CPU architecture")
+    }
+
+    fn allow_path(ruleset: i32, path: &Path, access: u64) -> Result<()> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).context("sandbox path")?;
+        let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+        if fd < 0 {
+            // Missing optional paths (e.g. no /sys in a container) are not fatal.
+            return Ok(());
+        }
+        let access = if path.is_dir() {
+            access
+        } else {
+            access & (ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE)
+        };
+        let rule = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: fd,
+        };
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset,
+                LANDLOCK_RULE_PATH_BENEATH,
+                &rule as *const PathBeneathAttr,
+                0u32,
+            )
+        };
+        unsafe {
+            libc::close(fd);
+        }
+        if result != 0 {
+            bail!("landlock add rule failed for {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::{bail, Result};
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_int};
+    use std::path::Path;
+
+    extern "C" {
+        fn sandbox_init(profile: *const c_char, flags: u64, errorbuf: *mut *mut c_char) -> c_int;
+        fn sandbox_free_error(errorbuf: *mut c_char);
+    }
+
+    /// Denies everything but what decoding on the CPU or through Metal needs; the socket to
+    /// the parent and stderr are already open. `MODEL` is replaced with the model path.
+    const PROFILE: &str = "(version 1)\
+        (deny default)\
+        (allow process-info* (target self))\
+        (allow signal (target self))\
+        (allow sysctl-read)\
+        (allow file-read-metadata)\
+        (allow file-read*\
+            (subpath \"/System\")\
+            (subpath \"/usr/lib\")\
+            (subpath \"/Library/GPUBundles\")\
+            (subpath \"/private/var/db/dyld\")\
+            (literal \"/dev/null\")\
+            (literal \"/dev/random\")\
+            (literal \"/dev/urandom\")\
+            (literal \"MODEL\"))\
+        (allow file-write-data (literal \"/dev/null\"))\
+        (allow iokit-open)\
+        (allow mach-lookup\
+            (global-name \"com.apple.MTLCompilerService\")\
+            (global-name \"com.apple.cvmsServ\"))";
+
+    pub fn check_available() -> Result<()> {
+        Ok(())
+    }
+
+    pub fn restrict(model_path: &Path) -> Result<()> {
+        let model = model_path
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        let profile = CString::new(PROFILE.replace("MODEL", &model))?;
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let result = unsafe { sandbox_init(profile.as_ptr(), 0, &mut error) };
+        if result != 0 {
+            let message = if error.is_null() {
+                "unknown error".to_string()
+            } else {
+                let message = unsafe { CStr::from_ptr(error) }
+                    .to_string_lossy()
+                    .into_owned();
+                unsafe { sandbox_free_error(error) };
+                message
+            };
+            bail!("sandbox_init failed: {message}");
+        }
+        Ok(())
+    }
+}
+
+/// Windows has no per-process network switch short of a firewall rule, so the child can
+/// still open connections; it can no longer start processes, touch the desktop or write
+/// to the user's files.
+#[cfg(windows)]
+mod platform {
+    use anyhow::{bail, Result};
+    use std::ffi::c_void;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        AllocateAndInitializeSid, FreeSid, GetLengthSid, SetTokenInformation, TokenIntegrityLevel,
+        PSID, SECURITY_MANDATORY_LABEL_AUTHORITY, SID_AND_ATTRIBUTES, TOKEN_ADJUST_DEFAULT,
+        TOKEN_MANDATORY_LABEL, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicUIRestrictions,
+        JobObjectExtendedLimitInformation, SetInformationJobObject,
+        JOBOBJECT_BASIC_UI_RESTRICTIONS, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_ACTIVE_PROCESS, JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION,
+        JOB_OBJECT_UILIMIT_DESKTOP, JOB_OBJECT_UILIMIT_DISPLAYSETTINGS,
+        JOB_OBJECT_UILIMIT_EXITWINDOWS, JOB_OBJECT_UILIMIT_GLOBALATOMS, JOB_OBJECT_UILIMIT_HANDLES,
+        JOB_OBJECT_UILIMIT_READCLIPBOARD, JOB_OBJECT_UILIMIT_SYSTEMPARAMETERS,
+        JOB_OBJECT_UILIMIT_WRITECLIPBOARD,
+    };
+    use windows_sys::Win32::System::SystemServices::{
+        SECURITY_MANDATORY_LOW_RID, SE_GROUP_INTEGRITY,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    pub fn check_available() -> Result<()> {
+        Ok(())
+    }
+
+    pub fn restrict(_model_path: &Path) -> Result<()> {
+        confine_to_job()?;
+        lower_integrity()
+    }
+
+    /// A job allowing one process, this one, with no access to other windows or the
+    /// clipboard. The handle is kept open for the life of the process.
+    fn confine_to_job() -> Result<()> {
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if job.is_null() {
+                bail!("create job object failed");
+            }
+            let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            limits.BasicLimitInformation.LimitFlags =
+                JOB_OBJECT_LIMIT_ACTIVE_PROCESS | JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION;
+            limits.BasicLimitInformation.ActiveProcessLimit = 1;
+            let ui = JOBOBJECT_BASIC_UI_RESTRICTIONS {
+                UIRestrictionsClass: JOB_OBJECT_UILIMIT_DESKTOP
+                    | JOB_OBJECT_UILIMIT_DISPLAYSETTINGS
+                    | JOB_OBJECT_UILIMIT_EXITWINDOWS
+                    | JOB_OBJECT_UILIMIT_GLOBALATOMS
+                    | JOB_OBJECT_UILIMIT_HANDLES
+                    | JOB_OBJECT_UILIMIT_READCLIPBOARD
+                    | JOB_OBJECT_UILIMIT_SYSTEMPARAMETERS
+                    | JOB_OBJECT_UILIMIT_WRITECLIPBOARD,
+            };
+            let limited = SetInformationJobObject(
+                job,
+                JobObjectExtendedLimitInformation,
+                &limits as *const _ as *const c_void,
+                std::mem::size_of_val(&limits) as u32,
+            ) != 0
+                && SetInformationJobObject(
+                    job,
+                    JobObjectBasicUIRestrictions,
+                    &ui as *const _ as *const c_void,
+                    std::mem::size_of_val(&ui) as u32,
+                ) != 0;
+            if !limited || AssignProcessToJobObject(job, GetCurrentProcess()) == 0 {
+                CloseHandle(job);
+                bail!("confine transcriber to a job object failed");
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the process to low integrity, which may not write to anything the user's
+    /// normal processes own.
+    fn lower_integrity() -> Result<()> {
+        unsafe {
+            let mut token: HANDLE = std::ptr::null_mut();
+            if OpenProcessToken(
+                GetCurrentProcess(),
+                TOKEN_ADJUST_DEFAULT | TOKEN_QUERY,
+                &mut token,
+            ) == 0
+            {
+                bail!("open process token failed");
+            }
+            let mut sid: PSID = std::ptr::null_mut();
+            let allocated = AllocateAndInitializeSid(
+                &SECURITY_MANDATORY_LABEL_AUTHORITY,
+                1,
+                SECURITY_MANDATORY_LOW_RID as u32,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                &mut sid,
+            ) != 0;
+            let lowered = allocated && {
+                let label = TOKEN_MANDATORY_LABEL {
+                    Label: SID_AND_ATTRIBUTES {
+                        Sid: sid,
+                        Attributes: SE_GROUP_INTEGRITY as u32,
+                    },
+                };
+                SetTokenInformation(
+                    token,
+                    TokenIntegrityLevel,
+                    &label as *const _ as *const c_void,
+                    std::mem::size_of_val(&label) as u32 + GetLengthSid(sid),
+                ) != 0
+            };
+            if allocated {
+                FreeSid(sid);
+            }
+            CloseHandle(token);
+            if !lowered {
+                bail!("lower transcriber integrity level failed");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use anyhow::{bail, Result};
+    use std::path::Path;
+
+    pub fn check_available() -> Result<()> {
+        bail!("sandboxing is not supported on this platform yet")
+    }
+
+    pub fn restrict(_model_path: &Path) -> Result<()> {
+        check_available()
+    }
+}
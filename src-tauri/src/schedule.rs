@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Daily window in which the model is kept loaded: it is preloaded `lead_minutes`
+/// before `warm_at` and unloaded at `unload_at`. Times are local `HH:MM`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScheduleConfig {
+    pub enabled: bool,
+    pub warm_at: String,
+    pub lead_minutes: u32,
+    pub unload_at: String,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            warm_at: "08:30".to_string(),
+            lead_minutes: 10,
+            unload_at: "19:00".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleStatus {
+    /// Whether the schedule currently wants the model loaded.
+    pub warm: bool,
+    /// Unix seconds of the next preload or unload.
+    pub next_change_at: u64,
+}
+
+/// Minutes since midnight for `HH:MM`.
+pub fn parse_time(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Start and end of the warm window in minutes since midnight; the end may be the
+/// smaller of the two when the window spans midnight.
+fn window(schedule: &ScheduleConfig) -> Option<(u32, u32)> {
+    let warm_at = parse_time(&schedule.warm_at)?;
+    let end = parse_time(&schedule.unload_at)?;
+    let lead = schedule.lead_minutes % MINUTES_PER_DAY;
+    let start = (warm_at + MINUTES_PER_DAY - lead) % MINUTES_PER_DAY;
+    (start != end).then_some((start, end))
+}
+
+/// Where the schedule stands at `local_secs` (unix seconds shifted to local time) and
+/// when it next changes, or `None` when it is off or its times are invalid.
+pub fn status(schedule: &ScheduleConfig, now: u64, local_secs: i64) -> Option<ScheduleStatus> {
+    if !schedule.enabled {
+        return None;
+    }
+    let (start, end) = window(schedule)?;
+    let day_secs = i64::from(MINUTES_PER_DAY) * 60;
+    let second = local_secs.rem_euclid(day_secs) as u32;
+    let minute = second / 60;
+    let warm = if start < end {
+        (start..end).contains(&minute)
+    } else {
+        minute >= start || minute < end
+    };
+    let boundary = if warm { end } else { start };
+    let until = (boundary * 60 + MINUTES_PER_DAY * 60 - second) % (MINUTES_PER_DAY * 60);
+    Some(ScheduleStatus {
+        warm,
+        next_change_at: now + u64::from(until),
+    })
+}
+
+/// Seconds the local time zone is ahead of UTC right now.
+#[cfg(unix)]
+pub fn utc_offset_secs() -> i64 {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff
+}
+
+#[cfg(windows)]
+pub fn utc_offset_secs() -> i64 {
+    use std::os::windows::process::CommandExt;
+    use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
+
+    // Without CREATE_NO_WINDOW a console flashes up on every refresh.
+    std::process::Command::new("powershell")
+        .creation_flags(CREATE_NO_WINDOW)
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "[int][TimeZoneInfo]::Local.GetUtcOffset([DateTime]::Now).TotalSeconds",
+        ])
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn utc_offset_secs() -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_time, status, ScheduleConfig};
+
+    #[test]
+    fn warm_window_wraps_and_reports_next_change() {
+        assert_eq!(parse_time("08:30"), Some(510));
+        assert_eq!(parse_time("24:00"), None);
+
+        let schedule = ScheduleConfig {
+            enabled: true,
+            ..ScheduleConfig::default()
+        };
+        // 08:19 local is before the 08:20 preload; 08:20 is inside the window.
+        let early = status(&schedule, 1_000, 8 * 3600 + 19 * 60).unwrap();
+        assert!(!early.warm);
+        assert_eq!(early.next_change_at, 1_060);
+        let warm = status(&schedule, 1_000, 8 * 3600 + 20 * 60).unwrap();
+        assert!(warm.warm);
+        assert_eq!(warm.next_change_at, 1_000 + (19 * 60 - 8 * 60 - 20) * 60);
+
+        let night = ScheduleConfig {
+            enabled: true,
+            warm_at: "22:00".to_string(),
+            lead_minutes: 0,
+            unload_at: "02:00".to_string(),
+        };
+        assert!(status(&night, 0, 23 * 3600).unwrap().warm);
+        assert!(status(&night, 0, 3600).unwrap().warm);
+        assert!(!status(&night, 0, 3 * 3600).unwrap().warm);
+        assert!(status(&ScheduleConfig::default(), 0, 0).is_none());
+    }
+}
@@ -0,0 +1,267 @@
+use crate::audio::AudioBuffer;
+use crate::paste;
+use crate::speech;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::time::Instant;
+
+pub const STAGE_AUDIO: &str = "audio";
+pub const STAGE_RESAMPLE: &str = "resample";
+pub const STAGE_MODEL: &str = "model";
+pub const STAGE_TRANSCRIBE: &str = "transcribe";
+pub const STAGE_POST_PROCESSING: &str = "post_processing";
+pub const STAGE_CLIPBOARD: &str = "clipboard";
+
+/// Spoken by the system voice for the transcription stage to hear back.
+pub const PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+/// Played instead of the phrase when there is no system voice, at a rate whisper does
+/// not use so the resampler is still exercised.
+const TONE_RATE: u32 = 44_100;
+const TONE_HZ: f32 = 440.0;
+const TONE_SECONDS: u32 = 2;
+/// Share of the phrase's words the transcript must contain; voices and small models
+/// get the odd word wrong.
+const MIN_WORDS_HEARD: f32 = 0.5;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageResult {
+    pub stage: &'static str,
+    pub passed: bool,
+    pub skipped: bool,
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub stages: Vec<StageResult>,
+}
+
+#[derive(Default)]
+pub struct SelfTest {
+    stages: Vec<StageResult>,
+}
+
+impl SelfTest {
+    /// Runs one stage, recording its outcome; `None` means later dependent stages should skip.
+    pub fn run<T>(&mut self, stage: &'static str, f: impl FnOnce() -> Result<T>) -> Option<T> {
+        let started = Instant::now();
+        let result = f();
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let (passed, message, value) = match result {
+            Ok(value) => (true, None, Some(value)),
+            Err(err) => (false, Some(format!("{err:#}")), None),
+        };
+        self.stages.push(StageResult {
+            stage,
+            passed,
+            skipped: false,
+            message,
+            duration_ms,
+        });
+        value
+    }
+
+    pub fn note(&mut self, message: String) {
+        if let Some(stage) = self.stages.last_mut() {
+            stage.message = Some(message);
+        }
+    }
+
+    pub fn skip(&mut self, stage: &'static str) {
+        self.stages.push(StageResult {
+            stage,
+            passed: false,
+            skipped: true,
+            message: Some("skipped because an earlier stage failed".to_string()),
+            duration_ms: 0,
+        });
+    }
+
+    pub fn finish(self) -> SelfTestReport {
+        SelfTestReport {
+            passed: self.stages.iter().all(|stage| stage.passed),
+            stages: self.stages,
+        }
+    }
+}
+
+/// Audio for the pipeline to decode, and what it says when that is known.
+pub struct Sample {
+    pub audio: AudioBuffer,
+    /// `None` for the tone, which has no words for the transcript to contain.
+    pub phrase: Option<&'static str>,
+}
+
+/// `PHRASE` in the system voice, or a tone where no voice is installed (e.g. Linux
+/// without espeak), so a missing voice never fails the stages after it.
+pub fn sample() -> Sample {
+    match spoken_sample() {
+        Ok(audio) => Sample {
+            audio,
+            phrase: Some(PHRASE),
+        },
+        Err(err) => {
+            eprintln!("self-test voice unavailable, decoding a tone: {err:#}");
+            Sample {
+                audio: tone_sample(),
+                phrase: None,
+            }
+        }
+    }
+}
+
+pub fn tone_sample() -> AudioBuffer {
+    let step = 2.0 * std::f32::consts::PI * TONE_HZ / TONE_RATE as f32;
+    AudioBuffer {
+        samples: (0..TONE_RATE * TONE_SECONDS)
+            .map(|n| 0.3 * (n as f32 * step).sin())
+            .collect(),
+        sample_rate: TONE_RATE,
+    }
+}
+
+/// `PHRASE` in the system voice, at whatever rate the voice speaks; voices do not use
+/// 16 kHz, so the resampler is exercised too.
+fn spoken_sample() -> Result<AudioBuffer> {
+    let file = tempfile::Builder::new()
+        .prefix("whisperdict-self-test")
+        .suffix(".wav")
+        .tempfile()
+        .context("create self-test wav")?;
+    speech::render_to_wav(PHRASE, file.path())?;
+    let mut reader = hound::WavReader::open(file.path()).context("open self-test wav")?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect()
+        }
+    }
+    .context("read self-test wav")?;
+    let channels = usize::from(spec.channels.max(1));
+    let samples = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect::<Vec<_>>();
+    if samples.is_empty() {
+        anyhow::bail!("the system voice produced no audio");
+    }
+    Ok(AudioBuffer {
+        samples,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+/// Checks `heard` contains enough of `PHRASE`; an empty transcript never passes.
+pub fn check_heard(heard: &str) -> Result<()> {
+    let words = |text: &str| -> Vec<String> {
+        text.split(|ch: char| !ch.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let expected = words(PHRASE);
+    let heard = words(heard);
+    if heard.is_empty() {
+        anyhow::bail!("the model heard nothing in \"{PHRASE}\"");
+    }
+    let matched = expected.iter().filter(|word| heard.contains(word)).count();
+    if (matched as f32) < expected.len() as f32 * MIN_WORDS_HEARD {
+        anyhow::bail!(
+            "the model heard \"{}\" instead of \"{PHRASE}\"",
+            heard.join(" ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks the resampled copy kept the expected length and level of the original.
+pub fn check_resampled(original: &AudioBuffer, resampled: &AudioBuffer) -> Result<()> {
+    if resampled.sample_rate != 16_000 {
+        anyhow::bail!(
+            "resampled to {} Hz instead of 16000 Hz",
+            resampled.sample_rate
+        );
+    }
+    let expected = original.samples.len() as f64 * 16_000.0 / original.sample_rate as f64;
+    let actual = resampled.samples.len() as f64;
+    if (actual - expected).abs() > expected * 0.02 {
+        anyhow::bail!("expected about {expected:.0} samples, got {actual:.0}");
+    }
+    let original_peak = peak(&original.samples);
+    let resampled_peak = peak(&resampled.samples);
+    if (resampled_peak - original_peak).abs() > original_peak * 0.2 {
+        anyhow::bail!("peak changed from {original_peak:.3} to {resampled_peak:.3}");
+    }
+    Ok(())
+}
+
+/// Round-trips `text` through the clipboard and puts back whatever it held before.
+pub fn check_clipboard(text: &str) -> Result<()> {
+    paste::keeping_clipboard(|clipboard| {
+        clipboard.set_text(text.to_string())?;
+        if clipboard.get_text()? != text {
+            anyhow::bail!("clipboard returned different text than was copied");
+        }
+        Ok(())
+    })
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_heard, check_resampled, tone_sample, SelfTest};
+    use crate::audio::{resample_for_whisper, RESAMPLER_LINEAR};
+
+    #[test]
+    fn sample_survives_resampling() {
+        let sample = tone_sample();
+        let resampled = resample_for_whisper(sample.clone(), RESAMPLER_LINEAR);
+        assert!(check_resampled(&sample, &resampled).is_ok());
+        let truncated = crate::audio::AudioBuffer {
+            samples: resampled.samples[..8_000].to_vec(),
+            sample_rate: 16_000,
+        };
+        assert!(check_resampled(&sample, &truncated).is_err());
+    }
+
+    #[test]
+    fn transcript_must_contain_the_phrase() {
+        assert!(check_heard("The quick brown fox jumps over the lazy dog.").is_ok());
+        assert!(check_heard("the quick brown box jumped over a lazy dog").is_ok());
+        assert!(check_heard("").is_err());
+        assert!(check_heard("[BLANK_AUDIO]").is_err());
+        assert!(check_heard("Thank you.").is_err());
+    }
+
+    #[test]
+    fn report_fails_when_any_stage_fails_or_skips() {
+        let mut test = SelfTest::default();
+        assert_eq!(test.run("ok", || Ok(1)), Some(1));
+        assert!(test.finish().passed);
+
+        let mut test = SelfTest::default();
+        assert_eq!(test.run("ok", || Ok(())), Some(()));
+        assert!(test
+            .run("broken", || -> anyhow::Result<()> {
+                anyhow::bail!("no device")
+            })
+            .is_none());
+        test.skip("later");
+        let report = test.finish();
+        assert!(!report.passed);
+        assert_eq!(report.stages[1].message.as_deref(), Some("no device"));
+        assert!(report.stages[2].skipped);
+    }
+}
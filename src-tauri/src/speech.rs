@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// When to read the final transcription aloud relative to pasting it.
+pub const READ_ALOUD_OFF: &str = "off";
+pub const READ_ALOUD_BEFORE: &str = "before";
+pub const READ_ALOUD_AFTER: &str = "after";
+
+/// The platform speech command, reading aloud or, with `output`, writing a WAV file
+/// instead; text is fed on stdin so it is never parsed as arguments.
+#[cfg(target_os = "macos")]
+fn speech_command(output: Option<&Path>) -> Result<Command> {
+    let mut command = Command::new("say");
+    command.args(["-f", "-"]);
+    if let Some(output) = output {
+        command
+            .args(["--file-format=WAVE", "--data-format=LEI16@22050", "-o"])
+            .arg(output);
+    }
+    Ok(command)
+}
+
+#[cfg(target_os = "windows")]
+fn speech_command(output: Option<&Path>) -> Result<Command> {
+    let mut command = Command::new("powershell");
+    command.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "Add-Type -AssemblyName System.Speech; \
+         $voice = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+         if ($env:WHISPERDICT_SPEECH_OUT) { $voice.SetOutputToWaveFile($env:WHISPERDICT_SPEECH_OUT) }; \
+         $voice.Speak([Console]::In.ReadToEnd()); $voice.Dispose()",
+    ]);
+    if let Some(output) = output {
+        command.env("WHISPERDICT_SPEECH_OUT", output);
+    }
+    Ok(command)
+}
+
+#[cfg(target_os = "linux")]
+fn speech_command(output: Option<&Path>) -> Result<Command> {
+    // speech-dispatcher only plays aloud, so files come from espeak.
+    if output.is_none() {
+        if let Ok(path) = which::which("spd-say") {
+            let mut command = Command::new(path);
+            command.args(["--wait", "--pipe-mode"]);
+            return Ok(command);
+        }
+    }
+    let path = which::which("espeak-ng")
+        .or_else(|_| which::which("espeak"))
+        .context("no text-to-speech command found (install speech-dispatcher or espeak-ng)")?;
+    let mut command = Command::new(path);
+    command.arg("--stdin");
+    if let Some(output) = output {
+        command.arg("-w").arg(output);
+    }
+    Ok(command)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn speech_command(_output: Option<&Path>) -> Result<Command> {
+    anyhow::bail!("text-to-speech is not supported on this platform")
+}
+
+/// Reads `text` aloud and returns once it has been spoken.
+pub fn speak_blocking(text: &str) -> Result<()> {
+    run(speech_command(None)?, text)
+}
+
+/// Speaks `text` into a WAV file at `output` instead of the speakers.
+pub fn render_to_wav(text: &str, output: &Path) -> Result<()> {
+    run(speech_command(Some(output))?, text)
+}
+
+fn run(mut command: Command, text: &str) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("start text-to-speech")?;
+    let mut stdin = child.stdin.take().context("text-to-speech stdin")?;
+    stdin
+        .write_all(text.as_bytes())
+        .context("write text-to-speech input")?;
+    drop(stdin);
+    let status = child.wait().context("wait for text-to-speech")?;
+    if !status.success() {
+        anyhow::bail!("text-to-speech exited with {status}");
+    }
+    Ok(())
+}
+
+/// Reads `text` aloud without blocking the caller.
+pub fn speak(text: &str) {
+    let text = text.to_string();
+    thread::spawn(move || {
+        if let Err(err) = speak_blocking(&text) {
+            eprintln!("read aloud failed: {err}");
+        }
+    });
+}
@@ -0,0 +1,144 @@
+use std::ops::Range;
+use std::time::Duration;
+
+/// New audio needed before the next partial decode.
+pub const CHUNK_STEP: Duration = Duration::from_secs(3);
+/// Audio repeated from the previous chunk so words cut at its edge are heard whole;
+/// `stitch` drops the words both chunks transcribed.
+pub const CHUNK_OVERLAP: Duration = Duration::from_secs(1);
+/// Longest run of repeated words looked for when joining chunks.
+const MAX_OVERLAP_WORDS: usize = 12;
+/// Window whose energy `split_at_pauses` compares when looking for a pause.
+const PAUSE_WINDOW: Duration = Duration::from_millis(100);
+
+/// Tracks where the next chunk starts in the recording's native samples.
+#[derive(Debug, Default)]
+pub struct ChunkCursor {
+    start: usize,
+}
+
+impl ChunkCursor {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// True once `available` samples past the cursor make a full chunk.
+    pub fn is_ready(&self, available: usize, sample_rate: u32) -> bool {
+        let overlap = if self.start == 0 {
+            0
+        } else {
+            samples_in(CHUNK_OVERLAP, sample_rate)
+        };
+        available >= overlap + samples_in(CHUNK_STEP, sample_rate)
+    }
+
+    /// Moves past a decoded chunk of `len` samples, keeping the overlap for the next one.
+    pub fn advance(&mut self, len: usize, sample_rate: u32) {
+        let end = self.start + len;
+        self.start = end.saturating_sub(samples_in(CHUNK_OVERLAP, sample_rate));
+    }
+}
+
+fn samples_in(duration: Duration, sample_rate: u32) -> usize {
+    (duration.as_millis() as u64 * sample_rate as u64 / 1000) as usize
+}
+
+/// Cuts `samples` into consecutive pieces of at most `max_len`, each ending at the
+/// quietest point of its last quarter so words are rarely split between two pieces.
+pub fn split_at_pauses(samples: &[f32], sample_rate: u32, max_len: Duration) -> Vec<Range<usize>> {
+    let max_len = samples_in(max_len, sample_rate).max(1);
+    let window = samples_in(PAUSE_WINDOW, sample_rate).clamp(1, max_len);
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > max_len {
+        let search = start + max_len - max_len / 4..start + max_len;
+        let energy = |at: usize| -> f32 { samples[at..at + window].iter().map(|s| s * s).sum() };
+        let quietest = search
+            .step_by(window)
+            .filter(|at| at + window <= samples.len())
+            .min_by(|a, b| energy(*a).total_cmp(&energy(*b)))
+            .map_or(start + max_len, |at| at + window / 2);
+        pieces.push(start..quietest);
+        start = quietest;
+    }
+    if start < samples.len() {
+        pieces.push(start..samples.len());
+    }
+    pieces
+}
+
+fn normalized(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Appends `next` to `confirmed`, dropping the longest run of leading words in `next`
+/// that repeats the end of `confirmed` (ignoring case and punctuation).
+pub fn stitch(confirmed: &str, next: &str) -> String {
+    let previous: Vec<String> = confirmed.split_whitespace().map(normalized).collect();
+    let words: Vec<&str> = next.split_whitespace().collect();
+    let incoming: Vec<String> = words.iter().map(|word| normalized(word)).collect();
+    let longest = previous.len().min(incoming.len()).min(MAX_OVERLAP_WORDS);
+    let overlap = (1..=longest)
+        .rev()
+        .find(|&k| previous[previous.len() - k..] == incoming[..k])
+        .unwrap_or(0);
+    let rest = words[overlap..].join(" ");
+    match (confirmed.trim_end(), rest.is_empty()) {
+        (_, true) => confirmed.trim_end().to_string(),
+        ("", false) => rest,
+        (head, false) => format!("{head} {rest}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_at_pauses, stitch, ChunkCursor};
+    use std::time::Duration;
+
+    #[test]
+    fn stitching_drops_words_heard_in_both_chunks() {
+        assert_eq!(
+            stitch("We should meet on", "meet on Tuesday, then."),
+            "We should meet on Tuesday, then."
+        );
+        assert_eq!(stitch("Hello there.", "There we go"), "Hello there. we go");
+        assert_eq!(stitch("", "First words"), "First words");
+        assert_eq!(stitch("No overlap", "at all"), "No overlap at all");
+    }
+
+    #[test]
+    fn splits_long_audio_at_the_quietest_point() {
+        // 10 s at 1 kHz, loud except for a pause around 8.5 s.
+        let samples: Vec<f32> = (0..10_000)
+            .map(|i| {
+                if (8_400..8_600).contains(&i) {
+                    0.0
+                } else {
+                    0.5
+                }
+            })
+            .collect();
+        let pieces = split_at_pauses(&samples, 1_000, Duration::from_secs(9));
+        assert_eq!(pieces.len(), 2);
+        assert!((8_400..8_600).contains(&pieces[0].end));
+        assert_eq!(pieces[1], pieces[0].end..10_000);
+        assert_eq!(
+            split_at_pauses(&samples[..500], 1_000, Duration::from_secs(9)),
+            vec![0..500]
+        );
+    }
+
+    #[test]
+    fn chunks_overlap_after_the_first() {
+        let mut cursor = ChunkCursor::default();
+        assert!(!cursor.is_ready(2_000, 1_000));
+        assert!(cursor.is_ready(3_000, 1_000));
+        cursor.advance(3_000, 1_000);
+        assert_eq!(cursor.start(), 2_000);
+        assert!(!cursor.is_ready(3_500, 1_000));
+        assert!(cursor.is_ready(4_000, 1_000));
+    }
+}
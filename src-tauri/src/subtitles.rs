@@ -0,0 +1,148 @@
+use crate::transcription::Segment;
+
+pub const FORMAT_TEXT: &str = "text";
+pub const FORMAT_SRT: &str = "srt";
+pub const FORMAT_VTT: &str = "vtt";
+/// The usual broadcast limit for one subtitle line.
+pub const DEFAULT_MAX_LINE_LEN: usize = 42;
+const LINES_PER_CUE: usize = 2;
+
+/// One subtitle on screen from `t0` to `t1` milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub t0: u64,
+    pub t1: u64,
+    pub lines: Vec<String>,
+}
+
+/// File extension for `format`, or `None` when it is not an output format.
+pub fn extension(format: &str) -> Option<&'static str> {
+    match format {
+        FORMAT_TEXT => Some("txt"),
+        FORMAT_SRT => Some("srt"),
+        FORMAT_VTT => Some("vtt"),
+        _ => None,
+    }
+}
+
+/// Wraps each segment into lines of at most `max_line_len` characters (longer words get
+/// a line of their own) and splits it into cues of two lines, sharing the segment's time
+/// among them by length.
+pub fn cues(segments: &[Segment], max_line_len: usize) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    for segment in segments {
+        let lines = wrap(&segment.text, max_line_len.max(1));
+        let total: usize = lines.iter().map(|line| line.chars().count()).sum();
+        let span = segment.t1.saturating_sub(segment.t0);
+        let mut done = 0;
+        for chunk in lines.chunks(LINES_PER_CUE) {
+            let len: usize = chunk.iter().map(|line| line.chars().count()).sum();
+            let at = |chars: usize| segment.t0 + span * chars as u64 / total.max(1) as u64;
+            cues.push(Cue {
+                t0: at(done),
+                t1: at(done + len),
+                lines: chunk.to_vec(),
+            });
+            done += len;
+        }
+    }
+    cues
+}
+
+fn wrap(text: &str, max_line_len: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= max_line_len => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+pub fn render(format: &str, segments: &[Segment], max_line_len: usize) -> String {
+    match format {
+        FORMAT_SRT => render_srt(&cues(segments, max_line_len)),
+        FORMAT_VTT => render_vtt(&cues(segments, max_line_len)),
+        _ => {
+            let text: Vec<&str> = segments.iter().map(|segment| segment.text.trim()).collect();
+            format!("{}\n", text.join(" "))
+        }
+    }
+}
+
+pub fn render_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (index, cue) in cues.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            timestamp(cue.t0, ','),
+            timestamp(cue.t1, ','),
+            cue.lines.join("\n")
+        ));
+    }
+    out
+}
+
+pub fn render_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            timestamp(cue.t0, '.'),
+            timestamp(cue.t1, '.'),
+            cue.lines.join("\n")
+        ));
+    }
+    out
+}
+
+/// `HH:MM:SS,mmm` for SRT, `HH:MM:SS.mmm` for WebVTT.
+fn timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1_000 % 60,
+        ms % 1_000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cues, render_srt, render_vtt};
+    use crate::transcription::Segment;
+
+    #[test]
+    fn splits_long_segments_into_timed_cues() {
+        let segments = vec![
+            Segment {
+                text: " Hello there.".to_string(),
+                t0: 0,
+                t1: 1_500,
+                ..Segment::default()
+            },
+            Segment {
+                text: "one two three four five six".to_string(),
+                t0: 3_661_000,
+                t1: 3_667_000,
+                ..Segment::default()
+            },
+        ];
+        let cues = cues(&segments, 9);
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[1].lines, vec!["one two", "three"]);
+        assert_eq!((cues[1].t0, cues[1].t1), (3_661_000, 3_664_000));
+        assert_eq!(cues[2].lines, vec!["four five", "six"]);
+        assert_eq!(
+            render_srt(&cues[..1]),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello\nthere.\n\n"
+        );
+        assert!(render_vtt(&cues).starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:01.500\n"));
+        assert!(render_vtt(&cues).contains("01:01:04.000 --> 01:01:07.000\nfour five\nsix\n"));
+    }
+}
@@ -0,0 +1,192 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Consecutive panics after which a worker is left stopped.
+const MAX_CONSECUTIVE_PANICS: u32 = 5;
+/// A worker that ran this long before panicking starts its backoff over.
+const STABLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+pub const COMPONENT_RUNNING: &str = "running";
+pub const COMPONENT_RESTARTING: &str = "restarting";
+pub const COMPONENT_FAILED: &str = "failed";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    pub name: &'static str,
+    pub state: &'static str,
+    pub panics: u32,
+    pub last_panic: Option<String>,
+}
+
+impl ComponentHealth {
+    pub fn is_degraded(&self) -> bool {
+        self.state != COMPONENT_RUNNING
+    }
+}
+
+/// Runs long-lived workers and restarts them with backoff when they panic, keeping a
+/// per-component health record for `get_status` and diagnostics. A worker that returns
+/// normally is considered finished and is not restarted.
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    components: Arc<Mutex<BTreeMap<&'static str, ComponentHealth>>>,
+}
+
+impl Supervisor {
+    pub fn spawn_thread<F>(&self, name: &'static str, work: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        let supervisor = self.clone();
+        thread::spawn(move || {
+            let mut run = Run::default();
+            loop {
+                supervisor.mark(name, COMPONENT_RUNNING, None);
+                let started = Instant::now();
+                let Err(payload) = panic::catch_unwind(AssertUnwindSafe(&work)) else {
+                    break;
+                };
+                match supervisor.after_panic(name, &mut run, started, panic_message(&*payload)) {
+                    Some(delay) => thread::sleep(delay),
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Like `spawn_thread`, for a task on the async runtime; `work` builds a fresh future
+    /// for every (re)start.
+    pub fn spawn_task<F, Fut>(&self, name: &'static str, work: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut run = Run::default();
+            loop {
+                supervisor.mark(name, COMPONENT_RUNNING, None);
+                let started = Instant::now();
+                let message = match tauri::async_runtime::spawn(work()).await {
+                    Ok(()) => break,
+                    Err(err) => err.to_string(),
+                };
+                match supervisor.after_panic(name, &mut run, started, message) {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => break,
+                }
+            }
+        });
+    }
+
+    pub fn components(&self) -> Vec<ComponentHealth> {
+        self.components.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn degraded(&self) -> Vec<ComponentHealth> {
+        self.components()
+            .into_iter()
+            .filter(ComponentHealth::is_degraded)
+            .collect()
+    }
+
+    fn mark(&self, name: &'static str, state: &'static str, panic: Option<String>) {
+        let mut components = self.components.lock().unwrap();
+        let entry = components.entry(name).or_insert_with(|| ComponentHealth {
+            name,
+            state,
+            panics: 0,
+            last_panic: None,
+        });
+        entry.state = state;
+        if let Some(panic) = panic {
+            entry.last_panic = Some(panic);
+            entry.panics += 1;
+        }
+    }
+
+    /// Records a panic and returns how long to wait before restarting, or `None` to give up.
+    fn after_panic(
+        &self,
+        name: &'static str,
+        run: &mut Run,
+        started: Instant,
+        message: String,
+    ) -> Option<Duration> {
+        eprintln!("{name} worker panicked: {message}");
+        let delay = run.next_delay(started.elapsed());
+        let state = if delay.is_some() {
+            COMPONENT_RESTARTING
+        } else {
+            COMPONENT_FAILED
+        };
+        self.mark(name, state, Some(message));
+        delay
+    }
+}
+
+#[derive(Default)]
+struct Run {
+    consecutive: u32,
+}
+
+impl Run {
+    /// Backoff doubling from `FIRST_BACKOFF` for each consecutive panic, reset when the
+    /// worker had been running stably.
+    fn next_delay(&mut self, ran_for: Duration) -> Option<Duration> {
+        if ran_for >= STABLE_AFTER {
+            self.consecutive = 0;
+        }
+        self.consecutive += 1;
+        if self.consecutive > MAX_CONSECUTIVE_PANICS {
+            return None;
+        }
+        Some((FIRST_BACKOFF * 2u32.pow(self.consecutive - 1)).min(MAX_BACKOFF))
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Run, Supervisor, COMPONENT_FAILED, MAX_CONSECUTIVE_PANICS, STABLE_AFTER};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn backs_off_then_gives_up_on_a_crash_looping_worker() {
+        let mut run = Run::default();
+        let quick = Duration::from_millis(10);
+        assert_eq!(run.next_delay(quick), Some(Duration::from_secs(1)));
+        assert_eq!(run.next_delay(quick), Some(Duration::from_secs(2)));
+        assert_eq!(run.next_delay(STABLE_AFTER), Some(Duration::from_secs(1)));
+
+        let supervisor = Supervisor::default();
+        let mut run = Run::default();
+        for _ in 0..MAX_CONSECUTIVE_PANICS {
+            assert!(supervisor
+                .after_panic("recorder", &mut run, Instant::now(), "boom".to_string())
+                .is_some());
+        }
+        assert!(supervisor
+            .after_panic("recorder", &mut run, Instant::now(), "boom".to_string())
+            .is_none());
+        let degraded = supervisor.degraded();
+        assert_eq!(degraded.len(), 1);
+        assert_eq!(degraded[0].state, COMPONENT_FAILED);
+        assert_eq!(degraded[0].panics, MAX_CONSECUTIVE_PANICS + 1);
+    }
+}
@@ -0,0 +1,155 @@
+use serde::Serialize;
+use std::process::Command;
+
+/// Temperatures and throttling seen right after a run; fields the platform does not
+/// expose stay `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThermalSnapshot {
+    pub cpu_celsius: Option<f32>,
+    pub gpu_celsius: Option<f32>,
+    pub cpu_throttled: Option<bool>,
+    pub gpu_throttled: Option<bool>,
+    /// Cumulative CPU throttle events since boot (Linux, Intel); diagnostics compare it
+    /// between runs to fill in `cpu_throttled`.
+    pub cpu_throttle_events: Option<u64>,
+}
+
+/// Reads the current thermal state; this shells out, so call it off the async runtime.
+pub fn read() -> ThermalSnapshot {
+    let mut snapshot = read_cpu();
+    if let Some((celsius, throttled)) = read_nvidia() {
+        snapshot.gpu_celsius = Some(celsius);
+        snapshot.gpu_throttled = Some(throttled);
+    }
+    snapshot
+}
+
+#[cfg(target_os = "linux")]
+fn read_cpu() -> ThermalSnapshot {
+    use std::fs;
+
+    let read = |path: std::path::PathBuf| fs::read_to_string(path).ok();
+    let mut cpu_celsius: Option<f32> = None;
+    if let Ok(zones) = fs::read_dir("/sys/class/thermal") {
+        for zone in zones.flatten() {
+            let path = zone.path();
+            let Some(kind) = read(path.join("type")) else {
+                continue;
+            };
+            if !is_cpu_zone(&kind) {
+                continue;
+            }
+            if let Some(celsius) = read(path.join("temp")).and_then(|raw| parse_millidegrees(&raw))
+            {
+                cpu_celsius = Some(cpu_celsius.map_or(celsius, |hottest| hottest.max(celsius)));
+            }
+        }
+    }
+    let cpu_throttle_events =
+        read("/sys/devices/system/cpu/cpu0/thermal_throttle/package_throttle_count".into())
+            .and_then(|raw| raw.trim().parse().ok());
+    ThermalSnapshot {
+        cpu_celsius,
+        cpu_throttle_events,
+        ..ThermalSnapshot::default()
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn read_cpu() -> ThermalSnapshot {
+    let cpu_throttled = Command::new("pmset")
+        .args(["-g", "therm"])
+        .output()
+        .ok()
+        .and_then(|output| parse_speed_limit(&String::from_utf8_lossy(&output.stdout)))
+        .map(|limit| limit < 100);
+    ThermalSnapshot {
+        cpu_throttled,
+        ..ThermalSnapshot::default()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_cpu() -> ThermalSnapshot {
+    ThermalSnapshot::default()
+}
+
+fn read_nvidia() -> Option<(f32, bool)> {
+    let path = which::which("nvidia-smi").ok()?;
+    let output = Command::new(path)
+        .args([
+            "--query-gpu=temperature.gpu,clocks_throttle_reasons.hw_thermal_slowdown,clocks_throttle_reasons.sw_thermal_slowdown",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_nvidia_smi(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn is_cpu_zone(kind: &str) -> bool {
+    let kind = kind.trim().to_ascii_lowercase();
+    ["x86_pkg_temp", "cpu", "soc", "k10temp", "coretemp"]
+        .iter()
+        .any(|name| kind.contains(name))
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn parse_millidegrees(raw: &str) -> Option<f32> {
+    raw.trim()
+        .parse::<i64>()
+        .ok()
+        .map(|milli| milli as f32 / 1000.0)
+}
+
+/// `CPU_Speed_Limit` from `pmset -g therm`; 100 means unthrottled.
+#[cfg(any(target_os = "macos", test))]
+fn parse_speed_limit(output: &str) -> Option<u32> {
+    output.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "CPU_Speed_Limit")
+            .then(|| value.trim().parse().ok())
+            .flatten()
+    })
+}
+
+/// First GPU from `nvidia-smi`: temperature and whether either thermal slowdown is active.
+fn parse_nvidia_smi(output: &str) -> Option<(f32, bool)> {
+    let line = output.lines().next()?;
+    let mut fields = line.split(',').map(str::trim);
+    let celsius = fields.next()?.parse().ok()?;
+    let throttled = fields.any(|reason| reason.eq_ignore_ascii_case("active"));
+    Some((celsius, throttled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_cpu_zone, parse_millidegrees, parse_nvidia_smi, parse_speed_limit};
+
+    #[test]
+    fn parses_platform_thermal_output() {
+        assert_eq!(
+            parse_nvidia_smi("71, Not Active, Active\n"),
+            Some((71.0, true))
+        );
+        assert_eq!(
+            parse_nvidia_smi("48, Not Active, Not Active\n"),
+            Some((48.0, false))
+        );
+        assert_eq!(parse_nvidia_smi(""), None);
+
+        let pmset = "Note: No thermal warning level has been recorded\n\
+                     CPU_Scheduler_Limit \t= 100\n\
+                     CPU_Available_CPUs \t= 8\n\
+                     CPU_Speed_Limit \t= 62\n";
+        assert_eq!(parse_speed_limit(pmset), Some(62));
+
+        assert_eq!(parse_millidegrees("54000\n"), Some(54.0));
+        assert!(is_cpu_zone("x86_pkg_temp\n"));
+        assert!(!is_cpu_zone("iwlwifi_1"));
+    }
+}
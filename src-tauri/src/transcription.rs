@@ -1,14 +1,284 @@
+use crate::compute;
 use anyhow::{Context, Result};
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext};
+use serde::{Deserialize, Serialize};
+use std::ffi::{c_char, c_void, CStr};
+use std::io::Write;
+use std::sync::{Mutex, Once};
+use whisper_rs::whisper_rs_sys::ggml_log_level;
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+};
+
+/// Language value that lets whisper detect the spoken language from the audio itself.
+pub const AUTO_LANGUAGE: &str = "auto";
+
+/// One whisper segment; `t0`/`t1` are milliseconds from the start of the audio and
+/// `confidence` is the mean probability of its text tokens.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Segment {
+    pub text: String,
+    pub t0: u64,
+    pub t1: u64,
+    pub confidence: f32,
+    /// Filled only when `word_timestamps` is on; words keep the model's raw text, before
+    /// replacements.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<Word>,
+    /// Set when diarizing and another speaker takes over after this segment. tinydiarize
+    /// marks where the speaker changes, not who speaks, so there are no speaker labels.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub speaker_turn: bool,
+}
+
+/// One word of a segment, timed like `Segment`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Word {
+    pub text: String,
+    pub t0: u64,
+    pub t1: u64,
+    pub confidence: f32,
+}
+
+/// Decoding settings the child applies per request; changing them does not reload
+/// the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DecodingParams {
+    /// 1 decodes greedily; larger values use beam search with that many beams.
+    pub beam_size: u32,
+    pub temperature: f32,
+    /// Step by which a segment is re-decoded at a higher temperature when it fails the
+    /// thresholds below (repetitive or low-confidence output); 0 disables the fallback.
+    pub temperature_inc: f32,
+    /// Compression threshold: text more repetitive than this is retried.
+    pub entropy_threshold: f32,
+    /// Average token log-probability below which a segment is retried.
+    pub logprob_threshold: f32,
+    /// CPU threads whisper decodes with; 0 uses every core.
+    pub threads: u32,
+    /// Decode each segment without the previous text as context.
+    pub no_context: bool,
+    pub suppress_blank: bool,
+    /// Longest segment in characters; 0 leaves segments unsplit.
+    pub max_segment_len: u32,
+    /// Split long segments at word boundaries rather than mid-word.
+    pub split_on_word: bool,
+}
+
+/// whisper.cpp's own defaults.
+pub const DEFAULT_TEMPERATURE_INC: f32 = 0.2;
+pub const DEFAULT_ENTROPY_THRESHOLD: f32 = 2.4;
+pub const DEFAULT_LOGPROB_THRESHOLD: f32 = -1.0;
+
+impl Default for DecodingParams {
+    fn default() -> Self {
+        Self {
+            beam_size: 1,
+            temperature: 0.0,
+            temperature_inc: DEFAULT_TEMPERATURE_INC,
+            entropy_threshold: DEFAULT_ENTROPY_THRESHOLD,
+            logprob_threshold: DEFAULT_LOGPROB_THRESHOLD,
+            threads: 0,
+            no_context: true,
+            suppress_blank: true,
+            max_segment_len: 0,
+            split_on_word: false,
+        }
+    }
+}
+
+impl DecodingParams {
+    /// A quick greedy decode without temperature fallback, for in-progress chunks.
+    pub fn for_partial(&self) -> Self {
+        Self {
+            beam_size: 1,
+            temperature_inc: 0.0,
+            ..self.clone()
+        }
+    }
+}
+
+/// Settings sent with each request rather than kept by the child.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TranscribeOptions {
+    /// A language code, or `AUTO_LANGUAGE`.
+    pub language: String,
+    /// Translate the speech into English instead of transcribing it.
+    pub translate: bool,
+    pub initial_prompt: String,
+    pub word_timestamps: bool,
+    /// Mark speaker turns; needs a tinydiarize model.
+    pub diarize: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    pub text: String,
+    pub segments: Vec<Segment>,
+    /// Tokens decoded (special tokens included) and the time whisper spent on them.
+    #[serde(default)]
+    pub tokens: u32,
+    #[serde(default)]
+    pub decode_ms: u64,
+    /// The language decoded with: the requested one, or what whisper detected under
+    /// `AUTO_LANGUAGE`.
+    #[serde(default)]
+    pub language: String,
+}
+
+/// Power-user knobs over whisper.cpp, kept together as one settings block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AdvancedDecoding {
+    pub no_context: bool,
+    pub suppress_blank: bool,
+    pub max_segment_len: u32,
+    pub split_on_word: bool,
+    /// Flash attention in the encoder; faster on most GPUs, and applying it reloads the
+    /// model.
+    pub flash_attn: bool,
+}
+
+impl Default for AdvancedDecoding {
+    fn default() -> Self {
+        let decoding = DecodingParams::default();
+        Self {
+            no_context: decoding.no_context,
+            suppress_blank: decoding.suppress_blank,
+            max_segment_len: decoding.max_segment_len,
+            split_on_word: decoding.split_on_word,
+            flash_attn: false,
+        }
+    }
+}
+
+/// How a model is loaded; changing any of it means loading the model again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextOptions {
+    /// A `compute::BACKEND_*` id.
+    pub backend: String,
+    /// Adapter index; only honoured in the child process, see `compute::device_env`.
+    pub gpu_device: u32,
+    pub flash_attn: bool,
+    /// Load the model's Core ML encoder; only meaningful in a `compute::COREML` build.
+    pub coreml: bool,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self {
+            backend: compute::BACKEND_AUTO.to_string(),
+            gpu_device: 0,
+            flash_attn: false,
+            coreml: compute::COREML,
+        }
+    }
+}
+
+/// whisper.cpp's log while a model loads; it is the only place that says whether a GPU
+/// backend was actually picked up.
+static LOAD_LOG: Mutex<Option<String>> = Mutex::new(None);
+/// Keeps loads from mixing their lines in `LOAD_LOG`.
+static LOAD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Forwards whisper.cpp's log to stderr, as its default does, and keeps the lines of a
+/// load in progress. Must never unwind into C.
+unsafe extern "C" fn capture_log(_level: ggml_log_level, text: *const c_char, _: *mut c_void) {
+    if text.is_null() {
+        return;
+    }
+    let text = CStr::from_ptr(text).to_string_lossy();
+    let _ = std::io::stderr().write_all(text.as_bytes());
+    if let Ok(mut log) = LOAD_LOG.lock() {
+        if let Some(log) = log.as_mut() {
+            log.push_str(&text);
+        }
+    }
+}
+
+/// Whether whisper.cpp reported a working GPU backend in `log`; without one it quietly
+/// runs on the CPU, e.g. when the driver is missing or the device fails to start.
+fn uses_gpu(log: &str) -> bool {
+    let mut lines = log.lines();
+    lines
+        .clone()
+        .any(|line| line.contains("whisper_backend_init_gpu: using"))
+        && !lines.any(|line| line.contains("whisper_backend_init_gpu: failed"))
+}
+
+/// Runs `load` while capturing whisper.cpp's log.
+fn logged_load<T>(load: impl FnOnce() -> T) -> (T, String) {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe {
+        whisper_rs::set_log_callback(Some(capture_log), std::ptr::null_mut());
+    });
+    let _serial = LOAD_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    *LOAD_LOG.lock().unwrap_or_else(|err| err.into_inner()) = Some(String::new());
+    let result = load();
+    let log = LOAD_LOG
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take()
+        .unwrap_or_default();
+    (result, log)
+}
+
+/// Loads a model as `options` ask; only `BACKEND_AUTO` falls back to the CPU when GPU
+/// init fails. A GPU-only backend fails when whisper.cpp ends up on the CPU anyway.
+pub fn load_context(model_path: &str, options: &ContextOptions) -> Result<WhisperContext> {
+    // whisper.cpp built with Core ML looks for the encoder next to the model path; a model
+    // loaded from memory has no path, so it runs whisper.cpp's own encoder instead.
+    let buffer = if compute::COREML && !options.coreml {
+        Some(std::fs::read(model_path).context("read model")?)
+    } else {
+        None
+    };
+    let load = |use_gpu: bool| {
+        let mut params = WhisperContextParameters::default();
+        params.use_gpu(use_gpu);
+        params.gpu_device(options.gpu_device as i32);
+        params.flash_attn(options.flash_attn);
+        match &buffer {
+            Some(buffer) => WhisperContext::new_from_buffer_with_params(buffer, params),
+            None => WhisperContext::new_with_params(model_path, params),
+        }
+    };
+    let backend = &options.backend;
+    match compute::gpu_mode(backend)? {
+        Some(true) => {
+            let (context, log) = logged_load(|| load(true));
+            let context = context.with_context(|| format!("load model ({backend})"))?;
+            if !uses_gpu(&log) {
+                anyhow::bail!("{backend} found no usable GPU; whisper.cpp would run on the CPU");
+            }
+            Ok(context)
+        }
+        Some(false) => load(false).context("load model (cpu)"),
+        None => load(true).or_else(|err| {
+            eprintln!("Whisperdict: GPU init failed ({err}), falling back to CPU");
+            load(false).context("load model (cpu)")
+        }),
+    }
+}
+
+/// whisper.cpp only splits segments at `max_len` while timing tokens, so a segment length
+/// limit turns token timestamps on even when words are not reported.
+fn token_timestamps(options: &TranscribeOptions, decoding: &DecodingParams) -> bool {
+    options.word_timestamps || decoding.max_segment_len > 0
+}
 
 pub fn transcribe_with_context(
     ctx: &WhisperContext,
     audio: &[f32],
-    language: Option<&str>,
-    detect_language: bool,
-) -> Result<String> {
+    options: &TranscribeOptions,
+    decoding: &DecodingParams,
+) -> Result<Transcript> {
     if audio.len() < 16_000 / 4 {
-        return Ok(String::new());
+        return Ok(Transcript::default());
     }
 
     let mut cleaned: Vec<f32> = Vec::with_capacity(audio.len());
@@ -26,96 +296,220 @@ pub fn transcribe_with_context(
         }
     }
 
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    let threads = std::thread::available_parallelism()
-        .map(|n| n.get() as i32)
-        .unwrap_or(4);
-    params.set_n_threads(threads.max(2));
-    params.set_speed_up(false);
-    let lang = if detect_language {
-        detect_language_by_scoring(ctx, &cleaned)
-            .or(language)
-            .or(Some("es"))
+    let strategy = if decoding.beam_size > 1 {
+        SamplingStrategy::BeamSearch {
+            beam_size: decoding.beam_size as i32,
+            patience: -1.0,
+        }
     } else {
-        language.or(Some("es"))
+        SamplingStrategy::Greedy { best_of: 1 }
     };
-    params.set_language(lang);
+    let mut params = FullParams::new(strategy);
+    params.set_temperature(decoding.temperature);
+    params.set_temperature_inc(decoding.temperature_inc);
+    params.set_entropy_thold(decoding.entropy_threshold);
+    params.set_logprob_thold(decoding.logprob_threshold);
+    params.set_no_context(decoding.no_context);
+    params.set_suppress_blank(decoding.suppress_blank);
+    params.set_max_len(decoding.max_segment_len.min(i32::MAX as u32) as i32);
+    params.set_split_on_word(decoding.split_on_word);
+    if !options.initial_prompt.is_empty() {
+        params.set_initial_prompt(&options.initial_prompt);
+    }
+    params.set_token_timestamps(token_timestamps(options, decoding));
+    params.set_tdrz_enable(options.diarize);
+    let threads = match decoding.threads {
+        0 => std::thread::available_parallelism()
+            .map(|n| n.get() as i32)
+            .unwrap_or(4)
+            .max(2),
+        threads => threads.min(i32::MAX as u32) as i32,
+    };
+    params.set_n_threads(threads);
+    let language = match options.language.trim() {
+        "" => AUTO_LANGUAGE,
+        language => language,
+    };
+    // whisper.cpp detects "auto" from the first window's encoder output, before decoding.
+    params.set_language(Some(language));
     params.set_detect_language(false);
-    params.set_translate(false);
+    params.set_translate(options.translate);
     params.set_print_progress(false);
     params.set_print_special(false);
     params.set_print_realtime(false);
 
     let mut state = ctx.create_state().context("create whisper state")?;
+    let started = std::time::Instant::now();
     state.full(params, &cleaned).context("transcribe audio")?;
+    let decode_ms = started.elapsed().as_millis() as u64;
+    let language = if language == AUTO_LANGUAGE {
+        state
+            .full_lang_id_from_state()
+            .ok()
+            .and_then(whisper_rs::get_lang_str)
+            .unwrap_or(AUTO_LANGUAGE)
+    } else {
+        language
+    };
 
-    let segments = state.full_n_segments().context("get segments")?;
+    let count = state.full_n_segments().context("get segments")?;
+    let mut tokens = 0u32;
     let mut text = String::new();
-    for i in 0..segments {
+    let mut segments = Vec::with_capacity(count.max(0) as usize);
+    for i in 0..count {
         let segment = state.full_get_segment_text(i).context("segment text")?;
+        tokens += state.full_n_tokens(i).unwrap_or(0).max(0) as u32;
         text.push_str(&segment);
+        let trimmed = segment.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        // Whisper timestamps are in centiseconds.
+        let t0 = state.full_get_segment_t0(i).unwrap_or(0).max(0) as u64 * 10;
+        let t1 = state.full_get_segment_t1(i).unwrap_or(0).max(0) as u64 * 10;
+        segments.push(Segment {
+            text: trimmed.to_string(),
+            t0,
+            t1,
+            confidence: segment_confidence(ctx, &state, i),
+            words: if options.word_timestamps {
+                group_words(segment_tokens(ctx, &state, i))
+            } else {
+                Vec::new()
+            },
+            speaker_turn: options.diarize && state.full_get_segment_speaker_turn_next(i),
+        });
     }
-    Ok(text.trim().to_string())
+    Ok(Transcript {
+        text: text.trim().to_string(),
+        segments,
+        tokens,
+        decode_ms,
+        language: language.to_string(),
+    })
 }
 
-fn detect_language_by_scoring(ctx: &WhisperContext, audio: &[f32]) -> Option<&'static str> {
-    let sample_len = (16_000.0 * 2.0) as usize;
-    let sample = if audio.len() > sample_len {
-        &audio[..sample_len]
+fn segment_confidence(ctx: &WhisperContext, state: &WhisperState, segment: i32) -> f32 {
+    let tokens = state.full_n_tokens(segment).unwrap_or(0);
+    let mut total = 0.0f32;
+    let mut counted = 0u32;
+    for token in 0..tokens {
+        // Skip timestamp and other special tokens, which sit at or above end-of-text.
+        let is_text = state
+            .full_get_token_id(segment, token)
+            .is_ok_and(|id| id < ctx.token_eot());
+        if !is_text {
+            continue;
+        }
+        total += state.full_get_token_prob(segment, token).unwrap_or(0.0);
+        counted += 1;
+    }
+    if counted == 0 {
+        0.0
     } else {
-        audio
-    };
+        total / counted as f32
+    }
+}
 
-    let candidates = ["es", "en", "pt", "fr", "de", "it"];
-    let mut best_lang = None;
-    let mut best_score = f32::MIN;
+/// Text tokens of a segment as (text, t0 ms, t1 ms, probability).
+fn segment_tokens(
+    ctx: &WhisperContext,
+    state: &WhisperState,
+    segment: i32,
+) -> Vec<(String, u64, u64, f32)> {
+    let count = state.full_n_tokens(segment).unwrap_or(0);
+    (0..count)
+        .filter_map(|token| {
+            let data = state.full_get_token_data(segment, token).ok()?;
+            if data.id >= ctx.token_eot() {
+                return None;
+            }
+            let text = state.full_get_token_text(segment, token).ok()?;
+            Some((
+                text,
+                data.t0.max(0) as u64 * 10,
+                data.t1.max(0) as u64 * 10,
+                data.p,
+            ))
+        })
+        .collect()
+}
 
-    for lang in candidates {
-        if let Ok(score) = score_language(ctx, sample, lang) {
-            if score > best_score {
-                best_score = score;
-                best_lang = Some(lang);
+/// Joins sub-word tokens into words: a token starting with a space begins a new word.
+fn group_words(tokens: Vec<(String, u64, u64, f32)>) -> Vec<Word> {
+    let mut words: Vec<(Word, u32)> = Vec::new();
+    for (text, t0, t1, p) in tokens {
+        match words.last_mut() {
+            Some((word, parts)) if !text.starts_with(' ') => {
+                word.text.push_str(&text);
+                word.t1 = t1.max(word.t1);
+                word.confidence += p;
+                *parts += 1;
             }
+            _ => words.push((
+                Word {
+                    text,
+                    t0,
+                    t1,
+                    confidence: p,
+                },
+                1,
+            )),
         }
     }
-    best_lang
+    words
+        .into_iter()
+        .filter_map(|(mut word, parts)| {
+            word.text = word.text.trim().to_string();
+            word.confidence /= parts as f32;
+            (!word.text.is_empty()).then_some(word)
+        })
+        .collect()
 }
 
-fn score_language(ctx: &WhisperContext, audio: &[f32], lang: &str) -> Result<f32> {
-    let mut state = ctx.create_state().context("create whisper state")?;
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    let threads = std::thread::available_parallelism()
-        .map(|n| n.get() as i32)
-        .unwrap_or(2);
-    params.set_n_threads(threads.max(1));
-    params.set_speed_up(false);
-    params.set_language(Some(lang));
-    params.set_detect_language(false);
-    params.set_translate(false);
-    params.set_print_progress(false);
-    params.set_print_special(false);
-    params.set_print_realtime(false);
-    params.set_single_segment(true);
-    params.set_max_tokens(32);
-
-    state.full(params, audio).context("score transcribe")?;
-
-    let segments = state.full_n_segments().context("score segments")?;
-    if segments == 0 {
-        return Ok(f32::MIN);
-    }
-    let mut total_prob = 0.0f32;
-    let mut total_tokens = 0i32;
-    for segment in 0..segments {
-        let tokens = state.full_n_tokens(segment).context("score tokens")?;
-        for token in 0..tokens {
-            let prob = state.full_get_token_prob(segment, token).unwrap_or(0.0);
-            total_prob += prob;
-            total_tokens += 1;
-        }
+#[cfg(test)]
+mod tests {
+    use super::{group_words, token_timestamps, uses_gpu, DecodingParams, TranscribeOptions};
+
+    #[test]
+    fn segment_length_limit_needs_token_timestamps() {
+        let options = TranscribeOptions::default();
+        let mut decoding = DecodingParams::default();
+        assert!(!token_timestamps(&options, &decoding));
+        decoding.max_segment_len = 40;
+        assert!(token_timestamps(&options, &decoding));
+        let words = TranscribeOptions {
+            word_timestamps: true,
+            ..TranscribeOptions::default()
+        };
+        assert!(token_timestamps(&words, &DecodingParams::default()));
+    }
+
+    #[test]
+    fn groups_sub_word_tokens_into_timed_words() {
+        let token = |text: &str, t0, t1, p| (text.to_string(), t0, t1, p);
+        let words = group_words(vec![
+            token(" Whis", 0, 200, 0.8),
+            token("per", 200, 400, 0.6),
+            token("dict", 400, 520, 1.0),
+            token(" works", 600, 900, 0.9),
+            token(".", 900, 950, 0.5),
+        ]);
+        let summary: Vec<_> = words
+            .iter()
+            .map(|word| (word.text.as_str(), word.t0, word.t1))
+            .collect();
+        assert_eq!(summary, vec![("Whisperdict", 0, 520), ("works.", 600, 950)]);
+        assert!((words[0].confidence - 0.8).abs() < 1e-6);
     }
-    if total_tokens == 0 {
-        return Ok(f32::MIN);
+
+    #[test]
+    fn tells_a_gpu_load_from_a_cpu_fallback() {
+        let gpu = "whisper_init_with_params_no_state: use gpu    = 1\nwhisper_backend_init_gpu: using CUDA0 backend\n";
+        assert!(uses_gpu(gpu));
+        assert!(!uses_gpu(&format!(
+            "{gpu}whisper_backend_init_gpu: failed to initialize CUDA0 backend\n"
+        )));
+        assert!(!uses_gpu("whisper_init_with_params_no_state: use gpu    = 1\nwhisper_model_load:      CPU total size =   147.37 MB\n"));
     }
-    Ok(total_prob / total_tokens as f32)
 }
@@ -1,18 +1,28 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use tauri::image::Image;
-use tauri::menu::{MenuBuilder, MenuItem};
-use tauri::tray::{TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::menu::{CheckMenuItem, MenuBuilder, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
 use tauri::{AppHandle, Manager};
 
+use crate::app_state::AppState;
+use crate::supervisor::Supervisor;
+use crate::windows;
+
 const ICON_SIZE: u32 = 16;
 const FRAME_MS: u64 = 140;
 
+pub const ACTION_TOGGLE_RECORDING: &str = "toggle_recording";
+pub const ACTION_NEXT_LANGUAGE: &str = "next_language";
+pub const ACTION_NONE: &str = "none";
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TrayMode {
     Idle,
     Recording,
+    Paused,
     Processing,
     Error,
 }
@@ -21,6 +31,12 @@ pub enum TrayMode {
 pub struct TrayController {
     mode: Arc<Mutex<TrayMode>>,
     tray: Arc<Mutex<Option<TrayIcon>>>,
+    /// Presentation mode shows one static, neutral glyph whatever the mode.
+    neutral: Arc<AtomicBool>,
+    /// Incognito dictation marks every icon with a violet corner dot.
+    incognito: Arc<AtomicBool>,
+    presentation_item: Arc<Mutex<Option<CheckMenuItem<tauri::Wry>>>>,
+    incognito_item: Arc<Mutex<Option<CheckMenuItem<tauri::Wry>>>>,
 }
 
 impl TrayController {
@@ -28,6 +44,10 @@ impl TrayController {
         Self {
             mode: Arc::new(Mutex::new(TrayMode::Idle)),
             tray: Arc::new(Mutex::new(None)),
+            neutral: Arc::new(AtomicBool::new(false)),
+            incognito: Arc::new(AtomicBool::new(false)),
+            presentation_item: Arc::new(Mutex::new(None)),
+            incognito_item: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -36,12 +56,44 @@ impl TrayController {
             Ok(item) => item,
             Err(_) => return,
         };
+        let history_item = match MenuItem::with_id(app, "history", "History", true, None::<&str>) {
+            Ok(item) => item,
+            Err(_) => return,
+        };
+        let presentation_item = match CheckMenuItem::with_id(
+            app,
+            "presentation",
+            "Presentation mode",
+            true,
+            self.neutral.load(Ordering::Relaxed),
+            None::<&str>,
+        ) {
+            Ok(item) => item,
+            Err(_) => return,
+        };
+        let incognito_item = match CheckMenuItem::with_id(
+            app,
+            "incognito",
+            "Incognito dictation",
+            true,
+            self.incognito.load(Ordering::Relaxed),
+            None::<&str>,
+        ) {
+            Ok(item) => item,
+            Err(_) => return,
+        };
         let quit_item = match MenuItem::with_id(app, "quit", "Quit", true, None::<&str>) {
             Ok(item) => item,
             Err(_) => return,
         };
         let menu = match MenuBuilder::new(app)
-            .items(&[&show_item, &quit_item])
+            .items(&[
+                &show_item,
+                &history_item,
+                &presentation_item,
+                &incognito_item,
+                &quit_item,
+            ])
             .build()
         {
             Ok(menu) => menu,
@@ -53,27 +105,74 @@ impl TrayController {
             .menu(&menu)
             .on_menu_event(|app, event| match event.id().as_ref() {
                 "show" => {
-                    if let Some(window) = app.get_webview_window("main") {
-                        let _ = window.show();
-                        let _ = window.set_focus();
-                    }
+                    let _ = windows::show_main_window(app);
+                }
+                "history" => {
+                    let _ = windows::show_history_window(app);
+                }
+                "presentation" => {
+                    let state = app.state::<AppState>();
+                    let enabled = !state.config.snapshot().presentation_mode;
+                    let _ = state.set_presentation_mode(enabled);
+                }
+                "incognito" => {
+                    let state = app.state::<AppState>();
+                    state.toggle_incognito(app);
                 }
                 "quit" => app.exit(0),
                 _ => {}
             })
-            .on_tray_icon_event(|_tray, _event: TrayIconEvent| {})
+            // Tauri's tray events carry clicks but no scroll deltas, so language switching
+            // is exposed as a click action (and the `cycle_language` command) instead.
+            .on_tray_icon_event(|tray, event: TrayIconEvent| {
+                if let TrayIconEvent::Click {
+                    button: MouseButton::Middle,
+                    button_state: MouseButtonState::Up,
+                    ..
+                } = event
+                {
+                    run_click_action(tray.app_handle().clone());
+                }
+            })
             .build(app)
             .ok();
         if let Ok(mut guard) = self.tray.lock() {
             *guard = tray;
         }
+        if let Ok(mut guard) = self.presentation_item.lock() {
+            *guard = Some(presentation_item);
+        }
+        if let Ok(mut guard) = self.incognito_item.lock() {
+            *guard = Some(incognito_item);
+        }
+        self.set_mode(self.mode.lock().map(|g| *g).unwrap_or(TrayMode::Idle));
+    }
+
+    pub fn set_presentation(&self, enabled: bool) {
+        self.neutral.store(enabled, Ordering::Relaxed);
+        if let Ok(guard) = self.presentation_item.lock() {
+            if let Some(item) = guard.as_ref() {
+                let _ = item.set_checked(enabled);
+            }
+        }
+        self.set_mode(self.mode.lock().map(|g| *g).unwrap_or(TrayMode::Idle));
+    }
+
+    pub fn set_incognito(&self, enabled: bool) {
+        self.incognito.store(enabled, Ordering::Relaxed);
+        if let Ok(guard) = self.incognito_item.lock() {
+            if let Some(item) = guard.as_ref() {
+                let _ = item.set_checked(enabled);
+            }
+        }
+        self.set_mode(self.mode.lock().map(|g| *g).unwrap_or(TrayMode::Idle));
     }
 
     pub fn set_mode(&self, mode: TrayMode) {
         if let Ok(mut guard) = self.mode.lock() {
             *guard = mode;
         }
-        let icon = render_icon(mode, 0);
+        let icon = icon_for(&self.neutral, &self.incognito, mode, 0);
         if let Ok(guard) = self.tray.lock() {
             if let Some(tray) = guard.as_ref() {
                 let _ = tray.set_icon(Some(icon));
@@ -81,41 +180,135 @@ impl TrayController {
         }
     }
 
-    pub fn start_animation(&self) {
+    pub fn set_tooltip(&self, tooltip: Option<&str>) {
+        if let Ok(guard) = self.tray.lock() {
+            if let Some(tray) = guard.as_ref() {
+                let _ = tray.set_tooltip(tooltip);
+            }
+        }
+    }
+
+    pub fn start_animation(&self, supervisor: &Supervisor) {
         let mode_ref = self.mode.clone();
         let tray_ref = self.tray.clone();
-        tauri::async_runtime::spawn(async move {
-            let mut frame: u8 = 0;
-            let mut last_mode = TrayMode::Idle;
-            loop {
-                let mode = mode_ref.lock().map(|g| *g).unwrap_or(TrayMode::Idle);
-                if mode != last_mode {
-                    frame = 0;
-                    last_mode = mode;
-                    let icon = render_icon(mode, 0);
-                    if let Ok(guard) = tray_ref.lock() {
-                        if let Some(tray) = guard.as_ref() {
-                            let _ = tray.set_icon(Some(icon));
+        let neutral = self.neutral.clone();
+        let incognito = self.incognito.clone();
+        supervisor.spawn_task("tray_animation", move || {
+            let mode_ref = mode_ref.clone();
+            let tray_ref = tray_ref.clone();
+            let neutral = neutral.clone();
+            let incognito = incognito.clone();
+            async move {
+                let mut frame: u8 = 0;
+                let mut last_mode = TrayMode::Idle;
+                loop {
+                    let mode = mode_ref.lock().map(|g| *g).unwrap_or(TrayMode::Idle);
+                    if mode != last_mode {
+                        frame = 0;
+                        last_mode = mode;
+                        let icon = icon_for(&neutral, &incognito, mode, 0);
+                        if let Ok(guard) = tray_ref.lock() {
+                            if let Some(tray) = guard.as_ref() {
+                                let _ = tray.set_icon(Some(icon));
+                            }
                         }
                     }
-                }
 
-                if mode == TrayMode::Recording || mode == TrayMode::Processing {
-                    frame = frame.wrapping_add(1);
-                    let icon = render_icon(mode, frame);
-                    if let Ok(guard) = tray_ref.lock() {
-                        if let Some(tray) = guard.as_ref() {
-                            let _ = tray.set_icon(Some(icon));
+                    let animated = mode == TrayMode::Recording || mode == TrayMode::Processing;
+                    if animated && !neutral.load(Ordering::Relaxed) {
+                        frame = frame.wrapping_add(1);
+                        let icon = icon_for(&neutral, &incognito, mode, frame);
+                        if let Ok(guard) = tray_ref.lock() {
+                            if let Some(tray) = guard.as_ref() {
+                                let _ = tray.set_icon(Some(icon));
+                            }
                         }
                     }
-                }
 
-                tokio::time::sleep(Duration::from_millis(FRAME_MS)).await;
+                    tokio::time::sleep(Duration::from_millis(FRAME_MS)).await;
+                }
             }
         });
     }
 }
 
+fn run_click_action(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let action = state.config.snapshot().tray_middle_click_action.clone();
+        match action.as_str() {
+            ACTION_TOGGLE_RECORDING => {
+                if state.status().recording {
+                    let _ = state.stop_recording(&app).await;
+                } else {
+                    let _ = state.start_recording(&app);
+                }
+            }
+            ACTION_NEXT_LANGUAGE => {
+                let _ = state.cycle_language(1);
+            }
+            _ => {}
+        }
+    });
+}
+
+fn icon_for(
+    neutral: &AtomicBool,
+    incognito: &AtomicBool,
+    mode: TrayMode,
+    frame: u8,
+) -> Image<'static> {
+    let icon = if neutral.load(Ordering::Relaxed) {
+        render_neutral()
+    } else {
+        render_icon(mode, frame)
+    };
+    if incognito.load(Ordering::Relaxed) {
+        mark_incognito(&icon)
+    } else {
+        icon
+    }
+}
+
+/// Stamps a violet dot with a dark rim into the bottom-right corner, over whatever the
+/// icon shows, so incognito stays visible while recording or processing too.
+fn mark_incognito(icon: &Image<'_>) -> Image<'static> {
+    let (width, height) = (icon.width(), icon.height());
+    let mut data = icon.rgba().to_vec();
+    let size = width.min(height) as i32;
+    let radius = (size / 5).max(2);
+    let (cx, cy) = (width as i32 - radius - 1, height as i32 - radius - 1);
+    for y in cy - radius - 1..=cy + radius + 1 {
+        for x in cx - radius - 1..=cx + radius + 1 {
+            let distance = (x - cx) * (x - cx) + (y - cy) * (y - cy);
+            let color = if distance <= radius * radius {
+                (150, 90, 255, 255)
+            } else if distance <= (radius + 1) * (radius + 1) {
+                (20, 20, 20, 255)
+            } else {
+                continue;
+            };
+            set_pixel(&mut data, width, x, y, color);
+        }
+    }
+    Image::new_owned(data, width, height)
+}
+
+/// A plain grey dot that says nothing about recording or errors.
+fn render_neutral() -> Image<'static> {
+    let mut data = vec![0u8; (ICON_SIZE * ICON_SIZE * 4) as usize];
+    let center = (ICON_SIZE as i32 - 1) / 2;
+    for y in 0..ICON_SIZE as i32 {
+        for x in 0..ICON_SIZE as i32 {
+            let (dx, dy) = (x - center, y - center);
+            if dx * dx + dy * dy <= 16 {
+                set_pixel(&mut data, ICON_SIZE, x, y, (160, 160, 160, 255));
+            }
+        }
+    }
+    Image::new_owned(data, ICON_SIZE, ICON_SIZE)
+}
+
 fn render_icon(mode: TrayMode, frame: u8) -> Image<'static> {
     if matches!(mode, TrayMode::Idle | TrayMode::Error) {
         if let Ok(icon) = Image::from_bytes(include_bytes!("../icons-app/32x32.png")) {
@@ -130,6 +323,7 @@ fn render_icon(mode: TrayMode, frame: u8) -> Image<'static> {
         TrayMode::Idle => draw_fallback_mark(&mut data, ICON_SIZE, (250, 250, 250, 255)),
         TrayMode::Error => draw_fallback_mark(&mut data, ICON_SIZE, (243, 18, 96, 255)),
         TrayMode::Recording => draw_recording(&mut data, ICON_SIZE, frame),
+        TrayMode::Paused => draw_paused(&mut data, ICON_SIZE),
         TrayMode::Processing => draw_processing(&mut data, ICON_SIZE, frame),
     }
 
@@ -215,6 +409,15 @@ fn draw_recording(data: &mut [u8], size: u32, frame: u8) {
     }
 }
 
+fn draw_paused(data: &mut [u8], size: u32) {
+    let color = (250, 190, 60, 255);
+    for y in 3..=(size as i32 - 4) {
+        for x in [4, 5, 6, 9, 10, 11] {
+            set_pixel(data, size, x, y, color);
+        }
+    }
+}
+
 fn draw_processing(data: &mut [u8], size: u32, frame: u8) {
     let center = (size as f32 - 1.0) / 2.0;
     let radius = (size as f32 / 2.0) - 2.5;
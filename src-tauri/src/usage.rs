@@ -0,0 +1,116 @@
+use crate::config::{config_path, AppConfig};
+use crate::quota::{self, QuotaPolicy};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// One finished dictation as written to the usage journal. Entries are numbered from
+/// `AppConfig::usage_seq`, which records the last one applied to the counters, so
+/// replaying the journal twice never counts an entry twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageEntry {
+    pub seq: u64,
+    pub audio_ms: u64,
+    /// Unix seconds, so a monthly quota is charged to the month the dictation happened in.
+    pub at: u64,
+    /// Whether it adds to `total_transcriptions_count`; incognito dictations only use quota.
+    pub counted: bool,
+}
+
+/// Next to the config file. Every dictation is appended and synced here before the
+/// counters in the config are touched, and entries the config has not caught up with are
+/// replayed at startup.
+pub fn journal_path() -> Result<PathBuf> {
+    Ok(config_path()?.with_file_name("usage.jsonl"))
+}
+
+pub fn append(path: &Path, entry: &UsageEntry) -> Result<()> {
+    let mut line = serde_json::to_string(entry).context("serialize usage entry")?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("open usage journal")?;
+    file.write_all(line.as_bytes())
+        .context("write usage journal")?;
+    file.sync_data().context("sync usage journal")
+}
+
+/// Entries in the journal; a line torn by a crash mid-write is skipped.
+pub fn read(path: &Path) -> Result<Vec<UsageEntry>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).context("read usage journal"),
+    };
+    Ok(data
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Applies `entry` to the counters unless the config already has it.
+pub fn apply(config: &mut AppConfig, entry: &UsageEntry, policy: QuotaPolicy) {
+    if entry.seq <= config.usage_seq {
+        return;
+    }
+    quota::consume(config, policy, entry.audio_ms, entry.at);
+    if entry.counted {
+        config.total_transcriptions_count = config.total_transcriptions_count.saturating_add(1);
+    }
+    config.usage_seq = entry.seq;
+}
+
+/// Drops the journal once the config holding every entry has been saved.
+pub fn compact(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err).context("compact usage journal"),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append, apply, read, UsageEntry};
+    use crate::config::AppConfig;
+    use crate::quota::QuotaPolicy;
+    use std::fs;
+
+    #[test]
+    fn replay_is_idempotent_and_skips_torn_lines() {
+        let path = std::env::temp_dir().join(format!("usage-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let entry = |seq, counted| UsageEntry {
+            seq,
+            audio_ms: 1_000,
+            at: 0,
+            counted,
+        };
+        append(&path, &entry(1, true)).unwrap();
+        append(&path, &entry(2, false)).unwrap();
+        let mut data = fs::read_to_string(&path).unwrap();
+        data.push_str(r#"{"seq":3,"audio"#);
+        fs::write(&path, data).unwrap();
+        let entries = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(entries, vec![entry(1, true), entry(2, false)]);
+
+        let mut config = AppConfig::default();
+        for _ in 0..2 {
+            for entry in &entries {
+                apply(&mut config, entry, QuotaPolicy::Lifetime);
+            }
+        }
+        let defaults = AppConfig::default();
+        assert_eq!(
+            config.free_transcriptions_left,
+            defaults.free_transcriptions_left - 2
+        );
+        assert_eq!(config.total_transcriptions_count, 1);
+        assert_eq!(config.usage_seq, 2);
+    }
+}
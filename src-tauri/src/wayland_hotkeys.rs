@@ -1,13 +1,27 @@
 use crate::app_state::AppState;
+use crate::config::AppConfig;
 use anyhow::Result;
 use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
 use futures_util::StreamExt;
+use std::collections::BTreeMap;
 use std::env;
 use tauri::{AppHandle, Manager};
 use tokio::sync::mpsc;
 
 enum Command {
     Update(String),
+    UpdateIncognito(String),
+    UpdateQuick(String),
+    UpdateTags(BTreeMap<String, String>),
+}
+
+/// Everything bound through the portal; it replaces the whole set on every bind.
+struct Bindings {
+    shortcut: String,
+    incognito: String,
+    quick: String,
+    /// Tag to shortcut, as in `AppConfig::tag_shortcuts`.
+    tags: BTreeMap<String, String>,
 }
 
 #[derive(Clone)]
@@ -16,11 +30,17 @@ pub struct WaylandHotkeys {
 }
 
 impl WaylandHotkeys {
-    pub fn start(app: AppHandle, shortcut: String) -> Option<Self> {
+    pub fn start(app: AppHandle, config: &AppConfig) -> Option<Self> {
         if env::var("WAYLAND_DISPLAY").is_err() {
             return None;
         }
 
+        let mut bindings = Bindings {
+            shortcut: config.shortcut.clone(),
+            incognito: config.incognito_shortcut.clone(),
+            quick: config.quick_shortcut.clone(),
+            tags: config.tag_shortcuts.clone(),
+        };
         let (tx, mut rx) = mpsc::channel::<Command>(8);
         tauri::async_runtime::spawn(async move {
             let proxy = match GlobalShortcuts::new().await {
@@ -33,8 +53,7 @@ impl WaylandHotkeys {
                 Err(_) => return,
             };
 
-            let mut current = shortcut;
-            let _ = bind_shortcut(&proxy, &session, &current).await;
+            let _ = bind_shortcuts(&proxy, &session, &bindings).await;
 
             let mut activated = match proxy.receive_activated().await {
                 Ok(stream) => stream,
@@ -44,9 +63,13 @@ impl WaylandHotkeys {
             loop {
                 tokio::select! {
                     Some(cmd) = rx.recv() => {
-                        let Command::Update(next) = cmd;
-                        current = next;
-                        let _ = bind_shortcut(&proxy, &session, &current).await;
+                        match cmd {
+                            Command::Update(next) => bindings.shortcut = next,
+                            Command::UpdateIncognito(next) => bindings.incognito = next,
+                            Command::UpdateQuick(next) => bindings.quick = next,
+                            Command::UpdateTags(next) => bindings.tags = next,
+                        }
+                        let _ = bind_shortcuts(&proxy, &session, &bindings).await;
                     }
                     event = activated.next() => {
                         if let Some(event) = event {
@@ -61,6 +84,22 @@ impl WaylandHotkeys {
                                         let _ = state.start_recording(&app_handle);
                                     }
                                 });
+                            } else if event.shortcut_id() == "toggle-incognito" {
+                                let state = app.state::<AppState>();
+                                state.toggle_incognito(&app);
+                            } else if event.shortcut_id() == "quick-dictation" {
+                                let app_handle = app.clone();
+                                tauri::async_runtime::spawn(async move {
+                                    let state = app_handle.state::<AppState>();
+                                    let _ = state.toggle_quick(&app_handle).await;
+                                });
+                            } else if let Some(tag) = event.shortcut_id().strip_prefix("tag:") {
+                                let app_handle = app.clone();
+                                let tag = tag.to_string();
+                                tauri::async_runtime::spawn(async move {
+                                    let state = app_handle.state::<AppState>();
+                                    let _ = state.toggle_tagged(&app_handle, tag).await;
+                                });
                             }
                         }
                     }
@@ -74,18 +113,52 @@ impl WaylandHotkeys {
     pub fn update(&self, shortcut: String) {
         let _ = self.tx.try_send(Command::Update(shortcut));
     }
+
+    pub fn update_incognito(&self, shortcut: String) {
+        let _ = self.tx.try_send(Command::UpdateIncognito(shortcut));
+    }
+
+    pub fn update_quick(&self, shortcut: String) {
+        let _ = self.tx.try_send(Command::UpdateQuick(shortcut));
+    }
+
+    pub fn update_tags(&self, tags: BTreeMap<String, String>) {
+        let _ = self.tx.try_send(Command::UpdateTags(tags));
+    }
 }
 
-async fn bind_shortcut(
+/// Binds the recording shortcut, plus the incognito, quick and tag ones that are set.
+async fn bind_shortcuts(
     proxy: &GlobalShortcuts<'_>,
     session: &ashpd::desktop::Session<'_, GlobalShortcuts<'_>>,
-    shortcut: &str,
+    bindings: &Bindings,
 ) -> Result<()> {
-    let shortcut = normalize_shortcut(shortcut);
-    let shortcuts = [
+    let shortcut = normalize_shortcut(&bindings.shortcut);
+    let mut shortcuts = vec![
         NewShortcut::new("toggle-recording", "Start or stop Whisperdict")
             .preferred_trigger(Some(shortcut.as_str())),
     ];
+    let incognito = normalize_shortcut(&bindings.incognito);
+    if !incognito.trim().is_empty() {
+        shortcuts.push(
+            NewShortcut::new("toggle-incognito", "Toggle incognito dictation")
+                .preferred_trigger(Some(incognito.as_str())),
+        );
+    }
+    let quick = normalize_shortcut(&bindings.quick);
+    if !quick.trim().is_empty() {
+        shortcuts.push(
+            NewShortcut::new("quick-dictation", "Dictate with the quick model")
+                .preferred_trigger(Some(quick.as_str())),
+        );
+    }
+    for (tag, shortcut) in &bindings.tags {
+        let shortcut = normalize_shortcut(shortcut);
+        shortcuts.push(
+            NewShortcut::new(format!("tag:{tag}"), format!("Dictate tagged \"{tag}\""))
+                .preferred_trigger(Some(shortcut.as_str())),
+        );
+    }
     let request = proxy.bind_shortcuts(session, &shortcuts, None).await?;
     let _ = request.response()?;
     Ok(())
@@ -0,0 +1,326 @@
+use crate::app_state::AppState;
+use anyhow::{Context, Result};
+use ashpd::desktop::remote_desktop::{DeviceType, KeyState, RemoteDesktop};
+use ashpd::desktop::{PersistMode, Session};
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc;
+
+pub const KEY_RETURN: i32 = 0xff0d;
+pub const KEY_TAB: i32 = 0xff09;
+pub const KEY_BACKSPACE: i32 = 0xff08;
+pub const KEY_INSERT: i32 = 0xff63;
+pub const KEY_SHIFT: i32 = 0xffe1;
+pub const KEY_CONTROL: i32 = 0xffe3;
+/// Keysyms for Unicode characters outside Latin-1 are this plus the code point.
+const UNICODE_KEYSYM: i32 = 0x0100_0000;
+/// Bounds the wait for the batch that opens the session, which waits for the user to
+/// answer the portal's permission dialog.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Bounds the wait for a batch once a session is open, on top of its key delays.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Allowance for each keystroke's round trip to the portal, so long batches are not
+/// given up on while they are still being typed.
+const STROKE_TIMEOUT: Duration = Duration::from_millis(5);
+/// How long a declined or silent portal is skipped before it is asked again.
+const RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// One key going down or up, as an X11 keysym; the compositor picks the keycode and
+/// any Shift level itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stroke {
+    pub keysym: i32,
+    pub pressed: bool,
+}
+
+/// The keysym typing `ch` produces.
+pub fn keysym(ch: char) -> i32 {
+    match ch {
+        '\n' => KEY_RETURN,
+        '\t' => KEY_TAB,
+        ' '..='~' | '\u{a0}'..='\u{ff}' => ch as i32,
+        _ => UNICODE_KEYSYM + ch as i32,
+    }
+}
+
+/// Presses and releases `key` once.
+pub fn tap(key: i32) -> [Stroke; 2] {
+    [
+        Stroke {
+            keysym: key,
+            pressed: true,
+        },
+        Stroke {
+            keysym: key,
+            pressed: false,
+        },
+    ]
+}
+
+/// `key` with `modifiers` held, releasing them in reverse order.
+pub fn chord(modifiers: &[i32], key: i32) -> Vec<Stroke> {
+    let press = modifiers.iter().map(|&keysym| Stroke {
+        keysym,
+        pressed: true,
+    });
+    let release = modifiers.iter().rev().map(|&keysym| Stroke {
+        keysym,
+        pressed: false,
+    });
+    press.chain(tap(key)).chain(release).collect()
+}
+
+pub fn text_strokes(text: &str) -> Vec<Stroke> {
+    text.chars().flat_map(|ch| tap(keysym(ch))).collect()
+}
+
+struct Batch {
+    strokes: Vec<Stroke>,
+    delay: Duration,
+    /// Set by the caller once it stops waiting and falls back to another input method,
+    /// so the batch is not typed a second time.
+    cancelled: Arc<AtomicBool>,
+    reply: std_mpsc::Sender<Result<(), String>>,
+}
+
+struct Portal {
+    proxy: RemoteDesktop<'static>,
+    session: Session<'static, RemoteDesktop<'static>>,
+}
+
+/// The portal session, opened on the first keystrokes so the permission dialog only
+/// shows up once something is typed.
+struct Connection {
+    app: AppHandle,
+    restore_token: String,
+    portal: Option<Portal>,
+    /// Why the portal cannot be used and since when; set once the user declines or
+    /// nothing answers, so batches go straight to the fallback until `RETRY_AFTER`.
+    unavailable: Option<(String, Instant)>,
+}
+
+impl Connection {
+    async fn send(&mut self, batch: &Batch) -> Result<()> {
+        if let Some((reason, since)) = &self.unavailable {
+            if since.elapsed() < RETRY_AFTER {
+                anyhow::bail!("RemoteDesktop portal unavailable: {reason}");
+            }
+            self.unavailable = None;
+        }
+        if self.portal.is_none() {
+            match connect(&self.restore_token).await {
+                Ok((portal, restore_token)) => {
+                    self.portal = Some(portal);
+                    CONNECTED.store(true, Ordering::Relaxed);
+                    if let Some(restore_token) = restore_token {
+                        self.restore_token = restore_token.clone();
+                        let state = self.app.state::<AppState>();
+                        if let Err(err) = state.set_remote_desktop_token(restore_token) {
+                            eprintln!("saving portal restore token failed: {err:#}");
+                        }
+                    }
+                }
+                Err(err) => {
+                    self.unavailable = Some((format!("{err:#}"), Instant::now()));
+                    return Err(err);
+                }
+            }
+        }
+        let portal = self.portal.as_ref().context("portal session missing")?;
+        let mut held = Vec::new();
+        for stroke in &batch.strokes {
+            if batch.cancelled.load(Ordering::Relaxed) {
+                for &keysym in held.iter().rev() {
+                    let _ = portal
+                        .proxy
+                        .notify_keyboard_keysym(&portal.session, keysym, KeyState::Released)
+                        .await;
+                }
+                anyhow::bail!("keystrokes cancelled");
+            }
+            let state = if stroke.pressed {
+                KeyState::Pressed
+            } else {
+                KeyState::Released
+            };
+            let sent = portal
+                .proxy
+                .notify_keyboard_keysym(&portal.session, stroke.keysym, state)
+                .await;
+            if let Err(err) = sent {
+                // The session may have been closed; the next batch opens a new one.
+                self.portal = None;
+                CONNECTED.store(false, Ordering::Relaxed);
+                return Err(err).context("send keystroke");
+            }
+            if stroke.pressed {
+                held.push(stroke.keysym);
+            } else {
+                held.retain(|&keysym| keysym != stroke.keysym);
+                if !batch.delay.is_zero() {
+                    tokio::time::sleep(batch.delay).await;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn connect(restore_token: &str) -> Result<(Portal, Option<String>)> {
+    let proxy = RemoteDesktop::new()
+        .await
+        .context("connect to RemoteDesktop portal")?;
+    let session = proxy
+        .create_session()
+        .await
+        .context("create RemoteDesktop session")?;
+    let restore_token = (!restore_token.is_empty()).then_some(restore_token);
+    proxy
+        .select_devices(
+            &session,
+            DeviceType::Keyboard.into(),
+            restore_token,
+            PersistMode::ExplicitlyRevoked,
+        )
+        .await
+        .context("select keyboard")?;
+    let response = proxy
+        .start(&session, None)
+        .await
+        .context("start RemoteDesktop session")?
+        .response()
+        .context("keyboard access was not granted")?;
+    if !response.devices().contains(DeviceType::Keyboard) {
+        anyhow::bail!("keyboard access was not granted");
+    }
+    let restore_token = response.restore_token().map(str::to_string);
+    Ok((Portal { proxy, session }, restore_token))
+}
+
+static INPUT: OnceLock<mpsc::Sender<Batch>> = OnceLock::new();
+/// Whether a session is open, so batches need not wait for the permission dialog.
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+/// Starts the task that owns the portal session, on Wayland only. `restore_token` lets
+/// the portal skip its dialog for access the user already granted.
+pub fn start(app: AppHandle, restore_token: String) {
+    if env::var("WAYLAND_DISPLAY").is_err() {
+        return;
+    }
+    let (tx, mut rx) = mpsc::channel::<Batch>(8);
+    if INPUT.set(tx).is_err() {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let mut connection = Connection {
+            app,
+            restore_token,
+            portal: None,
+            unavailable: None,
+        };
+        while let Some(batch) = rx.recv().await {
+            if batch.cancelled.load(Ordering::Relaxed) {
+                continue;
+            }
+            let result = connection
+                .send(&batch)
+                .await
+                .map_err(|err| format!("{err:#}"));
+            let _ = batch.reply.send(result);
+        }
+    });
+}
+
+/// Sends `strokes` through the portal, pausing `delay` after each key, and waits until
+/// they are delivered. Fails when the portal is not running or refuses. Blocks for as
+/// long as the permission dialog stays open, so never call it from the main thread.
+pub fn send(strokes: Vec<Stroke>, delay: Duration) -> Result<()> {
+    debug_assert_ne!(thread::current().name(), Some("main"));
+    let input = INPUT.get().context("RemoteDesktop portal is not running")?;
+    let timeout = batch_timeout(&strokes, delay, CONNECTED.load(Ordering::Relaxed));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (reply, result) = std_mpsc::channel();
+    input
+        .try_send(Batch {
+            strokes,
+            delay,
+            cancelled: cancelled.clone(),
+            reply,
+        })
+        .map_err(|_| anyhow::anyhow!("RemoteDesktop portal is busy"))?;
+    let answer = result.recv_timeout(timeout);
+    if answer.is_err() {
+        // The caller falls back to another input method, which must not race the
+        // portal typing the same keys later.
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    answer
+        .context("RemoteDesktop portal did not answer")?
+        .map_err(anyhow::Error::msg)
+}
+
+/// How long to wait for `strokes` to be typed: the delay after each released key and a
+/// round trip per stroke, on top of the wait for the portal itself.
+fn batch_timeout(strokes: &[Stroke], delay: Duration, connected: bool) -> Duration {
+    let wait = if connected {
+        REPLY_TIMEOUT
+    } else {
+        CONNECT_TIMEOUT
+    };
+    let releases = strokes.iter().filter(|stroke| !stroke.pressed).count() as u32;
+    wait + delay * releases + STROKE_TIMEOUT * strokes.len() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        batch_timeout, chord, keysym, text_strokes, Stroke, CONNECT_TIMEOUT, KEY_CONTROL,
+        KEY_RETURN, REPLY_TIMEOUT,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn maps_text_and_chords_to_keysyms() {
+        assert_eq!(keysym('a'), 0x61);
+        assert_eq!(keysym('é'), 0xe9);
+        assert_eq!(keysym('€'), 0x0100_20ac);
+        assert_eq!(keysym('\n'), KEY_RETURN);
+        let strokes = text_strokes("Hi");
+        assert_eq!(strokes.len(), 4);
+        assert_eq!(
+            strokes[0],
+            Stroke {
+                keysym: 0x48,
+                pressed: true
+            }
+        );
+        let paste: Vec<(i32, bool)> = chord(&[KEY_CONTROL], 'v' as i32)
+            .iter()
+            .map(|stroke| (stroke.keysym, stroke.pressed))
+            .collect();
+        assert_eq!(
+            paste,
+            vec![
+                (KEY_CONTROL, true),
+                (0x76, true),
+                (0x76, false),
+                (KEY_CONTROL, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn timeout_grows_with_the_batch() {
+        let delay = Duration::from_millis(12);
+        let short = text_strokes("ok");
+        let long = text_strokes(&"word ".repeat(400));
+        assert!(batch_timeout(&short, delay, true) < REPLY_TIMEOUT + Duration::from_secs(1));
+        // 2000 characters at 12 ms each take 24 s to type, far beyond the base wait.
+        assert!(batch_timeout(&long, delay, true) > REPLY_TIMEOUT + delay * 2000);
+        assert!(batch_timeout(&short, delay, false) > CONNECT_TIMEOUT);
+    }
+}
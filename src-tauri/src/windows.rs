@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+pub const HISTORY_WINDOW: &str = "history";
+pub const MAIN_WINDOW: &str = "main";
+/// Command-line flag that starts with the main window hidden, whatever `start_hidden` says.
+pub const HIDDEN_FLAG: &str = "--hidden";
+
+/// The main window is created hidden (see `tauri.conf.json`) and shown from `setup`
+/// unless the app starts hidden, so it never flashes on screen.
+pub fn show_main_window(app: &AppHandle) -> Result<()> {
+    let window = app
+        .get_webview_window(MAIN_WINDOW)
+        .context("main window not found")?;
+    window.unminimize().context("unminimize main window")?;
+    window.show().context("show main window")?;
+    window.set_focus().context("focus main window")?;
+    Ok(())
+}
+
+pub fn hide_main_window(app: &AppHandle) -> Result<()> {
+    let window = app
+        .get_webview_window(MAIN_WINDOW)
+        .context("main window not found")?;
+    window.hide().context("hide main window")
+}
+
+pub fn show_history_window(app: &AppHandle) -> Result<()> {
+    if let Some(window) = app.get_webview_window(HISTORY_WINDOW) {
+        window.show().context("show history window")?;
+        window.set_focus().context("focus history window")?;
+        return Ok(());
+    }
+
+    let window = WebviewWindowBuilder::new(
+        app,
+        HISTORY_WINDOW,
+        WebviewUrl::App("index.html?window=history".into()),
+    )
+    .title("Whisperdict History")
+    .inner_size(720.0, 560.0)
+    .build()
+    .context("create history window")?;
+    window.set_focus().context("focus history window")?;
+    Ok(())
+}
+
+/// Hides the history window, if open, so focus goes back to the app behind it.
+pub fn hide_history_window(app: &AppHandle) -> Result<()> {
+    match app.get_webview_window(HISTORY_WINDOW) {
+        Some(window) => window.hide().context("hide history window"),
+        None => Ok(()),
+    }
+}
+
+/// Mirrors download progress on the taskbar (Windows, Unity launchers) and the macOS
+/// dock so it stays visible while the window is minimized. Cleared when dropped.
+pub struct TaskbarProgress {
+    app: AppHandle,
+    last_percent: AtomicU64,
+}
+
+impl TaskbarProgress {
+    pub fn new(app: &AppHandle) -> Self {
+        Self {
+            app: app.clone(),
+            last_percent: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Called per downloaded chunk; only touches the window when the percentage moves.
+    pub fn update(&self, downloaded: u64, total: Option<u64>) {
+        let percent = total
+            .filter(|total| *total > 0)
+            .map(|total| (downloaded.saturating_mul(100) / total).min(100));
+        let key = percent.unwrap_or(u64::MAX - 1);
+        if self.last_percent.swap(key, Ordering::Relaxed) != key {
+            set_progress(&self.app, Some(percent));
+        }
+    }
+}
+
+impl Drop for TaskbarProgress {
+    fn drop(&mut self) {
+        set_progress(&self.app, None);
+    }
+}
+
+fn set_progress(app: &AppHandle, percent: Option<Option<u64>>) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    let state = match percent {
+        Some(Some(percent)) => ProgressBarState {
+            status: Some(ProgressBarStatus::Normal),
+            progress: Some(percent),
+        },
+        Some(None) => ProgressBarState {
+            status: Some(ProgressBarStatus::Indeterminate),
+            progress: None,
+        },
+        None => ProgressBarState {
+            status: Some(ProgressBarStatus::None),
+            progress: None,
+        },
+    };
+    let _ = window.set_progress_bar(state);
+    #[cfg(target_os = "macos")]
+    let _ = window.set_badge_label(percent.map(|percent| match percent {
+        Some(percent) => format!("{percent}%"),
+        None => "…".to_string(),
+    }));
+}
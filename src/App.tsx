@@ -256,11 +256,21 @@ function App() {
       );
       refreshLicenseState().catch(() => undefined);
     });
+    const stopPasteCopied = api.onPasteCopied(() => {
+      setStatusMessage("Copied to the clipboard; paste it where you need it.");
+    });
+    const stopAudioDropped = api.onAudioDropped((payload) => {
+      setStatusMessage(
+        `The recorder fell behind and lost ${payload.droppedMs} ms of audio.`,
+      );
+    });
 
     return () => {
       stopStatus();
       stopProgress();
       stopTranscription();
+      stopPasteCopied();
+      stopAudioDropped();
     };
   }, [api]);
 
@@ -262,5 +262,11 @@ export function createMockWhisperdictApi(): WhisperdictApi {
       transcriptionListeners.add(cb);
       return () => transcriptionListeners.delete(cb);
     },
+    onPasteCopied() {
+      return () => undefined;
+    },
+    onAudioDropped() {
+      return () => undefined;
+    },
   };
 }
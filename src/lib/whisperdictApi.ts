@@ -86,6 +86,14 @@ export type TranscriptionPayload = {
   durationMs?: number;
 };
 
+export type PasteCopiedPayload = {
+  chars: number;
+};
+
+export type AudioDroppedPayload = {
+  droppedMs: number;
+};
+
 export type WhisperdictError = {
   code?: string;
   message: string;
@@ -178,6 +186,8 @@ export interface WhisperdictApi {
   onStatus(cb: (payload: StatusPayload) => void): () => void;
   onProgress(cb: (payload: ProgressPayload) => void): () => void;
   onTranscription(cb: (payload: TranscriptionPayload) => void): () => void;
+  onPasteCopied(cb: (payload: PasteCopiedPayload) => void): () => void;
+  onAudioDropped(cb: (payload: AudioDroppedPayload) => void): () => void;
 }
 
 const isMock = import.meta.env.VITE_E2E === "1";
@@ -249,5 +259,17 @@ export function createWhisperdictApi(): WhisperdictApi {
         unlisten.then((fn) => fn()).catch(() => undefined);
       };
     },
+    onPasteCopied: (cb) => {
+      const unlisten = listen<PasteCopiedPayload>("paste:copied", (event) => cb(event.payload));
+      return () => {
+        unlisten.then((fn) => fn()).catch(() => undefined);
+      };
+    },
+    onAudioDropped: (cb) => {
+      const unlisten = listen<AudioDroppedPayload>("audio:dropped", (event) => cb(event.payload));
+      return () => {
+        unlisten.then((fn) => fn()).catch(() => undefined);
+      };
+    },
   };
 }